@@ -0,0 +1,243 @@
+//! Spaced-repetition flashcards written in blocks.
+//!
+//! A block is a card when it is tagged `#card` (or `#[[card]]`) or its first
+//! line reads `question :: answer`. For tagged blocks the first line is the
+//! question and the remaining lines, or failing that the child blocks, are
+//! the answer. Note the spaces around `::`: Logseq properties (`key:: value`)
+//! are not cards.
+//!
+//! Reviews are scheduled with SM-2. A card nobody has reviewed yet is new and
+//! due straight away.
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, Result};
+
+pub const DEFAULT_EASE: f64 = 2.5;
+const MIN_EASE: f64 = 1.3;
+const SEPARATOR: &str = " :: ";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardContent {
+    pub question: String,
+    /// `None` for tagged blocks whose answer is in their children
+    pub answer: Option<String>,
+}
+
+/// Answer buttons, mapped to SM-2 quality 1, 3, 4 and 5
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Grade {
+    Again,
+    Hard,
+    Good,
+    Easy,
+}
+
+impl Grade {
+    fn quality(self) -> f64 {
+        match self {
+            Grade::Again => 1.0,
+            Grade::Hard => 3.0,
+            Grade::Good => 4.0,
+            Grade::Easy => 5.0,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Grade::Again => "again",
+            Grade::Hard => "hard",
+            Grade::Good => "good",
+            Grade::Easy => "easy",
+        }
+    }
+}
+
+impl std::str::FromStr for Grade {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "again" => Ok(Grade::Again),
+            "hard" => Ok(Grade::Hard),
+            "good" => Ok(Grade::Good),
+            "easy" => Ok(Grade::Easy),
+            _ => Err(AppError::InvalidInput(format!("Invalid grade '{}'", value))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReviewState {
+    pub ease: f64,
+    pub interval_days: i64,
+    /// Successful reviews in a row
+    pub repetitions: i64,
+    /// Times the card was forgotten after being learnt
+    pub lapses: i64,
+    pub due_at: DateTime<Utc>,
+    pub last_reviewed_at: Option<DateTime<Utc>>,
+}
+
+impl ReviewState {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            ease: DEFAULT_EASE,
+            interval_days: 0,
+            repetitions: 0,
+            lapses: 0,
+            due_at: now,
+            last_reviewed_at: None,
+        }
+    }
+
+    /// The state after answering with `grade` at `now`
+    pub fn review(&self, grade: Grade, now: DateTime<Utc>) -> Self {
+        let quality = grade.quality();
+        let mut next = self.clone();
+        if grade == Grade::Again {
+            if self.repetitions > 0 {
+                next.lapses += 1;
+            }
+            next.repetitions = 0;
+            next.interval_days = 1;
+        } else {
+            next.interval_days = match self.repetitions {
+                0 => 1,
+                1 => 6,
+                _ => ((self.interval_days as f64) * self.ease).round().max(1.0) as i64,
+            };
+            next.repetitions += 1;
+        }
+        next.ease = (self.ease + 0.1 - (5.0 - quality) * (0.08 + (5.0 - quality) * 0.02)).max(MIN_EASE);
+        next.due_at = now + Duration::days(next.interval_days);
+        next.last_reviewed_at = Some(now);
+        next
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Card {
+    pub block_id: String,
+    pub page_id: String,
+    pub graph_id: String,
+    pub question: String,
+    pub answer: String,
+    /// Never reviewed
+    pub is_new: bool,
+    #[serde(flatten)]
+    pub state: ReviewState,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DailyReviews {
+    pub date: NaiveDate,
+    pub reviews: i64,
+    /// Reviews not answered with `again`
+    pub correct: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CardStats {
+    pub total_cards: i64,
+    pub new_cards: i64,
+    pub due_cards: i64,
+    /// Reviews in the period
+    pub reviews: i64,
+    /// Share of reviews in the period not answered with `again`
+    pub retention: Option<f64>,
+    pub average_ease: Option<f64>,
+    /// One entry per day of the period, oldest first
+    pub daily: Vec<DailyReviews>,
+}
+
+/// Parse the card written in a block, `None` when the block isn't a card
+pub fn parse(content: &str) -> Option<CardContent> {
+    let first = content.lines().next()?;
+    if let Some((question, answer)) = first.split_once(SEPARATOR) {
+        let rest: Vec<&str> = content.lines().skip(1).collect();
+        let answer = std::iter::once(answer).chain(rest).collect::<Vec<_>>().join("\n");
+        let (question, answer) = (question.trim(), answer.trim());
+        if !question.is_empty() && !answer.is_empty() {
+            return Some(CardContent { question: question.to_string(), answer: Some(answer.to_string()) });
+        }
+    }
+
+    if !content.split_whitespace().any(is_card_tag) {
+        return None;
+    }
+    let mut lines = content
+        .lines()
+        .map(|line| line.split_whitespace().filter(|word| !is_card_tag(word)).collect::<Vec<_>>().join(" "))
+        .skip_while(|line| line.is_empty());
+    let question = lines.next()?;
+    let answer = lines.collect::<Vec<_>>().join("\n");
+    let answer = answer.trim();
+    Some(CardContent {
+        question,
+        answer: if answer.is_empty() { None } else { Some(answer.to_string()) },
+    })
+}
+
+fn is_card_tag(word: &str) -> bool {
+    word.eq_ignore_ascii_case("#card") || word.eq_ignore_ascii_case("#[[card]]")
+}
+
+/// Review counts per day for the `days` days ending on `today`
+pub fn daily_reviews(reviews: &[(NaiveDate, Grade)], today: NaiveDate, days: u32) -> Vec<DailyReviews> {
+    (0..days as i64)
+        .rev()
+        .map(|ago| {
+            let date = today - Duration::days(ago);
+            let graded: Vec<Grade> = reviews.iter().filter(|(day, _)| *day == date).map(|(_, grade)| *grade).collect();
+            DailyReviews {
+                date,
+                reviews: graded.len() as i64,
+                correct: graded.iter().filter(|grade| **grade != Grade::Again).count() as i64,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let card = parse("Capital of France :: Paris").unwrap();
+        assert_eq!((card.question.as_str(), card.answer.as_deref()), ("Capital of France", Some("Paris")));
+
+        let card = parse("What is SM-2? #card\nA spaced repetition algorithm").unwrap();
+        assert_eq!(card.question, "What is SM-2?");
+        assert_eq!(card.answer.as_deref(), Some("A spaced repetition algorithm"));
+        assert_eq!(parse("#[[card]] Ownership rules").unwrap().answer, None);
+
+        assert_eq!(parse("type:: book"), None);
+        assert_eq!(parse("Just a note about #cards"), None);
+        assert_eq!(parse(" :: missing question"), None);
+    }
+
+    #[test]
+    fn test_review() {
+        let now = DateTime::parse_from_rfc3339("2026-10-18T09:00:00Z").unwrap().with_timezone(&Utc);
+        let state = ReviewState::new(now).review(Grade::Good, now);
+        assert_eq!((state.interval_days, state.repetitions, state.ease), (1, 1, DEFAULT_EASE));
+        let state = state.review(Grade::Good, now);
+        assert_eq!(state.interval_days, 6);
+        let state = state.review(Grade::Easy, now);
+        assert_eq!((state.interval_days, state.due_at), (15, now + Duration::days(15)));
+        assert!((state.ease - 2.6).abs() < 1e-9);
+
+        let forgotten = state.review(Grade::Again, now);
+        assert_eq!((forgotten.interval_days, forgotten.repetitions, forgotten.lapses), (1, 0, 1));
+        assert!((forgotten.ease - 2.06).abs() < 1e-9);
+        let mut hard = forgotten;
+        for _ in 0..10 {
+            hard = hard.review(Grade::Again, now);
+        }
+        assert_eq!(hard.ease, MIN_EASE);
+    }
+}
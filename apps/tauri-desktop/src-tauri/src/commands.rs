@@ -7,6 +7,8 @@ use std::time::Instant;
 mod tests;
 #[cfg(test)]
 mod integration_tests;
+pub mod cards;
+pub mod tasks;
 use crate::models::{
     AppInfo, Graph, Page, Block, Note, Tag, Settings,
     CreateGraphRequest, UpdateGraphRequest,
@@ -17,6 +19,7 @@ use crate::models::{
     BlockSearchRequest, BlockSearchResponse, BlockSearchResult,
 };
 use crate::state::AppState;
+use crate::file_operations::importer::Importer;
 use serde_json::Value;
 use sqlx::Row;
 use std::collections::HashMap;
@@ -401,11 +404,86 @@ pub async fn import_markdown_file(
     graph_id: String,
     state: State<'_, AppState>,
 ) -> Result<crate::file_operations::ImportResult> {
+    let context = crate::file_operations::importer::ImportContext {
+        assets_dir: graph_assets_dir(&graph_id)?,
+        graph_id,
+    };
     let db = state.db.lock().await;
     let path = std::path::Path::new(&file_path);
 
-    crate::file_operations::FileOperations::import_markdown_file(&db, path, &graph_id).await
-        .map_err(|e| crate::error::AppError::Database(format!("Import failed: {}", e)))
+    let result = crate::file_operations::importer::MarkdownImporter.import(&db, path, &context).await
+        .map_err(|e| crate::error::AppError::Database(format!("Import failed: {}", e)))?;
+    state.calendar_feed.notify_changed();
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn import_from_path(
+    path: String,
+    graph_id: String,
+    importer: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<crate::file_operations::ImportResult> {
+    let path = std::path::Path::new(&path);
+    let selected = match importer.as_deref() {
+        Some(id) => crate::file_operations::importer::find_importer(id)
+            .ok_or_else(|| crate::error::AppError::InvalidInput(format!("Unknown importer: {}", id)))?,
+        None => crate::file_operations::importer::detect_importer(path)
+            .ok_or_else(|| crate::error::AppError::InvalidInput(format!("No importer recognises {}", path.display())))?,
+    };
+    let context = crate::file_operations::importer::ImportContext {
+        assets_dir: graph_assets_dir(&graph_id)?,
+        graph_id,
+    };
+    let db = state.db.lock().await;
+
+    let result = selected.import(&db, path, &context).await
+        .map_err(|e| crate::error::AppError::Database(format!("{} import failed: {}", selected.id(), e)))?;
+    state.calendar_feed.notify_changed();
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn import_logseq_graph(
+    graph_dir: String,
+    graph_id: String,
+    state: State<'_, AppState>,
+) -> Result<crate::file_operations::ImportResult> {
+    let assets_dir = graph_assets_dir(&graph_id)?;
+    let db = state.db.lock().await;
+
+    let result = crate::file_operations::FileOperations::import_logseq_graph(&db, std::path::Path::new(&graph_dir), &graph_id, &assets_dir).await
+        .map_err(|e| crate::error::AppError::Database(format!("Logseq import failed: {}", e)))?;
+    state.calendar_feed.notify_changed();
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn import_obsidian_vault(
+    vault_dir: String,
+    graph_id: String,
+    state: State<'_, AppState>,
+) -> Result<crate::file_operations::ImportResult> {
+    let assets_dir = graph_assets_dir(&graph_id)?;
+    let db = state.db.lock().await;
+
+    let result = crate::file_operations::FileOperations::import_obsidian_vault(&db, std::path::Path::new(&vault_dir), &graph_id, &assets_dir).await
+        .map_err(|e| crate::error::AppError::Database(format!("Obsidian import failed: {}", e)))?;
+    state.calendar_feed.notify_changed();
+    Ok(result)
+}
+
+/// Managed attachment directory for a graph
+fn graph_assets_dir(graph_id: &str) -> Result<std::path::PathBuf> {
+    // The id becomes a directory name, so it must not reach outside the assets directory
+    if graph_id.is_empty() || graph_id.contains(['/', '\\']) || graph_id.contains("..") {
+        return Err(crate::error::AppError::InvalidInput(format!("Invalid graph id: {}", graph_id)));
+    }
+    Ok(dirs::data_dir()
+        .ok_or_else(|| crate::error::AppError::Internal("Could not find app data directory".to_string()))?
+        .join("com.minglog.desktop")
+        .join("assets")
+        .join(graph_id))
 }
 
 #[tauri::command]
@@ -423,6 +501,88 @@ pub async fn export_page_to_markdown(
     Ok(file_path.to_string_lossy().to_string())
 }
 
+#[tauri::command]
+pub async fn export_page_to_opml(
+    page_id: String,
+    output_dir: String,
+    state: State<'_, AppState>,
+) -> Result<String> {
+    let db = state.db.lock().await;
+    let output_path = std::path::Path::new(&output_dir);
+
+    let file_path = crate::file_operations::FileOperations::export_page_to_opml(&db, &page_id, output_path).await
+        .map_err(|e| crate::error::AppError::Database(format!("OPML export failed: {}", e)))?;
+
+    Ok(file_path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub async fn export_graph_to_html(
+    graph_id: String,
+    out_dir: String,
+    filter: Option<crate::file_operations::html::HtmlExportFilter>,
+    state: State<'_, AppState>,
+) -> Result<crate::file_operations::ExportResult> {
+    let db = state.db.lock().await;
+    let output_path = std::path::Path::new(&out_dir);
+
+    crate::file_operations::FileOperations::export_graph_to_html(&db, &graph_id, output_path, &filter.unwrap_or_default()).await
+        .map_err(|e| crate::error::AppError::Database(format!("HTML export failed: {}", e)))
+}
+
+#[tauri::command]
+pub async fn import_opml(
+    file_path: String,
+    graph_id: String,
+    state: State<'_, AppState>,
+) -> Result<crate::file_operations::ImportResult> {
+    let db = state.db.lock().await;
+    let path = std::path::Path::new(&file_path);
+
+    let result = crate::file_operations::FileOperations::import_opml(&db, path, &graph_id).await
+        .map_err(|e| crate::error::AppError::Database(format!("OPML import failed: {}", e)))?;
+    state.calendar_feed.notify_changed();
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn export_ics(
+    output_path: String,
+    state: State<'_, AppState>,
+) -> Result<crate::file_operations::ExportResult> {
+    let db = state.db.lock().await;
+
+    crate::file_operations::FileOperations::export_ics(&db, std::path::Path::new(&output_path)).await
+        .map_err(|e| crate::error::AppError::Database(format!("ICS export failed: {}", e)))
+}
+
+#[tauri::command]
+pub async fn import_ics(
+    file_path: String,
+    graph_id: String,
+    state: State<'_, AppState>,
+) -> Result<crate::file_operations::ics::IcsImportResult> {
+    let db = state.db.lock().await;
+    let path = std::path::Path::new(&file_path);
+
+    let result = crate::file_operations::FileOperations::import_ics(&db, path, &graph_id).await
+        .map_err(|e| crate::error::AppError::Database(format!("ICS import failed: {}", e)))?;
+    state.reminder_scheduler.notify_changed();
+    state.calendar_feed.notify_changed();
+    Ok(result)
+}
+
+/// Rewrite the calendar feed file and return its path for other apps to subscribe to
+#[tauri::command]
+pub async fn refresh_calendar_feed(state: State<'_, AppState>) -> Result<String> {
+    let path = crate::file_operations::ics::default_calendar_feed_path()?;
+    let db = state.db.lock().await;
+
+    crate::file_operations::FileOperations::export_ics(&db, &path).await
+        .map_err(|e| crate::error::AppError::Database(format!("Calendar feed update failed: {}", e)))?;
+    Ok(path.to_string_lossy().to_string())
+}
+
 #[tauri::command]
 pub async fn bulk_export_pages(
     page_ids: Vec<String>,
@@ -461,32 +621,78 @@ pub async fn create_backup(
     output_path: String,
     state: State<'_, AppState>,
 ) -> Result<String> {
+    let path = std::path::Path::new(&output_path);
     let db = state.db.lock().await;
+    let snapshot = crate::file_operations::FileOperations::snapshot_for_backup(&db, path).await
+        .map_err(|e| crate::error::AppError::Database(format!("Backup failed: {}", e)))?;
+    // Zipping only reads the snapshot, so other commands can go on meanwhile
+    drop(db);
+    crate::file_operations::FileOperations::create_backup_from_snapshot(&snapshot, path).await
+        .map_err(|e| crate::error::AppError::Database(format!("Backup failed: {}", e)))?;
 
-    // Get all data
-    let pages = db.get_all_pages().await?;
-    let mut all_blocks = Vec::new();
-    for page in &pages {
-        let blocks = db.get_blocks_by_page(&page.id).await?;
-        all_blocks.extend(blocks);
-    }
-    let tags = db.get_tags().await?;
+    Ok(output_path)
+}
 
-    let backup_data = crate::file_operations::BackupData {
-        version: "1.0".to_string(),
-        created_at: chrono::Utc::now(),
-        pages,
-        blocks: all_blocks,
-        tags,
+#[tauri::command]
+pub async fn verify_backup(path: String) -> Result<crate::file_operations::backup::BackupVerification> {
+    crate::file_operations::FileOperations::verify_backup(std::path::Path::new(&path))
+        .map_err(|e| crate::error::AppError::Database(format!("Backup verification failed: {}", e)))
+}
+
+#[tauri::command]
+pub async fn restore_backup(
+    path: String,
+    options: Option<crate::file_operations::backup::RestoreOptions>,
+    state: State<'_, AppState>,
+) -> Result<crate::file_operations::backup::RestoreReport> {
+    let db = state.db.lock().await;
+    let report = crate::file_operations::FileOperations::restore_backup_with_options(&db, &path, &options.unwrap_or_default()).await
+        .map_err(|e| crate::error::AppError::Database(format!("Restore failed: {}", e)))?;
+    state.calendar_feed.notify_changed();
+    Ok(report)
+}
+
+#[tauri::command]
+pub async fn list_backups(
+    dir: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<crate::file_operations::backup::BackupInfo>> {
+    let dir = match dir {
+        Some(dir) => std::path::PathBuf::from(dir),
+        None => {
+            let db = state.db.lock().await;
+            crate::file_operations::backup::scheduler::BackupConfig::load(&db).await
+                .map_err(|e| crate::error::AppError::Database(format!("Failed to read backup settings: {}", e)))?
+                .dir
+        }
     };
 
-    let json_content = serde_json::to_string_pretty(&backup_data)
-        .map_err(|e| crate::error::AppError::Database(format!("Serialization failed: {}", e)))?;
+    crate::file_operations::FileOperations::list_backups(&dir)
+        .map_err(|e| crate::error::AppError::Database(format!("Failed to list backups: {}", e)))
+}
 
-    std::fs::write(&output_path, json_content)
-        .map_err(|e| crate::error::AppError::Database(format!("Write failed: {}", e)))?;
+#[tauri::command]
+pub async fn backup_now(state: State<'_, AppState>) -> Result<crate::file_operations::backup::BackupInfo> {
+    state.backup_scheduler.backup_now().await
+        .map_err(|e| crate::error::AppError::Database(format!("Backup failed: {}", e)))
+}
 
-    Ok(output_path)
+#[tauri::command]
+pub async fn restore_to(
+    timestamp: String,
+    state: State<'_, AppState>,
+) -> Result<crate::file_operations::backup::pitr::RecoveryReport> {
+    let at = chrono::DateTime::parse_from_rfc3339(&timestamp)
+        .map_err(|e| crate::error::AppError::InvalidInput(format!("Invalid timestamp '{}': {}", timestamp, e)))?
+        .with_timezone(&chrono::Utc);
+
+    let mut db = state.db.lock().await;
+    let report = crate::file_operations::backup::pitr::restore_to(&mut db, at).await
+        .map_err(|e| crate::error::AppError::Database(format!("Point-in-time restore failed: {}", e)));
+    // The scheduler works out whether change logging is on for the swapped-in database
+    state.pitr_scheduler.notify_config_changed();
+    state.calendar_feed.notify_changed();
+    report
 }
 
 // Tag commands
@@ -532,9 +738,21 @@ pub async fn update_settings(
 ) -> Result<()> {
     let db = state.db.lock().await;
     
+    let backup_settings_changed = settings.keys().any(|key| key.starts_with("backup_"));
+    let pitr_settings_changed = settings.keys().any(|key| key.starts_with("pitr_"));
+    let weighting_changed = settings.contains_key(crate::tasks::rollup::WEIGHTED_PROGRESS_KEY);
     for (key, value) in settings {
         db.set_setting(&key, &value).await?;
     }
+    if backup_settings_changed {
+        state.backup_scheduler.notify_config_changed();
+    }
+    if pitr_settings_changed {
+        state.pitr_scheduler.notify_config_changed();
+    }
+    if weighting_changed {
+        db.recompute_project_stats().await?;
+    }
     
     Ok(())
 }
@@ -565,7 +783,10 @@ pub async fn export_data(
     // Get all data
     let notes = db.get_notes(None, None).await?;
     let tags = db.get_tags().await?;
-    let settings = db.get_all_settings().await?;
+    let settings: Vec<_> = db.get_all_settings().await?
+        .into_iter()
+        .filter(|setting| !crate::sync::is_device_local_setting(&setting.key))
+        .collect();
     
     // Create export data structure
     let export_data = serde_json::json!({
@@ -631,6 +852,9 @@ pub async fn import_data(
     if let Some(settings) = import_data["settings"].as_array() {
         for setting_value in settings {
             if let Ok(setting) = serde_json::from_value::<Settings>(setting_value.clone()) {
+                if crate::sync::is_device_local_setting(&setting.key) {
+                    continue;
+                }
                 let _ = db.set_setting(&setting.key, &setting.value).await;
             }
         }
@@ -683,7 +907,9 @@ pub async fn create_page(
     state: State<'_, AppState>,
 ) -> Result<Page> {
     let db = state.db.lock().await;
-    db.create_page(request).await
+    let page = db.create_page(request).await?;
+    state.sync_scheduler.notify_local_change();
+    Ok(page)
 }
 
 #[tauri::command]
@@ -707,13 +933,18 @@ pub async fn update_page(
     state: State<'_, AppState>,
 ) -> Result<Page> {
     let db = state.db.lock().await;
-    db.update_page(request).await
+    let page = db.update_page(request).await?;
+    state.sync_scheduler.notify_local_change();
+    Ok(page)
 }
 
 #[tauri::command]
 pub async fn delete_page(id: String, state: State<'_, AppState>) -> Result<()> {
     let db = state.db.lock().await;
-    db.delete_page(&id).await
+    db.delete_page(&id).await?;
+    state.sync_scheduler.notify_local_change();
+    state.calendar_feed.notify_changed();
+    Ok(())
 }
 
 // Block commands
@@ -723,7 +954,13 @@ pub async fn create_block(
     state: State<'_, AppState>,
 ) -> Result<Block> {
     let db = state.db.lock().await;
-    db.create_block(request).await
+    let block = db.create_block(request).await?;
+    db.record_block_change(&block.id, &block.page_id).await?;
+    state.sync_scheduler.notify_local_change();
+    // The block may hold a task whose deadline moved
+    state.reminder_scheduler.notify_changed();
+    state.calendar_feed.notify_changed();
+    Ok(block)
 }
 
 #[tauri::command]
@@ -747,13 +984,26 @@ pub async fn update_block(
     state: State<'_, AppState>,
 ) -> Result<Block> {
     let db = state.db.lock().await;
-    db.update_block(request).await
+    let block = db.update_block(request).await?;
+    db.record_block_change(&block.id, &block.page_id).await?;
+    state.sync_scheduler.notify_local_change();
+    // The block may hold a task whose deadline moved
+    state.reminder_scheduler.notify_changed();
+    state.calendar_feed.notify_changed();
+    Ok(block)
 }
 
 #[tauri::command]
 pub async fn delete_block(id: String, state: State<'_, AppState>) -> Result<()> {
     let db = state.db.lock().await;
-    db.delete_block(&id).await
+    let page_id = db.get_block(&id).await.ok().map(|block| block.page_id);
+    db.delete_block(&id).await?;
+    if let Some(page_id) = page_id {
+        db.record_block_change(&id, &page_id).await?;
+    }
+    state.sync_scheduler.notify_local_change();
+    state.calendar_feed.notify_changed();
+    Ok(())
 }
 
 // File Dialog commands
@@ -846,24 +1096,8 @@ tags: ["导入", "示例"]
             result.pages_imported += 1;
 
             // Convert markdown to blocks
-            let block_contents = crate::file_operations::FileOperations::markdown_to_blocks(&markdown_content);
-
-            for (index, content) in block_contents.into_iter().enumerate() {
-                let block_request = crate::models::CreateBlockRequest {
-                    graph_id: graph_id.clone(),
-                    page_id: page.id.clone(),
-                    content,
-                    parent_id: None,
-                    properties: None,
-                    refs: Some("[]".to_string()),
-                    order: Some(index as i32),
-                };
-
-                match db.create_block(block_request).await {
-                    Ok(_) => result.blocks_imported += 1,
-                    Err(e) => result.errors.push(format!("Failed to create block: {}", e)),
-                }
-            }
+            let blocks = crate::file_operations::FileOperations::markdown_to_blocks(&markdown_content);
+            crate::file_operations::FileOperations::create_outline_blocks(&db, &graph_id, &page.id, &blocks, &mut result).await;
         }
         Err(e) => result.errors.push(format!("Failed to create page: {}", e)),
     }
@@ -911,33 +1145,15 @@ pub async fn create_backup_with_dialog(
     _app: AppHandle,
     state: State<'_, AppState>,
 ) -> Result<String> {
-    let default_name = format!("minglog-backup-{}.json", chrono::Utc::now().format("%Y%m%d-%H%M%S"));
+    let default_name = format!("minglog-backup-{}.zip", chrono::Utc::now().format("%Y%m%d-%H%M%S"));
     let backup_path = std::env::temp_dir().join(&default_name);
 
     let db = state.db.lock().await;
-
-    // Get all data
-    let pages = db.get_all_pages().await?;
-    let mut all_blocks = Vec::new();
-    for page in &pages {
-        let blocks = db.get_blocks_by_page(&page.id).await?;
-        all_blocks.extend(blocks);
-    }
-    let tags = db.get_tags().await?;
-
-    let backup_data = crate::file_operations::BackupData {
-        version: "1.0".to_string(),
-        created_at: chrono::Utc::now(),
-        pages,
-        blocks: all_blocks,
-        tags,
-    };
-
-    let json_content = serde_json::to_string_pretty(&backup_data)
-        .map_err(|e| crate::error::AppError::Database(format!("Serialization failed: {}", e)))?;
-
-    std::fs::write(&backup_path, json_content)
-        .map_err(|e| crate::error::AppError::Database(format!("Write failed: {}", e)))?;
+    let snapshot = crate::file_operations::FileOperations::snapshot_for_backup(&db, &backup_path).await
+        .map_err(|e| crate::error::AppError::Database(format!("Backup failed: {}", e)))?;
+    drop(db);
+    crate::file_operations::FileOperations::create_backup_from_snapshot(&snapshot, &backup_path).await
+        .map_err(|e| crate::error::AppError::Database(format!("Backup failed: {}", e)))?;
 
     Ok(backup_path.to_string_lossy().to_string())
 }
@@ -950,6 +1166,7 @@ pub async fn configure_webdav_sync(
 ) -> Result<()> {
     let mut sync_manager = state.sync_manager.lock().await;
     sync_manager.set_config(config)?;
+    state.sync_scheduler.notify_config_changed();
     Ok(())
 }
 
@@ -961,6 +1178,44 @@ pub async fn get_webdav_config(
     Ok(sync_manager.get_config().cloned())
 }
 
+#[tauri::command]
+pub async fn configure_folder_sync(
+    config: crate::sync::FolderSyncConfig,
+    state: State<'_, AppState>,
+) -> Result<()> {
+    let mut sync_manager = state.sync_manager.lock().await;
+    sync_manager.set_folder_config(config)?;
+    state.sync_scheduler.notify_config_changed();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_folder_sync_config(
+    state: State<'_, AppState>,
+) -> Result<Option<crate::sync::FolderSyncConfig>> {
+    let sync_manager = state.sync_manager.lock().await;
+    Ok(sync_manager.get_folder_config().cloned())
+}
+
+#[tauri::command]
+pub async fn configure_s3_sync(
+    config: crate::sync::S3Config,
+    state: State<'_, AppState>,
+) -> Result<()> {
+    let mut sync_manager = state.sync_manager.lock().await;
+    sync_manager.set_s3_config(config)?;
+    state.sync_scheduler.notify_config_changed();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_s3_sync_config(
+    state: State<'_, AppState>,
+) -> Result<Option<crate::sync::S3Config>> {
+    let sync_manager = state.sync_manager.lock().await;
+    Ok(sync_manager.get_s3_config().cloned())
+}
+
 #[tauri::command]
 pub async fn test_webdav_connection(
     state: State<'_, AppState>,
@@ -974,16 +1229,130 @@ pub async fn start_webdav_sync(
     direction: crate::sync::SyncDirection,
     state: State<'_, AppState>,
 ) -> Result<crate::sync::SyncResult> {
-    let mut sync_manager = state.sync_manager.lock().await;
-    sync_manager.start_sync(direction).await
+    state.sync_scheduler.sync_now(direction).await
 }
 
 #[tauri::command]
 pub async fn stop_webdav_sync(
     state: State<'_, AppState>,
 ) -> Result<()> {
-    let mut sync_manager = state.sync_manager.lock().await;
-    sync_manager.stop_sync()
+    state.sync_scheduler.stop_sync()
+}
+
+// Block CRDT commands
+#[tauri::command]
+pub async fn get_block_version_vector(
+    state: State<'_, AppState>,
+) -> Result<crate::sync::crdt::VersionVector> {
+    let db = state.db.lock().await;
+    db.get_block_version_vector().await
+}
+
+#[tauri::command]
+pub async fn get_block_ops_since(
+    version: crate::sync::crdt::VersionVector,
+    state: State<'_, AppState>,
+) -> Result<crate::sync::crdt::OpBatch> {
+    let db = state.db.lock().await;
+    let ops = db.get_block_ops_since(&version).await?;
+    db.block_op_batch(&db.get_device_id().await?, ops).await
+}
+
+#[tauri::command]
+pub async fn apply_block_ops(
+    batch: crate::sync::crdt::OpBatch,
+    state: State<'_, AppState>,
+) -> Result<usize> {
+    let db = state.db.lock().await;
+    let applied = db.apply_block_ops(&batch).await?;
+    state.calendar_feed.notify_changed();
+    Ok(applied)
+}
+
+#[tauri::command]
+pub async fn exchange_block_ops(
+    state: State<'_, AppState>,
+) -> Result<crate::sync::OpExchangeResult> {
+    let backend = state.sync_manager.lock().await.backend()?;
+    let exchanged = crate::sync::exchange_block_ops(&state.db, backend.as_ref()).await?;
+    state.calendar_feed.notify_changed();
+    Ok(exchanged)
+}
+
+#[tauri::command]
+pub async fn start_p2p_sync(
+    port: Option<u16>,
+    state: State<'_, AppState>,
+) -> Result<crate::sync::DiscoveredPeer> {
+    let mut p2p_server = state.p2p_server.lock().await;
+    if let Some(server) = p2p_server.take() {
+        server.stop();
+    }
+    let addr = std::net::SocketAddr::from(([0, 0, 0, 0], port.unwrap_or(crate::sync::DEFAULT_P2P_PORT)));
+    let server = crate::sync::P2PServer::start(state.db.clone(), addr).await?;
+    let info = crate::sync::DiscoveredPeer {
+        device_id: server.device_id().to_string(),
+        address: addr.to_string(),
+    };
+    *p2p_server = Some(server);
+    Ok(info)
+}
+
+#[tauri::command]
+pub async fn stop_p2p_sync(
+    state: State<'_, AppState>,
+) -> Result<()> {
+    if let Some(server) = state.p2p_server.lock().await.take() {
+        server.stop();
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn begin_p2p_pairing(
+    state: State<'_, AppState>,
+) -> Result<String> {
+    let p2p_server = state.p2p_server.lock().await;
+    let server = p2p_server.as_ref()
+        .ok_or_else(|| crate::error::AppError::Sync("P2P sync is not running".to_string()))?;
+    Ok(server.begin_pairing())
+}
+
+#[tauri::command]
+pub async fn discover_p2p_peers(
+    state: State<'_, AppState>,
+) -> Result<Vec<crate::sync::DiscoveredPeer>> {
+    let device_id = state.db.lock().await.get_device_id().await?;
+    let peers = crate::sync::discover_peers(crate::sync::DEFAULT_P2P_PORT, std::time::Duration::from_secs(2)).await?;
+    Ok(peers.into_iter().filter(|peer| peer.device_id != device_id).collect())
+}
+
+#[tauri::command]
+pub async fn sync_p2p_peer(
+    address: String,
+    pairing_code: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<crate::sync::OpExchangeResult> {
+    let exchanged = crate::sync::sync_with_peer(&state.db, &address, pairing_code.as_deref()).await?;
+    state.calendar_feed.notify_changed();
+    Ok(exchanged)
+}
+
+#[tauri::command]
+pub async fn get_p2p_peers(
+    state: State<'_, AppState>,
+) -> Result<Vec<crate::sync::PairedPeer>> {
+    let db = state.db.lock().await;
+    crate::sync::get_paired_peers(&db).await
+}
+
+#[tauri::command]
+pub async fn remove_p2p_peer(
+    device_id: String,
+    state: State<'_, AppState>,
+) -> Result<bool> {
+    let db = state.db.lock().await;
+    crate::sync::remove_paired_peer(&db, &device_id).await
 }
 
 #[tauri::command]
@@ -1009,7 +1378,9 @@ pub async fn resolve_sync_conflict(
     state: State<'_, AppState>,
 ) -> Result<()> {
     let mut sync_manager = state.sync_manager.lock().await;
-    sync_manager.resolve_conflict(&file_path, resolution).await
+    sync_manager.resolve_conflict(&file_path, resolution).await?;
+    let db = state.db.lock().await;
+    sync_manager.save_file_sync_info(&db).await
 }
 
 #[tauri::command]
@@ -0,0 +1,34 @@
+use crate::cards::{Card, CardStats, Grade};
+use crate::error::Result;
+use crate::state::AppState;
+use chrono::Utc;
+use tauri::State;
+
+/// Days of review history in the stats when none is given
+const DEFAULT_STATS_DAYS: u32 = 30;
+
+#[tauri::command]
+pub async fn get_due_cards(
+    graph_id: Option<String>,
+    limit: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<Card>> {
+    let db = state.db.lock().await;
+    db.get_due_cards(Utc::now(), graph_id.as_deref(), limit).await
+}
+
+#[tauri::command]
+pub async fn grade_card(block_id: String, grade: Grade, state: State<'_, AppState>) -> Result<Card> {
+    let db = state.db.lock().await;
+    db.grade_card(&block_id, grade, Utc::now()).await
+}
+
+#[tauri::command]
+pub async fn get_card_stats(
+    days: Option<u32>,
+    utc_offset_minutes: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<CardStats> {
+    let db = state.db.lock().await;
+    db.get_card_stats(Utc::now(), days.unwrap_or(DEFAULT_STATS_DAYS), utc_offset_minutes.unwrap_or(0)).await
+}
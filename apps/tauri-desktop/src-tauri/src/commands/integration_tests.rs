@@ -3,22 +3,25 @@ mod integration_tests {
     use crate::models::*;
     use crate::state::AppState;
     use crate::database::Database;
-    use tokio::sync::Mutex;
-    use crate::sync::WebDAVSyncManager;
     use crate::commands::{init_app, get_app_info};
+    use crate::commands::tasks::{
+        due_this_week, find_reminder, list_tasks, next_actions, preview, snooze, stop_entry, task_occurrences, time_report, validate_create_project, validate_create_task,
+        validate_create_time_entry, validate_update_project, validate_update_task,
+    };
+    use crate::cards::Grade;
+    use crate::error::AppError;
+    use crate::tasks::time_report::{TimeReportGroup, TimeReportRequest};
     use tempfile::tempdir;
     use tokio;
     use std::sync::Arc;
 
     async fn create_test_app_state() -> Arc<AppState> {
-        let temp_dir = tempdir().unwrap();
-        let db_path = temp_dir.path().join("test_commands.db");
+        // Keep the directory: the pool opens new connections after this returns
+        let temp_dir = tempdir().unwrap().into_path();
+        let db_path = temp_dir.join("test_commands.db");
         let database = Database::new_with_path(db_path.to_str().unwrap()).await.unwrap();
         
-        Arc::new(AppState {
-            db: Arc::new(Mutex::new(database)),
-            sync_manager: Arc::new(Mutex::new(WebDAVSyncManager::new())),
-        })
+        Arc::new(AppState::from_database(database))
     }
 
     #[tokio::test]
@@ -306,4 +309,736 @@ mod integration_tests {
             assert!(page_result.is_ok(), "Concurrent command execution should succeed");
         }
     }
+
+    async fn create_task_test_state() -> (Arc<AppState>, tempfile::TempDir) {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_task_commands.db");
+        let database = Database::new_with_path(db_path.to_str().unwrap()).await.unwrap();
+
+        (Arc::new(AppState::from_database(database)), temp_dir)
+    }
+
+    fn task_request(title: &str, project_id: Option<String>) -> CreateTaskRequest {
+        CreateTaskRequest {
+            title: title.to_string(),
+            description: None,
+            priority: None,
+            due_date: None,
+            scheduled_date: None,
+            estimated_time: None,
+            project_id,
+            parent_task_id: None,
+            linked_notes: None,
+            linked_files: None,
+            tags: None,
+            contexts: None,
+            recurrence: None,
+        }
+    }
+
+    fn update_request(id: &str) -> UpdateTaskRequest {
+        UpdateTaskRequest {
+            id: id.to_string(),
+            title: None,
+            description: None,
+            status: None,
+            priority: None,
+            due_date: None,
+            scheduled_date: None,
+            estimated_time: None,
+            project_id: None,
+            parent_task_id: None,
+            linked_notes: None,
+            linked_files: None,
+            tags: None,
+            contexts: None,
+            recurrence: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_task_status_and_priority_are_validated() {
+        let request: UpdateTaskRequest = serde_json::from_value(serde_json::json!({
+            "id": "task-1",
+            "status": "in-progress",
+            "priority": "urgent"
+        })).unwrap();
+        assert_eq!(request.status, Some(TaskStatus::InProgress));
+        assert_eq!(request.priority, Some(TaskPriority::Urgent));
+
+        let bad_status = serde_json::from_value::<UpdateTaskRequest>(serde_json::json!({
+            "id": "task-1",
+            "status": "started"
+        }));
+        assert!(bad_status.is_err(), "Unknown task status should be rejected");
+
+        let bad_priority = serde_json::from_value::<CreateTaskRequest>(serde_json::json!({
+            "title": "Task",
+            "priority": "critical"
+        }));
+        assert!(bad_priority.is_err(), "Unknown task priority should be rejected");
+
+        let project: UpdateProjectRequest = serde_json::from_value(serde_json::json!({
+            "id": "project-1",
+            "status": "on-hold"
+        })).unwrap();
+        assert_eq!(project.status, Some(ProjectStatus::OnHold));
+        assert_eq!("waiting".parse::<TaskStatus>(), Ok(TaskStatus::Waiting));
+        assert!("later".parse::<TaskStatus>().is_err());
+        assert!(TaskPriority::Urgent > TaskPriority::High);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_stored_values_fall_back_to_defaults() {
+        let (state, _temp_dir) = create_task_test_state().await;
+        let db = state.db.lock().await;
+        let task = db.create_task(task_request("From a newer device", None)).await.unwrap();
+        sqlx::query("UPDATE tasks SET status = 'blocked', priority = 'critical' WHERE id = ?")
+            .bind(&task.id)
+            .execute(db.get_pool())
+            .await
+            .unwrap();
+
+        // One unreadable row must not break the whole list
+        let tasks = db.get_tasks(None).await.unwrap();
+        let stored = tasks.iter().find(|t| t.id == task.id).unwrap();
+        assert_eq!((stored.status, stored.priority), (TaskStatus::Todo, TaskPriority::Medium));
+    }
+
+    #[tokio::test]
+    async fn test_task_and_project_commands() {
+        let (state, _temp_dir) = create_task_test_state().await;
+        let db = state.db.lock().await;
+
+        // Project validation
+        let mut project_request = CreateProjectRequest {
+            name: "  ".to_string(),
+            description: None,
+            color: None,
+            start_date: None,
+            due_date: None,
+            linked_notes: None,
+            linked_files: None,
+        };
+        assert!(matches!(validate_create_project(&project_request), Err(AppError::InvalidInput(_))));
+        project_request.name = "Launch".to_string();
+        project_request.start_date = Some(chrono::Utc::now());
+        project_request.due_date = Some(chrono::Utc::now() - chrono::Duration::days(1));
+        assert!(matches!(validate_create_project(&project_request), Err(AppError::InvalidInput(_))));
+        project_request.due_date = None;
+        validate_create_project(&project_request).unwrap();
+        let project = db.create_project(project_request).await.unwrap();
+        assert_eq!(project.status, ProjectStatus::Active);
+
+        // Task validation
+        let empty = task_request("", None);
+        assert!(matches!(validate_create_task(&db, &empty).await, Err(AppError::InvalidInput(_))));
+        let orphan = task_request("Orphan", Some("missing-project".to_string()));
+        assert!(matches!(validate_create_task(&db, &orphan).await, Err(AppError::NotFound(_))));
+
+        let request = task_request("Write announcement", Some(project.id.clone()));
+        validate_create_task(&db, &request).await.unwrap();
+        let task = db.create_task(request).await.unwrap();
+        assert_eq!(task.status, TaskStatus::Todo);
+        assert_eq!(task.priority, TaskPriority::Medium);
+        db.create_task(task_request("Unrelated", None)).await.unwrap();
+
+        let mut self_parent = update_request(&task.id);
+        self_parent.parent_task_id = Some(task.id.clone());
+        assert!(matches!(validate_update_task(&db, &self_parent).await, Err(AppError::InvalidInput(_))));
+        let mut negative = update_request(&task.id);
+        negative.estimated_time = Some(-5);
+        assert!(matches!(validate_update_task(&db, &negative).await, Err(AppError::InvalidInput(_))));
+
+        // Completing sets completed_at, reopening clears it
+        let mut done = update_request(&task.id);
+        done.status = Some(TaskStatus::Done);
+        done.priority = Some(TaskPriority::High);
+        validate_update_task(&db, &done).await.unwrap();
+        let updated = db.update_task(done, false).await.unwrap();
+        assert_eq!(updated.status, TaskStatus::Done);
+        assert_eq!(updated.priority, TaskPriority::High);
+        assert!(updated.completed_at.is_some());
+
+        let done_tasks = list_tasks(&db, None, Some(TaskStatus::Done), None).await.unwrap();
+        assert_eq!(done_tasks.len(), 1);
+        let project_tasks = list_tasks(&db, None, None, Some(&project.id)).await.unwrap();
+        assert_eq!(project_tasks.len(), 1);
+        assert_eq!(list_tasks(&db, Some(1), None, None).await.unwrap().len(), 1);
+        assert!(list_tasks(&db, None, Some(TaskStatus::Todo), Some(&project.id)).await.unwrap().is_empty());
+
+        let mut reopen = update_request(&task.id);
+        reopen.status = Some(TaskStatus::InProgress);
+        let reopened = db.update_task(reopen, false).await.unwrap();
+        assert_eq!(reopened.status, TaskStatus::InProgress);
+        assert!(reopened.completed_at.is_none());
+
+        let mut complete_project = UpdateProjectRequest {
+            id: project.id.clone(),
+            name: None,
+            description: None,
+            status: Some(ProjectStatus::Completed),
+            color: None,
+            start_date: None,
+            due_date: Some(chrono::Utc::now() - chrono::Duration::days(2)),
+            linked_notes: None,
+            linked_files: None,
+        };
+        assert!(matches!(validate_update_project(&db, &complete_project).await, Err(AppError::InvalidInput(_))));
+        complete_project.due_date = None;
+        validate_update_project(&db, &complete_project).await.unwrap();
+        let completed = db.update_project(complete_project).await.unwrap();
+        assert_eq!(completed.status, ProjectStatus::Completed);
+        assert!(completed.completed_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_time_entry_commands() {
+        let (state, _temp_dir) = create_task_test_state().await;
+        let db = state.db.lock().await;
+        let task = db.create_task(task_request("Tracked", None)).await.unwrap();
+        let start = chrono::Utc::now() - chrono::Duration::hours(1);
+
+        let missing_task = CreateTimeEntryRequest {
+            task_id: "missing-task".to_string(),
+            start_time: start,
+            end_time: None,
+            description: None,
+        };
+        assert!(matches!(validate_create_time_entry(&db, &missing_task).await, Err(AppError::NotFound(_))));
+
+        let backwards = CreateTimeEntryRequest {
+            task_id: task.id.clone(),
+            start_time: start,
+            end_time: Some(start - chrono::Duration::minutes(1)),
+            description: None,
+        };
+        assert!(matches!(validate_create_time_entry(&db, &backwards).await, Err(AppError::InvalidInput(_))));
+
+        let request = CreateTimeEntryRequest {
+            task_id: task.id.clone(),
+            start_time: start,
+            end_time: None,
+            description: Some("Drafting".to_string()),
+        };
+        validate_create_time_entry(&db, &request).await.unwrap();
+        let entry = db.create_time_entry(request).await.unwrap();
+
+        assert!(matches!(
+            stop_entry(&db, &entry.id, start - chrono::Duration::minutes(5)).await,
+            Err(AppError::InvalidInput(_))
+        ));
+        let stopped = stop_entry(&db, &entry.id, start + chrono::Duration::minutes(30)).await.unwrap();
+        assert_eq!(stopped.duration, Some(30 * 60));
+        assert!(matches!(
+            stop_entry(&db, &entry.id, chrono::Utc::now()).await,
+            Err(AppError::InvalidInput(_))
+        ));
+
+        let entries = db.get_time_entries_by_task(&task.id).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].end_time, stopped.end_time);
+    }
+
+    #[tokio::test]
+    async fn test_recurring_task_completion() {
+        let (state, _temp_dir) = create_task_test_state().await;
+        let db = state.db.lock().await;
+        let due = chrono::DateTime::parse_from_rfc3339("2026-10-19T12:00:00Z").unwrap().with_timezone(&chrono::Utc);
+
+        let mut request = task_request("Weekly review", None);
+        request.due_date = Some(due);
+        request.tags = Some(vec!["review".to_string()]);
+        request.recurrence = Some("FREQ=WEEKLY;BYDAY=MO,TH;COUNT=2".parse().unwrap());
+        validate_create_task(&db, &request).await.unwrap();
+        let task = db.create_task(request).await.unwrap();
+
+        let upcoming = task_occurrences(&db, &task.id, Some(5), Some(0)).await.unwrap();
+        assert_eq!(upcoming, vec![due + chrono::Duration::days(3)]);
+
+        let mut done = update_request(&task.id);
+        done.status = Some(TaskStatus::Done);
+        let completed = db.update_task(done.clone(), false).await.unwrap();
+        assert!(completed.recurrence.is_none(), "The rule moves to the next occurrence");
+
+        let tasks = db.get_tasks(None).await.unwrap();
+        assert_eq!(tasks.len(), 2);
+        let next = tasks.iter().find(|t| t.id != task.id).unwrap();
+        assert_eq!(next.status, TaskStatus::Todo);
+        assert_eq!(next.due_date, Some(due + chrono::Duration::days(3)));
+        assert_eq!(next.tags, task.tags);
+        assert_eq!(next.recurrence.as_ref().and_then(|r| r.count), Some(1));
+
+        // Completing again doesn't spawn another copy, and the last occurrence ends the series
+        db.update_task(done, false).await.unwrap();
+        let mut last = update_request(&next.id);
+        last.status = Some(TaskStatus::Done);
+        db.update_task(last, false).await.unwrap();
+        assert_eq!(db.get_tasks(None).await.unwrap().len(), 2);
+
+        let mut invalid = task_request("Broken", None);
+        invalid.recurrence = Some(serde_json::from_value(serde_json::json!({"freq": "daily", "interval": 0})).unwrap());
+        assert!(matches!(validate_create_task(&db, &invalid).await, Err(AppError::InvalidInput(_))));
+        assert_eq!(
+            preview(&"FREQ=DAILY".parse().unwrap(), due, Some(2), Some(0)).unwrap(),
+            vec![due + chrono::Duration::days(1), due + chrono::Duration::days(2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_project_rollups() {
+        let (state, _temp_dir) = create_task_test_state().await;
+        let db = state.db.lock().await;
+        let project_request = |name: &str| CreateProjectRequest {
+            name: name.to_string(),
+            description: None,
+            color: None,
+            start_date: None,
+            due_date: None,
+            linked_notes: None,
+            linked_files: None,
+        };
+        let project = db.create_project(project_request("Rollups")).await.unwrap();
+        let other = db.create_project(project_request("Other")).await.unwrap();
+
+        let mut short = task_request("Short", Some(project.id.clone()));
+        short.estimated_time = Some(30);
+        let short = db.create_task(short).await.unwrap();
+        let mut long = task_request("Long", Some(project.id.clone()));
+        long.estimated_time = Some(90);
+        let long = db.create_task(long).await.unwrap();
+        let mut subtask = task_request("Subtask", Some(other.id.clone()));
+        subtask.parent_task_id = Some(long.id.clone());
+        db.create_task(subtask).await.unwrap();
+
+        let stats = db.get_project(&project.id).await.unwrap();
+        assert_eq!((stats.total_tasks, stats.completed_tasks, stats.progress), (2, 0, 0));
+
+        let mut done = update_request(&short.id);
+        done.status = Some(TaskStatus::Done);
+        db.update_task(done, false).await.unwrap();
+        let stats = db.get_project(&project.id).await.unwrap();
+        assert_eq!((stats.total_tasks, stats.completed_tasks, stats.progress), (2, 1, 50));
+
+        // Weighting by estimate is opt-in and recomputed when the setting changes
+        db.set_setting(crate::tasks::rollup::WEIGHTED_PROGRESS_KEY, "true").await.unwrap();
+        let projects = db.recompute_project_stats().await.unwrap();
+        let stats = projects.iter().find(|p| p.id == project.id).unwrap();
+        assert_eq!(stats.progress, 25);
+
+        // Moving a task updates both projects
+        let mut moved = update_request(&short.id);
+        moved.project_id = Some(other.id.clone());
+        db.update_task(moved, false).await.unwrap();
+        let stats = db.get_project(&project.id).await.unwrap();
+        assert_eq!((stats.total_tasks, stats.completed_tasks, stats.progress), (1, 0, 0));
+        let other_stats = db.get_project(&other.id).await.unwrap();
+        assert_eq!((other_stats.total_tasks, other_stats.completed_tasks), (2, 1));
+
+        // Deleting a task also removes its subtasks from their project
+        db.delete_task(&long.id).await.unwrap();
+        let stats = db.get_project(&project.id).await.unwrap();
+        assert_eq!(stats.total_tasks, 0);
+        let other_stats = db.get_project(&other.id).await.unwrap();
+        assert_eq!((other_stats.total_tasks, other_stats.completed_tasks, other_stats.progress), (1, 1, 100));
+
+        // Stale stats written by older versions are repaired
+        sqlx::query("UPDATE projects SET total_tasks = 7, progress = 3").execute(db.get_pool()).await.unwrap();
+        db.recompute_project_stats().await.unwrap();
+        assert_eq!(db.get_project(&other.id).await.unwrap().total_tasks, 1);
+    }
+
+    #[tokio::test]
+    async fn test_timers_and_reports() {
+        let (state, temp_dir) = create_task_test_state().await;
+        let db = state.db.lock().await;
+        let mut writing = task_request("Writing", None);
+        writing.tags = Some(vec!["deep".to_string()]);
+        let writing = db.create_task(writing).await.unwrap();
+        let email = db.create_task(task_request("Email", None)).await.unwrap();
+
+        // Starting a timer stops the one already running
+        let first = db.start_timer(&writing.id, None).await.unwrap();
+        let second = db.start_timer(&email.id, Some("Inbox zero".to_string())).await.unwrap();
+        let entries = db.get_time_entries_by_task(&writing.id).await.unwrap();
+        assert_eq!(entries[0].id, first.id);
+        assert!(entries[0].end_time.is_some());
+        assert_eq!(db.get_running_time_entry().await.unwrap().map(|entry| entry.id), Some(second.id.clone()));
+
+        let stopped = db.stop_timer().await.unwrap().unwrap();
+        assert_eq!(stopped.id, second.id);
+        assert!(db.get_running_time_entry().await.unwrap().is_none());
+        assert!(db.stop_timer().await.unwrap().is_none());
+
+        // actual_time is rolled up from entries, in minutes
+        let start = chrono::Utc::now() - chrono::Duration::hours(3);
+        let manual = db.create_time_entry(CreateTimeEntryRequest {
+            task_id: writing.id.clone(),
+            start_time: start,
+            end_time: Some(start + chrono::Duration::minutes(90)),
+            description: None,
+        }).await.unwrap();
+        assert_eq!(db.get_task(&writing.id).await.unwrap().actual_time, Some(90));
+        db.delete_time_entry(&manual.id).await.unwrap();
+        assert_eq!(db.get_task(&writing.id).await.unwrap().actual_time, Some(0));
+        db.create_time_entry(CreateTimeEntryRequest {
+            task_id: writing.id.clone(),
+            start_time: start,
+            end_time: Some(start + chrono::Duration::minutes(45)),
+            description: None,
+        }).await.unwrap();
+        // Outside the report range
+        let earlier = start - chrono::Duration::days(2);
+        db.create_time_entry(CreateTimeEntryRequest {
+            task_id: writing.id.clone(),
+            start_time: earlier,
+            end_time: Some(earlier + chrono::Duration::minutes(30)),
+            description: None,
+        }).await.unwrap();
+
+        let request = TimeReportRequest {
+            from: start - chrono::Duration::minutes(1),
+            to: chrono::Utc::now() + chrono::Duration::minutes(1),
+            group_by: TimeReportGroup::Tag,
+            utc_offset_minutes: 0,
+        };
+        let rows = time_report(&db, &request).await.unwrap();
+        assert_eq!(rows[0].label, "deep");
+        assert_eq!(rows[0].seconds / 60, 45);
+        assert_eq!(rows[0].entries, 2);
+        assert_eq!(rows[1].label, "Untagged");
+
+        let csv_path = temp_dir.path().join("report.csv");
+        let file = std::fs::File::create(&csv_path).unwrap();
+        crate::tasks::time_report::write_csv(&rows, request.group_by, file).unwrap();
+        let csv = std::fs::read_to_string(&csv_path).unwrap();
+        assert!(csv.starts_with("tag,label,entries,seconds,hours\ndeep,deep,2,"));
+
+        assert!(matches!(db.start_timer("missing-task", None).await, Err(AppError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_block_tasks_stay_in_sync() {
+        let (state, _temp_dir) = create_task_test_state().await;
+        let db = state.db.lock().await;
+        let page = db.create_page(CreatePageRequest {
+            name: "Oct 18th, 2026".to_string(),
+            title: None,
+            graph_id: "default".to_string(),
+            is_journal: Some(true),
+            journal_date: Some("2026-10-18".to_string()),
+            tags: None,
+            properties: None,
+        }).await.unwrap();
+        let block_request = |content: &str| CreateBlockRequest {
+            content: content.to_string(),
+            parent_id: None,
+            properties: None,
+            refs: None,
+            order: None,
+            page_id: page.id.clone(),
+            graph_id: "default".to_string(),
+        };
+
+        db.create_block(block_request("Just a thought")).await.unwrap();
+        let block = db.create_block(block_request("TODO [#A] buy milk\nDEADLINE: <2026-10-20 Tue>")).await.unwrap();
+        let tasks = db.get_tasks(None).await.unwrap();
+        assert_eq!(tasks.len(), 1);
+        let task = &tasks[0];
+        assert_eq!(task.block_id.as_deref(), Some(block.id.as_str()));
+        assert_eq!(task.title, "buy milk");
+        assert_eq!(task.priority, TaskPriority::High);
+        let local = |value: &str| {
+            let value = chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap();
+            crate::tasks::recurrence::from_local(&chrono::Local, value).unwrap()
+        };
+        assert_eq!(task.due_date, Some(local("2026-10-20 00:00")));
+        assert_eq!(task.linked_notes, serde_json::to_string(&[&page.id]).unwrap());
+
+        // Editing the block updates the task
+        let edit = |content: &str| UpdateBlockRequest {
+            id: block.id.clone(),
+            content: Some(content.to_string()),
+            parent_id: None,
+            properties: None,
+            refs: None,
+            order: None,
+            collapsed: None,
+        };
+        db.update_block(edit("DOING [#A] buy oat milk\nSCHEDULED: <2026-10-19 Mon 08:30>")).await.unwrap();
+        let task = db.get_task_by_block(&block.id).await.unwrap().unwrap();
+        assert_eq!(task.status, TaskStatus::InProgress);
+        assert_eq!(task.title, "buy oat milk");
+        assert!(task.due_date.is_none());
+        assert_eq!(task.scheduled_date, Some(local("2026-10-19 08:30")));
+
+        // Completing the task rewrites the block marker
+        let mut done = update_request(&task.id);
+        done.status = Some(TaskStatus::Done);
+        let task = db.update_task(done, false).await.unwrap();
+        assert!(task.completed_at.is_some());
+        assert_eq!(db.get_block(&block.id).await.unwrap().content, "DONE [#A] buy oat milk\nSCHEDULED: <2026-10-19 Mon 08:30>");
+
+        // Dates and description set on the task are written into the block and survive edits
+        let due_date = local("2026-10-21 18:00");
+        let mut details = update_request(&task.id);
+        details.due_date = Some(due_date);
+        details.description = Some("the oat one".to_string());
+        db.update_task(details, false).await.unwrap();
+        assert_eq!(
+            db.get_block(&block.id).await.unwrap().content,
+            "DONE [#A] buy oat milk\nSCHEDULED: <2026-10-19 Mon 08:30>\nDEADLINE: <2026-10-21 Wed 18:00>\nthe oat one"
+        );
+        db.update_block(edit("DONE [#A] buy more oat milk\nSCHEDULED: <2026-10-19 Mon 08:30>\nDEADLINE: <2026-10-21 Wed 18:00>\nthe oat one")).await.unwrap();
+        let task = db.get_task_by_block(&block.id).await.unwrap().unwrap();
+        assert_eq!((task.due_date, task.description.as_deref()), (Some(due_date), Some("the oat one")));
+
+        // Completing a recurring task from its block schedules the next occurrence
+        let mut repeat = update_request(&task.id);
+        repeat.status = Some(TaskStatus::Todo);
+        repeat.recurrence = Some("FREQ=DAILY".parse().unwrap());
+        db.update_task(repeat, false).await.unwrap();
+        db.update_block(edit("DONE [#A] buy more oat milk\nDEADLINE: <2026-10-21 Wed 18:00>")).await.unwrap();
+        let task = db.get_task_by_block(&block.id).await.unwrap().unwrap();
+        assert_eq!((task.status, task.scheduled_date, task.recurrence.is_none()), (TaskStatus::Done, None, true));
+        let tasks = db.get_tasks(None).await.unwrap();
+        let next = tasks.iter().find(|next| next.id != task.id).unwrap();
+        assert_eq!(next.due_date, Some(due_date + chrono::Duration::days(1)));
+        db.delete_task(&next.id).await.unwrap();
+
+        // Removing the marker unlinks the task but keeps it and its tracked time
+        db.start_timer(&task.id, None).await.unwrap();
+        db.update_block(edit("bought oat milk")).await.unwrap();
+        assert!(db.get_task_by_block(&block.id).await.unwrap().is_none());
+        let unlinked = db.get_task(&task.id).await.unwrap();
+        assert_eq!((unlinked.block_id, unlinked.status), (None, TaskStatus::Done));
+        assert_eq!(db.get_time_entries_by_task(&task.id).await.unwrap().len(), 1);
+        db.delete_task(&task.id).await.unwrap();
+        let block = db.create_block(block_request("LATER call mom")).await.unwrap();
+        assert_eq!(db.get_tasks(None).await.unwrap().len(), 1);
+
+        // A blocked task can't be started from its block, the marker is put back
+        let call = db.get_task_by_block(&block.id).await.unwrap().unwrap();
+        let blocker = db.create_task(task_request("Find phone", None)).await.unwrap();
+        db.add_task_dependency(&call.id, &blocker.id).await.unwrap();
+        let started = db.update_block(UpdateBlockRequest { content: Some("DOING call mom".to_string()), id: block.id.clone(), ..edit("") }).await.unwrap();
+        assert_eq!(started.content, "TODO call mom");
+        assert_eq!(db.get_task(&call.id).await.unwrap().status, TaskStatus::Todo);
+        db.delete_task(&blocker.id).await.unwrap();
+
+        db.delete_block(&block.id).await.unwrap();
+        assert!(db.get_tasks(None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_gtd_views() {
+        let (state, _temp_dir) = create_task_test_state().await;
+        let db = state.db.lock().await;
+        let now = chrono::Utc::now();
+        let with_status = |id: &str, status: TaskStatus| {
+            let mut request = update_request(id);
+            request.status = Some(status);
+            request
+        };
+
+        let idea = db.create_task(task_request("Learn piano", None)).await.unwrap();
+        db.update_task(with_status(&idea.id, TaskStatus::Inbox), false).await.unwrap();
+        let someday = db.create_task(task_request("Sail around the world", None)).await.unwrap();
+        db.update_task(with_status(&someday.id, TaskStatus::Someday), false).await.unwrap();
+
+        let mut call = task_request("Call plumber", None);
+        call.contexts = Some(vec!["@phone".to_string()]);
+        call.due_date = Some(now - chrono::Duration::days(1));
+        let call = db.create_task(call).await.unwrap();
+        let mut errand = task_request("Buy stamps", None);
+        errand.contexts = Some(vec!["@errands".to_string()]);
+        errand.due_date = Some(now + chrono::Duration::hours(1));
+        db.create_task(errand).await.unwrap();
+        let mut later = task_request("Renew passport", None);
+        later.scheduled_date = Some(now + chrono::Duration::days(30));
+        db.create_task(later).await.unwrap();
+
+        let reply = db.create_task(task_request("Reply from landlord", None)).await.unwrap();
+        db.update_task(with_status(&reply.id, TaskStatus::Waiting), false).await.unwrap();
+        let done = db.create_task(task_request("File taxes", None)).await.unwrap();
+        db.update_task(with_status(&done.id, TaskStatus::Done), false).await.unwrap();
+
+        let inbox = db.get_tasks_by_status(TaskStatus::Inbox).await.unwrap();
+        assert_eq!(inbox.iter().map(|task| task.title.as_str()).collect::<Vec<_>>(), ["Learn piano"]);
+        assert_eq!(db.get_tasks_by_status(TaskStatus::Someday).await.unwrap().len(), 1);
+
+        // Tasks scheduled for later aren't next actions yet
+        let groups = next_actions(&db, now).await.unwrap();
+        let contexts: Vec<(&str, usize)> = groups.iter().map(|group| (group.context.as_str(), group.tasks.len())).collect();
+        assert_eq!(contexts, [("@errands", 1), ("@phone", 1)]);
+
+        let waiting = db.get_waiting_tasks(chrono::Utc::now() + chrono::Duration::days(3)).await.unwrap();
+        assert_eq!(waiting.len(), 1);
+        assert_eq!((waiting[0].task.id.as_str(), waiting[0].waiting_days), (reply.id.as_str(), 3));
+
+        let overdue = db.get_overdue_tasks(now).await.unwrap();
+        assert_eq!(overdue.iter().map(|task| task.id.as_str()).collect::<Vec<_>>(), [call.id.as_str()]);
+
+        let due = due_this_week(&db, now, 0).await.unwrap();
+        assert!(due.iter().all(|task| task.due_date.is_some() && task.status != TaskStatus::Done));
+        assert!(matches!(due_this_week(&db, now, 24 * 60).await, Err(AppError::InvalidInput(_))));
+
+        let review = db.get_weekly_review(chrono::Utc::now(), 0).await.unwrap();
+        assert_eq!((review.completed, review.created), (1, 7));
+        assert_eq!((review.inbox, review.next_actions, review.waiting), (1, 2, 1));
+        assert_eq!((review.overdue, review.someday), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_task_dependencies() {
+        let (state, _temp_dir) = create_task_test_state().await;
+        let db = state.db.lock().await;
+        let project = db.create_project(CreateProjectRequest {
+            name: "Launch".to_string(),
+            description: None,
+            color: None,
+            start_date: None,
+            due_date: None,
+            linked_notes: None,
+            linked_files: None,
+        }).await.unwrap();
+        let create = |title: &str, hours: i32| {
+            let mut request = task_request(title, Some(project.id.clone()));
+            request.estimated_time = Some(hours * 60);
+            request
+        };
+        let design = db.create_task(create("Design", 2)).await.unwrap();
+        let build = db.create_task(create("Build", 3)).await.unwrap();
+        let docs = db.create_task(create("Docs", 1)).await.unwrap();
+        let ship = db.create_task(create("Ship", 1)).await.unwrap();
+
+        db.add_task_dependency(&build.id, &design.id).await.unwrap();
+        db.add_task_dependency(&docs.id, &design.id).await.unwrap();
+        db.add_task_dependency(&ship.id, &build.id).await.unwrap();
+        db.add_task_dependency(&ship.id, &docs.id).await.unwrap();
+        // Adding the same dependency twice is fine, closing a loop isn't
+        db.add_task_dependency(&ship.id, &docs.id).await.unwrap();
+        assert!(matches!(db.add_task_dependency(&design.id, &ship.id).await, Err(AppError::InvalidInput(_))));
+        assert!(matches!(db.add_task_dependency(&design.id, &design.id).await, Err(AppError::InvalidInput(_))));
+        assert!(matches!(db.add_task_dependency(&design.id, "missing").await, Err(AppError::NotFound(_))));
+        assert_eq!(db.get_task_blockers(&ship.id).await.unwrap().len(), 2);
+        assert_eq!(db.get_blocked_tasks(&design.id).await.unwrap().len(), 2);
+
+        // Blocked tasks can't be started unless forced, and aren't next actions
+        let mut start = update_request(&build.id);
+        start.status = Some(TaskStatus::InProgress);
+        assert!(matches!(db.check_blockers(&start, false).await, Err(AppError::InvalidInput(_))));
+        assert!(db.check_blockers(&start, true).await.is_ok());
+        let groups = next_actions(&db, chrono::Utc::now()).await.unwrap();
+        assert_eq!(groups.iter().flat_map(|group| &group.tasks).map(|task| task.id.as_str()).collect::<Vec<_>>(), [design.id.as_str()]);
+
+        let begin = chrono::Utc::now();
+        let schedule = db.get_project_schedule(&project.id, begin).await.unwrap();
+        assert_eq!(schedule.critical_path, [design.id.clone(), build.id.clone(), ship.id.clone()]);
+        assert_eq!(schedule.finish, begin + chrono::Duration::hours(6));
+
+        // Finished work takes no more time and no longer blocks
+        let mut done = update_request(&design.id);
+        done.status = Some(TaskStatus::Done);
+        db.update_task(done, false).await.unwrap();
+        assert!(db.check_blockers(&start, false).await.is_ok());
+        let schedule = db.get_project_schedule(&project.id, begin).await.unwrap();
+        assert_eq!(schedule.finish, begin + chrono::Duration::hours(4));
+
+        db.remove_task_dependency(&ship.id, &docs.id).await.unwrap();
+        db.delete_task(&build.id).await.unwrap();
+        assert!(db.get_task_blockers(&ship.id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_task_reminders() {
+        let (state, _temp_dir) = create_task_test_state().await;
+        let db = state.db.lock().await;
+        let due = chrono::Utc::now() + chrono::Duration::days(1);
+
+        let mut request = task_request("Standup", None);
+        request.due_date = Some(due);
+        request.recurrence = Some("FREQ=DAILY".parse().unwrap());
+        let task = db.create_task(request).await.unwrap();
+        let reminder = db.create_reminder(&task.id, 15).await.unwrap();
+        assert_eq!(reminder.remind_at, due - chrono::Duration::minutes(15));
+        // The same offset twice is one reminder
+        assert_eq!(db.create_reminder(&task.id, 15).await.unwrap().id, reminder.id);
+        db.create_reminder(&task.id, 60).await.unwrap();
+        assert!(matches!(db.create_reminder("missing", 5).await, Err(AppError::NotFound(_))));
+
+        let now = chrono::Utc::now();
+        assert!(matches!(snooze(&db, &reminder.id, Some(0), now).await, Err(AppError::InvalidInput(_))));
+        assert!(matches!(snooze(&db, "missing", None, now).await, Err(AppError::NotFound(_))));
+        let snoozed = snooze(&db, &reminder.id, None, now).await.unwrap();
+        assert_eq!(snoozed.next_at(), now + chrono::Duration::minutes(10));
+
+        // The next occurrence gets the same reminders
+        let mut done = update_request(&task.id);
+        done.status = Some(TaskStatus::Done);
+        db.update_task(done, false).await.unwrap();
+        let next = db.get_tasks(None).await.unwrap().into_iter().find(|t| t.id != task.id).unwrap();
+        let offsets: Vec<i32> = db.get_reminders_by_task(&next.id).await.unwrap().iter().map(|r| r.offset_minutes).collect();
+        assert_eq!(offsets, [60, 15]);
+
+        db.delete_task(&task.id).await.unwrap();
+        assert!(matches!(find_reminder(&db, &reminder.id).await, Err(AppError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_flashcards() {
+        let state = create_test_app_state().await;
+        let db = state.db.lock().await;
+        let page = db.create_page(CreatePageRequest {
+            name: "Rust".to_string(),
+            title: None,
+            graph_id: "default".to_string(),
+            is_journal: Some(false),
+            journal_date: None,
+            tags: None,
+            properties: None,
+        }).await.unwrap();
+        let block = |content: &str, parent_id: Option<String>| CreateBlockRequest {
+            content: content.to_string(),
+            page_id: page.id.clone(),
+            graph_id: "default".to_string(),
+            parent_id,
+            order: Some(0),
+            refs: None,
+            properties: None,
+        };
+        let inline = db.create_block(block("Borrow checker :: Enforces ownership rules", None)).await.unwrap();
+        let tagged = db.create_block(block("What does `?` do? #card", None)).await.unwrap();
+        db.create_block(block("Propagates errors", Some(tagged.id.clone()))).await.unwrap();
+        let note = db.create_block(block("Not a #cards block", None)).await.unwrap();
+
+        let now = chrono::Utc::now();
+        let due = db.get_due_cards(now, None, None).await.unwrap();
+        assert_eq!(due.len(), 2);
+        assert!(due.iter().all(|card| card.is_new));
+        let answer = &due.iter().find(|card| card.block_id == tagged.id).unwrap().answer;
+        assert_eq!(answer, "Propagates errors");
+        assert_eq!(db.get_due_cards(now, None, Some(1)).await.unwrap().len(), 1);
+        assert!(db.get_due_cards(now, Some("other"), None).await.unwrap().is_empty());
+
+        let graded = db.grade_card(&inline.id, Grade::Good, now).await.unwrap();
+        assert_eq!((graded.is_new, graded.state.interval_days), (false, 1));
+        db.grade_card(&tagged.id, Grade::Again, now).await.unwrap();
+        assert!(matches!(db.grade_card(&note.id, Grade::Good, now).await, Err(AppError::InvalidInput(_))));
+        assert!(matches!(db.grade_card("missing", Grade::Good, now).await, Err(AppError::NotFound(_))));
+        assert!(db.get_due_cards(now, None, None).await.unwrap().is_empty());
+        let tomorrow = now + chrono::Duration::days(1);
+        assert_eq!(db.get_due_cards(tomorrow, None, None).await.unwrap().len(), 2);
+
+        let stats = db.get_card_stats(now, 7, 0).await.unwrap();
+        assert_eq!((stats.total_cards, stats.new_cards, stats.due_cards, stats.reviews), (2, 0, 0, 2));
+        assert_eq!(stats.retention, Some(0.5));
+        assert_eq!(stats.daily.len(), 7);
+        assert_eq!(stats.daily.last().unwrap().reviews, 2);
+        assert!(db.get_card_stats(now, 0, 0).await.is_err());
+
+        // Deleting the block drops its review state and history
+        db.delete_block(&inline.id).await.unwrap();
+        assert_eq!(db.get_card_stats(now, 7, 0).await.unwrap().reviews, 1);
+    }
 }
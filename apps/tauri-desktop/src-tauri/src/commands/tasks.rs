@@ -0,0 +1,580 @@
+use crate::database::Database;
+use crate::error::{AppError, Result};
+use crate::models::{
+    Task, Project, TimeEntry, Reminder, TaskStatus,
+    CreateTaskRequest, UpdateTaskRequest,
+    CreateProjectRequest, UpdateProjectRequest,
+    CreateTimeEntryRequest,
+};
+use crate::state::AppState;
+use crate::tasks::dependencies::{ProjectSchedule, TaskDependency};
+use crate::tasks::gtd::{self, ContextGroup, WaitingTask, WeeklyReview};
+use crate::tasks::recurrence::Recurrence;
+use crate::tasks::time_report::{self, TimeReportRequest, TimeReportRow};
+use chrono::{DateTime, FixedOffset, Local, Utc};
+use tauri::State;
+
+// Task commands
+#[tauri::command]
+pub async fn create_task(
+    request: CreateTaskRequest,
+    state: State<'_, AppState>,
+) -> Result<Task> {
+    let db = state.db.lock().await;
+    validate_create_task(&db, &request).await?;
+    let task = db.create_task(request).await?;
+    state.calendar_feed.notify_changed();
+    Ok(task)
+}
+
+#[tauri::command]
+pub async fn get_task(id: String, state: State<'_, AppState>) -> Result<Task> {
+    let db = state.db.lock().await;
+    db.get_task(&id).await
+}
+
+#[tauri::command]
+pub async fn get_tasks(
+    limit: Option<usize>,
+    status: Option<TaskStatus>,
+    project_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<Task>> {
+    let db = state.db.lock().await;
+    list_tasks(&db, limit, status, project_id.as_deref()).await
+}
+
+#[tauri::command]
+pub async fn update_task(
+    request: UpdateTaskRequest,
+    force: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<Task> {
+    let db = state.db.lock().await;
+    validate_update_task(&db, &request).await?;
+    let task = db.update_task(request, force.unwrap_or(false)).await?;
+    // The block the task is written in may have been rewritten
+    if task.block_id.is_some() {
+        state.sync_scheduler.notify_local_change();
+    }
+    // Reminders follow the due date, and recurring tasks copy theirs
+    state.reminder_scheduler.notify_changed();
+    state.calendar_feed.notify_changed();
+    Ok(task)
+}
+
+#[tauri::command]
+pub async fn delete_task(id: String, state: State<'_, AppState>) -> Result<()> {
+    let db = state.db.lock().await;
+    db.get_task(&id).await?;
+    db.delete_task(&id).await?;
+    state.calendar_feed.notify_changed();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn search_tasks(
+    query: String,
+    limit: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<Task>> {
+    let db = state.db.lock().await;
+    db.search_tasks(&query, limit).await
+}
+
+#[tauri::command]
+pub async fn preview_occurrences(
+    recurrence: Recurrence,
+    start: DateTime<Utc>,
+    limit: Option<usize>,
+    utc_offset_minutes: Option<i32>,
+) -> Result<Vec<DateTime<Utc>>> {
+    preview(&recurrence, start, limit, utc_offset_minutes)
+}
+
+#[tauri::command]
+pub async fn preview_task_occurrences(
+    task_id: String,
+    limit: Option<usize>,
+    utc_offset_minutes: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<DateTime<Utc>>> {
+    let db = state.db.lock().await;
+    task_occurrences(&db, &task_id, limit, utc_offset_minutes).await
+}
+
+// Task dependency commands
+#[tauri::command]
+pub async fn add_task_dependency(
+    task_id: String,
+    blocker_id: String,
+    state: State<'_, AppState>,
+) -> Result<TaskDependency> {
+    let db = state.db.lock().await;
+    db.add_task_dependency(&task_id, &blocker_id).await
+}
+
+#[tauri::command]
+pub async fn remove_task_dependency(
+    task_id: String,
+    blocker_id: String,
+    state: State<'_, AppState>,
+) -> Result<()> {
+    let db = state.db.lock().await;
+    db.remove_task_dependency(&task_id, &blocker_id).await
+}
+
+#[tauri::command]
+pub async fn get_task_blockers(task_id: String, state: State<'_, AppState>) -> Result<Vec<Task>> {
+    let db = state.db.lock().await;
+    db.get_task_blockers(&task_id).await
+}
+
+#[tauri::command]
+pub async fn get_blocked_tasks(task_id: String, state: State<'_, AppState>) -> Result<Vec<Task>> {
+    let db = state.db.lock().await;
+    db.get_blocked_tasks(&task_id).await
+}
+
+#[tauri::command]
+pub async fn get_project_schedule(
+    project_id: String,
+    start: Option<DateTime<Utc>>,
+    state: State<'_, AppState>,
+) -> Result<ProjectSchedule> {
+    let db = state.db.lock().await;
+    db.get_project_schedule(&project_id, start.unwrap_or_else(Utc::now)).await
+}
+
+// Reminder commands
+#[tauri::command]
+pub async fn add_task_reminder(
+    task_id: String,
+    offset_minutes: i32,
+    state: State<'_, AppState>,
+) -> Result<Reminder> {
+    require_non_negative("offset_minutes", Some(offset_minutes))?;
+    let db = state.db.lock().await;
+    let reminder = db.create_reminder(&task_id, offset_minutes).await?;
+    state.reminder_scheduler.notify_changed();
+    Ok(reminder)
+}
+
+#[tauri::command]
+pub async fn get_task_reminders(task_id: String, state: State<'_, AppState>) -> Result<Vec<Reminder>> {
+    let db = state.db.lock().await;
+    db.get_reminders_by_task(&task_id).await
+}
+
+#[tauri::command]
+pub async fn delete_reminder(id: String, state: State<'_, AppState>) -> Result<()> {
+    let db = state.db.lock().await;
+    find_reminder(&db, &id).await?;
+    db.delete_reminder(&id).await?;
+    state.reminder_scheduler.notify_changed();
+    Ok(())
+}
+
+/// Fire the reminder again in `minutes` (10 by default)
+#[tauri::command]
+pub async fn snooze_reminder(
+    id: String,
+    minutes: Option<i64>,
+    state: State<'_, AppState>,
+) -> Result<Reminder> {
+    let db = state.db.lock().await;
+    let reminder = snooze(&db, &id, minutes, Utc::now()).await?;
+    state.reminder_scheduler.notify_changed();
+    Ok(reminder)
+}
+
+// GTD commands
+#[tauri::command]
+pub async fn get_inbox_tasks(state: State<'_, AppState>) -> Result<Vec<Task>> {
+    let db = state.db.lock().await;
+    db.get_tasks_by_status(TaskStatus::Inbox).await
+}
+
+#[tauri::command]
+pub async fn get_next_actions(state: State<'_, AppState>) -> Result<Vec<ContextGroup>> {
+    let db = state.db.lock().await;
+    next_actions(&db, Utc::now()).await
+}
+
+#[tauri::command]
+pub async fn get_waiting_tasks(state: State<'_, AppState>) -> Result<Vec<WaitingTask>> {
+    let db = state.db.lock().await;
+    db.get_waiting_tasks(Utc::now()).await
+}
+
+#[tauri::command]
+pub async fn get_overdue_tasks(state: State<'_, AppState>) -> Result<Vec<Task>> {
+    let db = state.db.lock().await;
+    db.get_overdue_tasks(Utc::now()).await
+}
+
+#[tauri::command]
+pub async fn get_tasks_due_this_week(
+    utc_offset_minutes: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<Task>> {
+    let db = state.db.lock().await;
+    due_this_week(&db, Utc::now(), utc_offset_minutes.unwrap_or(0)).await
+}
+
+#[tauri::command]
+pub async fn get_someday_tasks(state: State<'_, AppState>) -> Result<Vec<Task>> {
+    let db = state.db.lock().await;
+    db.get_tasks_by_status(TaskStatus::Someday).await
+}
+
+#[tauri::command]
+pub async fn get_weekly_review(
+    utc_offset_minutes: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<WeeklyReview> {
+    let utc_offset_minutes = utc_offset_minutes.unwrap_or(0);
+    gtd::validate_offset(utc_offset_minutes)?;
+    let db = state.db.lock().await;
+    db.get_weekly_review(Utc::now(), utc_offset_minutes).await
+}
+
+// Project commands
+#[tauri::command]
+pub async fn create_project(
+    request: CreateProjectRequest,
+    state: State<'_, AppState>,
+) -> Result<Project> {
+    validate_create_project(&request)?;
+    let db = state.db.lock().await;
+    db.create_project(request).await
+}
+
+#[tauri::command]
+pub async fn get_project(id: String, state: State<'_, AppState>) -> Result<Project> {
+    let db = state.db.lock().await;
+    db.get_project(&id).await
+}
+
+#[tauri::command]
+pub async fn get_projects(
+    limit: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<Project>> {
+    let db = state.db.lock().await;
+    db.get_projects(limit).await
+}
+
+#[tauri::command]
+pub async fn update_project(
+    request: UpdateProjectRequest,
+    state: State<'_, AppState>,
+) -> Result<Project> {
+    let db = state.db.lock().await;
+    validate_update_project(&db, &request).await?;
+    db.update_project(request).await
+}
+
+#[tauri::command]
+pub async fn delete_project(id: String, state: State<'_, AppState>) -> Result<()> {
+    let db = state.db.lock().await;
+    db.get_project(&id).await?;
+    db.delete_project(&id).await
+}
+
+#[tauri::command]
+pub async fn recompute_project_stats(state: State<'_, AppState>) -> Result<Vec<Project>> {
+    let db = state.db.lock().await;
+    db.recompute_project_stats().await
+}
+
+#[tauri::command]
+pub async fn search_projects(
+    query: String,
+    limit: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<Project>> {
+    let db = state.db.lock().await;
+    db.search_projects(&query, limit).await
+}
+
+// Time tracking commands
+#[tauri::command]
+pub async fn create_time_entry(
+    request: CreateTimeEntryRequest,
+    state: State<'_, AppState>,
+) -> Result<TimeEntry> {
+    let db = state.db.lock().await;
+    validate_create_time_entry(&db, &request).await?;
+    let entry = db.create_time_entry(request).await?;
+    state.calendar_feed.notify_changed();
+    Ok(entry)
+}
+
+#[tauri::command]
+pub async fn get_time_entries(
+    task_id: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<TimeEntry>> {
+    let db = state.db.lock().await;
+    db.get_task(&task_id).await?;
+    db.get_time_entries_by_task(&task_id).await
+}
+
+#[tauri::command]
+pub async fn stop_time_entry(
+    id: String,
+    end_time: Option<DateTime<Utc>>,
+    state: State<'_, AppState>,
+) -> Result<TimeEntry> {
+    let db = state.db.lock().await;
+    let entry = stop_entry(&db, &id, end_time.unwrap_or_else(Utc::now)).await?;
+    state.calendar_feed.notify_changed();
+    Ok(entry)
+}
+
+#[tauri::command]
+pub async fn delete_time_entry(id: String, state: State<'_, AppState>) -> Result<()> {
+    let db = state.db.lock().await;
+    db.delete_time_entry(&id).await?;
+    state.calendar_feed.notify_changed();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn start_timer(
+    task_id: String,
+    description: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<TimeEntry> {
+    let db = state.db.lock().await;
+    // Starting a timer stops the one that was running
+    let entry = db.start_timer(&task_id, description).await?;
+    state.calendar_feed.notify_changed();
+    Ok(entry)
+}
+
+#[tauri::command]
+pub async fn stop_timer(state: State<'_, AppState>) -> Result<Option<TimeEntry>> {
+    let db = state.db.lock().await;
+    let entry = db.stop_timer().await?;
+    state.calendar_feed.notify_changed();
+    Ok(entry)
+}
+
+#[tauri::command]
+pub async fn get_running_timer(state: State<'_, AppState>) -> Result<Option<TimeEntry>> {
+    let db = state.db.lock().await;
+    db.get_running_time_entry().await
+}
+
+#[tauri::command]
+pub async fn get_time_report(
+    request: TimeReportRequest,
+    state: State<'_, AppState>,
+) -> Result<Vec<TimeReportRow>> {
+    let db = state.db.lock().await;
+    time_report(&db, &request).await
+}
+
+#[tauri::command]
+pub async fn export_time_report_csv(
+    request: TimeReportRequest,
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<String> {
+    let db = state.db.lock().await;
+    let rows = time_report(&db, &request).await?;
+    drop(db);
+
+    let file = std::fs::File::create(&path)?;
+    time_report::write_csv(&rows, request.group_by, file)?;
+    Ok(path)
+}
+
+// Helpers shared by the commands above
+pub(crate) async fn time_report(db: &Database, request: &TimeReportRequest) -> Result<Vec<TimeReportRow>> {
+    request.validate()?;
+    let entries = db.get_tracked_entries(request.from, request.to).await?;
+    Ok(time_report::build_report(&entries, request))
+}
+
+pub(crate) async fn list_tasks(
+    db: &Database,
+    limit: Option<usize>,
+    status: Option<TaskStatus>,
+    project_id: Option<&str>,
+) -> Result<Vec<Task>> {
+    if status.is_none() && project_id.is_none() {
+        return db.get_tasks(limit).await;
+    }
+
+    let tasks = match project_id {
+        Some(project_id) => db.get_tasks_by_project(project_id).await?,
+        None => db.get_tasks(None).await?,
+    };
+    Ok(tasks
+        .into_iter()
+        .filter(|task| status.map_or(true, |status| task.status == status))
+        .take(limit.unwrap_or(usize::MAX))
+        .collect())
+}
+
+pub(crate) async fn next_actions(db: &Database, now: DateTime<Utc>) -> Result<Vec<ContextGroup>> {
+    Ok(gtd::group_by_context(db.get_next_actions(now).await?))
+}
+
+pub(crate) async fn due_this_week(db: &Database, now: DateTime<Utc>, utc_offset_minutes: i32) -> Result<Vec<Task>> {
+    gtd::validate_offset(utc_offset_minutes)?;
+    let (from, to) = gtd::week_bounds(now, utc_offset_minutes);
+    db.get_tasks_due_between(from, to).await
+}
+
+/// Upcoming occurrences after `start`, which is the current one. Without an
+/// offset the rule is expanded in the local time zone, as completing the task does.
+pub(crate) fn preview(
+    recurrence: &Recurrence,
+    start: DateTime<Utc>,
+    limit: Option<usize>,
+    utc_offset_minutes: Option<i32>,
+) -> Result<Vec<DateTime<Utc>>> {
+    recurrence.validate().map_err(AppError::InvalidInput)?;
+    let limit = limit.unwrap_or(10);
+    match utc_offset_minutes {
+        Some(minutes) => {
+            gtd::validate_offset(minutes)?;
+            let zone = FixedOffset::east_opt(minutes * 60)
+                .ok_or_else(|| AppError::InvalidInput(format!("Invalid UTC offset {}", minutes)))?;
+            Ok(recurrence.occurrences(start, zone).skip(1).take(limit).collect())
+        }
+        None => Ok(recurrence.occurrences(start, Local).skip(1).take(limit).collect()),
+    }
+}
+
+pub(crate) async fn task_occurrences(
+    db: &Database,
+    task_id: &str,
+    limit: Option<usize>,
+    utc_offset_minutes: Option<i32>,
+) -> Result<Vec<DateTime<Utc>>> {
+    let task = db.get_task(task_id).await?;
+    let Some(recurrence) = &task.recurrence else {
+        return Ok(Vec::new());
+    };
+    preview(recurrence, task.due_date.unwrap_or_else(Utc::now), limit, utc_offset_minutes)
+}
+
+pub(crate) async fn validate_create_task(db: &Database, request: &CreateTaskRequest) -> Result<()> {
+    require_text("Task title", &request.title)?;
+    require_non_negative("estimated_time", request.estimated_time)?;
+    if let Some(recurrence) = &request.recurrence {
+        recurrence.validate().map_err(AppError::InvalidInput)?;
+    }
+    if let Some(project_id) = &request.project_id {
+        db.get_project(project_id).await
+            .map_err(|_| AppError::NotFound(format!("Project '{}' not found", project_id)))?;
+    }
+    if let Some(parent_task_id) = &request.parent_task_id {
+        db.get_task(parent_task_id).await
+            .map_err(|_| AppError::NotFound(format!("Parent task '{}' not found", parent_task_id)))?;
+    }
+    Ok(())
+}
+
+pub(crate) async fn validate_update_task(db: &Database, request: &UpdateTaskRequest) -> Result<()> {
+    db.get_task(&request.id).await?;
+    if let Some(title) = &request.title {
+        require_text("Task title", title)?;
+    }
+    require_non_negative("estimated_time", request.estimated_time)?;
+    if let Some(recurrence) = &request.recurrence {
+        recurrence.validate().map_err(AppError::InvalidInput)?;
+    }
+    if let Some(project_id) = &request.project_id {
+        db.get_project(project_id).await
+            .map_err(|_| AppError::NotFound(format!("Project '{}' not found", project_id)))?;
+    }
+    if let Some(parent_task_id) = &request.parent_task_id {
+        if *parent_task_id == request.id {
+            return Err(AppError::InvalidInput("A task cannot be its own parent".to_string()));
+        }
+        db.get_task(parent_task_id).await
+            .map_err(|_| AppError::NotFound(format!("Parent task '{}' not found", parent_task_id)))?;
+    }
+    Ok(())
+}
+
+pub(crate) async fn find_reminder(db: &Database, id: &str) -> Result<Reminder> {
+    db.get_reminder(id).await
+        .map_err(|_| AppError::NotFound(format!("Reminder '{}' not found", id)))
+}
+
+pub(crate) async fn snooze(db: &Database, id: &str, minutes: Option<i64>, now: DateTime<Utc>) -> Result<Reminder> {
+    let minutes = minutes.unwrap_or(10);
+    if minutes <= 0 {
+        return Err(AppError::InvalidInput("Snooze minutes must be positive".to_string()));
+    }
+    find_reminder(db, id).await?;
+    db.snooze_reminder(id, now + chrono::Duration::minutes(minutes)).await
+}
+
+pub(crate) fn validate_create_project(request: &CreateProjectRequest) -> Result<()> {
+    require_text("Project name", &request.name)?;
+    require_ordered_dates(request.start_date, request.due_date)
+}
+
+pub(crate) async fn validate_update_project(db: &Database, request: &UpdateProjectRequest) -> Result<()> {
+    let project = db.get_project(&request.id).await?;
+    if let Some(name) = &request.name {
+        require_text("Project name", name)?;
+    }
+    require_ordered_dates(
+        request.start_date.or(project.start_date),
+        request.due_date.or(project.due_date),
+    )
+}
+
+pub(crate) async fn validate_create_time_entry(db: &Database, request: &CreateTimeEntryRequest) -> Result<()> {
+    db.get_task(&request.task_id).await
+        .map_err(|_| AppError::NotFound(format!("Task '{}' not found", request.task_id)))?;
+    if let Some(end_time) = request.end_time {
+        if end_time < request.start_time {
+            return Err(AppError::InvalidInput("end_time must not be before start_time".to_string()));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) async fn stop_entry(db: &Database, id: &str, end_time: DateTime<Utc>) -> Result<TimeEntry> {
+    let entry = db.get_time_entry(id).await?;
+    if entry.end_time.is_some() {
+        return Err(AppError::InvalidInput(format!("Time entry '{}' is already stopped", id)));
+    }
+    if end_time < entry.start_time {
+        return Err(AppError::InvalidInput("end_time must not be before start_time".to_string()));
+    }
+    db.update_time_entry_end(id, end_time).await
+}
+
+fn require_text(field: &str, value: &str) -> Result<()> {
+    if value.trim().is_empty() {
+        return Err(AppError::InvalidInput(format!("{} cannot be empty", field)));
+    }
+    Ok(())
+}
+
+fn require_non_negative(field: &str, value: Option<i32>) -> Result<()> {
+    match value {
+        Some(value) if value < 0 => Err(AppError::InvalidInput(format!("{} cannot be negative", field))),
+        _ => Ok(()),
+    }
+}
+
+fn require_ordered_dates(start: Option<DateTime<Utc>>, due: Option<DateTime<Utc>>) -> Result<()> {
+    match (start, due) {
+        (Some(start), Some(due)) if due < start => {
+            Err(AppError::InvalidInput("due_date must not be before start_date".to_string()))
+        }
+        _ => Ok(()),
+    }
+}
@@ -8,7 +8,6 @@ mod tests {
     use tempfile::tempdir;
     use tokio;
     use std::sync::Arc;
-    use tokio::sync::Mutex;
     use serde_json::Value;
 
     // Test helper functions that work directly with AppState instead of tauri::State
@@ -106,18 +105,14 @@ mod tests {
     }
 
     async fn create_test_app_state() -> AppState {
-        let temp_dir = tempdir().unwrap();
-        let db_path = temp_dir.path().join("test.db");
+        // Keep the directory: the pool opens new connections after this returns
+        let temp_dir = tempdir().unwrap().into_path();
+        let db_path = temp_dir.join("test.db");
         let db = Database::new_with_path(db_path.to_str().unwrap()).await.unwrap();
 
         // Default graph is now created automatically in migrate()
 
-        let sync_manager = crate::sync::WebDAVSyncManager::new();
-
-        AppState {
-            db: Arc::new(Mutex::new(db)),
-            sync_manager: Arc::new(Mutex::new(sync_manager)),
-        }
+        AppState::from_database(db)
     }
 
     #[tokio::test]
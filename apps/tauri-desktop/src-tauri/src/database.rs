@@ -1,25 +1,48 @@
+use crate::cards::{self, Card, CardStats, Grade, ReviewState};
 use crate::error::{AppError, Result};
 
+mod fts;
+
 #[cfg(test)]
 mod tests;
 #[cfg(test)]
 mod integration_tests;
 use crate::models::{
-    Graph, Page, Block, Note, Tag, Settings, Task, Project, TimeEntry,
+    Graph, Page, Block, Link, Note, Tag, Settings, Task, Project, TimeEntry, Reminder,
+    TaskStatus, TaskPriority, ProjectStatus,
     CreateGraphRequest, UpdateGraphRequest,
     CreatePageRequest, UpdatePageRequest,
-    CreateBlockRequest, UpdateBlockRequest,
+    CreateBlockRequest, UpdateBlockRequest, CreateLinkRequest,
     CreateNoteRequest, UpdateNoteRequest, CreateTagRequest,
     CreateTaskRequest, UpdateTaskRequest,
     CreateProjectRequest, UpdateProjectRequest,
     CreateTimeEntryRequest,
-    SearchRequest, SearchResult
+    SearchRequest, SearchResult,
+    decode_datetime, decode_optional_datetime,
+};
+use crate::sync::FileSyncInfo;
+use crate::sync::crdt::{
+    self, BlockOp, BlockOpKind, BlockSnapshot, BlockTarget, Hlc, HybridClock, OpBatch, VersionVector,
 };
-use chrono::Utc;
-use sqlx::{sqlite::{SqlitePool, SqlitePoolOptions}, Row};
+use crate::tasks::block_task;
+use crate::tasks::dependencies::{self, ProjectSchedule, ScheduleInput, TaskDependency};
+use crate::tasks::gtd::{self, WaitingTask, WeeklyReview};
+use crate::tasks::recurrence::Recurrence;
+use crate::tasks::rollup::{self, TaskWeight, WEIGHTED_PROGRESS_KEY};
+use crate::tasks::time_report::TrackedEntry;
+use chrono::{DateTime, TimeZone, Utc};
+use sqlx::{sqlite::{SqlitePool, SqlitePoolOptions}, FromRow, Row};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::str::FromStr;
 use std::path::PathBuf;
 
+// Version of the schema created by `migrate`, recorded in backups so a restore
+// can tell whether an archive comes from a newer app
+pub const SCHEMA_VERSION: i64 = 8;
+
+// Setting holding this device's id, which is also its HLC node id
+pub const DEVICE_ID_KEY: &str = "device_id";
+
 #[derive(Debug)]
 pub struct Database {
     pool: SqlitePool,
@@ -96,7 +119,20 @@ impl Database {
         Ok(db)
     }
 
-    fn get_database_path() -> Result<PathBuf> {
+    // Open a database file read-only and as-is, without running migrations
+    pub async fn open_snapshot(db_path: &str) -> Result<Self> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(
+                sqlx::sqlite::SqliteConnectOptions::from_str(&format!("sqlite:{}", db_path))?
+                    .read_only(true)
+            )
+            .await?;
+
+        Ok(Self { pool })
+    }
+
+    pub fn get_database_path() -> Result<PathBuf> {
         let app_data_dir = dirs::data_dir()
             .ok_or_else(|| AppError::Internal("Could not find app data directory".to_string()))?;
         
@@ -164,6 +200,8 @@ impl Database {
         )
         .execute(&self.pool)
         .await?;
+        // Calendar component an imported event came from
+        self.add_column_if_missing("blocks", "ics_uid", "TEXT").await?;
 
         // Create legacy tables for backward compatibility
         sqlx::query(
@@ -299,57 +337,48 @@ impl Database {
         )
         .execute(&self.pool)
         .await?;
-        
+
         // Create triggers to keep FTS tables in sync
+        self.create_fts_triggers(&["blocks", "pages", "notes"]).await?;
 
-        // Blocks FTS triggers
+        // Create block operation log for CRDT sync
         sqlx::query(
             r#"
-            CREATE TRIGGER IF NOT EXISTS blocks_fts_insert AFTER INSERT ON blocks BEGIN
-                INSERT INTO blocks_fts(id, content, page_id, graph_id) VALUES (new.id, new.content, new.page_id, new.graph_id);
-            END
+            CREATE TABLE IF NOT EXISTS block_ops (
+                id TEXT PRIMARY KEY,
+                device_id TEXT NOT NULL,
+                block_id TEXT NOT NULL,
+                page_id TEXT NOT NULL,
+                op TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            )
             "#,
         )
         .execute(&self.pool)
         .await?;
 
-        sqlx::query(
-            r#"
-            CREATE TRIGGER IF NOT EXISTS blocks_fts_delete AFTER DELETE ON blocks BEGIN
-                DELETE FROM blocks_fts WHERE id = old.id;
-            END
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_block_ops_page_id ON block_ops(page_id)")
+            .execute(&self.pool)
+            .await?;
 
-        sqlx::query(
-            r#"
-            CREATE TRIGGER IF NOT EXISTS blocks_fts_update AFTER UPDATE ON blocks BEGIN
-                DELETE FROM blocks_fts WHERE id = old.id;
-                INSERT INTO blocks_fts(id, content, page_id, graph_id) VALUES (new.id, new.content, new.page_id, new.graph_id);
-            END
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_block_ops_device ON block_ops(device_id, id)")
+            .execute(&self.pool)
+            .await?;
 
-        // Pages FTS triggers
-        sqlx::query(
-            r#"
-            CREATE TRIGGER IF NOT EXISTS pages_fts_insert AFTER INSERT ON pages BEGIN
-                INSERT INTO pages_fts(id, name, title, graph_id) VALUES (new.id, new.name, new.title, new.graph_id);
-            END
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_block_ops_block ON block_ops(block_id, id)")
+            .execute(&self.pool)
+            .await?;
 
+        // Blocks written without going through record_block_change, e.g. by imports,
+        // restores or a page deletion, wait here until they are recorded as ops.
+        // The triggers avoid OR IGNORE, which an upsert on blocks would override.
         sqlx::query(
             r#"
-            CREATE TRIGGER IF NOT EXISTS pages_fts_delete AFTER DELETE ON pages BEGIN
-                DELETE FROM pages_fts WHERE id = old.id;
-            END
+            CREATE TABLE IF NOT EXISTS block_ops_pending (
+                block_id TEXT NOT NULL,
+                page_id TEXT NOT NULL,
+                PRIMARY KEY (block_id, page_id)
+            )
             "#,
         )
         .execute(&self.pool)
@@ -357,20 +386,22 @@ impl Database {
 
         sqlx::query(
             r#"
-            CREATE TRIGGER IF NOT EXISTS pages_fts_update AFTER UPDATE ON pages BEGIN
-                DELETE FROM pages_fts WHERE id = old.id;
-                INSERT INTO pages_fts(id, name, title, graph_id) VALUES (new.id, new.name, new.title, new.graph_id);
+            CREATE TRIGGER IF NOT EXISTS blocks_ops_pending_insert AFTER INSERT ON blocks BEGIN
+                INSERT INTO block_ops_pending (block_id, page_id) SELECT new.id, new.page_id
+                    WHERE NOT EXISTS (SELECT 1 FROM block_ops_pending WHERE block_id = new.id AND page_id = new.page_id);
             END
             "#,
         )
         .execute(&self.pool)
         .await?;
 
-        // Legacy notes FTS triggers
         sqlx::query(
             r#"
-            CREATE TRIGGER IF NOT EXISTS notes_fts_insert AFTER INSERT ON notes BEGIN
-                INSERT INTO notes_fts(id, title, content) VALUES (new.id, new.title, new.content);
+            CREATE TRIGGER IF NOT EXISTS blocks_ops_pending_update AFTER UPDATE ON blocks BEGIN
+                INSERT INTO block_ops_pending (block_id, page_id) SELECT old.id, old.page_id
+                    WHERE NOT EXISTS (SELECT 1 FROM block_ops_pending WHERE block_id = old.id AND page_id = old.page_id);
+                INSERT INTO block_ops_pending (block_id, page_id) SELECT new.id, new.page_id
+                    WHERE NOT EXISTS (SELECT 1 FROM block_ops_pending WHERE block_id = new.id AND page_id = new.page_id);
             END
             "#,
         )
@@ -379,60 +410,77 @@ impl Database {
 
         sqlx::query(
             r#"
-            CREATE TRIGGER IF NOT EXISTS notes_fts_delete AFTER DELETE ON notes BEGIN
-                DELETE FROM notes_fts WHERE id = old.id;
+            CREATE TRIGGER IF NOT EXISTS blocks_ops_pending_delete AFTER DELETE ON blocks BEGIN
+                INSERT INTO block_ops_pending (block_id, page_id) SELECT old.id, old.page_id
+                    WHERE NOT EXISTS (SELECT 1 FROM block_ops_pending WHERE block_id = old.id AND page_id = old.page_id);
             END
             "#,
         )
         .execute(&self.pool)
         .await?;
 
+        // Create links table for bidirectional linking
         sqlx::query(
             r#"
-            CREATE TRIGGER IF NOT EXISTS notes_fts_update AFTER UPDATE ON notes BEGIN
-                DELETE FROM notes_fts WHERE id = old.id;
-                INSERT INTO notes_fts(id, title, content) VALUES (new.id, new.title, new.content);
-            END
+            CREATE TABLE IF NOT EXISTS links (
+                id TEXT PRIMARY KEY,
+                source_type TEXT NOT NULL CHECK (source_type IN ('page', 'block')),
+                source_id TEXT NOT NULL,
+                target_type TEXT NOT NULL CHECK (target_type IN ('page', 'block')),
+                target_id TEXT NOT NULL,
+                link_type TEXT NOT NULL CHECK (link_type IN ('page-reference', 'block-reference')),
+                context TEXT,
+                position INTEGER,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                UNIQUE(source_type, source_id, target_type, target_id, position)
+            )
             "#,
         )
         .execute(&self.pool)
         .await?;
 
-        // Create default graph if it doesn't exist (for testing compatibility)
-        sqlx::query(
-            r#"
-            INSERT OR IGNORE INTO graphs (id, name, path, settings, created_at, updated_at)
-            VALUES ('default', 'Default Graph', 'default', '{}', datetime('now'), datetime('now'))
-            "#
-        )
-        .execute(&self.pool)
-        .await?;
+        // Create indexes for links table
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_links_source ON links(source_type, source_id)")
+            .execute(&self.pool)
+            .await?;
 
-        Ok(())
-    }
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_links_target ON links(target_type, target_id)")
+            .execute(&self.pool)
+            .await?;
 
-    // Database optimization method
-    async fn optimize_database(&self) -> Result<()> {
-        // Run ANALYZE to update query planner statistics
-        sqlx::query("ANALYZE")
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_links_type ON links(link_type)")
             .execute(&self.pool)
             .await?;
 
-        // Optimize FTS tables
-        sqlx::query("INSERT INTO blocks_fts(blocks_fts) VALUES('optimize')")
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_links_created_at ON links(created_at)")
             .execute(&self.pool)
-            .await
-            .ok(); // Ignore errors if FTS table doesn't exist
+            .await?;
+
+        // Create page aliases table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS page_aliases (
+                id TEXT PRIMARY KEY,
+                page_id TEXT NOT NULL,
+                alias TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (page_id) REFERENCES pages(id) ON DELETE CASCADE,
+                UNIQUE(alias)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
 
-        sqlx::query("INSERT INTO pages_fts(pages_fts) VALUES('optimize')")
+        // Create indexes for page aliases table
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_page_aliases_page_id ON page_aliases(page_id)")
             .execute(&self.pool)
-            .await
-            .ok(); // Ignore errors if FTS table doesn't exist
+            .await?;
 
-        sqlx::query("INSERT INTO notes_fts(notes_fts) VALUES('optimize')")
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_page_aliases_alias ON page_aliases(alias)")
             .execute(&self.pool)
-            .await
-            .ok(); // Ignore errors if FTS table doesn't exist
+            .await?;
 
         // Create task management tables - projects first, then tasks
         sqlx::query(
@@ -469,7 +517,9 @@ impl Database {
                 status TEXT NOT NULL DEFAULT 'todo',
                 priority TEXT NOT NULL DEFAULT 'medium',
                 due_date TEXT,
+                scheduled_date TEXT,
                 completed_at TEXT,
+                status_changed_at TEXT,
                 estimated_time INTEGER,
                 actual_time INTEGER,
                 project_id TEXT,
@@ -479,9 +529,11 @@ impl Database {
                 tags TEXT DEFAULT '[]',
                 contexts TEXT DEFAULT '[]',
                 recurrence TEXT,
+                block_id TEXT,
                 created_at TEXT NOT NULL,
                 updated_at TEXT NOT NULL,
                 created_by TEXT,
+                FOREIGN KEY (block_id) REFERENCES blocks(id) ON DELETE CASCADE,
                 FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE SET NULL,
                 FOREIGN KEY (parent_task_id) REFERENCES tasks(id) ON DELETE CASCADE
             )
@@ -490,6 +542,17 @@ impl Database {
         .execute(&self.pool)
         .await?;
 
+        // Columns added after the tasks table was first released
+        self.add_column_if_missing("tasks", "scheduled_date", "TEXT").await?;
+        self.add_column_if_missing("tasks", "block_id", "TEXT REFERENCES blocks(id) ON DELETE CASCADE").await?;
+        self.add_column_if_missing("tasks", "status_changed_at", "TEXT").await?;
+        // Calendar component an imported to-do came from
+        self.add_column_if_missing("tasks", "ics_uid", "TEXT").await?;
+        // Best guess for tasks that predate the column
+        sqlx::query("UPDATE tasks SET status_changed_at = updated_at WHERE status_changed_at IS NULL")
+            .execute(&self.pool)
+            .await?;
+
         sqlx::query(
             r#"
             CREATE TABLE IF NOT EXISTS task_time_entries (
@@ -507,6 +570,91 @@ impl Database {
         .execute(&self.pool)
         .await?;
 
+        // `task_id` can't start before `blocker_id` is finished
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS task_dependencies (
+                id TEXT PRIMARY KEY,
+                task_id TEXT NOT NULL,
+                blocker_id TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                UNIQUE (task_id, blocker_id),
+                CHECK (task_id != blocker_id),
+                FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE,
+                FOREIGN KEY (blocker_id) REFERENCES tasks(id) ON DELETE CASCADE
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS reminders (
+                id TEXT PRIMARY KEY,
+                task_id TEXT NOT NULL,
+                offset_minutes INTEGER NOT NULL DEFAULT 0,
+                remind_at TEXT NOT NULL,
+                snoozed_until TEXT,
+                fired_at TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                UNIQUE (task_id, offset_minutes),
+                FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Flashcard review state, keyed by the card's block
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS cards (
+                id TEXT PRIMARY KEY,
+                ease REAL NOT NULL DEFAULT 2.5,
+                interval_days INTEGER NOT NULL DEFAULT 0,
+                repetitions INTEGER NOT NULL DEFAULT 0,
+                lapses INTEGER NOT NULL DEFAULT 0,
+                due_at TEXT NOT NULL,
+                last_reviewed_at TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                FOREIGN KEY (id) REFERENCES blocks(id) ON DELETE CASCADE
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS card_reviews (
+                id TEXT PRIMARY KEY,
+                card_id TEXT NOT NULL,
+                grade TEXT NOT NULL CHECK (grade IN ('again', 'hard', 'good', 'easy')),
+                interval_days INTEGER NOT NULL,
+                ease REAL NOT NULL,
+                reviewed_at TEXT NOT NULL,
+                FOREIGN KEY (card_id) REFERENCES cards(id) ON DELETE CASCADE
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_cards_due_at ON cards(due_at)")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_card_reviews_card_id ON card_reviews(card_id)")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_card_reviews_reviewed_at ON card_reviews(reviewed_at)")
+            .execute(&self.pool)
+            .await?;
+
         // Create indexes for task management tables
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_tasks_status ON tasks(status)")
             .execute(&self.pool)
@@ -528,6 +676,22 @@ impl Database {
             .execute(&self.pool)
             .await?;
 
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_tasks_scheduled_date ON tasks(scheduled_date)")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS idx_tasks_block_id ON tasks(block_id) WHERE block_id IS NOT NULL")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_tasks_ics_uid ON tasks(ics_uid) WHERE ics_uid IS NOT NULL")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_blocks_ics_uid ON blocks(ics_uid) WHERE ics_uid IS NOT NULL")
+            .execute(&self.pool)
+            .await?;
+
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_projects_status ON projects(status)")
             .execute(&self.pool)
             .await?;
@@ -540,6 +704,18 @@ impl Database {
             .execute(&self.pool)
             .await?;
 
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_time_entries_start_time ON task_time_entries(start_time)")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_task_dependencies_blocker_id ON task_dependencies(blocker_id)")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_reminders_task_id ON reminders(task_id)")
+            .execute(&self.pool)
+            .await?;
+
         // Create FTS tables for task search
         sqlx::query(
             r#"
@@ -571,68 +747,83 @@ impl Database {
         .execute(&self.pool)
         .await?;
 
-        // Create links table for bidirectional linking
+        self.create_fts_triggers(&["tasks", "projects"]).await?;
+
+        // What each file looked like when it was last synced with a sync target, so
+        // a remote deletion isn't taken for a new local file after a restart. The
+        // records belong to this device and are left out of backups.
         sqlx::query(
             r#"
-            CREATE TABLE IF NOT EXISTS links (
-                id TEXT PRIMARY KEY,
-                source_type TEXT NOT NULL CHECK (source_type IN ('page', 'block')),
-                source_id TEXT NOT NULL,
-                target_type TEXT NOT NULL CHECK (target_type IN ('page', 'block')),
-                target_id TEXT NOT NULL,
-                link_type TEXT NOT NULL CHECK (link_type IN ('page-reference', 'block-reference')),
-                context TEXT,
-                position INTEGER,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL,
-                UNIQUE(source_type, source_id, target_type, target_id, position)
+            CREATE TABLE IF NOT EXISTS file_sync_info (
+                location TEXT NOT NULL,
+                file_path TEXT NOT NULL,
+                info TEXT NOT NULL,
+                PRIMARY KEY (location, file_path)
             )
             "#,
         )
         .execute(&self.pool)
         .await?;
 
-        // Create page aliases table
+        // Row-level change log for point-in-time recovery, filled by triggers that are
+        // only installed while recovery is enabled
         sqlx::query(
             r#"
-            CREATE TABLE IF NOT EXISTS page_aliases (
-                id TEXT PRIMARY KEY,
-                page_id TEXT NOT NULL,
-                alias TEXT NOT NULL,
-                created_at TEXT NOT NULL,
-                FOREIGN KEY (page_id) REFERENCES pages(id) ON DELETE CASCADE,
-                UNIQUE(alias)
+            CREATE TABLE IF NOT EXISTS change_log (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                table_name TEXT NOT NULL,
+                row_key TEXT NOT NULL,
+                op TEXT NOT NULL,
+                row_data TEXT,
+                changed_at TEXT NOT NULL
             )
             "#,
         )
         .execute(&self.pool)
         .await?;
 
-        // Create indexes for links table
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_links_source ON links(source_type, source_id)")
-            .execute(&self.pool)
-            .await?;
+        // Create default graph if it doesn't exist (for testing compatibility)
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO graphs (id, name, path, settings, created_at, updated_at)
+            VALUES ('default', 'Default Graph', 'default', '{}', datetime('now'), datetime('now'))
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
 
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_links_target ON links(target_type, target_id)")
-            .execute(&self.pool)
-            .await?;
+        Ok(())
+    }
 
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_links_type ON links(link_type)")
-            .execute(&self.pool)
+    async fn add_column_if_missing(&self, table: &str, column: &str, definition: &str) -> Result<()> {
+        let exists: Option<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info(?) WHERE name = ?")
+            .bind(table)
+            .bind(column)
+            .fetch_optional(&self.pool)
             .await?;
+        if exists.is_none() {
+            sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+                .execute(&self.pool)
+                .await?;
+        }
 
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_links_created_at ON links(created_at)")
-            .execute(&self.pool)
-            .await?;
+        Ok(())
+    }
 
-        // Create indexes for page aliases table
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_page_aliases_page_id ON page_aliases(page_id)")
+    // Database optimization method
+    async fn optimize_database(&self) -> Result<()> {
+        // Run ANALYZE to update query planner statistics
+        sqlx::query("ANALYZE")
             .execute(&self.pool)
             .await?;
 
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_page_aliases_alias ON page_aliases(alias)")
-            .execute(&self.pool)
-            .await?;
+        // Optimize FTS tables
+        for (table, _) in fts::FTS_COLUMNS {
+            sqlx::query(&format!("INSERT INTO {0}_fts({0}_fts) VALUES('optimize')", table))
+                .execute(&self.pool)
+                .await
+                .ok(); // Ignore errors if FTS table doesn't exist
+        }
 
         // Run VACUUM to reclaim space (only if needed)
         // Note: This is expensive, so we only do it occasionally
@@ -648,6 +839,11 @@ impl Database {
         &self.pool
     }
 
+    // Close every connection, e.g. before the database file is replaced
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
     // Note operations
     pub async fn create_note(&self, request: CreateNoteRequest) -> Result<Note> {
         let mut note = Note::new(request.title, request.content);
@@ -919,14 +1115,48 @@ impl Database {
         Ok(())
     }
     
-    pub async fn get_all_settings(&self) -> Result<Vec<Settings>> {
-        let settings = sqlx::query_as::<_, Settings>(
-            "SELECT key, value, updated_at FROM settings"
+    // File sync records
+    /// Records of the files last synced with the sync target at `location`
+    pub async fn get_file_sync_info(&self, location: &str) -> Result<HashMap<String, FileSyncInfo>> {
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            "SELECT file_path, info FROM file_sync_info WHERE location = ?"
         )
+        .bind(location)
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(settings)
+        rows.into_iter()
+            .map(|(file_path, info)| Ok((file_path, serde_json::from_str(&info)?)))
+            .collect()
+    }
+
+    /// Replace the records kept for the sync target at `location`
+    pub async fn save_file_sync_info(&self, location: &str, records: &HashMap<String, FileSyncInfo>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM file_sync_info WHERE location = ?")
+            .bind(location)
+            .execute(&mut *tx)
+            .await?;
+        for (file_path, info) in records {
+            sqlx::query("INSERT INTO file_sync_info (location, file_path, info) VALUES (?, ?, ?)")
+                .bind(location)
+                .bind(file_path)
+                .bind(serde_json::to_string(info)?)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get_all_settings(&self) -> Result<Vec<Settings>> {
+        let settings = sqlx::query_as::<_, Settings>(
+            "SELECT key, value, updated_at FROM settings"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(settings)
     }
 
     // Graph operations
@@ -1061,6 +1291,23 @@ impl Database {
         Ok(page)
     }
 
+    /// The journal page of a `YYYY-MM-DD` date, if it exists
+    pub async fn get_journal_page(&self, graph_id: &str, journal_date: &str) -> Result<Option<Page>> {
+        let page = sqlx::query_as::<_, Page>(
+            r#"
+            SELECT id, name, title, properties, tags, is_journal, journal_date, created_at, updated_at, graph_id
+            FROM pages WHERE graph_id = ? AND is_journal = 1 AND journal_date = ?
+            ORDER BY created_at LIMIT 1
+            "#
+        )
+        .bind(graph_id)
+        .bind(journal_date)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(page)
+    }
+
     pub async fn get_pages_by_graph(&self, graph_id: &str) -> Result<Vec<Page>> {
         let pages = sqlx::query_as::<_, Page>(
             r#"
@@ -1176,6 +1423,77 @@ impl Database {
         Ok(())
     }
 
+    pub async fn add_page_alias(&self, page_id: &str, alias: &str) -> Result<()> {
+        sqlx::query("INSERT INTO page_aliases (id, page_id, alias, created_at) VALUES (?, ?, ?, ?)")
+            .bind(uuid::Uuid::new_v4().to_string())
+            .bind(page_id)
+            .bind(alias)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_page_aliases(&self, page_id: &str) -> Result<Vec<String>> {
+        let aliases = sqlx::query_scalar("SELECT alias FROM page_aliases WHERE page_id = ? ORDER BY created_at, alias")
+            .bind(page_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(aliases)
+    }
+
+    // Link operations
+    pub async fn create_link(&self, request: CreateLinkRequest) -> Result<Link> {
+        let now = Utc::now();
+        let link = Link {
+            id: uuid::Uuid::new_v4().to_string(),
+            source_type: request.source_type,
+            source_id: request.source_id,
+            target_type: request.target_type,
+            target_id: request.target_id,
+            link_type: request.link_type,
+            context: request.context,
+            position: request.position,
+            created_at: now,
+            updated_at: now,
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO links (id, source_type, source_id, target_type, target_id, link_type, context, position, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&link.id)
+        .bind(&link.source_type)
+        .bind(&link.source_id)
+        .bind(&link.target_type)
+        .bind(&link.target_id)
+        .bind(&link.link_type)
+        .bind(&link.context)
+        .bind(link.position)
+        .bind(link.created_at.to_rfc3339())
+        .bind(link.updated_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(link)
+    }
+
+    pub async fn get_links_to(&self, target_type: &str, target_id: &str) -> Result<Vec<Link>> {
+        let links = sqlx::query_as::<_, Link>(
+            "SELECT * FROM links WHERE target_type = ? AND target_id = ? ORDER BY created_at, position"
+        )
+        .bind(target_type)
+        .bind(target_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(links)
+    }
+
     // Block operations
     pub async fn create_block(&self, request: CreateBlockRequest) -> Result<Block> {
         let mut block = Block::new(request.content, request.page_id, request.graph_id);
@@ -1213,6 +1531,10 @@ impl Database {
         .execute(&self.pool)
         .await?;
 
+        if self.sync_block_task(&block).await?.is_some() {
+            return self.get_block(&block.id).await;
+        }
+
         Ok(block)
     }
 
@@ -1244,6 +1566,51 @@ impl Database {
         Ok(blocks)
     }
 
+    /// Blocks with a `SCHEDULED:` line
+    pub async fn get_scheduled_blocks(&self) -> Result<Vec<Block>> {
+        let blocks = sqlx::query_as::<_, Block>(
+            r#"
+            SELECT id, content, parent_id, properties, refs, "order", collapsed, created_at, updated_at, page_id, graph_id
+            FROM blocks WHERE content LIKE '%SCHEDULED:%' ORDER BY created_at
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(blocks)
+    }
+
+    /// Remember the calendar component `uid` a block was imported from
+    pub async fn set_block_ics_uid(&self, block_id: &str, uid: &str) -> Result<()> {
+        sqlx::query("UPDATE blocks SET ics_uid = ? WHERE id = ?")
+            .bind(uid)
+            .bind(block_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Remember the calendar component `uid` a task was imported from
+    pub async fn set_task_ics_uid(&self, task_id: &str, uid: &str) -> Result<()> {
+        sqlx::query("UPDATE tasks SET ics_uid = ? WHERE id = ?")
+            .bind(uid)
+            .bind(task_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Whether a task or block imported from the calendar component `uid` still exists
+    pub async fn has_ics_uid(&self, uid: &str) -> Result<bool> {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM tasks WHERE ics_uid = ?1) OR EXISTS (SELECT 1 FROM blocks WHERE ics_uid = ?1)",
+        )
+        .bind(uid)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(exists)
+    }
+
     pub async fn update_block(&self, request: UpdateBlockRequest) -> Result<Block> {
         let now = Utc::now();
 
@@ -1301,10 +1668,21 @@ impl Database {
                 .await?;
         }
 
-        self.get_block(&request.id).await
+        let block = self.get_block(&request.id).await?;
+        // Syncing the task may put back parts of the block, e.g. the marker of a blocked task
+        if request.content.is_some() && self.sync_block_task(&block).await?.is_some() {
+            return self.get_block(&request.id).await;
+        }
+
+        Ok(block)
     }
 
     pub async fn delete_block(&self, id: &str) -> Result<()> {
+        // The foreign key removes the block's task too, but this keeps project stats current
+        if let Some(task) = self.get_task_by_block(id).await? {
+            self.delete_task(&task.id).await?;
+        }
+
         sqlx::query("DELETE FROM blocks WHERE id = ?")
             .bind(id)
             .execute(&self.pool)
@@ -1313,422 +1691,1904 @@ impl Database {
         Ok(())
     }
 
-    // Task management operations
-    #[allow(dead_code)]
-    pub async fn create_task(&self, request: CreateTaskRequest) -> Result<Task> {
-        let task = Task {
-            id: uuid::Uuid::new_v4().to_string(),
-            title: request.title,
-            description: request.description,
-            status: "todo".to_string(),
-            priority: request.priority.unwrap_or_else(|| "medium".to_string()),
-            due_date: request.due_date,
-            completed_at: None,
-            estimated_time: request.estimated_time,
-            actual_time: None,
-            project_id: request.project_id,
-            parent_task_id: request.parent_task_id,
-            linked_notes: serde_json::to_string(&request.linked_notes.unwrap_or_default()).unwrap_or_else(|_| "[]".to_string()),
-            linked_files: serde_json::to_string(&request.linked_files.unwrap_or_default()).unwrap_or_else(|_| "[]".to_string()),
-            tags: serde_json::to_string(&request.tags.unwrap_or_default()).unwrap_or_else(|_| "[]".to_string()),
-            contexts: serde_json::to_string(&request.contexts.unwrap_or_default()).unwrap_or_else(|_| "[]".to_string()),
-            recurrence: None,
-            created_at: Utc::now(),
-            updated_at: Utc::now(),
-            created_by: None,
-        };
-
-        sqlx::query(
-            r#"
-            INSERT INTO tasks (
-                id, title, description, status, priority, due_date, estimated_time,
-                project_id, parent_task_id, linked_notes, linked_files, tags, contexts,
-                created_at, updated_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            "#,
-        )
-        .bind(&task.id)
-        .bind(&task.title)
-        .bind(&task.description)
-        .bind(&task.status)
-        .bind(&task.priority)
-        .bind(task.due_date.map(|d| d.to_rfc3339()))
-        .bind(task.estimated_time)
-        .bind(&task.project_id)
-        .bind(&task.parent_task_id)
-        .bind(&task.linked_notes)
-        .bind(&task.linked_files)
-        .bind(&task.tags)
-        .bind(&task.contexts)
-        .bind(task.created_at.to_rfc3339())
-        .bind(task.updated_at.to_rfc3339())
-        .execute(&self.pool)
-        .await?;
-
-        Ok(task)
-    }
-
-    pub async fn get_task(&self, id: &str) -> Result<Task> {
+    // Tasks written in blocks
+    pub async fn get_task_by_block(&self, block_id: &str) -> Result<Option<Task>> {
         let task = sqlx::query_as::<_, Task>(
             r#"
             SELECT id, title, description, status, priority, due_date, completed_at,
                    estimated_time, actual_time, project_id, parent_task_id,
-                   linked_notes, linked_files, tags, contexts, recurrence,
+                   linked_notes, linked_files, tags, contexts, recurrence, scheduled_date, block_id,
                    created_at, updated_at, created_by
-            FROM tasks WHERE id = ?
+            FROM tasks WHERE block_id = ?
             "#
         )
-        .bind(id)
-        .fetch_one(&self.pool)
+        .bind(block_id)
+        .fetch_optional(&self.pool)
         .await?;
 
         Ok(task)
     }
 
-    pub async fn update_task(&self, request: UpdateTaskRequest) -> Result<Task> {
-        let now = Utc::now();
-
-        if let Some(title) = &request.title {
-            sqlx::query("UPDATE tasks SET title = ?, updated_at = ? WHERE id = ?")
-                .bind(title)
-                .bind(now.to_rfc3339())
-                .bind(&request.id)
-                .execute(&self.pool)
-                .await?;
-        }
-
-        if let Some(description) = &request.description {
-            sqlx::query("UPDATE tasks SET description = ?, updated_at = ? WHERE id = ?")
-                .bind(description)
-                .bind(now.to_rfc3339())
-                .bind(&request.id)
-                .execute(&self.pool)
-                .await?;
-        }
-
-        if let Some(status) = &request.status {
-            sqlx::query("UPDATE tasks SET status = ?, updated_at = ? WHERE id = ?")
-                .bind(status)
-                .bind(now.to_rfc3339())
-                .bind(&request.id)
-                .execute(&self.pool)
-                .await?;
+    // Create or update the task written in a block so it matches the block's content. A block
+    // that stops being a task only unlinks it, so its time entries, dependencies and reminders
+    // stay; the task goes away with the block itself.
+    pub async fn sync_block_task(&self, block: &Block) -> Result<Option<Task>> {
+        let existing = self.get_task_by_block(&block.id).await?;
+        let Some(parsed) = block_task::parse(&block.content) else {
+            if let Some(task) = existing {
+                sqlx::query("UPDATE tasks SET block_id = NULL, updated_at = ? WHERE id = ?")
+                    .bind(Utc::now().to_rfc3339())
+                    .bind(&task.id)
+                    .execute(&self.pool)
+                    .await?;
+            }
+            return Ok(None);
+        };
 
-            // If marking as completed, set completed_at
-            if status == "done" {
-                sqlx::query("UPDATE tasks SET completed_at = ?, updated_at = ? WHERE id = ?")
-                    .bind(now.to_rfc3339())
-                    .bind(now.to_rfc3339())
-                    .bind(&request.id)
+        let task = match existing {
+            Some(task) => task,
+            None => {
+                let task = self.create_task(CreateTaskRequest {
+                    title: parsed.title.clone(),
+                    description: None,
+                    priority: None,
+                    due_date: None,
+                    scheduled_date: None,
+                    estimated_time: None,
+                    project_id: None,
+                    parent_task_id: None,
+                    linked_notes: Some(vec![block.page_id.clone()]),
+                    linked_files: None,
+                    tags: None,
+                    contexts: None,
+                    recurrence: None,
+                }).await?;
+                sqlx::query("UPDATE tasks SET block_id = ? WHERE id = ?")
+                    .bind(&block.id)
+                    .bind(&task.id)
                     .execute(&self.pool)
                     .await?;
+                Task { block_id: Some(block.id.clone()), ..task }
             }
-        }
+        };
 
-        if let Some(priority) = &request.priority {
-            sqlx::query("UPDATE tasks SET priority = ?, updated_at = ? WHERE id = ?")
-                .bind(priority)
-                .bind(now.to_rfc3339())
-                .bind(&request.id)
-                .execute(&self.pool)
-                .await?;
+        // Keep the task's own status and priority while the block still agrees with them
+        let mut status = if block_task::same_status(parsed.status, task.status) { task.status } else { parsed.status };
+        // A blocked task can't be started from its block either, the marker is put back instead
+        let blocked = status == TaskStatus::InProgress
+            && task.status != TaskStatus::InProgress
+            && !self.get_open_blockers(&task.id).await?.is_empty();
+        if blocked {
+            status = task.status;
         }
-
-        if let Some(due_date) = &request.due_date {
-            sqlx::query("UPDATE tasks SET due_date = ?, updated_at = ? WHERE id = ?")
-                .bind(due_date.to_rfc3339())
-                .bind(now.to_rfc3339())
-                .bind(&request.id)
-                .execute(&self.pool)
-                .await?;
+        let block_priority = parsed.priority.unwrap_or(TaskPriority::Medium);
+        let priority = if block_task::same_priority(block_priority, task.priority) { task.priority } else { block_priority };
+
+        let unchanged = task.title == parsed.title
+            && task.description == parsed.description
+            && task.status == status
+            && task.priority == priority
+            && task.due_date == parsed.deadline
+            && task.scheduled_date == parsed.scheduled;
+        if unchanged {
+            if blocked {
+                self.sync_task_block(&task).await?;
+            }
+            return Ok(Some(task));
         }
 
-        if let Some(estimated_time) = &request.estimated_time {
-            sqlx::query("UPDATE tasks SET estimated_time = ?, updated_at = ? WHERE id = ?")
-                .bind(estimated_time)
-                .bind(now.to_rfc3339())
-                .bind(&request.id)
+        // An update request can't clear a field, so clear what the block no longer has first
+        if (task.description.is_some() && parsed.description.is_none())
+            || (task.due_date.is_some() && parsed.deadline.is_none())
+            || (task.scheduled_date.is_some() && parsed.scheduled.is_none())
+        {
+            sqlx::query("UPDATE tasks SET description = ?, due_date = ?, scheduled_date = ?, updated_at = ? WHERE id = ?")
+                .bind(parsed.description.as_ref().and(task.description.as_ref()))
+                .bind(parsed.deadline.and(task.due_date).map(|d| d.to_rfc3339()))
+                .bind(parsed.scheduled.and(task.scheduled_date).map(|d| d.to_rfc3339()))
+                .bind(Utc::now().to_rfc3339())
+                .bind(&task.id)
                 .execute(&self.pool)
                 .await?;
         }
 
-        if let Some(actual_time) = &request.actual_time {
-            sqlx::query("UPDATE tasks SET actual_time = ?, updated_at = ? WHERE id = ?")
-                .bind(actual_time)
-                .bind(now.to_rfc3339())
-                .bind(&request.id)
-                .execute(&self.pool)
-                .await?;
+        // Everything else goes through update_task, so completing the block schedules the
+        // next occurrence and moving its deadline moves the reminders along
+        let request = UpdateTaskRequest {
+            id: task.id.clone(),
+            title: Some(parsed.title).filter(|title| *title != task.title),
+            description: parsed.description.filter(|description| task.description.as_ref() != Some(description)),
+            status: Some(status).filter(|status| *status != task.status),
+            priority: Some(priority).filter(|priority| *priority != task.priority),
+            due_date: parsed.deadline.filter(|deadline| task.due_date != Some(*deadline)),
+            scheduled_date: parsed.scheduled.filter(|scheduled| task.scheduled_date != Some(*scheduled)),
+            estimated_time: None,
+            project_id: None,
+            parent_task_id: None,
+            linked_notes: None,
+            linked_files: None,
+            tags: None,
+            contexts: None,
+            recurrence: None,
+        };
+        let task = self.update_task(request, false).await?;
+        if blocked {
+            self.sync_task_block(&task).await?;
         }
+        Ok(Some(task))
+    }
 
-        if let Some(project_id) = &request.project_id {
-            sqlx::query("UPDATE tasks SET project_id = ?, updated_at = ? WHERE id = ?")
-                .bind(project_id)
-                .bind(now.to_rfc3339())
-                .bind(&request.id)
-                .execute(&self.pool)
-                .await?;
+    // Rewrite the block a task is written in after the task changed
+    async fn sync_task_block(&self, task: &Task) -> Result<()> {
+        let Some(block_id) = &task.block_id else {
+            return Ok(());
+        };
+        let block = self.get_block(block_id).await?;
+        let Some(content) = block_task::rewrite(&block.content, task) else {
+            return Ok(());
+        };
+        if content == block.content {
+            return Ok(());
         }
 
-        if let Some(tags) = &request.tags {
-            let tags_json = serde_json::to_string(tags).unwrap_or_else(|_| "[]".to_string());
-            sqlx::query("UPDATE tasks SET tags = ?, updated_at = ? WHERE id = ?")
-                .bind(tags_json)
-                .bind(now.to_rfc3339())
-                .bind(&request.id)
-                .execute(&self.pool)
-                .await?;
-        }
+        sqlx::query("UPDATE blocks SET content = ?, updated_at = ? WHERE id = ?")
+            .bind(&content)
+            .bind(Utc::now().to_rfc3339())
+            .bind(&block.id)
+            .execute(&self.pool)
+            .await?;
+        self.record_block_change(&block.id, &block.page_id).await?;
 
-        if let Some(contexts) = &request.contexts {
-            let contexts_json = serde_json::to_string(contexts).unwrap_or_else(|_| "[]".to_string());
-            sqlx::query("UPDATE tasks SET contexts = ?, updated_at = ? WHERE id = ?")
-                .bind(contexts_json)
-                .bind(now.to_rfc3339())
-                .bind(&request.id)
-                .execute(&self.pool)
-                .await?;
-        }
+        Ok(())
+    }
 
-        self.get_task(&request.id).await
+    // Block CRDT operations
+    /// This device's id, generated on first use
+    pub async fn get_device_id(&self) -> Result<String> {
+        if let Some(device_id) = self.get_setting(DEVICE_ID_KEY).await? {
+            return Ok(device_id);
+        }
+        let device_id = uuid::Uuid::new_v4().simple().to_string();
+        self.set_setting(DEVICE_ID_KEY, &device_id).await?;
+        Ok(device_id)
     }
 
-    pub async fn delete_task(&self, id: &str) -> Result<()> {
-        sqlx::query("DELETE FROM tasks WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
+    async fn block_clock(&self) -> Result<HybridClock> {
+        let device_id = self.get_device_id().await?;
+        let last: Option<String> = sqlx::query_scalar("SELECT MAX(id) FROM block_ops")
+            .fetch_one(&self.pool)
             .await?;
+        let last = last.map(|id| id.parse::<Hlc>()).transpose()?;
+        Ok(HybridClock::new(&device_id, last.as_ref()))
+    }
 
-        Ok(())
+    fn block_op_from_row(row: &sqlx::sqlite::SqliteRow) -> Result<BlockOp> {
+        let id: String = row.get("id");
+        let op: String = row.get("op");
+        Ok(BlockOp {
+            id: id.parse()?,
+            block_id: row.get("block_id"),
+            page_id: row.get("page_id"),
+            kind: serde_json::from_str::<BlockOpKind>(&op)?,
+        })
     }
 
-    pub async fn get_tasks(&self, limit: Option<usize>) -> Result<Vec<Task>> {
-        let query = if let Some(limit) = limit {
-            format!(
-                r#"
-                SELECT id, title, description, status, priority, due_date, completed_at,
-                       estimated_time, actual_time, project_id, parent_task_id,
-                       linked_notes, linked_files, tags, contexts, recurrence,
-                       created_at, updated_at, created_by
-                FROM tasks ORDER BY created_at DESC LIMIT {}
-                "#,
-                limit
-            )
-        } else {
+    async fn get_page_block_ops<'e, E>(executor: E, page_id: &str) -> Result<Vec<BlockOp>>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+    {
+        sqlx::query("SELECT id, block_id, page_id, op FROM block_ops WHERE page_id = ? ORDER BY id")
+            .bind(page_id)
+            .fetch_all(executor)
+            .await?
+            .iter()
+            .map(Self::block_op_from_row)
+            .collect()
+    }
+
+    /// Record a local change to a block, including its deletion, as CRDT ops
+    pub async fn record_block_change(&self, block_id: &str, page_id: &str) -> Result<Vec<BlockOp>> {
+        self.record_block_changes(page_id, &[block_id.to_string()]).await
+    }
+
+    /// Record every block written since its last op, whichever way it was written
+    pub async fn record_pending_block_changes(&self) -> Result<Vec<BlockOp>> {
+        let pending: Vec<(String, String)> = sqlx::query_as(
+            "SELECT page_id, block_id FROM block_ops_pending ORDER BY page_id, block_id"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut by_page: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (page_id, block_id) in pending {
+            by_page.entry(page_id).or_default().push(block_id);
+        }
+        let mut new_ops = Vec::new();
+        for (page_id, block_ids) in by_page {
+            new_ops.extend(self.record_block_changes(&page_id, &block_ids).await?);
+        }
+        Ok(new_ops)
+    }
+
+    /// Record local changes to blocks of one page as CRDT ops; a block that is no
+    /// longer on the page is recorded as deleted
+    async fn record_block_changes(&self, page_id: &str, block_ids: &[String]) -> Result<Vec<BlockOp>> {
+        let mut clock = self.block_clock().await?;
+        let mut page_ops = Self::get_page_block_ops(&self.pool, page_id).await?;
+        let blocks = self.get_blocks_by_page(page_id).await?;
+        let mut new_ops = Vec::new();
+
+        // The first time a page takes part in sync, its existing blocks get create ops too
+        let tracked: HashSet<&str> = page_ops.iter().map(|op| op.block_id.as_str()).collect();
+        let untracked: Vec<&Block> = blocks.iter()
+            .filter(|block| !block_ids.contains(&block.id) && !tracked.contains(block.id.as_str()))
+            .collect();
+        for block in untracked {
+            let ops = crdt::diff_block(&mut clock, None, &[], &Self::block_target(block, &blocks));
+            new_ops.extend(ops);
+        }
+        page_ops.extend(new_ops.iter().cloned());
+
+        let snapshots = crdt::materialize(&page_ops);
+        let mut ops_by_block: HashMap<&str, Vec<&BlockOp>> = HashMap::new();
+        for op in &page_ops {
+            ops_by_block.entry(op.block_id.as_str()).or_default().push(op);
+        }
+        for block_id in block_ids {
+            let current = snapshots.iter().find(|snapshot| snapshot.id == *block_id);
+            match blocks.iter().find(|block| block.id == *block_id) {
+                Some(block) => {
+                    let block_ops = ops_by_block.get(block_id.as_str()).map_or(&[][..], Vec::as_slice);
+                    let text = crdt::visible_text(block_ops);
+                    new_ops.extend(crdt::diff_block(&mut clock, current, &text, &Self::block_target(block, &blocks)));
+                }
+                None => {
+                    if let Some(current) = current.filter(|current| !current.deleted) {
+                        new_ops.push(crdt::delete_block_op(&mut clock, current));
+                    }
+                }
+            }
+        }
+
+        let mut tx = self.pool.begin().await?;
+        for op in &new_ops {
+            Self::insert_block_op(&mut *tx, op).await?;
+        }
+        Self::compact_page_block_ops(&mut *tx, page_id).await?;
+        for block_id in block_ids {
+            sqlx::query("DELETE FROM block_ops_pending WHERE block_id = ? AND page_id = ?")
+                .bind(block_id)
+                .bind(page_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(new_ops)
+    }
+
+    /// Where a block sits locally, with its previous sibling taken by ("order", id)
+    fn block_target(block: &Block, blocks: &[Block]) -> BlockTarget {
+        let after = blocks.iter()
+            .filter(|other| other.parent_id == block.parent_id && other.id != block.id)
+            .filter(|other| (other.order, &other.id) < (block.order, &block.id))
+            .max_by(|a, b| (a.order, &a.id).cmp(&(b.order, &b.id)))
+            .map(|other| other.id.clone());
+        BlockTarget {
+            id: block.id.clone(),
+            page_id: block.page_id.clone(),
+            graph_id: block.graph_id.clone(),
+            parent_id: block.parent_id.clone(),
+            after,
+            content: block.content.clone(),
+            properties: block.properties.clone(),
+            refs: block.refs.clone(),
+            collapsed: block.collapsed,
+        }
+    }
+
+    async fn insert_block_op<'e, E>(executor: E, op: &BlockOp) -> Result<bool>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+    {
+        let result = sqlx::query(
             r#"
-            SELECT id, title, description, status, priority, due_date, completed_at,
-                   estimated_time, actual_time, project_id, parent_task_id,
-                   linked_notes, linked_files, tags, contexts, recurrence,
-                   created_at, updated_at, created_by
-            FROM tasks ORDER BY created_at DESC
-            "#.to_string()
-        };
+            INSERT OR IGNORE INTO block_ops (id, device_id, block_id, page_id, op, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(op.id.to_string())
+        .bind(&op.id.node)
+        .bind(&op.block_id)
+        .bind(&op.page_id)
+        .bind(serde_json::to_string(&op.kind)?)
+        .bind(Utc::now().to_rfc3339())
+        .execute(executor)
+        .await?;
 
-        let tasks = sqlx::query_as::<_, Task>(&query)
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Drop the ops of a page that no longer change its merged blocks: field values
+    /// set again later, and the text and fields of a block deleted later. Only ops
+    /// superseded by the same device go, so every device that receives an op also
+    /// receives what replaces it, and the newest op of each device stays for the
+    /// version vector.
+    async fn compact_page_block_ops<'e, E>(executor: E, page_id: &str) -> Result<u64>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+    {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM block_ops
+            WHERE page_id = ?
+                AND id NOT IN (SELECT MAX(id) FROM block_ops GROUP BY device_id)
+                AND EXISTS (
+                    SELECT 1 FROM block_ops later
+                    WHERE later.block_id = block_ops.block_id
+                        AND later.page_id = block_ops.page_id
+                        AND later.device_id = block_ops.device_id
+                        AND later.id > block_ops.id
+                        AND (
+                            (json_extract(block_ops.op, '$.type') = 'set_field'
+                                AND json_extract(later.op, '$.type') = 'set_field'
+                                AND json_extract(later.op, '$.field') = json_extract(block_ops.op, '$.field'))
+                            OR (json_extract(block_ops.op, '$.type') IN ('insert_text', 'delete_text', 'set_field')
+                                AND json_extract(later.op, '$.type') = 'delete')
+                        )
+                )
+            "#,
+        )
+        .bind(page_id)
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Latest op timestamp known from each device
+    pub async fn get_block_version_vector(&self) -> Result<VersionVector> {
+        self.record_pending_block_changes().await?;
+        let rows = sqlx::query("SELECT device_id, MAX(id) AS last FROM block_ops GROUP BY device_id")
             .fetch_all(&self.pool)
             .await?;
 
-        Ok(tasks)
+        rows.iter()
+            .map(|row| {
+                let last: String = row.get("last");
+                Ok((row.get("device_id"), last.parse()?))
+            })
+            .collect()
     }
 
-    // Project management operations
-    pub async fn create_project(&self, request: CreateProjectRequest) -> Result<Project> {
-        let project = Project {
+    /// Ops a device made after `since`
+    pub async fn get_device_block_ops(&self, device_id: &str, since: Option<&Hlc>) -> Result<Vec<BlockOp>> {
+        self.record_pending_block_changes().await?;
+        sqlx::query("SELECT id, block_id, page_id, op FROM block_ops WHERE device_id = ? AND id > ? ORDER BY id")
+            .bind(device_id)
+            .bind(since.map(|hlc| hlc.to_string()).unwrap_or_default())
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(Self::block_op_from_row)
+            .collect()
+    }
+
+    /// Every op the other side doesn't have yet
+    pub async fn get_block_ops_since(&self, version: &VersionVector) -> Result<Vec<BlockOp>> {
+        self.record_pending_block_changes().await?;
+        let devices: Vec<String> = sqlx::query_scalar("SELECT DISTINCT device_id FROM block_ops")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut ops = Vec::new();
+        for device_id in devices {
+            ops.extend(self.get_device_block_ops(&device_id, version.get(&device_id)).await?);
+        }
+        Ok(ops)
+    }
+
+    /// A batch of ops for other devices, carrying the pages the ops belong to
+    pub async fn block_op_batch(&self, device_id: &str, ops: Vec<BlockOp>) -> Result<OpBatch> {
+        let page_ids: BTreeSet<&str> = ops.iter().map(|op| op.page_id.as_str()).collect();
+        let mut pages = Vec::new();
+        for page_id in page_ids {
+            // A page deleted here since has no row left to send
+            let page = sqlx::query_as::<_, Page>(
+                r#"
+                SELECT id, name, title, properties, tags, is_journal, journal_date, created_at, updated_at, graph_id
+                FROM pages WHERE id = ?
+                "#
+            )
+            .bind(page_id)
+            .fetch_optional(&self.pool)
+            .await?;
+            pages.extend(page);
+        }
+        Ok(OpBatch { device_id: device_id.to_string(), pages, ops })
+    }
+
+    /// Merge a batch from another device: create the pages it brings that are missing
+    /// here, take renames of newer page rows, and update the blocks of the pages its
+    /// ops touch. Returns how many ops were new.
+    pub async fn apply_block_ops(&self, batch: &OpBatch) -> Result<usize> {
+        // Local changes go in first, so the merge doesn't write over them
+        self.record_pending_block_changes().await?;
+        let mut tx = self.pool.begin().await?;
+        let mut applied = 0;
+        let mut pages = BTreeSet::new();
+
+        for page in &batch.pages {
+            Self::merge_synced_page(&mut tx, page).await?;
+        }
+        for op in &batch.ops {
+            if Self::insert_block_op(&mut *tx, op).await? {
+                applied += 1;
+                pages.insert(op.page_id.clone());
+            }
+        }
+
+        for page_id in &pages {
+            let page_exists = sqlx::query("SELECT 1 FROM pages WHERE id = ?")
+                .bind(page_id)
+                .fetch_optional(&mut *tx)
+                .await?
+                .is_some();
+            if !page_exists {
+                // The batch didn't bring the page, or its name is taken by another page here
+                log::warn!("Skipping block ops for missing page: {}", page_id);
+                continue;
+            }
+
+            Self::compact_page_block_ops(&mut *tx, page_id).await?;
+            let page_ops = Self::get_page_block_ops(&mut *tx, page_id).await?;
+            let snapshots = crdt::materialize(&page_ops);
+            Self::write_block_snapshots(&mut tx, &snapshots).await?;
+            // The merged blocks already match the op log
+            sqlx::query("DELETE FROM block_ops_pending WHERE page_id = ?")
+                .bind(page_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        // Tasks written in synced blocks follow them too
+        for page_id in &pages {
+            for block in self.get_blocks_by_page(page_id).await? {
+                self.sync_block_task(&block).await?;
+            }
+        }
+        Ok(applied)
+    }
+
+    /// Insert a page row from another device, or update ours when theirs is newer.
+    /// A row whose name belongs to a different page here, or whose graph doesn't
+    /// exist here, is left out.
+    async fn merge_synced_page(tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>, page: &Page) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO pages (id, name, title, properties, tags, is_journal, journal_date, created_at, updated_at, graph_id)
+            SELECT ?, ?, ?, ?, ?, ?, ?, ?, ?, ?
+            WHERE EXISTS (SELECT 1 FROM graphs WHERE id = ?)
+                AND NOT EXISTS (SELECT 1 FROM pages WHERE graph_id = ? AND name = ? AND id != ?)
+            ON CONFLICT(id) DO UPDATE SET
+                name = excluded.name,
+                title = excluded.title,
+                properties = excluded.properties,
+                tags = excluded.tags,
+                is_journal = excluded.is_journal,
+                journal_date = excluded.journal_date,
+                updated_at = excluded.updated_at
+            WHERE excluded.updated_at > pages.updated_at
+            "#,
+        )
+        .bind(&page.id)
+        .bind(&page.name)
+        .bind(&page.title)
+        .bind(&page.properties)
+        .bind(&page.tags)
+        .bind(page.is_journal)
+        .bind(&page.journal_date)
+        .bind(page.created_at.to_rfc3339())
+        .bind(page.updated_at.to_rfc3339())
+        .bind(&page.graph_id)
+        .bind(&page.graph_id)
+        .bind(&page.graph_id)
+        .bind(&page.name)
+        .bind(&page.id)
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    /// Write the merged blocks to the blocks table, parents before their children
+    async fn write_block_snapshots(
+        tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        snapshots: &[BlockSnapshot],
+    ) -> Result<()> {
+        let by_id: HashMap<&str, &BlockSnapshot> = snapshots.iter().map(|s| (s.id.as_str(), s)).collect();
+        let depth = |snapshot: &BlockSnapshot| {
+            let mut depth = 0;
+            let mut current = snapshot.parent_id.as_deref();
+            while let Some(parent) = current.and_then(|id| by_id.get(id)) {
+                depth += 1;
+                if depth > snapshots.len() {
+                    break;
+                }
+                current = parent.parent_id.as_deref();
+            }
+            depth
+        };
+        let mut live: Vec<&BlockSnapshot> = snapshots.iter().filter(|s| !s.deleted).collect();
+        live.sort_by_key(|snapshot| depth(snapshot));
+
+        for snapshot in snapshots.iter().filter(|s| s.deleted) {
+            sqlx::query("DELETE FROM blocks WHERE id = ?")
+                .bind(&snapshot.id)
+                .execute(&mut **tx)
+                .await?;
+        }
+
+        let timestamp = |hlc: &Hlc| -> DateTime<Utc> {
+            Utc.timestamp_millis_opt(hlc.wall).single().unwrap_or_else(Utc::now)
+        };
+        for snapshot in live {
+            // A parent that only exists on another device, outside sync, leaves the block at the page root
+            let parent_id = match &snapshot.parent_id {
+                Some(parent_id) if !by_id.contains_key(parent_id.as_str()) => {
+                    sqlx::query("SELECT 1 FROM blocks WHERE id = ?")
+                        .bind(parent_id)
+                        .fetch_optional(&mut **tx)
+                        .await?
+                        .map(|_| parent_id.clone())
+                }
+                parent_id => parent_id.clone(),
+            };
+
+            sqlx::query(
+                r#"
+                INSERT INTO blocks (id, content, parent_id, properties, refs, "order", collapsed, created_at, updated_at, page_id, graph_id)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT(id) DO UPDATE SET
+                    content = excluded.content,
+                    parent_id = excluded.parent_id,
+                    properties = excluded.properties,
+                    refs = excluded.refs,
+                    "order" = excluded."order",
+                    collapsed = excluded.collapsed,
+                    updated_at = excluded.updated_at
+                "#,
+            )
+            .bind(&snapshot.id)
+            .bind(&snapshot.content)
+            .bind(&parent_id)
+            .bind(&snapshot.properties)
+            .bind(&snapshot.refs)
+            .bind(snapshot.order)
+            .bind(snapshot.collapsed)
+            .bind(timestamp(&snapshot.created).to_rfc3339())
+            .bind(timestamp(&snapshot.updated).to_rfc3339())
+            .bind(&snapshot.page_id)
+            .bind(&snapshot.graph_id)
+            .execute(&mut **tx)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    // Task management operations
+    #[allow(dead_code)]
+    pub async fn create_task(&self, request: CreateTaskRequest) -> Result<Task> {
+        let task = Self::new_task(request);
+        Self::insert_task(&self.pool, &task).await?;
+
+        if let Some(project_id) = &task.project_id {
+            self.refresh_project_stats(project_id).await?;
+        }
+
+        Ok(task)
+    }
+
+    fn new_task(request: CreateTaskRequest) -> Task {
+        Task {
             id: uuid::Uuid::new_v4().to_string(),
-            name: request.name,
+            title: request.title,
             description: request.description,
-            status: "active".to_string(),
-            color: request.color,
-            start_date: request.start_date,
+            status: TaskStatus::Todo,
+            priority: request.priority.unwrap_or(TaskPriority::Medium),
             due_date: request.due_date,
+            scheduled_date: request.scheduled_date,
             completed_at: None,
+            estimated_time: request.estimated_time,
+            actual_time: None,
+            project_id: request.project_id,
+            parent_task_id: request.parent_task_id,
             linked_notes: serde_json::to_string(&request.linked_notes.unwrap_or_default()).unwrap_or_else(|_| "[]".to_string()),
             linked_files: serde_json::to_string(&request.linked_files.unwrap_or_default()).unwrap_or_else(|_| "[]".to_string()),
-            progress: 0,
-            total_tasks: 0,
-            completed_tasks: 0,
+            tags: serde_json::to_string(&request.tags.unwrap_or_default()).unwrap_or_else(|_| "[]".to_string()),
+            contexts: serde_json::to_string(&request.contexts.unwrap_or_default()).unwrap_or_else(|_| "[]".to_string()),
+            recurrence: request.recurrence,
+            block_id: None,
             created_at: Utc::now(),
             updated_at: Utc::now(),
             created_by: None,
-        };
+        }
+    }
+
+    async fn insert_task<'e, E>(executor: E, task: &Task) -> Result<()>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+    {
+        sqlx::query(
+            r#"
+            INSERT INTO tasks (
+                id, title, description, status, priority, due_date, scheduled_date, estimated_time,
+                project_id, parent_task_id, linked_notes, linked_files, tags, contexts,
+                recurrence, status_changed_at, created_at, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&task.id)
+        .bind(&task.title)
+        .bind(&task.description)
+        .bind(task.status.as_str())
+        .bind(task.priority.as_str())
+        .bind(task.due_date.map(|d| d.to_rfc3339()))
+        .bind(task.scheduled_date.map(|d| d.to_rfc3339()))
+        .bind(task.estimated_time)
+        .bind(&task.project_id)
+        .bind(&task.parent_task_id)
+        .bind(&task.linked_notes)
+        .bind(&task.linked_files)
+        .bind(&task.tags)
+        .bind(&task.contexts)
+        .bind(task.recurrence.as_ref().map(Recurrence::to_json))
+        .bind(task.created_at.to_rfc3339())
+        .bind(task.created_at.to_rfc3339())
+        .bind(task.updated_at.to_rfc3339())
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_task(&self, id: &str) -> Result<Task> {
+        let task = sqlx::query_as::<_, Task>(
+            r#"
+            SELECT id, title, description, status, priority, due_date, completed_at,
+                   estimated_time, actual_time, project_id, parent_task_id,
+                   linked_notes, linked_files, tags, contexts, recurrence, scheduled_date, block_id,
+                   created_at, updated_at, created_by
+            FROM tasks WHERE id = ?
+            "#
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(task)
+    }
+
+    /// Update a task. Starting a task that still waits for open tasks fails
+    /// unless `force` is set.
+    pub async fn update_task(&self, request: UpdateTaskRequest, force: bool) -> Result<Task> {
+        let now = Utc::now();
+
+        let previous = self.get_task(&request.id).await?;
+        self.check_blockers(&request, force).await?;
+        // Completing a recurring task schedules its next occurrence
+        let completing = request.status == Some(TaskStatus::Done) && previous.status != TaskStatus::Done;
+
+        if let Some(title) = &request.title {
+            sqlx::query("UPDATE tasks SET title = ?, updated_at = ? WHERE id = ?")
+                .bind(title)
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some(description) = &request.description {
+            sqlx::query("UPDATE tasks SET description = ?, updated_at = ? WHERE id = ?")
+                .bind(description)
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some(status) = &request.status {
+            sqlx::query("UPDATE tasks SET status = ?, updated_at = ? WHERE id = ?")
+                .bind(status.as_str())
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+
+            if *status != previous.status {
+                sqlx::query("UPDATE tasks SET status_changed_at = ? WHERE id = ?")
+                    .bind(now.to_rfc3339())
+                    .bind(&request.id)
+                    .execute(&self.pool)
+                    .await?;
+            }
+
+            // Set completed_at when marking as done, clear it when reopening
+            sqlx::query("UPDATE tasks SET completed_at = ?, updated_at = ? WHERE id = ?")
+                .bind((*status == TaskStatus::Done).then(|| now.to_rfc3339()))
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some(priority) = &request.priority {
+            sqlx::query("UPDATE tasks SET priority = ?, updated_at = ? WHERE id = ?")
+                .bind(priority.as_str())
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some(due_date) = &request.due_date {
+            sqlx::query("UPDATE tasks SET due_date = ?, updated_at = ? WHERE id = ?")
+                .bind(due_date.to_rfc3339())
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+
+            if previous.due_date != Some(*due_date) {
+                self.reschedule_reminders(&request.id, *due_date).await?;
+            }
+        }
+
+        if let Some(scheduled_date) = &request.scheduled_date {
+            sqlx::query("UPDATE tasks SET scheduled_date = ?, updated_at = ? WHERE id = ?")
+                .bind(scheduled_date.to_rfc3339())
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some(estimated_time) = &request.estimated_time {
+            sqlx::query("UPDATE tasks SET estimated_time = ?, updated_at = ? WHERE id = ?")
+                .bind(estimated_time)
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some(project_id) = &request.project_id {
+            sqlx::query("UPDATE tasks SET project_id = ?, updated_at = ? WHERE id = ?")
+                .bind(project_id)
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some(parent_task_id) = &request.parent_task_id {
+            sqlx::query("UPDATE tasks SET parent_task_id = ?, updated_at = ? WHERE id = ?")
+                .bind(parent_task_id)
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some(linked_notes) = &request.linked_notes {
+            let linked_notes_json = serde_json::to_string(linked_notes).unwrap_or_else(|_| "[]".to_string());
+            sqlx::query("UPDATE tasks SET linked_notes = ?, updated_at = ? WHERE id = ?")
+                .bind(linked_notes_json)
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some(linked_files) = &request.linked_files {
+            let linked_files_json = serde_json::to_string(linked_files).unwrap_or_else(|_| "[]".to_string());
+            sqlx::query("UPDATE tasks SET linked_files = ?, updated_at = ? WHERE id = ?")
+                .bind(linked_files_json)
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some(tags) = &request.tags {
+            let tags_json = serde_json::to_string(tags).unwrap_or_else(|_| "[]".to_string());
+            sqlx::query("UPDATE tasks SET tags = ?, updated_at = ? WHERE id = ?")
+                .bind(tags_json)
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some(contexts) = &request.contexts {
+            let contexts_json = serde_json::to_string(contexts).unwrap_or_else(|_| "[]".to_string());
+            sqlx::query("UPDATE tasks SET contexts = ?, updated_at = ? WHERE id = ?")
+                .bind(contexts_json)
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some(recurrence) = &request.recurrence {
+            sqlx::query("UPDATE tasks SET recurrence = ?, updated_at = ? WHERE id = ?")
+                .bind(recurrence.to_json())
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        let task = self.get_task(&request.id).await?;
+        let written_in_block = request.title.is_some()
+            || request.description.is_some()
+            || request.status.is_some()
+            || request.priority.is_some()
+            || request.due_date.is_some()
+            || request.scheduled_date.is_some();
+        if task.block_id.is_some() && written_in_block {
+            self.sync_task_block(&task).await?;
+        }
+
+        let project_ids: BTreeSet<&String> = previous.project_id.iter().chain(task.project_id.iter()).collect();
+        for project_id in project_ids {
+            self.refresh_project_stats(project_id).await?;
+        }
+
+        if completing && task.recurrence.is_some() {
+            self.create_next_occurrence(&task).await?;
+            return self.get_task(&request.id).await;
+        }
+
+        Ok(task)
+    }
+
+    // Create the task's next occurrence and hand the recurrence rule over to it, so
+    // reopening and completing the finished task again doesn't spawn a duplicate
+    pub async fn create_next_occurrence(&self, task: &Task) -> Result<Option<Task>> {
+        let Some(recurrence) = &task.recurrence else {
+            return Ok(None);
+        };
+
+        // Tasks without a due date repeat from when they were completed
+        let anchor = task.due_date.or(task.completed_at).unwrap_or_else(Utc::now);
+        let next = recurrence.next_after(anchor, chrono::Local).map(|due_date| {
+            Self::new_task(CreateTaskRequest {
+                title: task.title.clone(),
+                description: task.description.clone(),
+                priority: Some(task.priority),
+                due_date: Some(due_date),
+                scheduled_date: task.scheduled_date.map(|scheduled| scheduled + (due_date - anchor)),
+                estimated_time: task.estimated_time,
+                project_id: task.project_id.clone(),
+                parent_task_id: task.parent_task_id.clone(),
+                linked_notes: serde_json::from_str(&task.linked_notes).ok(),
+                linked_files: serde_json::from_str(&task.linked_files).ok(),
+                tags: serde_json::from_str(&task.tags).ok(),
+                contexts: serde_json::from_str(&task.contexts).ok(),
+                recurrence: Some(recurrence.advance()),
+            })
+        });
+        let reminders = self.get_reminders_by_task(&task.id).await?;
+
+        // Handing the rule over and inserting the next occurrence happen together,
+        // so a failure can neither lose the series nor duplicate it
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE tasks SET recurrence = NULL, updated_at = ? WHERE id = ?")
+            .bind(Utc::now().to_rfc3339())
+            .bind(&task.id)
+            .execute(&mut *tx)
+            .await?;
+        if let Some(next) = &next {
+            Self::insert_task(&mut *tx, next).await?;
+            for reminder in &reminders {
+                let reminder = Self::new_reminder(next, reminder.offset_minutes)?;
+                Self::insert_reminder(&mut *tx, &reminder).await?;
+            }
+        }
+        tx.commit().await?;
+
+        if let Some(project_id) = next.as_ref().and_then(|next| next.project_id.as_ref()) {
+            self.refresh_project_stats(project_id).await?;
+        }
+        Ok(next)
+    }
+
+    pub async fn delete_task(&self, id: &str) -> Result<()> {
+        // Subtasks are deleted along with the task, so their projects need refreshing too
+        let project_ids: Vec<String> = sqlx::query_scalar(
+            r#"
+            WITH RECURSIVE subtree(id) AS (
+                SELECT id FROM tasks WHERE id = ?
+                UNION
+                SELECT tasks.id FROM tasks JOIN subtree ON tasks.parent_task_id = subtree.id
+            )
+            SELECT DISTINCT project_id FROM tasks
+            WHERE id IN (SELECT id FROM subtree) AND project_id IS NOT NULL
+            "#
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        sqlx::query("DELETE FROM tasks WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        for project_id in &project_ids {
+            self.refresh_project_stats(project_id).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_tasks(&self, limit: Option<usize>) -> Result<Vec<Task>> {
+        let query = if let Some(limit) = limit {
+            format!(
+                r#"
+                SELECT id, title, description, status, priority, due_date, completed_at,
+                       estimated_time, actual_time, project_id, parent_task_id,
+                       linked_notes, linked_files, tags, contexts, recurrence, scheduled_date, block_id,
+                       created_at, updated_at, created_by
+                FROM tasks ORDER BY created_at DESC LIMIT {}
+                "#,
+                limit
+            )
+        } else {
+            r#"
+            SELECT id, title, description, status, priority, due_date, completed_at,
+                   estimated_time, actual_time, project_id, parent_task_id,
+                   linked_notes, linked_files, tags, contexts, recurrence, scheduled_date, block_id,
+                   created_at, updated_at, created_by
+            FROM tasks ORDER BY created_at DESC
+            "#.to_string()
+        };
+
+        let tasks = sqlx::query_as::<_, Task>(&query)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(tasks)
+    }
+
+    // Project management operations
+    pub async fn create_project(&self, request: CreateProjectRequest) -> Result<Project> {
+        let project = Project {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: request.name,
+            description: request.description,
+            status: ProjectStatus::Active,
+            color: request.color,
+            start_date: request.start_date,
+            due_date: request.due_date,
+            completed_at: None,
+            linked_notes: serde_json::to_string(&request.linked_notes.unwrap_or_default()).unwrap_or_else(|_| "[]".to_string()),
+            linked_files: serde_json::to_string(&request.linked_files.unwrap_or_default()).unwrap_or_else(|_| "[]".to_string()),
+            progress: 0,
+            total_tasks: 0,
+            completed_tasks: 0,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            created_by: None,
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO projects (
+                id, name, description, status, color, start_date, due_date,
+                linked_notes, linked_files, progress, total_tasks, completed_tasks,
+                created_at, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&project.id)
+        .bind(&project.name)
+        .bind(&project.description)
+        .bind(project.status.as_str())
+        .bind(&project.color)
+        .bind(project.start_date.map(|d| d.to_rfc3339()))
+        .bind(project.due_date.map(|d| d.to_rfc3339()))
+        .bind(&project.linked_notes)
+        .bind(&project.linked_files)
+        .bind(project.progress)
+        .bind(project.total_tasks)
+        .bind(project.completed_tasks)
+        .bind(project.created_at.to_rfc3339())
+        .bind(project.updated_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(project)
+    }
+
+    pub async fn get_project(&self, id: &str) -> Result<Project> {
+        let project = sqlx::query_as::<_, Project>(
+            r#"
+            SELECT id, name, description, status, color, start_date, due_date, completed_at,
+                   linked_notes, linked_files, progress, total_tasks, completed_tasks,
+                   created_at, updated_at, created_by
+            FROM projects WHERE id = ?
+            "#
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(project)
+    }
+
+    pub async fn update_project(&self, request: UpdateProjectRequest) -> Result<Project> {
+        let now = Utc::now();
+
+        if let Some(name) = &request.name {
+            sqlx::query("UPDATE projects SET name = ?, updated_at = ? WHERE id = ?")
+                .bind(name)
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some(description) = &request.description {
+            sqlx::query("UPDATE projects SET description = ?, updated_at = ? WHERE id = ?")
+                .bind(description)
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some(status) = &request.status {
+            sqlx::query("UPDATE projects SET status = ?, updated_at = ? WHERE id = ?")
+                .bind(status.as_str())
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+
+            // Set completed_at when marking as completed, clear it when reopening
+            sqlx::query("UPDATE projects SET completed_at = ?, updated_at = ? WHERE id = ?")
+                .bind((*status == ProjectStatus::Completed).then(|| now.to_rfc3339()))
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some(color) = &request.color {
+            sqlx::query("UPDATE projects SET color = ?, updated_at = ? WHERE id = ?")
+                .bind(color)
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some(start_date) = &request.start_date {
+            sqlx::query("UPDATE projects SET start_date = ?, updated_at = ? WHERE id = ?")
+                .bind(start_date.to_rfc3339())
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some(due_date) = &request.due_date {
+            sqlx::query("UPDATE projects SET due_date = ?, updated_at = ? WHERE id = ?")
+                .bind(due_date.to_rfc3339())
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some(linked_notes) = &request.linked_notes {
+            let linked_notes_json = serde_json::to_string(linked_notes).unwrap_or_else(|_| "[]".to_string());
+            sqlx::query("UPDATE projects SET linked_notes = ?, updated_at = ? WHERE id = ?")
+                .bind(linked_notes_json)
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some(linked_files) = &request.linked_files {
+            let linked_files_json = serde_json::to_string(linked_files).unwrap_or_else(|_| "[]".to_string());
+            sqlx::query("UPDATE projects SET linked_files = ?, updated_at = ? WHERE id = ?")
+                .bind(linked_files_json)
+                .bind(now.to_rfc3339())
+                .bind(&request.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        self.get_project(&request.id).await
+    }
+
+    pub async fn delete_project(&self, id: &str) -> Result<()> {
+        // First, update all tasks in this project to remove project_id
+        sqlx::query("UPDATE tasks SET project_id = NULL WHERE project_id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        // Then delete the project
+        sqlx::query("DELETE FROM projects WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Recalculate the task counts and progress stored on a project
+    pub async fn refresh_project_stats(&self, project_id: &str) -> Result<()> {
+        let tasks: Vec<TaskWeight> = sqlx::query("SELECT status, estimated_time FROM tasks WHERE project_id = ? AND status != ?")
+            .bind(project_id)
+            .bind(TaskStatus::Cancelled.as_str())
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| TaskWeight {
+                done: row.get::<String, _>("status") == TaskStatus::Done.as_str(),
+                estimated_time: row.get("estimated_time"),
+            })
+            .collect();
+        let weighted = self.get_setting(WEIGHTED_PROGRESS_KEY).await?
+            .map_or(false, |value| value.trim() == "true");
+
+        sqlx::query("UPDATE projects SET total_tasks = ?, completed_tasks = ?, progress = ? WHERE id = ?")
+            .bind(tasks.len() as i32)
+            .bind(tasks.iter().filter(|task| task.done).count() as i32)
+            .bind(rollup::progress(&tasks, weighted))
+            .bind(project_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Repair the stats of every project, e.g. after data written by older versions
+    pub async fn recompute_project_stats(&self) -> Result<Vec<Project>> {
+        let project_ids: Vec<String> = sqlx::query_scalar("SELECT id FROM projects")
+            .fetch_all(&self.pool)
+            .await?;
+        for project_id in &project_ids {
+            self.refresh_project_stats(project_id).await?;
+        }
+
+        self.get_projects(None).await
+    }
+
+    pub async fn get_projects(&self, limit: Option<usize>) -> Result<Vec<Project>> {
+        let query = if let Some(limit) = limit {
+            format!(
+                r#"
+                SELECT id, name, description, status, color, start_date, due_date, completed_at,
+                       linked_notes, linked_files, progress, total_tasks, completed_tasks,
+                       created_at, updated_at, created_by
+                FROM projects ORDER BY created_at DESC LIMIT {}
+                "#,
+                limit
+            )
+        } else {
+            r#"
+            SELECT id, name, description, status, color, start_date, due_date, completed_at,
+                   linked_notes, linked_files, progress, total_tasks, completed_tasks,
+                   created_at, updated_at, created_by
+            FROM projects ORDER BY created_at DESC
+            "#.to_string()
+        };
+
+        let projects = sqlx::query_as::<_, Project>(&query)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(projects)
+    }
+
+    pub async fn get_tasks_by_project(&self, project_id: &str) -> Result<Vec<Task>> {
+        let tasks = sqlx::query_as::<_, Task>(
+            r#"
+            SELECT id, title, description, status, priority, due_date, completed_at,
+                   estimated_time, actual_time, project_id, parent_task_id,
+                   linked_notes, linked_files, tags, contexts, recurrence, scheduled_date, block_id,
+                   created_at, updated_at, created_by
+            FROM tasks WHERE project_id = ? ORDER BY created_at DESC
+            "#
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(tasks)
+    }
+
+    // GTD views
+    /// Tasks with the given status, oldest first
+    pub async fn get_tasks_by_status(&self, status: TaskStatus) -> Result<Vec<Task>> {
+        self.fetch_tasks("WHERE status = ? ORDER BY created_at ASC", &[status.as_str().to_string()]).await
+    }
+
+    /// Open `todo` and `in-progress` tasks that aren't scheduled after `now`
+    /// and aren't waiting for another open task
+    pub async fn get_next_actions(&self, now: DateTime<Utc>) -> Result<Vec<Task>> {
+        self.fetch_tasks(
+            r#"
+            WHERE status IN ('todo', 'in-progress')
+              AND (scheduled_date IS NULL OR scheduled_date <= ?)
+              AND NOT EXISTS (
+                  SELECT 1 FROM task_dependencies d JOIN tasks b ON b.id = d.blocker_id
+                  WHERE d.task_id = tasks.id AND b.status NOT IN ('done', 'cancelled')
+              )
+            ORDER BY created_at ASC
+            "#,
+            &[now.to_rfc3339()],
+        )
+        .await
+    }
+
+    /// Waiting-for tasks, the longest waiting first
+    pub async fn get_waiting_tasks(&self, now: DateTime<Utc>) -> Result<Vec<WaitingTask>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, title, description, status, priority, due_date, completed_at,
+                   estimated_time, actual_time, project_id, parent_task_id,
+                   linked_notes, linked_files, tags, contexts, recurrence, scheduled_date, block_id,
+                   created_at, updated_at, created_by, status_changed_at
+            FROM tasks WHERE status = 'waiting'
+            ORDER BY COALESCE(status_changed_at, updated_at) ASC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut tasks = Vec::with_capacity(rows.len());
+        for row in rows {
+            let task = Task::from_row(&row)?;
+            let since: Option<String> = row.get("status_changed_at");
+            let waiting_since = since
+                .and_then(|since| DateTime::parse_from_rfc3339(&since).ok())
+                .map_or(task.updated_at, |since| since.with_timezone(&Utc));
+            tasks.push(WaitingTask {
+                waiting_days: gtd::waiting_days(waiting_since, now),
+                waiting_since,
+                task,
+            });
+        }
+
+        Ok(tasks)
+    }
+
+    /// Open tasks due before `now`, the most overdue first
+    pub async fn get_overdue_tasks(&self, now: DateTime<Utc>) -> Result<Vec<Task>> {
+        self.fetch_tasks(
+            r#"
+            WHERE due_date IS NOT NULL AND due_date < ?
+              AND status NOT IN ('done', 'cancelled')
+            ORDER BY due_date ASC
+            "#,
+            &[now.to_rfc3339()],
+        )
+        .await
+    }
+
+    /// Open tasks due in `[from, to)`, in due date order
+    pub async fn get_tasks_due_between(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<Task>> {
+        self.fetch_tasks(
+            r#"
+            WHERE due_date >= ? AND due_date < ?
+              AND status NOT IN ('done', 'cancelled')
+            ORDER BY due_date ASC
+            "#,
+            &[from.to_rfc3339(), to.to_rfc3339()],
+        )
+        .await
+    }
+
+    /// Counts for the weekly review: what was completed and created in the
+    /// seven days up to `now`, and what is waiting to be looked at
+    pub async fn get_weekly_review(&self, now: DateTime<Utc>, utc_offset_minutes: i32) -> Result<WeeklyReview> {
+        let from = now - chrono::Duration::days(7);
+        let (week_start, week_end) = gtd::week_bounds(now, utc_offset_minutes);
+
+        let row = sqlx::query(
+            r#"
+            SELECT
+                COUNT(CASE WHEN status = 'done' AND completed_at >= ? AND completed_at <= ? THEN 1 END) AS completed,
+                COUNT(CASE WHEN created_at >= ? AND created_at <= ? THEN 1 END) AS created,
+                COUNT(CASE WHEN status = 'inbox' THEN 1 END) AS inbox,
+                COUNT(CASE WHEN status IN ('todo', 'in-progress')
+                            AND (scheduled_date IS NULL OR scheduled_date <= ?)
+                            AND NOT EXISTS (
+                                SELECT 1 FROM task_dependencies d JOIN tasks b ON b.id = d.blocker_id
+                                WHERE d.task_id = tasks.id AND b.status NOT IN ('done', 'cancelled')
+                            ) THEN 1 END) AS next_actions,
+                COUNT(CASE WHEN status = 'waiting' THEN 1 END) AS waiting,
+                COUNT(CASE WHEN due_date < ? AND status NOT IN ('done', 'cancelled') THEN 1 END) AS overdue,
+                COUNT(CASE WHEN due_date >= ? AND due_date < ?
+                            AND status NOT IN ('done', 'cancelled') THEN 1 END) AS due_this_week,
+                COUNT(CASE WHEN status = 'someday' THEN 1 END) AS someday
+            FROM tasks
+            "#
+        )
+        .bind(from.to_rfc3339())
+        .bind(now.to_rfc3339())
+        .bind(from.to_rfc3339())
+        .bind(now.to_rfc3339())
+        .bind(now.to_rfc3339())
+        .bind(now.to_rfc3339())
+        .bind(week_start.to_rfc3339())
+        .bind(week_end.to_rfc3339())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(WeeklyReview {
+            from,
+            to: now,
+            completed: row.get("completed"),
+            created: row.get("created"),
+            inbox: row.get("inbox"),
+            next_actions: row.get("next_actions"),
+            waiting: row.get("waiting"),
+            overdue: row.get("overdue"),
+            due_this_week: row.get("due_this_week"),
+            someday: row.get("someday"),
+        })
+    }
+
+    // Task dependencies
+    pub async fn get_task_dependencies(&self) -> Result<Vec<TaskDependency>> {
+        let rows = sqlx::query("SELECT task_id, blocker_id FROM task_dependencies ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| TaskDependency {
+                task_id: row.get("task_id"),
+                blocker_id: row.get("blocker_id"),
+            })
+            .collect())
+    }
+
+    /// Make `task_id` wait for `blocker_id`, refusing dependencies that would
+    /// form a cycle
+    pub async fn add_task_dependency(&self, task_id: &str, blocker_id: &str) -> Result<TaskDependency> {
+        self.get_task(task_id).await
+            .map_err(|_| AppError::NotFound(format!("Task '{}' not found", task_id)))?;
+        self.get_task(blocker_id).await
+            .map_err(|_| AppError::NotFound(format!("Task '{}' not found", blocker_id)))?;
+        if dependencies::creates_cycle(&self.get_task_dependencies().await?, task_id, blocker_id) {
+            return Err(AppError::InvalidInput(format!(
+                "Task '{}' can't wait for '{}': that would create a dependency cycle",
+                task_id, blocker_id
+            )));
+        }
+
+        sqlx::query(
+            "INSERT OR IGNORE INTO task_dependencies (id, task_id, blocker_id, created_at) VALUES (?, ?, ?, ?)"
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(task_id)
+        .bind(blocker_id)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(TaskDependency {
+            task_id: task_id.to_string(),
+            blocker_id: blocker_id.to_string(),
+        })
+    }
+
+    pub async fn remove_task_dependency(&self, task_id: &str, blocker_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM task_dependencies WHERE task_id = ? AND blocker_id = ?")
+            .bind(task_id)
+            .bind(blocker_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Tasks `task_id` waits for
+    pub async fn get_task_blockers(&self, task_id: &str) -> Result<Vec<Task>> {
+        self.fetch_tasks(
+            "WHERE id IN (SELECT blocker_id FROM task_dependencies WHERE task_id = ?) ORDER BY created_at ASC",
+            &[task_id.to_string()],
+        )
+        .await
+    }
+
+    /// Tasks waiting for `task_id`
+    pub async fn get_blocked_tasks(&self, task_id: &str) -> Result<Vec<Task>> {
+        self.fetch_tasks(
+            "WHERE id IN (SELECT task_id FROM task_dependencies WHERE blocker_id = ?) ORDER BY created_at ASC",
+            &[task_id.to_string()],
+        )
+        .await
+    }
+
+    /// Blockers of `task_id` that are neither done nor cancelled
+    pub async fn get_open_blockers(&self, task_id: &str) -> Result<Vec<Task>> {
+        self.fetch_tasks(
+            r#"
+            WHERE id IN (SELECT blocker_id FROM task_dependencies WHERE task_id = ?)
+              AND status NOT IN ('done', 'cancelled')
+            ORDER BY created_at ASC
+            "#,
+            &[task_id.to_string()],
+        )
+        .await
+    }
+
+    /// Starting a task while it still waits for open tasks needs `force`
+    pub async fn check_blockers(&self, request: &UpdateTaskRequest, force: bool) -> Result<()> {
+        if force || request.status != Some(TaskStatus::InProgress) {
+            return Ok(());
+        }
+        let blockers = self.get_open_blockers(&request.id).await?;
+        if blockers.is_empty() {
+            return Ok(());
+        }
+        let titles: Vec<&str> = blockers.iter().map(|task| task.title.as_str()).collect();
+        Err(AppError::InvalidInput(format!("Task is blocked by: {}", titles.join(", "))))
+    }
+
+    /// Earliest and latest start and finish of a project's open and done
+    /// tasks from `start`, and its critical path. Cancelled tasks are left out.
+    pub async fn get_project_schedule(&self, project_id: &str, start: DateTime<Utc>) -> Result<ProjectSchedule> {
+        self.get_project(project_id).await?;
+        let tasks = self.fetch_tasks(
+            "WHERE project_id = ? AND status != 'cancelled' ORDER BY created_at ASC",
+            &[project_id.to_string()],
+        )
+        .await?;
+
+        let inputs: Vec<ScheduleInput> = tasks
+            .into_iter()
+            .map(|task| ScheduleInput {
+                duration_minutes: match task.status {
+                    TaskStatus::Done => 0,
+                    _ => task.estimated_time.unwrap_or(0) as i64,
+                },
+                due_date: task.due_date,
+                task_id: task.id,
+                title: task.title,
+            })
+            .collect();
+        dependencies::schedule(project_id, &inputs, &self.get_task_dependencies().await?, start)
+    }
+
+    // Reminders
+    /// Remind `offset_minutes` before the task is due. Adding an offset the task
+    /// already has returns the existing reminder.
+    pub async fn create_reminder(&self, task_id: &str, offset_minutes: i32) -> Result<Reminder> {
+        let task = self.get_task(task_id).await
+            .map_err(|_| AppError::NotFound(format!("Task '{}' not found", task_id)))?;
+        if task.due_date.is_none() {
+            return Err(AppError::InvalidInput(format!("Task '{}' has no due date to remind about", task.title)));
+        }
+
+        let existing = sqlx::query_as::<_, Reminder>(
+            r#"
+            SELECT id, task_id, offset_minutes, remind_at, snoozed_until, fired_at, created_at, updated_at
+            FROM reminders WHERE task_id = ? AND offset_minutes = ?
+            "#
+        )
+        .bind(task_id)
+        .bind(offset_minutes)
+        .fetch_optional(&self.pool)
+        .await?;
+        if let Some(existing) = existing {
+            return Ok(existing);
+        }
+
+        let reminder = Self::new_reminder(&task, offset_minutes)?;
+        Self::insert_reminder(&self.pool, &reminder).await?;
+        Ok(reminder)
+    }
+
+    fn new_reminder(task: &Task, offset_minutes: i32) -> Result<Reminder> {
+        let due_date = task.due_date
+            .ok_or_else(|| AppError::InvalidInput(format!("Task '{}' has no due date to remind about", task.title)))?;
+        let now = Utc::now();
+        Ok(Reminder {
+            id: uuid::Uuid::new_v4().to_string(),
+            task_id: task.id.clone(),
+            offset_minutes,
+            remind_at: due_date - chrono::Duration::minutes(offset_minutes as i64),
+            snoozed_until: None,
+            fired_at: None,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    async fn insert_reminder<'e, E>(executor: E, reminder: &Reminder) -> Result<()>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+    {
+        sqlx::query(
+            r#"
+            INSERT INTO reminders (id, task_id, offset_minutes, remind_at, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&reminder.id)
+        .bind(&reminder.task_id)
+        .bind(reminder.offset_minutes)
+        .bind(reminder.remind_at.to_rfc3339())
+        .bind(reminder.created_at.to_rfc3339())
+        .bind(reminder.updated_at.to_rfc3339())
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_reminder(&self, id: &str) -> Result<Reminder> {
+        let reminder = sqlx::query_as::<_, Reminder>(
+            r#"
+            SELECT id, task_id, offset_minutes, remind_at, snoozed_until, fired_at, created_at, updated_at
+            FROM reminders WHERE id = ?
+            "#
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(reminder)
+    }
+
+    pub async fn get_reminders_by_task(&self, task_id: &str) -> Result<Vec<Reminder>> {
+        let reminders = sqlx::query_as::<_, Reminder>(
+            r#"
+            SELECT id, task_id, offset_minutes, remind_at, snoozed_until, fired_at, created_at, updated_at
+            FROM reminders WHERE task_id = ? ORDER BY remind_at
+            "#
+        )
+        .bind(task_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(reminders)
+    }
+
+    pub async fn delete_reminder(&self, id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM reminders WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Fire the reminder again at `until`, even if it already fired
+    pub async fn snooze_reminder(&self, id: &str, until: DateTime<Utc>) -> Result<Reminder> {
+        sqlx::query("UPDATE reminders SET snoozed_until = ?, fired_at = NULL, updated_at = ? WHERE id = ?")
+            .bind(until.to_rfc3339())
+            .bind(Utc::now().to_rfc3339())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        self.get_reminder(id).await
+    }
 
-        sqlx::query(
+    pub async fn mark_reminder_fired(&self, id: &str, fired_at: DateTime<Utc>) -> Result<()> {
+        sqlx::query("UPDATE reminders SET fired_at = ?, updated_at = ? WHERE id = ?")
+            .bind(fired_at.to_rfc3339())
+            .bind(fired_at.to_rfc3339())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Reminders that haven't fired and are due by `now`, with their tasks.
+    /// Reminders of finished tasks, or tasks no longer due, are left alone.
+    pub async fn get_due_reminders(&self, now: DateTime<Utc>) -> Result<Vec<(Reminder, Task)>> {
+        let reminders = sqlx::query_as::<_, Reminder>(
             r#"
-            INSERT INTO projects (
-                id, name, description, status, color, start_date, due_date,
-                linked_notes, linked_files, progress, total_tasks, completed_tasks,
-                created_at, updated_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            "#,
+            SELECT r.id, r.task_id, r.offset_minutes, r.remind_at, r.snoozed_until, r.fired_at,
+                   r.created_at, r.updated_at
+            FROM reminders r JOIN tasks t ON t.id = r.task_id
+            WHERE r.fired_at IS NULL AND COALESCE(r.snoozed_until, r.remind_at) <= ?
+              AND t.due_date IS NOT NULL AND t.status NOT IN ('done', 'cancelled')
+            ORDER BY COALESCE(r.snoozed_until, r.remind_at)
+            "#
         )
-        .bind(&project.id)
-        .bind(&project.name)
-        .bind(&project.description)
-        .bind(&project.status)
-        .bind(&project.color)
-        .bind(project.start_date.map(|d| d.to_rfc3339()))
-        .bind(project.due_date.map(|d| d.to_rfc3339()))
-        .bind(&project.linked_notes)
-        .bind(&project.linked_files)
-        .bind(project.progress)
-        .bind(project.total_tasks)
-        .bind(project.completed_tasks)
-        .bind(project.created_at.to_rfc3339())
-        .bind(project.updated_at.to_rfc3339())
-        .execute(&self.pool)
+        .bind(now.to_rfc3339())
+        .fetch_all(&self.pool)
         .await?;
 
-        Ok(project)
+        let mut due = Vec::with_capacity(reminders.len());
+        for reminder in reminders {
+            let task = self.get_task(&reminder.task_id).await?;
+            due.push((reminder, task));
+        }
+        Ok(due)
     }
 
-    pub async fn get_project(&self, id: &str) -> Result<Project> {
-        let project = sqlx::query_as::<_, Project>(
+    /// When the next pending reminder fires
+    pub async fn next_reminder_at(&self) -> Result<Option<DateTime<Utc>>> {
+        let next: Option<String> = sqlx::query_scalar(
             r#"
-            SELECT id, name, description, status, color, start_date, due_date, completed_at,
-                   linked_notes, linked_files, progress, total_tasks, completed_tasks,
-                   created_at, updated_at, created_by
-            FROM projects WHERE id = ?
+            SELECT MIN(COALESCE(r.snoozed_until, r.remind_at))
+            FROM reminders r JOIN tasks t ON t.id = r.task_id
+            WHERE r.fired_at IS NULL
+              AND t.due_date IS NOT NULL AND t.status NOT IN ('done', 'cancelled')
             "#
         )
-        .bind(id)
         .fetch_one(&self.pool)
         .await?;
 
-        Ok(project)
+        Ok(next
+            .and_then(|next| DateTime::parse_from_rfc3339(&next).ok())
+            .map(|next| next.with_timezone(&Utc)))
     }
 
-    pub async fn update_project(&self, request: UpdateProjectRequest) -> Result<Project> {
+    /// Move a task's reminders along with its new due date. Reminders whose new
+    /// time is still ahead fire again.
+    async fn reschedule_reminders(&self, task_id: &str, due_date: DateTime<Utc>) -> Result<()> {
         let now = Utc::now();
-
-        if let Some(name) = &request.name {
-            sqlx::query("UPDATE projects SET name = ?, updated_at = ? WHERE id = ?")
-                .bind(name)
-                .bind(now.to_rfc3339())
-                .bind(&request.id)
-                .execute(&self.pool)
-                .await?;
-        }
-
-        if let Some(description) = &request.description {
-            sqlx::query("UPDATE projects SET description = ?, updated_at = ? WHERE id = ?")
-                .bind(description)
-                .bind(now.to_rfc3339())
-                .bind(&request.id)
-                .execute(&self.pool)
-                .await?;
+        for reminder in self.get_reminders_by_task(task_id).await? {
+            let remind_at = due_date - chrono::Duration::minutes(reminder.offset_minutes as i64);
+            let fired_at = reminder.fired_at.filter(|_| remind_at <= now);
+            sqlx::query(
+                "UPDATE reminders SET remind_at = ?, snoozed_until = NULL, fired_at = ?, updated_at = ? WHERE id = ?"
+            )
+            .bind(remind_at.to_rfc3339())
+            .bind(fired_at.map(|d| d.to_rfc3339()))
+            .bind(now.to_rfc3339())
+            .bind(&reminder.id)
+            .execute(&self.pool)
+            .await?;
         }
 
-        if let Some(status) = &request.status {
-            sqlx::query("UPDATE projects SET status = ?, updated_at = ? WHERE id = ?")
-                .bind(status)
-                .bind(now.to_rfc3339())
-                .bind(&request.id)
-                .execute(&self.pool)
-                .await?;
+        Ok(())
+    }
 
-            // If marking as completed, set completed_at
-            if status == "completed" {
-                sqlx::query("UPDATE projects SET completed_at = ?, updated_at = ? WHERE id = ?")
-                    .bind(now.to_rfc3339())
-                    .bind(now.to_rfc3339())
-                    .bind(&request.id)
-                    .execute(&self.pool)
-                    .await?;
+    // Flashcards
+    /// Cards due by `now`, overdue reviews first and then new cards, oldest
+    /// first
+    pub async fn get_due_cards(&self, now: DateTime<Utc>, graph_id: Option<&str>, limit: Option<usize>) -> Result<Vec<Card>> {
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut due = Vec::new();
+        for (block, state) in self.fetch_card_blocks(graph_id).await? {
+            if due.len() >= limit {
+                break;
+            }
+            if state.as_ref().map_or(true, |state| state.due_at <= now) {
+                if let Some(card) = self.card_from_block(block, state, now).await? {
+                    due.push(card);
+                }
             }
         }
+        Ok(due)
+    }
 
-        if let Some(color) = &request.color {
-            sqlx::query("UPDATE projects SET color = ?, updated_at = ? WHERE id = ?")
-                .bind(color)
-                .bind(now.to_rfc3339())
-                .bind(&request.id)
-                .execute(&self.pool)
-                .await?;
+    /// Record an answer to the card written in `block_id` and schedule its
+    /// next review
+    pub async fn grade_card(&self, block_id: &str, grade: Grade, now: DateTime<Utc>) -> Result<Card> {
+        let block = self.get_block(block_id).await
+            .map_err(|_| AppError::NotFound(format!("Block '{}' not found", block_id)))?;
+        let state = self.get_review_state(block_id).await?;
+        let previous = state.clone().unwrap_or_else(|| ReviewState::new(now));
+        let mut card = self.card_from_block(block, state, now).await?
+            .ok_or_else(|| AppError::InvalidInput(format!("Block '{}' is not a card", block_id)))?;
+        let next = previous.review(grade, now);
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            r#"
+            INSERT INTO cards (id, ease, interval_days, repetitions, lapses, due_at, last_reviewed_at, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+                ease = excluded.ease,
+                interval_days = excluded.interval_days,
+                repetitions = excluded.repetitions,
+                lapses = excluded.lapses,
+                due_at = excluded.due_at,
+                last_reviewed_at = excluded.last_reviewed_at,
+                updated_at = excluded.updated_at
+            "#
+        )
+        .bind(block_id)
+        .bind(next.ease)
+        .bind(next.interval_days)
+        .bind(next.repetitions)
+        .bind(next.lapses)
+        .bind(next.due_at.to_rfc3339())
+        .bind(now.to_rfc3339())
+        .bind(now.to_rfc3339())
+        .bind(now.to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO card_reviews (id, card_id, grade, interval_days, ease, reviewed_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(block_id)
+        .bind(grade.as_str())
+        .bind(next.interval_days)
+        .bind(next.ease)
+        .bind(now.to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        card.is_new = false;
+        card.state = next;
+        Ok(card)
+    }
+
+    /// Card counts now, and reviews over the `days` days up to today in the
+    /// user's timezone
+    pub async fn get_card_stats(&self, now: DateTime<Utc>, days: u32, utc_offset_minutes: i32) -> Result<CardStats> {
+        gtd::validate_offset(utc_offset_minutes)?;
+        if days == 0 || days > 366 {
+            return Err(AppError::InvalidInput(format!("Invalid number of days {}", days)));
         }
 
-        if let Some(start_date) = &request.start_date {
-            sqlx::query("UPDATE projects SET start_date = ?, updated_at = ? WHERE id = ?")
-                .bind(start_date.to_rfc3339())
-                .bind(now.to_rfc3339())
-                .bind(&request.id)
-                .execute(&self.pool)
-                .await?;
+        let (mut total_cards, mut new_cards, mut due_cards) = (0, 0, 0);
+        let mut eases = Vec::new();
+        for (block, state) in self.fetch_card_blocks(None).await? {
+            if cards::parse(&block.content).is_none() {
+                continue;
+            }
+            total_cards += 1;
+            match state {
+                None => new_cards += 1,
+                Some(state) => {
+                    if state.due_at <= now {
+                        due_cards += 1;
+                    }
+                    eases.push(state.ease);
+                }
+            }
         }
 
-        if let Some(due_date) = &request.due_date {
-            sqlx::query("UPDATE projects SET due_date = ?, updated_at = ? WHERE id = ?")
-                .bind(due_date.to_rfc3339())
-                .bind(now.to_rfc3339())
-                .bind(&request.id)
-                .execute(&self.pool)
-                .await?;
+        let offset = chrono::Duration::minutes(utc_offset_minutes as i64);
+        let today = (now + offset).date_naive();
+        let first_day = today - chrono::Duration::days(days as i64 - 1);
+        let from = Utc.from_utc_datetime(&first_day.and_time(chrono::NaiveTime::MIN)) - offset;
+        let rows = sqlx::query("SELECT grade, reviewed_at FROM card_reviews WHERE reviewed_at >= ? AND reviewed_at <= ?")
+            .bind(from.to_rfc3339())
+            .bind(now.to_rfc3339())
+            .fetch_all(&self.pool)
+            .await?;
+        let mut reviews = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let grade: String = row.get("grade");
+            reviews.push(((decode_datetime(row, "reviewed_at")? + offset).date_naive(), grade.parse::<Grade>()?));
         }
 
-        self.get_project(&request.id).await
+        let correct = reviews.iter().filter(|(_, grade)| *grade != Grade::Again).count();
+        Ok(CardStats {
+            total_cards,
+            new_cards,
+            due_cards,
+            reviews: reviews.len() as i64,
+            retention: if reviews.is_empty() { None } else { Some(correct as f64 / reviews.len() as f64) },
+            average_ease: if eases.is_empty() { None } else { Some(eases.iter().sum::<f64>() / eases.len() as f64) },
+            daily: cards::daily_reviews(&reviews, today, days),
+        })
     }
 
-    pub async fn delete_project(&self, id: &str) -> Result<()> {
-        // First, update all tasks in this project to remove project_id
-        sqlx::query("UPDATE tasks SET project_id = NULL WHERE project_id = ?")
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
-
-        // Then delete the project
-        sqlx::query("DELETE FROM projects WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
+    async fn get_review_state(&self, block_id: &str) -> Result<Option<ReviewState>> {
+        let row = sqlx::query(
+            r#"
+            SELECT ease AS card_ease, interval_days AS card_interval_days, repetitions AS card_repetitions,
+                   lapses AS card_lapses, due_at AS card_due_at, last_reviewed_at AS card_last_reviewed_at
+            FROM cards WHERE id = ?
+            "#
+        )
+        .bind(block_id)
+        .fetch_optional(&self.pool)
+        .await?;
 
-        Ok(())
+        Ok(row.as_ref().map(decode_review_state).transpose()?.flatten())
     }
 
-    pub async fn get_projects(&self, limit: Option<usize>) -> Result<Vec<Project>> {
-        let query = if let Some(limit) = limit {
-            format!(
-                r#"
-                SELECT id, name, description, status, color, start_date, due_date, completed_at,
-                       linked_notes, linked_files, progress, total_tasks, completed_tasks,
-                       created_at, updated_at, created_by
-                FROM projects ORDER BY created_at DESC LIMIT {}
-                "#,
-                limit
-            )
-        } else {
+    /// Blocks that may be cards with their review state, in review order.
+    /// The LIKE patterns are loose; `cards::parse` has the final say.
+    async fn fetch_card_blocks(&self, graph_id: Option<&str>) -> Result<Vec<(Block, Option<ReviewState>)>> {
+        let rows = sqlx::query(
             r#"
-            SELECT id, name, description, status, color, start_date, due_date, completed_at,
-                   linked_notes, linked_files, progress, total_tasks, completed_tasks,
-                   created_at, updated_at, created_by
-            FROM projects ORDER BY created_at DESC
-            "#.to_string()
-        };
+            SELECT b.id, b.content, b.parent_id, b.properties, b.refs, b."order", b.collapsed,
+                   b.created_at, b.updated_at, b.page_id, b.graph_id,
+                   c.ease AS card_ease, c.interval_days AS card_interval_days, c.repetitions AS card_repetitions,
+                   c.lapses AS card_lapses, c.due_at AS card_due_at, c.last_reviewed_at AS card_last_reviewed_at
+            FROM blocks b LEFT JOIN cards c ON c.id = b.id
+            WHERE (b.content LIKE '%#card%' OR b.content LIKE '%#[[card]]%' OR b.content LIKE '% :: %')
+              AND (? IS NULL OR b.graph_id = ?)
+            ORDER BY c.due_at IS NULL, c.due_at, b.created_at
+            "#
+        )
+        .bind(graph_id)
+        .bind(graph_id)
+        .fetch_all(&self.pool)
+        .await?;
 
-        let projects = sqlx::query_as::<_, Project>(&query)
-            .fetch_all(&self.pool)
-            .await?;
+        rows.iter()
+            .map(|row| Ok((Block::from_row(row)?, decode_review_state(row)?)))
+            .collect()
+    }
 
-        Ok(projects)
+    async fn card_from_block(&self, block: Block, state: Option<ReviewState>, now: DateTime<Utc>) -> Result<Option<Card>> {
+        let Some(content) = cards::parse(&block.content) else {
+            return Ok(None);
+        };
+        let answer = match content.answer {
+            Some(answer) => answer,
+            None => {
+                let children = sqlx::query_scalar::<_, String>(
+                    r#"SELECT content FROM blocks WHERE parent_id = ? ORDER BY "order""#
+                )
+                .bind(&block.id)
+                .fetch_all(&self.pool)
+                .await?;
+                children.join("\n")
+            }
+        };
+
+        Ok(Some(Card {
+            block_id: block.id,
+            page_id: block.page_id,
+            graph_id: block.graph_id,
+            question: content.question,
+            answer,
+            is_new: state.is_none(),
+            state: state.unwrap_or_else(|| ReviewState::new(now)),
+        }))
     }
 
-    pub async fn get_tasks_by_project(&self, project_id: &str) -> Result<Vec<Task>> {
-        let tasks = sqlx::query_as::<_, Task>(
+    async fn fetch_tasks(&self, filter: &str, binds: &[String]) -> Result<Vec<Task>> {
+        let sql = format!(
             r#"
             SELECT id, title, description, status, priority, due_date, completed_at,
                    estimated_time, actual_time, project_id, parent_task_id,
-                   linked_notes, linked_files, tags, contexts, recurrence,
+                   linked_notes, linked_files, tags, contexts, recurrence, scheduled_date, block_id,
                    created_at, updated_at, created_by
-            FROM tasks WHERE project_id = ? ORDER BY created_at DESC
-            "#
-        )
-        .bind(project_id)
-        .fetch_all(&self.pool)
-        .await?;
+            FROM tasks {}
+            "#,
+            filter
+        );
+        let mut query = sqlx::query_as::<_, Task>(&sql);
+        for bind in binds {
+            query = query.bind(bind);
+        }
 
-        Ok(tasks)
+        Ok(query.fetch_all(&self.pool).await?)
     }
 
     // Time tracking operations
     pub async fn create_time_entry(&self, request: CreateTimeEntryRequest) -> Result<TimeEntry> {
+        // Only one timer runs at a time
+        if request.end_time.is_none() {
+            self.stop_timer().await?;
+        }
+
         let time_entry = TimeEntry {
             id: uuid::Uuid::new_v4().to_string(),
             task_id: request.task_id,
@@ -1758,9 +3618,127 @@ impl Database {
         .execute(&self.pool)
         .await?;
 
+        if time_entry.duration.is_some() {
+            self.refresh_task_actual_time(&time_entry.task_id).await?;
+        }
+
         Ok(time_entry)
     }
 
+    pub async fn get_running_time_entry(&self) -> Result<Option<TimeEntry>> {
+        let entry = sqlx::query_as::<_, TimeEntry>(
+            r#"
+            SELECT id, task_id, start_time, end_time, duration, description, created_at
+            FROM task_time_entries WHERE end_time IS NULL ORDER BY start_time DESC LIMIT 1
+            "#
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(entry)
+    }
+
+    // Start timing a task, stopping whatever timer was running
+    pub async fn start_timer(&self, task_id: &str, description: Option<String>) -> Result<TimeEntry> {
+        self.get_task(task_id).await?;
+        self.create_time_entry(CreateTimeEntryRequest {
+            task_id: task_id.to_string(),
+            start_time: Utc::now(),
+            end_time: None,
+            description,
+        }).await
+    }
+
+    // Stop the running timer, if any, and return it
+    pub async fn stop_timer(&self) -> Result<Option<TimeEntry>> {
+        let running: Vec<TimeEntry> = sqlx::query_as::<_, TimeEntry>(
+            "SELECT id, task_id, start_time, end_time, duration, description, created_at FROM task_time_entries WHERE end_time IS NULL ORDER BY start_time"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        // Older versions allowed several open entries, so stop all of them
+        let now = Utc::now();
+        let mut stopped = None;
+        for entry in running {
+            stopped = Some(self.update_time_entry_end(&entry.id, now.max(entry.start_time)).await?);
+        }
+
+        Ok(stopped)
+    }
+
+    // Roll the task's time entries up into actual_time, in minutes
+    pub async fn refresh_task_actual_time(&self, task_id: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE tasks SET
+                actual_time = (
+                    SELECT (COALESCE(SUM(duration), 0) + 30) / 60
+                    FROM task_time_entries WHERE task_id = ? AND duration IS NOT NULL
+                ),
+                updated_at = ?
+            WHERE id = ?
+            "#
+        )
+        .bind(task_id)
+        .bind(Utc::now().to_rfc3339())
+        .bind(task_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Stopped time entries with the task details needed for reports
+    /// Stopped time entries that started in `[from, to)`
+    pub async fn get_tracked_entries(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<TrackedEntry>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT e.start_time, e.duration, t.id AS task_id, t.title, t.project_id, t.tags,
+                   p.name AS project_name
+            FROM task_time_entries e
+            JOIN tasks t ON t.id = e.task_id
+            LEFT JOIN projects p ON p.id = t.project_id
+            WHERE e.duration IS NOT NULL
+              AND e.start_time >= ? AND e.start_time < ?
+            "#
+        )
+        .bind(from.to_rfc3339())
+        .bind(to.to_rfc3339())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut entries = Vec::with_capacity(rows.len());
+        for row in rows {
+            let start_time: String = row.get("start_time");
+            let Ok(start_time) = DateTime::parse_from_rfc3339(&start_time) else {
+                continue;
+            };
+            let tags: Option<String> = row.get("tags");
+            entries.push(TrackedEntry {
+                start_time: start_time.with_timezone(&Utc),
+                duration: row.get("duration"),
+                task_id: row.get("task_id"),
+                task_title: row.get("title"),
+                project_id: row.get("project_id"),
+                project_name: row.get("project_name"),
+                tags: tags.and_then(|tags| serde_json::from_str(&tags).ok()).unwrap_or_default(),
+            });
+        }
+
+        Ok(entries)
+    }
+
+    pub async fn get_all_time_entries(&self) -> Result<Vec<TimeEntry>> {
+        let entries = sqlx::query_as::<_, TimeEntry>(
+            "SELECT id, task_id, start_time, end_time, duration, description, created_at FROM task_time_entries ORDER BY start_time"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(entries)
+    }
+
     pub async fn get_time_entries_by_task(&self, task_id: &str) -> Result<Vec<TimeEntry>> {
         let entries = sqlx::query_as::<_, TimeEntry>(
             r#"
@@ -1775,6 +3753,17 @@ impl Database {
         Ok(entries)
     }
 
+    pub async fn get_time_entry(&self, id: &str) -> Result<TimeEntry> {
+        let entry = sqlx::query_as::<_, TimeEntry>(
+            "SELECT id, task_id, start_time, end_time, duration, description, created_at FROM task_time_entries WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(entry)
+    }
+
     pub async fn update_time_entry_end(&self, id: &str, end_time: chrono::DateTime<Utc>) -> Result<TimeEntry> {
         // Get the existing entry to calculate duration
         let entry = sqlx::query_as::<_, TimeEntry>(
@@ -1806,15 +3795,26 @@ impl Database {
             created_at: entry.created_at,
         };
 
+        self.refresh_task_actual_time(&updated_entry.task_id).await?;
+
         Ok(updated_entry)
     }
 
     pub async fn delete_time_entry(&self, id: &str) -> Result<()> {
+        let task_id: Option<String> = sqlx::query_scalar("SELECT task_id FROM task_time_entries WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
         sqlx::query("DELETE FROM task_time_entries WHERE id = ?")
             .bind(id)
             .execute(&self.pool)
             .await?;
 
+        if let Some(task_id) = task_id {
+            self.refresh_task_actual_time(&task_id).await?;
+        }
+
         Ok(())
     }
 
@@ -1826,7 +3826,7 @@ impl Database {
                 r#"
                 SELECT id, title, description, status, priority, due_date, completed_at,
                        estimated_time, actual_time, project_id, parent_task_id,
-                       linked_notes, linked_files, tags, contexts, recurrence,
+                       linked_notes, linked_files, tags, contexts, recurrence, scheduled_date, block_id,
                        created_at, updated_at, created_by
                 FROM tasks
                 WHERE title LIKE ? OR description LIKE ? OR tags LIKE ? OR contexts LIKE ?
@@ -1838,7 +3838,7 @@ impl Database {
             r#"
             SELECT id, title, description, status, priority, due_date, completed_at,
                    estimated_time, actual_time, project_id, parent_task_id,
-                   linked_notes, linked_files, tags, contexts, recurrence,
+                   linked_notes, linked_files, tags, contexts, recurrence, scheduled_date, block_id,
                    created_at, updated_at, created_by
             FROM tasks
             WHERE title LIKE ? OR description LIKE ? OR tags LIKE ? OR contexts LIKE ?
@@ -1891,3 +3891,19 @@ impl Database {
         Ok(projects)
     }
 }
+
+/// Review state from the `card_*` columns of a row, `None` for new cards
+fn decode_review_state(row: &sqlx::sqlite::SqliteRow) -> std::result::Result<Option<ReviewState>, sqlx::Error> {
+    let ease: Option<f64> = row.try_get("card_ease")?;
+    let Some(ease) = ease else {
+        return Ok(None);
+    };
+    Ok(Some(ReviewState {
+        ease,
+        interval_days: row.try_get("card_interval_days")?,
+        repetitions: row.try_get("card_repetitions")?,
+        lapses: row.try_get("card_lapses")?,
+        due_at: decode_datetime(row, "card_due_at")?,
+        last_reviewed_at: decode_optional_datetime(row, "card_last_reviewed_at")?,
+    }))
+}
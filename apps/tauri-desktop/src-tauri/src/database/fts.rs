@@ -0,0 +1,115 @@
+//! Triggers keeping the external-content FTS tables in sync with their tables.
+//!
+//! Earlier versions deleted FTS rows by id, which corrupts an external-content
+//! index as soon as a row is updated, and never installed triggers for tasks and
+//! projects. Missing or legacy triggers are (re)created and their index rebuilt
+//! once from the content table.
+
+use super::Database;
+use crate::error::Result;
+
+/// Content tables with an `<table>_fts` index, and the indexed columns in FTS order
+pub(super) const FTS_COLUMNS: [(&str, &[&str]); 5] = [
+    ("blocks", &["id", "content", "page_id", "graph_id"]),
+    ("pages", &["id", "name", "title", "graph_id"]),
+    ("notes", &["id", "title", "content"]),
+    ("tasks", &["id", "title", "description", "tags", "contexts"]),
+    ("projects", &["id", "name", "description"]),
+];
+
+impl Database {
+    /// Install the triggers of the FTS tables over `tables`
+    pub(super) async fn create_fts_triggers(&self, tables: &[&str]) -> Result<()> {
+        for (table, columns) in FTS_COLUMNS.into_iter().filter(|(table, _)| tables.contains(table)) {
+            let update_trigger: Option<String> = sqlx::query_scalar(
+                "SELECT sql FROM sqlite_master WHERE type = 'trigger' AND name = ?"
+            )
+            .bind(format!("{}_fts_update", table))
+            .fetch_optional(&self.pool)
+            .await?;
+            let legacy = update_trigger.as_deref().map_or(false, |sql| sql.contains(&format!("DELETE FROM {}_fts", table)));
+            if legacy {
+                for action in ["insert", "delete", "update"] {
+                    sqlx::query(&format!("DROP TRIGGER IF EXISTS {}_fts_{}", table, action))
+                        .execute(&self.pool)
+                        .await?;
+                }
+            }
+
+            let names = columns.join(", ");
+            let values = |row: &str| columns.iter().map(|column| format!("{}.{}", row, column)).collect::<Vec<_>>().join(", ");
+            let insert = format!(
+                "INSERT INTO {table}_fts(rowid, {names}) VALUES (new.rowid, {values});",
+                table = table, names = names, values = values("new")
+            );
+            let delete = format!(
+                "INSERT INTO {table}_fts({table}_fts, rowid, {names}) VALUES ('delete', old.rowid, {values});",
+                table = table, names = names, values = values("old")
+            );
+            for (action, body) in [("insert", insert.clone()), ("delete", delete.clone()), ("update", format!("{}\n{}", delete, insert))] {
+                sqlx::query(&format!(
+                    "CREATE TRIGGER IF NOT EXISTS {table}_fts_{action} AFTER {event} ON {table} BEGIN\n{body}\nEND",
+                    table = table, action = action, event = action.to_uppercase(), body = body
+                ))
+                .execute(&self.pool)
+                .await?;
+            }
+
+            if legacy || update_trigger.is_none() {
+                sqlx::query(&format!("INSERT INTO {0}_fts({0}_fts) VALUES('rebuild')", table))
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    async fn matches(db: &Database, table: &str, query: &str) -> Vec<String> {
+        sqlx::query_scalar(&format!("SELECT id FROM {0}_fts WHERE {0}_fts MATCH ? ORDER BY id", table))
+            .bind(query)
+            .fetch_all(db.get_pool())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_task_and_project_search_follows_edits() {
+        let dir = tempdir().unwrap();
+        let db = Database::new_with_path(dir.path().join("fts.db").to_str().unwrap()).await.unwrap();
+
+        sqlx::query("INSERT INTO tasks (id, title, created_at, updated_at) VALUES ('t1', 'Renew passport', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z')")
+            .execute(db.get_pool()).await.unwrap();
+        sqlx::query("INSERT INTO projects (id, name, description, created_at, updated_at) VALUES ('p1', 'Travel', 'Summer trip', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z')")
+            .execute(db.get_pool()).await.unwrap();
+        assert_eq!(matches(&db, "tasks", "passport").await, ["t1"]);
+        assert_eq!(matches(&db, "projects", "summer").await, ["p1"]);
+
+        sqlx::query("UPDATE tasks SET title = 'Renew visa' WHERE id = 't1'").execute(db.get_pool()).await.unwrap();
+        assert!(matches(&db, "tasks", "passport").await.is_empty());
+        assert_eq!(matches(&db, "tasks", "visa").await, ["t1"]);
+        sqlx::query("DELETE FROM projects WHERE id = 'p1'").execute(db.get_pool()).await.unwrap();
+        assert!(matches(&db, "projects", "summer").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rows_written_before_the_triggers_are_indexed() {
+        let dir = tempdir().unwrap();
+        let db = Database::new_with_path(dir.path().join("fts.db").to_str().unwrap()).await.unwrap();
+        for action in ["insert", "delete", "update"] {
+            sqlx::query(&format!("DROP TRIGGER tasks_fts_{}", action)).execute(db.get_pool()).await.unwrap();
+        }
+        sqlx::query("INSERT INTO tasks (id, title, created_at, updated_at) VALUES ('t1', 'Call plumber', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z')")
+            .execute(db.get_pool()).await.unwrap();
+        assert!(matches(&db, "tasks", "plumber").await.is_empty());
+
+        db.create_fts_triggers(&["tasks"]).await.unwrap();
+        assert_eq!(matches(&db, "tasks", "plumber").await, ["t1"]);
+    }
+}
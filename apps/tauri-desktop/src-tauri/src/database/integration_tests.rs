@@ -165,6 +165,62 @@ mod integration_tests {
         assert!(first_result.content.contains("searchable"));
     }
 
+    #[tokio::test]
+    async fn test_legacy_fts_triggers_are_replaced() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("legacy_fts.db");
+        let db = Database::new_with_path(db_path.to_str().unwrap()).await.unwrap();
+        let note = db.create_note(CreateNoteRequest {
+            title: "Groceries".to_string(),
+            content: "apples".to_string(),
+            tags: None,
+        }).await.unwrap();
+
+        // The trigger older versions installed
+        sqlx::query(
+            "DROP TRIGGER notes_fts_update; \
+            CREATE TRIGGER notes_fts_update AFTER UPDATE ON notes BEGIN \
+                DELETE FROM notes_fts WHERE id = old.id; \
+                INSERT INTO notes_fts(id, title, content) VALUES (new.id, new.title, new.content); \
+            END",
+        )
+        .execute(db.get_pool())
+        .await
+        .unwrap();
+        db.close().await;
+
+        let db = Database::new_with_path(db_path.to_str().unwrap()).await.unwrap();
+        let trigger: String = sqlx::query_scalar("SELECT sql FROM sqlite_master WHERE name = 'notes_fts_update'")
+            .fetch_one(db.get_pool())
+            .await
+            .unwrap();
+        assert!(!trigger.contains("DELETE FROM notes_fts"), "{}", trigger);
+
+        db.update_note(UpdateNoteRequest {
+            id: note.id,
+            title: None,
+            content: Some("pears".to_string()),
+            tags: None,
+            is_favorite: None,
+            is_archived: None,
+        }).await.unwrap();
+        sqlx::query("INSERT INTO notes_fts(notes_fts) VALUES('integrity-check')")
+            .execute(db.get_pool())
+            .await
+            .unwrap();
+        let search = |query: &str| SearchRequest {
+            query: query.to_string(),
+            tags: None,
+            date_from: None,
+            date_to: None,
+            include_archived: Some(false),
+            limit: Some(10),
+            offset: Some(0),
+        };
+        assert_eq!(db.search_notes(search("pears")).await.unwrap().notes.len(), 1);
+        assert!(db.search_notes(search("apples")).await.unwrap().notes.is_empty());
+    }
+
     #[tokio::test]
     async fn test_pagination() {
         let (db, _temp_dir, _graph_id) = create_test_database().await.unwrap();
@@ -307,8 +363,9 @@ mod integration_tests {
         let task_request = CreateTaskRequest {
             title: "Test Task".to_string(),
             description: Some("Test task description".to_string()),
-            priority: Some("high".to_string()),
+            priority: Some(TaskPriority::High),
             due_date: None,
+            scheduled_date: None,
             estimated_time: None,
             project_id: Some("non-existent-project".to_string()), // This should fail
             parent_task_id: None,
@@ -316,6 +373,7 @@ mod integration_tests {
             linked_files: None,
             tags: None,
             contexts: None,
+            recurrence: None,
         };
 
         let result = db.create_task(task_request).await;
@@ -337,8 +395,9 @@ mod integration_tests {
         let task_request_valid = CreateTaskRequest {
             title: "Valid Test Task".to_string(),
             description: Some("Valid test task description".to_string()),
-            priority: Some("medium".to_string()),
+            priority: Some(TaskPriority::Medium),
             due_date: None,
+            scheduled_date: None,
             estimated_time: None,
             project_id: Some(project.id.clone()),
             parent_task_id: None,
@@ -346,6 +405,7 @@ mod integration_tests {
             linked_files: None,
             tags: None,
             contexts: None,
+            recurrence: None,
         };
 
         let task = db.create_task(task_request_valid).await;
@@ -357,6 +417,7 @@ mod integration_tests {
             description: None,
             priority: None,
             due_date: None,
+            scheduled_date: None,
             estimated_time: None,
             project_id: Some(project.id),
             parent_task_id: Some("non-existent-parent".to_string()),
@@ -364,6 +425,7 @@ mod integration_tests {
             linked_files: None,
             tags: None,
             contexts: None,
+            recurrence: None,
         };
 
         let result = db.create_task(task_request_invalid_parent).await;
@@ -2,11 +2,23 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
-use pulldown_cmark::{Parser, Options, Event, Tag};
 use serde::{Deserialize, Serialize};
 
-use crate::models::{Page, Block, Tag as TagModel, CreatePageRequest, CreateBlockRequest};
+use crate::models::{Page, Block, Tag as TagModel, CreatePageRequest, CreateBlockRequest, UpdateBlockRequest};
+use self::outline::OutlineBlock;
 use crate::database::Database;
+use crate::encoding::percent_decode;
+
+pub mod backup;
+pub mod html;
+pub mod ics;
+pub mod importer;
+pub mod logseq;
+pub mod notion;
+pub mod obsidian;
+pub mod opml;
+pub mod roam;
+pub mod outline;
 
 #[cfg(test)]
 mod tests;
@@ -59,101 +71,32 @@ impl FileOperations {
             journal_date: None,
         };
         
-        let markdown_content = if content.starts_with("---\n") {
-            // Extract frontmatter
-            let end_pos = content[4..].find("\n---\n").map(|pos| pos + 4);
-            if let Some(end_pos) = end_pos {
-                let frontmatter_str = &content[4..end_pos];
-                let content_str = &content[end_pos + 5..];
-                
-                // Parse YAML frontmatter
-                if let Ok(fm) = serde_yaml::from_str::<MarkdownFrontmatter>(frontmatter_str) {
-                    frontmatter = fm;
-                }
-                
-                content_str.to_string()
-            } else {
-                content.to_string()
-            }
-        } else {
-            content.to_string()
-        };
+        let (frontmatter_str, markdown_content) = Self::split_frontmatter(content);
+        
+        // Parse YAML frontmatter
+        if let Some(Ok(fm)) = frontmatter_str.map(serde_yaml::from_str::<MarkdownFrontmatter>) {
+            frontmatter = fm;
+        }
         
-        Ok((frontmatter, markdown_content))
+        Ok((frontmatter, markdown_content.to_string()))
     }
     
-    /// Convert Markdown content to blocks
-    pub fn markdown_to_blocks(markdown: &str) -> Vec<String> {
-        let mut blocks = Vec::new();
-        let mut current_block = String::new();
-        
-        let parser = Parser::new_ext(markdown, Options::all());
-        let mut in_code_block = false;
-        
-        for event in parser {
-            match event {
-                Event::Start(Tag::CodeBlock(_)) => {
-                    if !current_block.trim().is_empty() {
-                        blocks.push(current_block.trim().to_string());
-                        current_block.clear();
-                    }
-                    in_code_block = true;
-                    current_block.push_str("```");
-                }
-                Event::End(Tag::CodeBlock(_)) => {
-                    current_block.push_str("```");
-                    blocks.push(current_block.trim().to_string());
-                    current_block.clear();
-                    in_code_block = false;
-                }
-                Event::Start(Tag::Heading(_, _, _)) => {
-                    if !current_block.trim().is_empty() {
-                        blocks.push(current_block.trim().to_string());
-                        current_block.clear();
-                    }
-                }
-                Event::End(Tag::Heading(_, _, _)) => {
-                    blocks.push(current_block.trim().to_string());
-                    current_block.clear();
-                }
-                Event::Start(Tag::Paragraph) => {
-                    if !current_block.trim().is_empty() && !in_code_block {
-                        blocks.push(current_block.trim().to_string());
-                        current_block.clear();
-                    }
-                }
-                Event::End(Tag::Paragraph) => {
-                    if !in_code_block && !current_block.trim().is_empty() {
-                        blocks.push(current_block.trim().to_string());
-                        current_block.clear();
-                    }
-                }
-                Event::Text(text) => {
-                    current_block.push_str(&text);
-                }
-                Event::Code(code) => {
-                    current_block.push('`');
-                    current_block.push_str(&code);
-                    current_block.push('`');
-                }
-                Event::SoftBreak | Event::HardBreak => {
-                    if in_code_block {
-                        current_block.push('\n');
-                    } else {
-                        current_block.push(' ');
-                    }
-                }
-                _ => {}
+    /// Split a leading `---` YAML block from the rest of the document
+    pub fn split_frontmatter(content: &str) -> (Option<&str>, &str) {
+        if let Some(rest) = content.strip_prefix("---\n") {
+            if let Some(body) = rest.strip_prefix("---\n") {
+                return (Some(""), body);
+            }
+            if let Some(end_pos) = rest.find("\n---\n") {
+                return (Some(&rest[..end_pos]), &rest[end_pos + 5..]);
             }
         }
-        
-        // Add any remaining content
-        if !current_block.trim().is_empty() {
-            blocks.push(current_block.trim().to_string());
-        }
-        
-        // Filter out empty blocks
-        blocks.into_iter().filter(|b| !b.trim().is_empty()).collect()
+        (None, content)
+    }
+    
+    /// Convert Markdown content to a block tree
+    pub fn markdown_to_blocks(markdown: &str) -> Vec<OutlineBlock> {
+        outline::parse(markdown)
     }
     
     /// Convert page and blocks to Markdown format
@@ -187,20 +130,117 @@ impl FileOperations {
         
         markdown.push_str("---\n\n");
         
-        // Add page title as heading if different from frontmatter title
-        if let Some(title) = &page.title {
-            markdown.push_str(&format!("# {}\n\n", title));
-        } else {
-            markdown.push_str(&format!("# {}\n\n", page.name));
+        // Add blocks as a nested outline
+        markdown.push_str(&outline::render(&outline::blocks_to_outline(blocks)));
+        
+        markdown
+    }
+    
+    /// Parse timestamps written by `page_to_markdown`
+    fn parse_frontmatter_time(value: &str) -> Option<DateTime<Utc>> {
+        chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+            .ok()
+            .map(|time| DateTime::<Utc>::from_naive_utc_and_offset(time, Utc))
+    }
+    
+    /// Keep the creation and edit times of an imported page or block instead of the import time
+    async fn restore_timestamps(
+        db: &Database,
+        table: &'static str,
+        id: &str,
+        created: Option<DateTime<Utc>>,
+        updated: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        if created.is_none() && updated.is_none() {
+            return Ok(());
         }
+        let query = format!("UPDATE {} SET created_at = COALESCE(?, created_at), updated_at = COALESCE(?, updated_at) WHERE id = ?", table);
+        sqlx::query(&query)
+            .bind(created.map(|time| time.to_rfc3339()))
+            .bind(updated.map(|time| time.to_rfc3339()))
+            .bind(id)
+            .execute(db.get_pool())
+            .await?;
+        Ok(())
+    }
+    
+    /// Create an outline's blocks under a page, parents first and keeping sibling order
+    pub async fn create_outline_blocks(
+        db: &Database,
+        graph_id: &str,
+        page_id: &str,
+        blocks: &[OutlineBlock],
+        result: &mut ImportResult,
+    ) -> Vec<Block> {
+        let mut created_blocks = Vec::new();
+        let mut pending: Vec<(Option<String>, usize, &OutlineBlock)> = blocks.iter()
+            .enumerate()
+            .rev()
+            .map(|(index, block)| (None, index, block))
+            .collect();
         
-        // Add blocks content
-        for block in blocks {
-            markdown.push_str(&block.content);
-            markdown.push_str("\n\n");
+        while let Some((parent_id, index, block)) = pending.pop() {
+            let block_request = CreateBlockRequest {
+                graph_id: graph_id.to_string(),
+                page_id: page_id.to_string(),
+                content: block.content.clone(),
+                parent_id,
+                properties: outline::properties_to_json(&block.properties),
+                refs: Some("[]".to_string()),
+                order: Some(index as i32),
+            };
+            
+            match db.create_block(block_request).await {
+                Ok(mut created) => {
+                    result.blocks_imported += 1;
+                    if block.collapsed {
+                        let collapse = UpdateBlockRequest {
+                            id: created.id.clone(),
+                            content: None,
+                            parent_id: None,
+                            properties: None,
+                            refs: None,
+                            order: None,
+                            collapsed: Some(true),
+                        };
+                        match db.update_block(collapse).await {
+                            Ok(updated) => created = updated,
+                            Err(e) => result.errors.push(format!("Failed to collapse block: {}", e)),
+                        }
+                    }
+                    pending.extend(block.children.iter()
+                        .enumerate()
+                        .rev()
+                        .map(|(index, child)| (Some(created.id.clone()), index, child)));
+                    created_blocks.push(created);
+                }
+                Err(e) => result.errors.push(format!("Failed to create block: {}", e)),
+            }
         }
         
-        markdown
+        created_blocks
+    }
+    
+    /// Create a page with the blocks of an outline document, recording failures in `result`
+    pub async fn import_outline_page(
+        db: &Database,
+        page_request: CreatePageRequest,
+        markdown: &str,
+        result: &mut ImportResult,
+    ) -> Option<Page> {
+        match db.create_page(page_request).await {
+            Ok(page) => {
+                result.pages_imported += 1;
+                
+                let blocks = Self::markdown_to_blocks(markdown);
+                Self::create_outline_blocks(db, &page.graph_id, &page.id, &blocks, result).await;
+                Some(page)
+            }
+            Err(e) => {
+                result.errors.push(format!("Failed to create page: {}", e));
+                None
+            }
+        }
     }
     
     /// Import single Markdown file
@@ -240,31 +280,11 @@ impl FileOperations {
             journal_date: frontmatter.journal_date,
         };
         
-        match db.create_page(page_request).await {
-            Ok(page) => {
-                result.pages_imported += 1;
-                
-                // Convert markdown to blocks
-                let block_contents = Self::markdown_to_blocks(&markdown_content);
-                
-                for (index, content) in block_contents.into_iter().enumerate() {
-                    let block_request = CreateBlockRequest {
-                        graph_id: graph_id.to_string(),
-                        page_id: page.id.clone(),
-                        content,
-                        parent_id: None,
-                        properties: None,
-                        refs: Some("[]".to_string()),
-                        order: Some(index as i32),
-                    };
-                    
-                    match db.create_block(block_request).await {
-                        Ok(_) => result.blocks_imported += 1,
-                        Err(e) => result.errors.push(format!("Failed to create block: {}", e)),
-                    }
-                }
-            }
-            Err(e) => result.errors.push(format!("Failed to create page: {}", e)),
+        if let Some(page) = Self::import_outline_page(db, page_request, &markdown_content, &mut result).await {
+            // Keep the original timestamps so exporting again yields the same file
+            let created = frontmatter.created.as_deref().and_then(Self::parse_frontmatter_time);
+            let updated = frontmatter.updated.as_deref().and_then(Self::parse_frontmatter_time);
+            Self::restore_timestamps(db, "pages", &page.id, created, updated).await?;
         }
         
         Ok(result)
@@ -322,74 +342,18 @@ impl FileOperations {
             export_path: output_dir.to_string_lossy().to_string(),
         })
     }
+}
 
-    /// Create backup of all data
-    pub async fn create_backup(
-        db: &Database,
-        output_path: &str,
-    ) -> Result<()> {
-        let pages = db.get_all_pages().await?;
-        let mut all_blocks = Vec::new();
-        for page in &pages {
-            let blocks = db.get_blocks_by_page(&page.id).await?;
-            all_blocks.extend(blocks);
-        }
-        let tags = db.get_tags().await?;
-
-        let backup_data = BackupData {
-            version: "1.0".to_string(),
-            created_at: chrono::Utc::now(),
-            pages,
-            blocks: all_blocks,
-            tags,
-        };
-
-        let json_content = serde_json::to_string_pretty(&backup_data)?;
-        fs::write(output_path, json_content)?;
-
-        Ok(())
-    }
-
-    /// Restore backup data
-    pub async fn restore_backup(
-        db: &Database,
-        backup_path: &str,
-    ) -> Result<()> {
-        let backup_content = fs::read_to_string(backup_path)?;
-        let backup_data: BackupData = serde_json::from_str(&backup_content)?;
-
-        // Clear existing data (in a transaction)
-        // Note: This is a simplified implementation
-        // In production, you'd want proper transaction handling
-
-        // Restore pages
-        for page in backup_data.pages {
-            let create_request = crate::models::CreatePageRequest {
-                name: page.name,
-                title: page.title,
-                graph_id: page.graph_id,
-                is_journal: Some(page.is_journal),
-                journal_date: page.journal_date,
-                tags: Some(page.tags),
-                properties: page.properties,
-            };
-            db.create_page(create_request).await?;
-        }
-
-        // Restore blocks
-        for block in backup_data.blocks {
-            let create_request = crate::models::CreateBlockRequest {
-                content: block.content,
-                page_id: block.page_id,
-                graph_id: block.graph_id,
-                parent_id: block.parent_id,
-                order: Some(block.order),
-                refs: Some(block.refs),
-                properties: block.properties,
-            };
-            db.create_block(create_request).await?;
+/// Text between `open` and `close` markers that does not span lines
+fn delimited<'a>(text: &'a str, open: &'a str, close: &'a str) -> impl Iterator<Item = &'a str> {
+    let mut rest = text;
+    std::iter::from_fn(move || loop {
+        let start = rest.find(open)? + open.len();
+        let end = rest[start..].find(close)? + start;
+        let inner = &rest[start..end];
+        rest = &rest[end + close.len()..];
+        if !inner.is_empty() && !inner.contains('\n') {
+            return Some(inner);
         }
-
-        Ok(())
-    }
+    })
 }
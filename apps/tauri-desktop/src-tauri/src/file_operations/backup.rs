@@ -0,0 +1,999 @@
+//! Backup archives.
+//!
+//! A backup is a zip holding one NDJSON file per table (`tables/<name>.ndjson`, one
+//! row object per line) and a `manifest.json` with the format and schema version
+//! plus the row count and SHA-256 of every table file. Tables are streamed row by
+//! row, so memory use does not grow with the size of the graph. Blobs are written
+//! as `{"$blob": "<hex>"}`.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Utc};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::{Sqlite, SqliteArguments, SqliteConnection, SqliteRow};
+use sqlx::{Column, Row, TypeInfo, ValueRef};
+use zip::write::FileOptions;
+
+use super::{BackupData, FileOperations};
+use crate::database::{Database, SCHEMA_VERSION};
+use crate::sync::{is_device_local_setting, DEVICE_LOCAL_SETTINGS};
+
+pub mod pitr;
+pub mod scheduler;
+
+pub const BACKUP_FORMAT: &str = "minglog-backup";
+pub const BACKUP_FORMAT_VERSION: u32 = 2;
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Tables in a backup, parents before the tables that reference them.
+/// `block_ops` is left out: the op log belongs to the devices that wrote it.
+/// Restored blocks are queued in `block_ops_pending` and recorded as ops on the
+/// next sync, like any other local edit.
+/// For the same reason the device-local sync settings never enter a backup, and
+/// a restore leaves this device's own values in place (see `portable_rows`).
+pub const BACKUP_TABLES: [&str; 15] = [
+    "graphs",
+    "pages",
+    "blocks",
+    "tags",
+    "page_aliases",
+    "links",
+    "cards",
+    "card_reviews",
+    "notes",
+    "projects",
+    "tasks",
+    "task_time_entries",
+    "task_dependencies",
+    "reminders",
+    "settings",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format: String,
+    pub version: u32,
+    pub schema_version: i64,
+    pub app_version: String,
+    pub created_at: DateTime<Utc>,
+    pub tables: Vec<TableManifest>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableManifest {
+    pub name: String,
+    pub file: String,
+    pub rows: u64,
+    pub bytes: u64,
+    pub sha256: String,
+}
+
+/// Result of checking an archive against its manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupVerification {
+    pub valid: bool,
+    pub manifest: Option<BackupManifest>,
+    pub errors: Vec<String>,
+}
+
+/// How restored rows are combined with what is already in the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestoreMode {
+    /// Delete the backed-up tables first, leaving exactly what the backup holds
+    ReplaceAll,
+    /// Keep existing rows and only add the ones that are missing
+    MergeSkipExisting,
+    /// Overwrite existing rows whose `updated_at` is older than the backup's
+    MergeNewerWins,
+}
+
+impl Default for RestoreMode {
+    fn default() -> Self {
+        RestoreMode::MergeSkipExisting
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RestoreOptions {
+    #[serde(default)]
+    pub mode: RestoreMode,
+    /// Run the restore and report the changes, then roll everything back
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TableRestoreReport {
+    pub name: String,
+    pub deleted: u64,
+    pub inserted: u64,
+    pub updated: u64,
+    /// Rows left alone because they already exist or clash with a unique key
+    pub skipped: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreReport {
+    pub mode: RestoreMode,
+    pub dry_run: bool,
+    pub tables: Vec<TableRestoreReport>,
+    /// Backed-up pages whose name is already taken by another page in the graph
+    pub merged_pages: Vec<MergedPage>,
+}
+
+/// A backed-up page merged into the existing page of the same name. Its blocks,
+/// aliases, links and task links are moved over to the existing page.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergedPage {
+    pub graph_id: String,
+    pub name: String,
+    pub backup_id: String,
+    pub page_id: String,
+}
+
+/// A backup archive found in a backup directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupInfo {
+    pub path: String,
+    pub file_name: String,
+    pub created_at: DateTime<Utc>,
+    pub size: u64,
+    pub schema_version: i64,
+}
+
+/// Which automatic backups survive pruning. Each rule keeps the newest backup of
+/// its period; a backup is kept if any rule keeps it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    pub keep_last: usize,
+    pub keep_daily: usize,
+    pub keep_weekly: usize,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self { keep_last: 10, keep_daily: 7, keep_weekly: 4 }
+    }
+}
+
+impl RetentionPolicy {
+    /// Indices into `backups` (newest first) that the policy no longer keeps.
+    /// The newest backup is always kept.
+    pub fn prune(&self, backups: &[BackupInfo]) -> Vec<usize> {
+        let mut days = Vec::new();
+        let mut weeks = Vec::new();
+        let mut pruned = Vec::new();
+
+        for (index, backup) in backups.iter().enumerate() {
+            let day = backup.created_at.date_naive();
+            let week = (day.iso_week().year(), day.iso_week().week());
+            let mut keep = index < self.keep_last.max(1);
+            if !days.contains(&day) && days.len() < self.keep_daily {
+                days.push(day);
+                keep = true;
+            }
+            if !weeks.contains(&week) && weeks.len() < self.keep_weekly {
+                weeks.push(week);
+                keep = true;
+            }
+            if !keep {
+                pruned.push(index);
+            }
+        }
+
+        pruned
+    }
+}
+
+/// Hashes, counts and forwards everything written to it
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+    bytes: u64,
+    lines: u64,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.bytes += written as u64;
+        self.lines += buf[..written].iter().filter(|byte| **byte == b'\n').count() as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[allow(dead_code)]
+impl FileOperations {
+    /// Write a backup archive of every table in `BACKUP_TABLES` to `output_path`
+    pub async fn create_backup(
+        db: &Database,
+        output_path: &str,
+    ) -> Result<BackupManifest> {
+        let mut zip = zip::ZipWriter::new(File::create(output_path)?);
+        let options = FileOptions::default().large_file(true);
+        let mut tables = Vec::new();
+
+        for table in BACKUP_TABLES {
+            if !table_exists(db, table).await? {
+                continue;
+            }
+
+            let file = format!("tables/{}.ndjson", table);
+            zip.start_file(file.as_str(), options)?;
+            let mut writer = HashingWriter { inner: &mut zip, hasher: Sha256::new(), bytes: 0, lines: 0 };
+
+            let query = format!("SELECT * FROM {} WHERE {} ORDER BY rowid", table, portable_rows(table, table));
+            let mut rows = sqlx::query(&query).fetch(db.get_pool());
+            while let Some(row) = rows.try_next().await? {
+                serde_json::to_writer(&mut writer, &row_to_json(&row)?)?;
+                writer.write_all(b"\n")?;
+            }
+
+            tables.push(TableManifest {
+                name: table.to_string(),
+                file,
+                rows: writer.lines,
+                bytes: writer.bytes,
+                sha256: hex(&writer.hasher.finalize()),
+            });
+        }
+
+        let manifest = BackupManifest {
+            format: BACKUP_FORMAT.to_string(),
+            version: BACKUP_FORMAT_VERSION,
+            schema_version: SCHEMA_VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            created_at: Utc::now(),
+            tables,
+        };
+        zip.start_file(MANIFEST_FILE, FileOptions::default())?;
+        serde_json::to_writer_pretty(&mut zip, &manifest)?;
+        zip.finish()?;
+
+        Ok(manifest)
+    }
+
+    /// Check that an archive is complete and unmodified
+    pub fn verify_backup(path: &Path) -> Result<BackupVerification> {
+        let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+        let mut errors = Vec::new();
+
+        let manifest: BackupManifest = match archive.by_name(MANIFEST_FILE) {
+            Ok(file) => serde_json::from_reader(file).map_err(|e| anyhow!("Invalid manifest: {}", e))?,
+            Err(_) => return Err(anyhow!("Not a backup archive: {} is missing", MANIFEST_FILE)),
+        };
+        if manifest.format != BACKUP_FORMAT {
+            errors.push(format!("Unknown backup format '{}'", manifest.format));
+        }
+        if manifest.version > BACKUP_FORMAT_VERSION {
+            errors.push(format!("Backup format version {} is newer than supported version {}", manifest.version, BACKUP_FORMAT_VERSION));
+        }
+
+        for table in &manifest.tables {
+            let file = match archive.by_name(&table.file) {
+                Ok(file) => file,
+                Err(_) => {
+                    errors.push(format!("{}: {} is missing", table.name, table.file));
+                    continue;
+                }
+            };
+            let mut writer = HashingWriter { inner: io::sink(), hasher: Sha256::new(), bytes: 0, lines: 0 };
+            io::copy(&mut { file }, &mut writer)?;
+
+            let sha256 = hex(&writer.hasher.finalize());
+            if sha256 != table.sha256 {
+                errors.push(format!("{}: checksum mismatch (expected {}, found {})", table.name, table.sha256, sha256));
+            }
+            if writer.lines != table.rows {
+                errors.push(format!("{}: expected {} rows, found {}", table.name, table.rows, writer.lines));
+            }
+        }
+
+        Ok(BackupVerification {
+            valid: errors.is_empty(),
+            manifest: Some(manifest),
+            errors,
+        })
+    }
+
+    /// Take a consistent snapshot of the live database with `VACUUM INTO` and
+    /// write it to `output_path` as a backup archive
+    pub async fn create_snapshot_backup(
+        db: &Database,
+        output_path: &Path,
+    ) -> Result<BackupManifest> {
+        let snapshot_path = Self::snapshot_for_backup(db, output_path).await?;
+        Self::create_backup_from_snapshot(&snapshot_path, output_path).await
+    }
+
+    /// Snapshot the live database with `VACUUM INTO` next to `output_path`. This is
+    /// the only step that needs the live database.
+    pub async fn snapshot_for_backup(db: &Database, output_path: &Path) -> Result<PathBuf> {
+        let snapshot_path = output_path.with_extension("snapshot.db");
+        if snapshot_path.exists() {
+            std::fs::remove_file(&snapshot_path)?;
+        }
+        sqlx::query("VACUUM INTO ?")
+            .bind(snapshot_path.to_string_lossy().to_string())
+            .execute(db.get_pool())
+            .await?;
+        Ok(snapshot_path)
+    }
+
+    /// Write the snapshot taken by `snapshot_for_backup` to `output_path` as a
+    /// backup archive, then remove the snapshot
+    pub async fn create_backup_from_snapshot(snapshot_path: &Path, output_path: &Path) -> Result<BackupManifest> {
+        let snapshot_file = snapshot_path.to_string_lossy().to_string();
+        let result = async {
+            let snapshot = Database::open_snapshot(&snapshot_file).await?;
+            let manifest = Self::create_backup(&snapshot, &output_path.to_string_lossy()).await;
+            snapshot.close().await;
+            manifest
+        }
+        .await;
+        std::fs::remove_file(snapshot_path)?;
+
+        result
+    }
+
+    /// Backup archives in `dir`, newest first. Files without a readable manifest are skipped.
+    pub fn list_backups(dir: &Path) -> Result<Vec<BackupInfo>> {
+        let mut backups = Vec::new();
+        if !dir.is_dir() {
+            return Ok(backups);
+        }
+
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("zip") {
+                continue;
+            }
+            let manifest = zip::ZipArchive::new(File::open(&path)?).ok().and_then(|mut archive| {
+                let file = archive.by_name(MANIFEST_FILE).ok()?;
+                serde_json::from_reader::<_, BackupManifest>(file).ok()
+            });
+            let Some(manifest) = manifest.filter(|manifest| manifest.format == BACKUP_FORMAT) else {
+                continue;
+            };
+
+            backups.push(BackupInfo {
+                file_name: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+                size: std::fs::metadata(&path)?.len(),
+                path: path.to_string_lossy().to_string(),
+                created_at: manifest.created_at,
+                schema_version: manifest.schema_version,
+            });
+        }
+
+        backups.sort_by_key(|backup| std::cmp::Reverse(backup.created_at));
+        Ok(backups)
+    }
+
+    /// Restore a backup, keeping existing rows
+    pub async fn restore_backup(
+        db: &Database,
+        backup_path: &str,
+    ) -> Result<RestoreReport> {
+        Self::restore_backup_with_options(db, backup_path, &RestoreOptions::default()).await
+    }
+
+    /// Restore a backup archive, or a version 1 JSON backup, in a single transaction.
+    /// Rows keep their original ids, so references between tables stay intact.
+    pub async fn restore_backup_with_options(
+        db: &Database,
+        backup_path: &str,
+        options: &RestoreOptions,
+    ) -> Result<RestoreReport> {
+        let mut magic = [0u8; 4];
+        let is_archive = File::open(backup_path)?.read(&mut magic)? == 4 && magic == *b"PK\x03\x04";
+
+        let mut tables = Vec::new();
+        let mut manifest = None;
+        let mut legacy = Vec::new();
+        if is_archive {
+            let verification = Self::verify_backup(Path::new(backup_path))?;
+            if !verification.valid {
+                return Err(anyhow!("Backup failed verification: {}", verification.errors.join("; ")));
+            }
+            let backup_manifest = verification.manifest.ok_or_else(|| anyhow!("Backup has no manifest"))?;
+            if backup_manifest.schema_version > SCHEMA_VERSION {
+                return Err(anyhow!("Backup schema version {} is newer than this app's {}", backup_manifest.schema_version, SCHEMA_VERSION));
+            }
+            for table in &backup_manifest.tables {
+                if BACKUP_TABLES.contains(&table.name.as_str()) && table_exists(db, &table.name).await? {
+                    tables.push(TableRestoreReport { name: table.name.clone(), ..Default::default() });
+                }
+            }
+            manifest = Some(backup_manifest);
+        } else {
+            let backup_data: BackupData = serde_json::from_str(&std::fs::read_to_string(backup_path)?)?;
+            let to_rows = |items: Vec<Value>| -> Vec<Map<String, Value>> {
+                items.into_iter().filter_map(|item| match item {
+                    Value::Object(row) => Some(row),
+                    _ => None,
+                }).collect()
+            };
+            legacy = vec![
+                to_rows(backup_data.pages.iter().map(serde_json::to_value).collect::<Result<_, _>>()?),
+                to_rows(backup_data.blocks.iter().map(serde_json::to_value).collect::<Result<_, _>>()?),
+                to_rows(backup_data.tags.iter().map(serde_json::to_value).collect::<Result<_, _>>()?),
+            ];
+            tables = ["pages", "blocks", "tags"].iter()
+                .map(|name| TableRestoreReport { name: name.to_string(), ..Default::default() })
+                .collect();
+        }
+
+        let mut tx = db.get_pool().begin().await?;
+        // Rows are restored table by table, so a block may arrive before its parent
+        sqlx::query("PRAGMA defer_foreign_keys = ON").execute(&mut *tx).await?;
+        if options.mode == RestoreMode::ReplaceAll {
+            clear_tables(&mut tx, &mut tables).await?;
+        }
+        let mut merged_pages = Vec::new();
+
+        if let Some(manifest) = &manifest {
+            let mut archive = zip::ZipArchive::new(File::open(backup_path)?)?;
+            for report in &mut tables {
+                let Some(table) = manifest.tables.iter().find(|table| table.name == report.name) else {
+                    continue;
+                };
+                for line in BufReader::new(archive.by_name(&table.file)?).lines() {
+                    let line = line?;
+                    if line.is_empty() {
+                        continue;
+                    }
+                    let row: Map<String, Value> = serde_json::from_str(&line)?;
+                    restore_row(&mut tx, report, &row, options.mode, &mut merged_pages).await?;
+                }
+            }
+        } else {
+            for (report, rows) in tables.iter_mut().zip(&legacy) {
+                for row in rows {
+                    restore_row(&mut tx, report, row, options.mode, &mut merged_pages).await?;
+                }
+            }
+        }
+
+        if options.dry_run {
+            tx.rollback().await?;
+        } else {
+            tx.commit().await?;
+        }
+
+        Ok(RestoreReport {
+            mode: options.mode,
+            dry_run: options.dry_run,
+            tables,
+            merged_pages,
+        })
+    }
+}
+
+async fn table_exists(db: &Database, table: &str) -> Result<bool> {
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?")
+        .bind(table)
+        .fetch_one(db.get_pool())
+        .await?;
+    Ok(count > 0)
+}
+
+/// Column values keep SQLite's storage class: integers, reals, text and hex blobs
+fn row_to_json(row: &SqliteRow) -> Result<Map<String, Value>> {
+    let mut object = Map::new();
+    for column in row.columns() {
+        let index = column.ordinal();
+        let raw = row.try_get_raw(index)?;
+        let value = if raw.is_null() {
+            Value::Null
+        } else {
+            match raw.type_info().name() {
+                "INTEGER" | "BOOLEAN" => Value::from(row.try_get_unchecked::<i64, _>(index)?),
+                "REAL" => Value::from(row.try_get_unchecked::<f64, _>(index)?),
+                "BLOB" => {
+                    let bytes = row.try_get_unchecked::<Vec<u8>, _>(index)?;
+                    serde_json::json!({ "$blob": hex(&bytes) })
+                }
+                _ => Value::from(row.try_get_unchecked::<String, _>(index)?),
+            }
+        };
+        object.insert(column.name().to_string(), value);
+    }
+    Ok(object)
+}
+
+/// Empty the tables being restored, children first, keeping device-local settings
+async fn clear_tables(conn: &mut SqliteConnection, tables: &mut [TableRestoreReport]) -> Result<()> {
+    for report in tables.iter_mut().rev() {
+        let sql = format!("DELETE FROM {} WHERE {}", report.name, portable_rows(&report.name, &report.name));
+        report.deleted = sqlx::query(&sql).execute(&mut *conn).await?.rows_affected();
+    }
+    Ok(())
+}
+
+/// Column names are interpolated into SQL, so only plain identifiers are accepted
+fn check_columns(table: &str, row: &Map<String, Value>) -> Result<()> {
+    match row.keys().find(|column| !column.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')) {
+        Some(column) => Err(anyhow!("Invalid column name '{}' in {}", column, table)),
+        None => Ok(()),
+    }
+}
+
+/// Condition on `alias` matching the rows of `table` that may leave this device
+fn portable_rows(table: &str, alias: &str) -> String {
+    if table != "settings" {
+        return "1".to_string();
+    }
+    let keys: Vec<String> = DEVICE_LOCAL_SETTINGS.iter().map(|key| format!("'{}'", key)).collect();
+    format!("{}.\"key\" NOT IN ({})", alias, keys.join(", "))
+}
+
+/// Primary key of a backed-up table
+fn key_column(table: &str) -> &'static str {
+    if table == "settings" { "key" } else { "id" }
+}
+
+/// Write one backed-up row according to `mode` and count the outcome. Pages that
+/// clash with an existing page's name are merged into it, see [`MergedPage`].
+async fn restore_row(
+    conn: &mut SqliteConnection,
+    report: &mut TableRestoreReport,
+    row: &Map<String, Value>,
+    mode: RestoreMode,
+    merged_pages: &mut Vec<MergedPage>,
+) -> Result<()> {
+    let table = report.name.as_str();
+    check_columns(table, row)?;
+    let row = merged_page_refs(table, row, merged_pages);
+    let row = row.as_ref();
+    let key = key_column(table);
+    let key_value = row.get(key).ok_or_else(|| anyhow!("Row in {} has no {}", table, key))?;
+    // Older archives still carry another device's id and pairings
+    if table == "settings" && key_value.as_str().map_or(false, is_device_local_setting) {
+        report.skipped += 1;
+        return Ok(());
+    }
+
+    if mode != RestoreMode::ReplaceAll {
+        let sql = format!("SELECT COUNT(*) FROM {} WHERE \"{}\" = ?", table, key);
+        let existing: i64 = bind_json(sqlx::query_scalar(&sql), key_value)?.fetch_one(&mut *conn).await?;
+        if existing > 0 {
+            let backup_updated = row.get("updated_at").and_then(Value::as_str);
+            let newer = match (mode, backup_updated) {
+                (RestoreMode::MergeNewerWins, Some(backup_updated)) => {
+                    let sql = format!("SELECT updated_at FROM {} WHERE \"{}\" = ?", table, key);
+                    let current: Option<String> = bind_json(sqlx::query_scalar(&sql), key_value)?.fetch_one(&mut *conn).await?;
+                    current.map_or(true, |current| is_newer(backup_updated, &current))
+                }
+                _ => false,
+            };
+
+            if newer {
+                let assignments: Vec<String> = row.keys().map(|column| format!("\"{}\" = ?", column)).collect();
+                // A renamed row may clash with a unique key, it then stays as it is
+                let sql = format!("UPDATE OR IGNORE {} SET {} WHERE \"{}\" = ?", table, assignments.join(", "), key);
+                let mut query = sqlx::query(&sql);
+                for value in row.values() {
+                    query = bind_json(query, value)?;
+                }
+                if bind_json(query, key_value)?.execute(&mut *conn).await?.rows_affected() > 0 {
+                    report.updated += 1;
+                } else {
+                    report.skipped += 1;
+                }
+            } else {
+                report.skipped += 1;
+            }
+            return Ok(());
+        }
+    }
+
+    let columns: Vec<String> = row.keys().map(|column| format!("\"{}\"", column)).collect();
+    let placeholders = vec!["?"; columns.len()].join(", ");
+    let sql = format!("INSERT OR IGNORE INTO {} ({}) VALUES ({})", table, columns.join(", "), placeholders);
+    let mut query = sqlx::query(&sql);
+    for value in row.values() {
+        query = bind_json(query, value)?;
+    }
+    if query.execute(&mut *conn).await?.rows_affected() > 0 {
+        report.inserted += 1;
+    } else {
+        report.skipped += 1;
+        if table == "pages" {
+            if let Some(merged) = same_name_page(conn, row).await? {
+                merged_pages.push(merged);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The existing page a backed-up page was kept out by, through UNIQUE(graph_id, name)
+async fn same_name_page(conn: &mut SqliteConnection, row: &Map<String, Value>) -> Result<Option<MergedPage>> {
+    let text = |column: &str| row.get(column).and_then(Value::as_str).unwrap_or_default().to_string();
+    let (graph_id, name, backup_id) = (text("graph_id"), text("name"), text("id"));
+    let page_id: Option<String> = sqlx::query_scalar("SELECT id FROM pages WHERE graph_id = ? AND name = ? AND id != ?")
+        .bind(&graph_id)
+        .bind(&name)
+        .bind(&backup_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    Ok(page_id.map(|page_id| MergedPage { graph_id, name, backup_id, page_id }))
+}
+
+/// Point a row's page references at the pages their backed-up pages were merged into
+fn merged_page_refs<'a>(table: &str, row: &'a Map<String, Value>, merged_pages: &[MergedPage]) -> Cow<'a, Map<String, Value>> {
+    if merged_pages.is_empty() {
+        return Cow::Borrowed(row);
+    }
+    let page_ids: HashMap<&str, &str> = merged_pages
+        .iter()
+        .map(|merged| (merged.backup_id.as_str(), merged.page_id.as_str()))
+        .collect();
+    let merged_id = |value: &Value| value.as_str().and_then(|id| page_ids.get(id)).map(|id| Value::from(*id));
+
+    let mut row = row.clone();
+    let mut columns = Vec::new();
+    match table {
+        "blocks" | "page_aliases" => columns.push("page_id"),
+        "links" => {
+            for (kind, column) in [("source_type", "source_id"), ("target_type", "target_id")] {
+                if row.get(kind).and_then(Value::as_str) == Some("page") {
+                    columns.push(column);
+                }
+            }
+        }
+        "tasks" => {
+            let notes = row.get("linked_notes").and_then(Value::as_str).and_then(|notes| serde_json::from_str::<Vec<String>>(notes).ok());
+            if let Some(notes) = notes {
+                let notes: Vec<&str> = notes.iter().map(|id| page_ids.get(id.as_str()).copied().unwrap_or(id.as_str())).collect();
+                row.insert("linked_notes".to_string(), Value::from(serde_json::to_string(&notes).unwrap_or_default()));
+            }
+        }
+        _ => {}
+    }
+    for column in columns {
+        if let Some(id) = row.get(column).and_then(merged_id) {
+            row.insert(column.to_string(), id);
+        }
+    }
+    Cow::Owned(row)
+}
+
+/// Bind a backed-up value, turning `{"$blob": ...}` back into bytes
+fn bind_json<'q, Q: BindValue<'q>>(query: Q, value: &Value) -> Result<Q> {
+    Ok(match value {
+        Value::Null => query.bind_value(None::<String>),
+        Value::Bool(value) => query.bind_value(*value),
+        Value::Number(number) => match number.as_i64() {
+            Some(integer) => query.bind_value(integer),
+            None => query.bind_value(number.as_f64()),
+        },
+        Value::String(text) => query.bind_value(text.clone()),
+        Value::Object(object) => match object.get("$blob").and_then(Value::as_str) {
+            Some(blob) => query.bind_value(decode_hex(blob)?),
+            None => query.bind_value(value.to_string()),
+        },
+        Value::Array(_) => query.bind_value(value.to_string()),
+    })
+}
+
+/// `Query` and `QueryScalar` both take binds but share no trait for it
+trait BindValue<'q>: Sized {
+    fn bind_value<T>(self, value: T) -> Self
+    where
+        T: 'q + Send + sqlx::Encode<'q, Sqlite> + sqlx::Type<Sqlite>;
+}
+
+impl<'q> BindValue<'q> for sqlx::query::Query<'q, Sqlite, SqliteArguments<'q>> {
+    fn bind_value<T>(self, value: T) -> Self
+    where
+        T: 'q + Send + sqlx::Encode<'q, Sqlite> + sqlx::Type<Sqlite>,
+    {
+        self.bind(value)
+    }
+}
+
+impl<'q, O> BindValue<'q> for sqlx::query::QueryScalar<'q, Sqlite, O, SqliteArguments<'q>> {
+    fn bind_value<T>(self, value: T) -> Self
+    where
+        T: 'q + Send + sqlx::Encode<'q, Sqlite> + sqlx::Type<Sqlite>,
+    {
+        self.bind(value)
+    }
+}
+
+fn is_newer(backup: &str, current: &str) -> bool {
+    match (DateTime::parse_from_rfc3339(backup), DateTime::parse_from_rfc3339(current)) {
+        (Ok(backup), Ok(current)) => backup > current,
+        _ => backup > current,
+    }
+}
+
+fn decode_hex(text: &str) -> Result<Vec<u8>> {
+    (0..text.len())
+        .step_by(2)
+        .map(|index| {
+            text.get(index..index + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| anyhow!("Invalid blob in backup"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreateBlockRequest, CreatePageRequest};
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_backup_archive_round_trip() {
+        let dir = tempdir().unwrap();
+        let db = Database::new_with_path(dir.path().join("source.db").to_str().unwrap()).await.unwrap();
+        let page = db.create_page(CreatePageRequest {
+            graph_id: "default".to_string(),
+            name: "Backed up".to_string(),
+            title: None,
+            properties: None,
+            tags: None,
+            is_journal: Some(false),
+            journal_date: None,
+        }).await.unwrap();
+        let block = db.create_block(CreateBlockRequest {
+            graph_id: "default".to_string(),
+            page_id: page.id.clone(),
+            content: "multi\nline \"quoted\"".to_string(),
+            parent_id: None,
+            properties: None,
+            refs: None,
+            order: Some(0),
+        }).await.unwrap();
+        db.add_page_alias(&page.id, "Saved").await.unwrap();
+        sqlx::query("INSERT INTO tasks (id, title, estimated_time, created_at, updated_at) VALUES ('t1', 'Write backup', 30, '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z')")
+            .execute(db.get_pool()).await.unwrap();
+
+        let backup_path = dir.path().join("backup.zip");
+        let manifest = FileOperations::create_backup(&db, backup_path.to_str().unwrap()).await.unwrap();
+        let rows = |name: &str| manifest.tables.iter().find(|table| table.name == name).map(|table| table.rows);
+        assert_eq!(manifest.schema_version, SCHEMA_VERSION);
+        assert_eq!(rows("pages"), Some(1));
+        assert_eq!(rows("blocks"), Some(1));
+        assert_eq!(rows("page_aliases"), Some(1));
+        assert_eq!(rows("tasks"), Some(1));
+        assert_eq!(rows("block_ops"), None);
+
+        let verification = FileOperations::verify_backup(&backup_path).unwrap();
+        assert!(verification.valid, "{:?}", verification.errors);
+
+        // Restoring into an empty database keeps the original ids
+        let target = Database::new_with_path(dir.path().join("target.db").to_str().unwrap()).await.unwrap();
+        FileOperations::restore_backup(&target, backup_path.to_str().unwrap()).await.unwrap();
+        let restored = target.get_blocks_by_page(&page.id).await.unwrap();
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[0].id, block.id);
+        assert_eq!(restored[0].content, block.content);
+        assert_eq!(target.get_page_aliases(&page.id).await.unwrap(), vec!["Saved"]);
+        let estimate: i64 = sqlx::query_scalar("SELECT estimated_time FROM tasks WHERE id = 't1'")
+            .fetch_one(target.get_pool()).await.unwrap();
+        assert_eq!(estimate, 30);
+    }
+
+    #[tokio::test]
+    async fn test_verify_backup_detects_tampering() {
+        let dir = tempdir().unwrap();
+        let db = Database::new_with_path(dir.path().join("source.db").to_str().unwrap()).await.unwrap();
+        let backup_path = dir.path().join("backup.zip");
+        let manifest = FileOperations::create_backup(&db, backup_path.to_str().unwrap()).await.unwrap();
+
+        // Rewrite the archive with an edited graphs table but the original manifest
+        let tampered_path = dir.path().join("tampered.zip");
+        let mut source = zip::ZipArchive::new(File::open(&backup_path).unwrap()).unwrap();
+        let mut tampered = zip::ZipWriter::new(File::create(&tampered_path).unwrap());
+        for index in 0..source.len() {
+            let mut file = source.by_index(index).unwrap();
+            let name = file.name().to_string();
+            let mut data = Vec::new();
+            file.read_to_end(&mut data).unwrap();
+            if name == "tables/graphs.ndjson" {
+                data = String::from_utf8(data).unwrap().replace("Default", "Edited").into_bytes();
+            }
+            tampered.start_file(name, FileOptions::default()).unwrap();
+            tampered.write_all(&data).unwrap();
+        }
+        tampered.finish().unwrap();
+
+        let verification = FileOperations::verify_backup(&tampered_path).unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.errors.len(), 1, "{:?}", verification.errors);
+        assert!(verification.errors[0].starts_with("graphs: checksum mismatch"));
+        assert_eq!(verification.manifest.unwrap().tables.len(), manifest.tables.len());
+        assert!(FileOperations::restore_backup(&db, tampered_path.to_str().unwrap()).await.is_err());
+    }
+
+    async fn page(db: &Database, name: &str) -> crate::models::Page {
+        db.create_page(CreatePageRequest {
+            graph_id: "default".to_string(),
+            name: name.to_string(),
+            title: None,
+            properties: None,
+            tags: None,
+            is_journal: Some(false),
+            journal_date: None,
+        }).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_restore_modes_and_dry_run() {
+        let dir = tempdir().unwrap();
+        let db = Database::new_with_path(dir.path().join("graph.db").to_str().unwrap()).await.unwrap();
+        let kept = page(&db, "Kept").await;
+        let backup_path = dir.path().join("backup.zip");
+        let backup_path = backup_path.to_str().unwrap();
+        FileOperations::create_backup(&db, backup_path).await.unwrap();
+
+        // After the backup: one page is edited, one is added
+        sqlx::query("UPDATE pages SET title = 'Edited', updated_at = '2000-01-01T00:00:00+00:00' WHERE id = ?")
+            .bind(&kept.id).execute(db.get_pool()).await.unwrap();
+        let added = page(&db, "Added later").await;
+        let pages = |report: &RestoreReport| report.tables.iter().find(|table| table.name == "pages").cloned().unwrap();
+
+        let dry_run = RestoreOptions { mode: RestoreMode::ReplaceAll, dry_run: true };
+        let report = FileOperations::restore_backup_with_options(&db, backup_path, &dry_run).await.unwrap();
+        assert_eq!((pages(&report).deleted, pages(&report).inserted), (2, 1));
+        assert!(db.get_page(&added.id).await.is_ok(), "a dry run must not change anything");
+
+        let report = FileOperations::restore_backup(&db, backup_path).await.unwrap();
+        assert_eq!((pages(&report).inserted, pages(&report).skipped), (0, 1));
+        assert_eq!(db.get_page(&kept.id).await.unwrap().title.as_deref(), Some("Edited"));
+
+        let newer_wins = RestoreOptions { mode: RestoreMode::MergeNewerWins, dry_run: false };
+        let report = FileOperations::restore_backup_with_options(&db, backup_path, &newer_wins).await.unwrap();
+        assert_eq!(pages(&report).updated, 1);
+        assert_eq!(db.get_page(&kept.id).await.unwrap().title, None);
+
+        let replace = RestoreOptions { mode: RestoreMode::ReplaceAll, dry_run: false };
+        FileOperations::restore_backup_with_options(&db, backup_path, &replace).await.unwrap();
+        assert!(db.get_page(&added.id).await.is_err());
+        assert!(db.get_page(&kept.id).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_device_local_settings_stay_on_device() {
+        let dir = tempdir().unwrap();
+        let db = Database::new_with_path(dir.path().join("graph.db").to_str().unwrap()).await.unwrap();
+        db.get_device_id().await.unwrap();
+        db.set_setting("theme", "dark").await.unwrap();
+        let backup_path = dir.path().join("backup.zip");
+        let backup_path = backup_path.to_str().unwrap();
+        let manifest = FileOperations::create_backup(&db, backup_path).await.unwrap();
+        let settings = manifest.tables.iter().find(|table| table.name == "settings").unwrap();
+        assert_eq!(settings.rows, 1);
+
+        // Restoring on another device keeps that device's id
+        let other = Database::new_with_path(dir.path().join("other.db").to_str().unwrap()).await.unwrap();
+        let other_id = other.get_device_id().await.unwrap();
+        let replace = RestoreOptions { mode: RestoreMode::ReplaceAll, dry_run: false };
+        FileOperations::restore_backup_with_options(&other, backup_path, &replace).await.unwrap();
+        assert_eq!(other.get_device_id().await.unwrap(), other_id);
+        assert_eq!(other.get_setting("theme").await.unwrap().as_deref(), Some("dark"));
+    }
+
+    #[tokio::test]
+    async fn test_restore_json_backup_keeps_ids() {
+        let dir = tempdir().unwrap();
+        let db = Database::new_with_path(dir.path().join("source.db").to_str().unwrap()).await.unwrap();
+        let parent_page = page(&db, "Legacy").await;
+        let block = db.create_block(CreateBlockRequest {
+            graph_id: "default".to_string(),
+            page_id: parent_page.id.clone(),
+            content: "From a JSON backup".to_string(),
+            parent_id: None,
+            properties: None,
+            refs: None,
+            order: Some(0),
+        }).await.unwrap();
+
+        let legacy = BackupData {
+            version: "1.0".to_string(),
+            created_at: Utc::now(),
+            pages: vec![parent_page.clone()],
+            blocks: vec![block.clone()],
+            tags: Vec::new(),
+        };
+        let backup_path = dir.path().join("backup.json");
+        std::fs::write(&backup_path, serde_json::to_string_pretty(&legacy).unwrap()).unwrap();
+
+        let target = Database::new_with_path(dir.path().join("target.db").to_str().unwrap()).await.unwrap();
+        let report = FileOperations::restore_backup(&target, backup_path.to_str().unwrap()).await.unwrap();
+        assert!(report.tables.iter().all(|table| table.skipped == 0));
+        let restored = target.get_blocks_by_page(&parent_page.id).await.unwrap();
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[0].id, block.id);
+        assert!(target.get_page(&parent_page.id).await.unwrap().created_at == parent_page.created_at);
+    }
+
+    #[tokio::test]
+    async fn test_restore_merges_pages_with_a_taken_name() {
+        let dir = tempdir().unwrap();
+        let db = Database::new_with_path(dir.path().join("source.db").to_str().unwrap()).await.unwrap();
+        let meeting = page(&db, "Meeting").await;
+        let block = db.create_block(CreateBlockRequest {
+            graph_id: "default".to_string(),
+            page_id: meeting.id.clone(),
+            content: "Agenda".to_string(),
+            parent_id: None,
+            properties: None,
+            refs: None,
+            order: Some(0),
+        }).await.unwrap();
+        sqlx::query("INSERT INTO cards (id, due_at, created_at, updated_at) VALUES (?, '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z')")
+            .bind(&block.id).execute(db.get_pool()).await.unwrap();
+        sqlx::query("INSERT INTO tasks (id, title, linked_notes, block_id, created_at, updated_at) VALUES ('t1', 'Send notes', ?, ?, '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z')")
+            .bind(serde_json::to_string(&[&meeting.id]).unwrap())
+            .bind(&block.id)
+            .execute(db.get_pool()).await.unwrap();
+        let backup_path = dir.path().join("backup.zip");
+        FileOperations::create_backup(&db, backup_path.to_str().unwrap()).await.unwrap();
+
+        // The other graph made its own "Meeting" page
+        let target = Database::new_with_path(dir.path().join("target.db").to_str().unwrap()).await.unwrap();
+        let existing = page(&target, "Meeting").await;
+        let report = FileOperations::restore_backup(&target, backup_path.to_str().unwrap()).await.unwrap();
+        assert_eq!(report.merged_pages.len(), 1);
+        assert_eq!((report.merged_pages[0].backup_id.as_str(), report.merged_pages[0].page_id.as_str()), (meeting.id.as_str(), existing.id.as_str()));
+        assert_eq!(report.merged_pages[0].name, "Meeting");
+
+        let blocks = target.get_blocks_by_page(&existing.id).await.unwrap();
+        assert_eq!(blocks.iter().map(|block| block.id.as_str()).collect::<Vec<_>>(), [block.id.as_str()]);
+        let cards: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM cards").fetch_one(target.get_pool()).await.unwrap();
+        assert_eq!(cards, 1);
+        let task = target.get_task("t1").await.unwrap();
+        assert_eq!(task.block_id.as_deref(), Some(block.id.as_str()));
+        assert_eq!(task.linked_notes, serde_json::to_string(&[&existing.id]).unwrap());
+        assert!(target.get_page(&meeting.id).await.is_err());
+    }
+
+    #[test]
+    fn test_retention_policy_keeps_last_daily_and_weekly() {
+        // One backup every 12 hours for three weeks, newest first
+        let newest = DateTime::parse_from_rfc3339("2024-03-31T18:00:00Z").unwrap().with_timezone(&Utc);
+        let backups: Vec<BackupInfo> = (0..42)
+            .map(|index| BackupInfo {
+                path: format!("{}.zip", index),
+                file_name: format!("{}.zip", index),
+                created_at: newest - chrono::Duration::hours(12 * index),
+                size: 0,
+                schema_version: SCHEMA_VERSION,
+            })
+            .collect();
+
+        let policy = RetentionPolicy { keep_last: 3, keep_daily: 4, keep_weekly: 3 };
+        let pruned = policy.prune(&backups);
+        let kept: Vec<usize> = (0..backups.len()).filter(|index| !pruned.contains(index)).collect();
+        // Last three, the evening backups of the four most recent days (0, 2, 4, 6),
+        // and the newest backup of each of the three most recent ISO weeks
+        // (2024-03-31 is a Sunday, so weeks start at indices 0, 14 and 28)
+        assert_eq!(kept, vec![0, 1, 2, 4, 6, 14, 28]);
+
+        let none = RetentionPolicy { keep_last: 0, keep_daily: 0, keep_weekly: 0 };
+        assert_eq!(none.prune(&backups).len(), backups.len() - 1);
+    }
+}
@@ -0,0 +1,836 @@
+//! Point-in-time recovery.
+//!
+//! While recovery is enabled, triggers record every insert, update and delete on the
+//! backed-up tables in `change_log`. The scheduler periodically moves logged changes
+//! into NDJSON segments in the recovery directory and takes a base snapshot with
+//! `VACUUM INTO` once the newest one is older than the base interval. To recover,
+//! the newest base taken before the target time is copied and the logged changes up
+//! to that time are replayed onto the copy.
+//!
+//! Files in the recovery directory:
+//! - `base-<seq>-<time>.db`: a snapshot containing every change up to `seq`
+//! - `changes-<first>-<last>.ndjson`: the change log entries `first..=last`
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::sqlite::SqliteConnection;
+use sqlx::Row;
+use tokio::sync::{Mutex, Notify};
+use tokio_util::sync::CancellationToken;
+
+use super::{bind_json, check_columns, key_column, portable_rows, BACKUP_TABLES};
+use crate::database::Database;
+use crate::sync::{is_device_local_setting, DEVICE_LOCAL_SETTINGS};
+
+pub const ENABLED_KEY: &str = "pitr_enabled";
+pub const DIR_KEY: &str = "pitr_dir";
+pub const ARCHIVE_INTERVAL_KEY: &str = "pitr_archive_interval_minutes";
+pub const BASE_INTERVAL_KEY: &str = "pitr_base_interval_hours";
+pub const KEEP_BASES_KEY: &str = "pitr_keep_bases";
+
+const DEFAULT_ARCHIVE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const DEFAULT_BASE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+const DEFAULT_KEEP_BASES: usize = 7;
+
+/// How often to re-read the settings while recovery is off
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+const BASE_TIME_FORMAT: &str = "%Y%m%dT%H%M%S%.3f";
+
+/// FTS indexes rebuilt after replaying changes onto a base
+const FTS_TABLES: [&str; 5] = ["blocks_fts", "pages_fts", "notes_fts", "tasks_fts", "projects_fts"];
+
+/// Recovery settings as stored in the `settings` table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PitrConfig {
+    pub enabled: bool,
+    pub dir: PathBuf,
+    pub archive_interval: Duration,
+    pub base_interval: Duration,
+    pub keep_bases: usize,
+}
+
+impl PitrConfig {
+    pub async fn load(db: &Database) -> Result<Self> {
+        let number = |value: Option<String>| value.and_then(|value| value.trim().parse::<u64>().ok());
+
+        let enabled = db.get_setting(ENABLED_KEY).await?.map_or(false, |value| value.trim() == "true");
+        let dir = match db.get_setting(DIR_KEY).await?.filter(|dir| !dir.trim().is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => default_pitr_dir()?,
+        };
+        let archive_interval = number(db.get_setting(ARCHIVE_INTERVAL_KEY).await?)
+            .filter(|minutes| *minutes > 0)
+            .map_or(DEFAULT_ARCHIVE_INTERVAL, |minutes| Duration::from_secs(minutes * 60));
+        let base_interval = number(db.get_setting(BASE_INTERVAL_KEY).await?)
+            .filter(|hours| *hours > 0)
+            .map_or(DEFAULT_BASE_INTERVAL, |hours| Duration::from_secs(hours * 60 * 60));
+        let keep_bases = number(db.get_setting(KEEP_BASES_KEY).await?)
+            .map_or(DEFAULT_KEEP_BASES, |n| (n as usize).max(1));
+
+        Ok(Self { enabled, dir, archive_interval, base_interval, keep_bases })
+    }
+}
+
+pub fn default_pitr_dir() -> Result<PathBuf> {
+    Ok(dirs::data_dir()
+        .ok_or_else(|| anyhow!("Could not find app data directory"))?
+        .join("com.minglog.desktop")
+        .join("recovery"))
+}
+
+/// One row of `change_log`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeEntry {
+    pub seq: i64,
+    pub table_name: String,
+    pub row_key: String,
+    pub op: String,
+    /// The row after the change as a JSON object, `None` for deletes
+    pub row_data: Option<String>,
+    pub changed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BaseSnapshot {
+    pub path: String,
+    pub taken_at: DateTime<Utc>,
+    /// Last change log entry contained in the snapshot
+    pub seq: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryReport {
+    pub target_time: DateTime<Utc>,
+    pub base: BaseSnapshot,
+    pub changes_applied: u64,
+    /// The reconstructed database
+    pub path: String,
+    /// Where the replaced database was moved to, when it was swapped out
+    pub previous_path: Option<String>,
+}
+
+/// Record changes to every backed-up table, except device-local settings. Triggers
+/// are recreated so they pick up columns added by migrations.
+pub async fn install_change_log_triggers(conn: &mut SqliteConnection) -> Result<()> {
+    remove_change_log_triggers(conn).await?;
+
+    for table in BACKUP_TABLES {
+        let columns: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info(?)")
+            .bind(table)
+            .fetch_all(&mut *conn)
+            .await?;
+        if columns.is_empty() {
+            continue;
+        }
+        let key = key_column(table);
+        let row = |alias: &str| {
+            let pairs: Vec<String> = columns.iter()
+                .map(|column| format!("'{}', {}.\"{}\"", column, alias, column))
+                .collect();
+            format!("json_object({})", pairs.join(", "))
+        };
+
+        for (op, alias) in [("insert", "NEW"), ("update", "NEW"), ("delete", "OLD")] {
+            let row_data = if op == "delete" { "NULL".to_string() } else { row(alias) };
+            let sql = format!(
+                "CREATE TRIGGER change_log_{table}_{op} AFTER {upper} ON {table} WHEN {portable} BEGIN \
+                 INSERT INTO change_log (table_name, row_key, op, row_data, changed_at) \
+                 VALUES ('{table}', {alias}.\"{key}\", '{op}', {row_data}, strftime('%Y-%m-%dT%H:%M:%fZ', 'now')); \
+                 END",
+                table = table,
+                op = op,
+                upper = op.to_uppercase(),
+                alias = alias,
+                key = key,
+                row_data = row_data,
+                portable = portable_rows(table, alias),
+            );
+            sqlx::query(&sql).execute(&mut *conn).await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn remove_change_log_triggers(conn: &mut SqliteConnection) -> Result<()> {
+    for table in BACKUP_TABLES {
+        for op in ["insert", "update", "delete"] {
+            sqlx::query(&format!("DROP TRIGGER IF EXISTS change_log_{}_{}", table, op))
+                .execute(&mut *conn)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Snapshot the live database into `dir`
+pub async fn create_base_snapshot(db: &Database, dir: &Path) -> Result<BaseSnapshot> {
+    std::fs::create_dir_all(dir)?;
+    let partial = dir.join("base.partial.db");
+    if partial.exists() {
+        std::fs::remove_file(&partial)?;
+    }
+    sqlx::query("VACUUM INTO ?")
+        .bind(partial.to_string_lossy().to_string())
+        .execute(db.get_pool())
+        .await?;
+
+    let snapshot = Database::open_snapshot(&partial.to_string_lossy()).await?;
+    let seq = last_seq(&snapshot).await;
+    snapshot.close().await;
+    let seq = seq?;
+
+    let taken_at = Utc::now();
+    let path = dir.join(format!("base-{:020}-{}.db", seq, taken_at.format(BASE_TIME_FORMAT)));
+    std::fs::rename(&partial, &path)?;
+
+    Ok(BaseSnapshot { path: path.to_string_lossy().to_string(), taken_at, seq })
+}
+
+/// The last sequence number handed out by `change_log`
+async fn last_seq(db: &Database) -> Result<i64> {
+    let seq: Option<i64> = sqlx::query_scalar("SELECT seq FROM sqlite_sequence WHERE name = 'change_log'")
+        .fetch_optional(db.get_pool())
+        .await?;
+    Ok(seq.unwrap_or(0))
+}
+
+/// Base snapshots in `dir`, newest first
+pub fn list_base_snapshots(dir: &Path) -> Result<Vec<BaseSnapshot>> {
+    let mut bases = Vec::new();
+    if !dir.is_dir() {
+        return Ok(bases);
+    }
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Some((seq, time)) = name.strip_prefix("base-")
+            .and_then(|rest| rest.strip_suffix(".db"))
+            .and_then(|rest| rest.split_once('-')) else {
+            continue;
+        };
+        let (Ok(seq), Ok(time)) = (seq.parse::<i64>(), NaiveDateTime::parse_from_str(time, BASE_TIME_FORMAT)) else {
+            continue;
+        };
+
+        bases.push(BaseSnapshot {
+            path: path.to_string_lossy().to_string(),
+            taken_at: Utc.from_utc_datetime(&time),
+            seq,
+        });
+    }
+
+    bases.sort_by_key(|base| std::cmp::Reverse(base.seq));
+    Ok(bases)
+}
+
+/// Archived change log segments in `dir` as `(first, last, path)`, oldest first
+fn list_segments(dir: &Path) -> Result<Vec<(i64, i64, PathBuf)>> {
+    let mut segments = Vec::new();
+    if !dir.is_dir() {
+        return Ok(segments);
+    }
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let range = path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("changes-"))
+            .and_then(|rest| rest.strip_suffix(".ndjson"))
+            .and_then(|rest| rest.split_once('-'))
+            .and_then(|(first, last)| Some((first.parse::<i64>().ok()?, last.parse::<i64>().ok()?)));
+        if let Some((first, last)) = range {
+            segments.push((first, last, path));
+        }
+    }
+
+    segments.sort_by_key(|(first, _, _)| *first);
+    Ok(segments)
+}
+
+/// Move everything in `change_log` into a new segment file
+pub async fn archive_changes(db: &Database, dir: &Path) -> Result<Option<PathBuf>> {
+    std::fs::create_dir_all(dir)?;
+    let partial = dir.join("changes.partial.ndjson");
+    let mut writer = std::io::BufWriter::new(File::create(&partial)?);
+    let mut range: Option<(i64, i64)> = None;
+
+    let mut rows = sqlx::query("SELECT seq, table_name, row_key, op, row_data, changed_at FROM change_log ORDER BY seq")
+        .fetch(db.get_pool());
+    while let Some(row) = rows.try_next().await? {
+        let entry = entry_from_row(&row)?;
+        range = Some((range.map_or(entry.seq, |(first, _)| first), entry.seq));
+        serde_json::to_writer(&mut writer, &entry)?;
+        writer.write_all(b"\n")?;
+    }
+    drop(rows);
+    writer.flush()?;
+    drop(writer);
+
+    let Some((first, last)) = range else {
+        std::fs::remove_file(&partial)?;
+        return Ok(None);
+    };
+    let path = dir.join(format!("changes-{:020}-{:020}.ndjson", first, last));
+    std::fs::rename(&partial, &path)?;
+    sqlx::query("DELETE FROM change_log WHERE seq <= ?")
+        .bind(last)
+        .execute(db.get_pool())
+        .await?;
+
+    Ok(Some(path))
+}
+
+fn entry_from_row(row: &sqlx::sqlite::SqliteRow) -> Result<ChangeEntry> {
+    let changed_at: String = row.try_get("changed_at")?;
+    Ok(ChangeEntry {
+        seq: row.try_get("seq")?,
+        table_name: row.try_get("table_name")?,
+        row_key: row.try_get("row_key")?,
+        op: row.try_get("op")?,
+        row_data: row.try_get("row_data")?,
+        changed_at: DateTime::parse_from_rfc3339(&changed_at)?.with_timezone(&Utc),
+    })
+}
+
+/// Keep the newest `keep_bases` snapshots and the segments still needed to replay them
+pub fn prune(dir: &Path, keep_bases: usize) -> Result<()> {
+    let bases = list_base_snapshots(dir)?;
+    let (kept, removed) = bases.split_at(keep_bases.max(1).min(bases.len()));
+    for base in removed {
+        std::fs::remove_file(&base.path)?;
+    }
+    if let Some(oldest) = kept.last() {
+        for (_, last, path) in list_segments(dir)? {
+            if last <= oldest.seq {
+                std::fs::remove_file(path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Rebuild the database as it was at `at` into `output`, then check its integrity.
+/// The live database is only read.
+pub async fn reconstruct_at(
+    db: &Database,
+    dir: &Path,
+    at: DateTime<Utc>,
+    output: &Path,
+) -> Result<RecoveryReport> {
+    let base = list_base_snapshots(dir)?
+        .into_iter()
+        .find(|base| base.taken_at <= at)
+        .ok_or_else(|| anyhow!("No recovery snapshot was taken before {}", at.to_rfc3339()))?;
+
+    for suffix in ["", "-wal", "-shm"] {
+        let path = PathBuf::from(format!("{}{}", output.to_string_lossy(), suffix));
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+    }
+    std::fs::copy(&base.path, output)?;
+
+    let target = Database::new_with_path(&output.to_string_lossy()).await?;
+    let replayed = replay(db, dir, &target, &base, at).await;
+    target.close().await;
+
+    Ok(RecoveryReport {
+        target_time: at,
+        changes_applied: replayed?,
+        path: output.to_string_lossy().to_string(),
+        previous_path: None,
+        base,
+    })
+}
+
+async fn replay(
+    db: &Database,
+    dir: &Path,
+    target: &Database,
+    base: &BaseSnapshot,
+    at: DateTime<Utc>,
+) -> Result<u64> {
+    let mut conn = target.get_pool().acquire().await?;
+    // Cascades were logged as changes of their own
+    sqlx::query("PRAGMA foreign_keys = OFF").execute(&mut *conn).await?;
+    remove_change_log_triggers(&mut conn).await?;
+
+    let mut applied = 0;
+    for (_, last, path) in list_segments(dir)? {
+        if last <= base.seq {
+            continue;
+        }
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let entry: ChangeEntry = serde_json::from_str(&line)?;
+            if entry.seq > base.seq && entry.changed_at <= at {
+                apply_change(&mut conn, &entry).await?;
+                applied += 1;
+            }
+        }
+    }
+
+    // Changes not archived yet are still in the live log
+    let pending: Vec<ChangeEntry> = sqlx::query(
+        "SELECT seq, table_name, row_key, op, row_data, changed_at FROM change_log WHERE seq > ? ORDER BY seq"
+    )
+    .bind(base.seq)
+    .fetch_all(db.get_pool())
+    .await?
+    .iter()
+    .map(entry_from_row)
+    .collect::<Result<_>>()?;
+    for entry in pending.iter().filter(|entry| entry.changed_at <= at) {
+        apply_change(&mut conn, entry).await?;
+        applied += 1;
+    }
+
+    // The restored database stays this device's, with its current pairings and sync progress
+    for key in DEVICE_LOCAL_SETTINGS {
+        sqlx::query("DELETE FROM settings WHERE key = ?").bind(key).execute(&mut *conn).await?;
+        if let Some(value) = db.get_setting(key).await? {
+            sqlx::query("INSERT INTO settings (key, value, updated_at) VALUES (?, ?, ?)")
+                .bind(key)
+                .bind(value)
+                .bind(Utc::now().to_rfc3339())
+                .execute(&mut *conn)
+                .await?;
+        }
+    }
+
+    // So is the op log, which other devices have already seen. The restored
+    // blocks are recorded against it, so the restore reaches them as edits.
+    sqlx::query("DELETE FROM block_ops").execute(&mut *conn).await?;
+    let ops = sqlx::query("SELECT id, device_id, block_id, page_id, op, created_at FROM block_ops")
+        .fetch_all(db.get_pool())
+        .await?;
+    for op in &ops {
+        sqlx::query("INSERT INTO block_ops (id, device_id, block_id, page_id, op, created_at) VALUES (?, ?, ?, ?, ?, ?)")
+            .bind(op.get::<String, _>("id"))
+            .bind(op.get::<String, _>("device_id"))
+            .bind(op.get::<String, _>("block_id"))
+            .bind(op.get::<String, _>("page_id"))
+            .bind(op.get::<String, _>("op"))
+            .bind(op.get::<String, _>("created_at"))
+            .execute(&mut *conn)
+            .await?;
+    }
+    sqlx::query(
+        "INSERT OR IGNORE INTO block_ops_pending (block_id, page_id) \
+         SELECT id, page_id FROM blocks UNION SELECT block_id, page_id FROM block_ops"
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query("DELETE FROM change_log").execute(&mut *conn).await?;
+    for fts in FTS_TABLES {
+        let exists: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?")
+            .bind(fts)
+            .fetch_one(&mut *conn)
+            .await?;
+        if exists > 0 {
+            sqlx::query(&format!("INSERT INTO {}({}) VALUES('rebuild')", fts, fts))
+                .execute(&mut *conn)
+                .await?;
+        }
+    }
+
+    let integrity: String = sqlx::query_scalar("PRAGMA integrity_check").fetch_one(&mut *conn).await?;
+    if integrity != "ok" {
+        return Err(anyhow!("Reconstructed database failed the integrity check: {}", integrity));
+    }
+    let violations = sqlx::query("PRAGMA foreign_key_check").fetch_all(&mut *conn).await?;
+    if !violations.is_empty() {
+        let tables: Vec<String> = violations.iter().filter_map(|row| row.try_get::<String, _>(0).ok()).collect();
+        return Err(anyhow!("Reconstructed database has {} broken references in {}", violations.len(), tables.join(", ")));
+    }
+
+    Ok(applied)
+}
+
+async fn apply_change(conn: &mut SqliteConnection, entry: &ChangeEntry) -> Result<()> {
+    let table = entry.table_name.as_str();
+    if !BACKUP_TABLES.contains(&table) {
+        return Err(anyhow!("Unexpected table '{}' in change log", table));
+    }
+    let key = key_column(table);
+    // Logged before device-local settings were left out of the log
+    if table == "settings" && is_device_local_setting(&entry.row_key) {
+        return Ok(());
+    }
+
+    if entry.op == "delete" {
+        sqlx::query(&format!("DELETE FROM {} WHERE \"{}\" = ?", table, key))
+            .bind(&entry.row_key)
+            .execute(&mut *conn)
+            .await?;
+        return Ok(());
+    }
+
+    let row: Map<String, Value> = serde_json::from_str(entry.row_data.as_deref().unwrap_or("{}"))?;
+    check_columns(table, &row)?;
+    let columns: Vec<String> = row.keys().map(|column| format!("\"{}\"", column)).collect();
+    let assignments: Vec<String> = columns.iter().map(|column| format!("{} = excluded.{}", column, column)).collect();
+    let sql = format!(
+        "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT(\"{}\") DO UPDATE SET {}",
+        table,
+        columns.join(", "),
+        vec!["?"; columns.len()].join(", "),
+        key,
+        assignments.join(", "),
+    );
+    let mut query = sqlx::query(&sql);
+    for value in row.values() {
+        query = bind_json(query, value)?;
+    }
+    query.execute(&mut *conn).await?;
+    Ok(())
+}
+
+/// Reconstruct the live database as it was at `at` and swap it in. The replaced
+/// file is kept next to it, and recovery files from before the swap are moved
+/// into a subdirectory because their history no longer leads to the new database.
+/// Replaying drops the change log triggers, so callers should tell the
+/// `PitrScheduler` the configuration changed.
+pub async fn restore_to(db: &mut Database, at: DateTime<Utc>) -> Result<RecoveryReport> {
+    let config = PitrConfig::load(db).await?;
+    let live_path = Database::get_database_path()?;
+    let stamp = Utc::now().format("%Y%m%d-%H%M%S");
+    let restored_path = live_path.with_file_name(format!("minglog.restore-{}.db", stamp));
+    let previous_path = live_path.with_file_name(format!("minglog.before-restore-{}.db", stamp));
+
+    let mut report = reconstruct_at(db, &config.dir, at, &restored_path).await?;
+
+    db.close().await;
+    if let Err(e) = swap_database_files(&live_path, &restored_path, &previous_path) {
+        // The original files are back in place, so the app keeps working on them
+        *db = Database::new().await?;
+        return Err(e);
+    }
+    *db = Database::new().await?;
+
+    let timeline = config.dir.join(format!("before-restore-{}", stamp));
+    std::fs::create_dir_all(&timeline)?;
+    for entry in std::fs::read_dir(&config.dir)? {
+        let path = entry?.path();
+        if path.is_file() {
+            std::fs::rename(&path, timeline.join(path.file_name().unwrap_or_default()))?;
+        }
+    }
+    // The restored settings decide whether recovery stays on
+    if PitrConfig::load(db).await?.enabled {
+        install_change_log_triggers(&mut *db.get_pool().acquire().await?).await?;
+        create_base_snapshot(db, &config.dir).await?;
+    }
+
+    report.path = live_path.to_string_lossy().to_string();
+    report.previous_path = Some(previous_path.to_string_lossy().to_string());
+    Ok(report)
+}
+
+/// Move the live database files aside to `previous` and `restored` into their
+/// place. If a rename fails, the files already moved are put back.
+fn swap_database_files(live: &Path, restored: &Path, previous: &Path) -> Result<()> {
+    let with_suffix = |path: &Path, suffix: &str| PathBuf::from(format!("{}{}", path.to_string_lossy(), suffix));
+    let mut moved = Vec::new();
+    let mut swap = || -> Result<()> {
+        for suffix in ["", "-wal", "-shm"] {
+            let from = with_suffix(live, suffix);
+            if from.exists() {
+                let to = with_suffix(previous, suffix);
+                std::fs::rename(&from, &to)?;
+                moved.push((from, to));
+            }
+        }
+        std::fs::rename(restored, live)?;
+        Ok(())
+    };
+    let result = swap();
+
+    if result.is_err() {
+        for (from, to) in moved.iter().rev() {
+            if let Err(e) = std::fs::rename(to, from) {
+                log::error!("Failed to move {} back after a failed restore: {}", to.display(), e);
+            }
+        }
+    }
+    result
+}
+
+/// Keeps the change log archived and base snapshots fresh while recovery is enabled
+pub struct PitrScheduler {
+    database: Arc<Mutex<Database>>,
+    reschedule: Notify,
+    shutdown: CancellationToken,
+}
+
+impl std::fmt::Debug for PitrScheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PitrScheduler")
+            .field("shutdown", &self.shutdown.is_cancelled())
+            .finish_non_exhaustive()
+    }
+}
+
+impl PitrScheduler {
+    pub fn new(database: Arc<Mutex<Database>>) -> Self {
+        Self {
+            database,
+            reschedule: Notify::new(),
+            shutdown: CancellationToken::new(),
+        }
+    }
+
+    /// Start the scheduling loop in the background
+    pub fn start(self: &Arc<Self>) -> tauri::async_runtime::JoinHandle<()> {
+        let scheduler = Arc::clone(self);
+        tauri::async_runtime::spawn(async move {
+            log::info!("Recovery scheduler started");
+            scheduler.run().await;
+            log::info!("Recovery scheduler stopped");
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// Re-read the recovery settings
+    pub fn notify_config_changed(&self) {
+        self.reschedule.notify_one();
+    }
+
+    /// Archive the change log, and take a base snapshot if the newest one is too old
+    pub async fn checkpoint_now(&self) -> Result<()> {
+        let db = self.database.lock().await;
+        let config = PitrConfig::load(&db).await?;
+        archive_changes(&db, &config.dir).await?;
+
+        let base_due = match list_base_snapshots(&config.dir)?.first() {
+            Some(newest) => Utc::now() - newest.taken_at >= chrono::Duration::from_std(config.base_interval)?,
+            None => true,
+        };
+        if base_due {
+            create_base_snapshot(&db, &config.dir).await?;
+        }
+        prune(&config.dir, config.keep_bases)
+    }
+
+    async fn run(&self) {
+        let mut installed = false;
+
+        loop {
+            let config = {
+                let db = self.database.lock().await;
+                PitrConfig::load(&db).await
+            };
+            let delay = match config {
+                Ok(config) if config.enabled => {
+                    if !installed {
+                        match self.set_logging(true).await {
+                            Ok(()) => installed = true,
+                            Err(e) => log::warn!("Failed to enable change logging: {}", e),
+                        }
+                        if let Err(e) = self.checkpoint_now().await {
+                            log::warn!("Recovery checkpoint failed: {}", e);
+                        }
+                    }
+                    config.archive_interval
+                }
+                Ok(_) => {
+                    if installed {
+                        match self.set_logging(false).await {
+                            Ok(()) => installed = false,
+                            Err(e) => log::warn!("Failed to disable change logging: {}", e),
+                        }
+                    }
+                    IDLE_POLL_INTERVAL
+                }
+                Err(e) => {
+                    log::warn!("Failed to read recovery settings: {}", e);
+                    IDLE_POLL_INTERVAL
+                }
+            };
+
+            tokio::select! {
+                _ = self.shutdown.cancelled() => return,
+                _ = self.reschedule.notified() => continue,
+                _ = tokio::time::sleep(delay) => {}
+            }
+
+            if installed {
+                if let Err(e) = self.checkpoint_now().await {
+                    log::warn!("Recovery checkpoint failed: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Install or remove the change log triggers. Turning logging off also drops
+    /// entries that will never be archived.
+    async fn set_logging(&self, enabled: bool) -> Result<()> {
+        let db = self.database.lock().await;
+        let mut conn = db.get_pool().acquire().await?;
+        if enabled {
+            install_change_log_triggers(&mut conn).await
+        } else {
+            remove_change_log_triggers(&mut conn).await?;
+            sqlx::query("DELETE FROM change_log").execute(&mut *conn).await?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreatePageRequest, UpdatePageRequest};
+    use tempfile::tempdir;
+
+    fn page(name: &str) -> CreatePageRequest {
+        CreatePageRequest {
+            graph_id: "default".to_string(),
+            name: name.to_string(),
+            title: None,
+            properties: None,
+            tags: None,
+            is_journal: Some(false),
+            journal_date: None,
+        }
+    }
+
+    async fn pause() {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    #[tokio::test]
+    async fn test_reconstruct_replays_changes_up_to_target_time() {
+        let dir = tempdir().unwrap();
+        let recovery_dir = dir.path().join("recovery");
+        let db = Database::new_with_path(dir.path().join("graph.db").to_str().unwrap()).await.unwrap();
+        install_change_log_triggers(&mut db.get_pool().acquire().await.unwrap()).await.unwrap();
+
+        let first = db.create_page(page("First")).await.unwrap();
+        let base = create_base_snapshot(&db, &recovery_dir).await.unwrap();
+        assert!(base.seq > 0);
+        pause().await;
+
+        let second = db.create_page(page("Second")).await.unwrap();
+        db.update_page(UpdatePageRequest {
+            id: first.id.clone(),
+            name: None,
+            title: Some("Renamed".to_string()),
+            is_journal: None,
+            journal_date: None,
+            tags: None,
+            properties: None,
+        }).await.unwrap();
+        let segment = archive_changes(&db, &recovery_dir).await.unwrap();
+        assert!(segment.is_some());
+        pause().await;
+        let target_time = Utc::now();
+        pause().await;
+
+        // Only in the live change log, and after the target time
+        db.delete_page(&second.id).await.unwrap();
+        let third = db.create_page(page("Third")).await.unwrap();
+
+        let output = dir.path().join("restored.db");
+        let report = reconstruct_at(&db, &recovery_dir, target_time, &output).await.unwrap();
+        assert_eq!(report.base.seq, base.seq);
+        assert!(report.changes_applied >= 2);
+
+        let restored = Database::new_with_path(output.to_str().unwrap()).await.unwrap();
+        assert_eq!(restored.get_page(&first.id).await.unwrap().title.as_deref(), Some("Renamed"));
+        assert!(restored.get_page(&second.id).await.is_ok());
+        assert!(restored.get_page(&third.id).await.is_err());
+
+        // Replaying the pending entries as well gives the current state
+        let now = dir.path().join("now.db");
+        reconstruct_at(&db, &recovery_dir, Utc::now(), &now).await.unwrap();
+        let current = Database::new_with_path(now.to_str().unwrap()).await.unwrap();
+        assert!(current.get_page(&second.id).await.is_err());
+        assert!(current.get_page(&third.id).await.is_ok());
+
+        let before_base = base.taken_at - chrono::Duration::seconds(1);
+        assert!(reconstruct_at(&db, &recovery_dir, before_base, &output).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_device_local_settings_are_not_logged() {
+        let dir = tempdir().unwrap();
+        let db = Database::new_with_path(dir.path().join("graph.db").to_str().unwrap()).await.unwrap();
+        install_change_log_triggers(&mut db.get_pool().acquire().await.unwrap()).await.unwrap();
+
+        db.get_device_id().await.unwrap();
+        db.set_setting("theme", "dark").await.unwrap();
+        let logged: Vec<String> = sqlx::query_scalar("SELECT row_key FROM change_log WHERE table_name = 'settings'")
+            .fetch_all(db.get_pool())
+            .await
+            .unwrap();
+        assert_eq!(logged, ["theme"]);
+    }
+
+    #[test]
+    fn test_failed_swap_puts_the_live_database_back() {
+        let dir = tempdir().unwrap();
+        let live = dir.path().join("minglog.db");
+        let previous = dir.path().join("minglog.before-restore.db");
+        std::fs::write(&live, "live").unwrap();
+        std::fs::write(dir.path().join("minglog.db-wal"), "wal").unwrap();
+
+        assert!(swap_database_files(&live, &dir.path().join("missing.db"), &previous).is_err());
+        assert_eq!(std::fs::read_to_string(&live).unwrap(), "live");
+        assert!(dir.path().join("minglog.db-wal").exists());
+        assert!(!previous.exists());
+
+        let restored = dir.path().join("restored.db");
+        std::fs::write(&restored, "restored").unwrap();
+        swap_database_files(&live, &restored, &previous).unwrap();
+        assert_eq!(std::fs::read_to_string(&live).unwrap(), "restored");
+        assert_eq!(std::fs::read_to_string(&previous).unwrap(), "live");
+    }
+
+    #[tokio::test]
+    async fn test_prune_keeps_segments_needed_by_remaining_bases() {
+        let dir = tempdir().unwrap();
+        let recovery_dir = dir.path().join("recovery");
+        let db = Database::new_with_path(dir.path().join("graph.db").to_str().unwrap()).await.unwrap();
+        install_change_log_triggers(&mut db.get_pool().acquire().await.unwrap()).await.unwrap();
+
+        db.create_page(page("One")).await.unwrap();
+        archive_changes(&db, &recovery_dir).await.unwrap();
+        create_base_snapshot(&db, &recovery_dir).await.unwrap();
+        pause().await;
+        db.create_page(page("Two")).await.unwrap();
+        archive_changes(&db, &recovery_dir).await.unwrap();
+        create_base_snapshot(&db, &recovery_dir).await.unwrap();
+        db.create_page(page("Three")).await.unwrap();
+        archive_changes(&db, &recovery_dir).await.unwrap();
+
+        prune(&recovery_dir, 1).unwrap();
+        let bases = list_base_snapshots(&recovery_dir).unwrap();
+        assert_eq!(bases.len(), 1);
+        let segments = list_segments(&recovery_dir).unwrap();
+        assert_eq!(segments.len(), 1);
+        assert!(segments[0].0 > bases[0].seq);
+    }
+}
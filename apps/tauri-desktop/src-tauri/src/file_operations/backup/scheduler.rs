@@ -0,0 +1,246 @@
+//! Automatic backups.
+//!
+//! The scheduler writes a snapshot archive into the backup directory every
+//! `backup_interval_minutes` and then prunes older automatic backups according to
+//! the retention settings. Archives whose names don't start with
+//! `AUTO_BACKUP_PREFIX` (manual backups) are never pruned.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use tokio::sync::{Mutex, Notify};
+use tokio_util::sync::CancellationToken;
+
+use super::{BackupInfo, RetentionPolicy};
+use crate::database::Database;
+use crate::file_operations::FileOperations;
+
+pub const INTERVAL_KEY: &str = "backup_interval_minutes";
+pub const DIR_KEY: &str = "backup_dir";
+pub const KEEP_LAST_KEY: &str = "backup_keep_last";
+pub const KEEP_DAILY_KEY: &str = "backup_keep_daily";
+pub const KEEP_WEEKLY_KEY: &str = "backup_keep_weekly";
+
+pub const AUTO_BACKUP_PREFIX: &str = "minglog-auto-";
+
+/// How often to re-read the settings while automatic backups are off
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Backup settings as stored in the `settings` table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupConfig {
+    /// `None` when automatic backups are off
+    pub interval: Option<Duration>,
+    pub dir: PathBuf,
+    pub retention: RetentionPolicy,
+}
+
+impl BackupConfig {
+    pub async fn load(db: &Database) -> Result<Self> {
+        let number = |value: Option<String>| value.and_then(|value| value.trim().parse::<u64>().ok());
+        let defaults = RetentionPolicy::default();
+
+        let interval = number(db.get_setting(INTERVAL_KEY).await?)
+            .filter(|minutes| *minutes > 0)
+            .map(|minutes| Duration::from_secs(minutes * 60));
+        let dir = match db.get_setting(DIR_KEY).await?.filter(|dir| !dir.trim().is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => default_backup_dir()?,
+        };
+        let retention = RetentionPolicy {
+            keep_last: number(db.get_setting(KEEP_LAST_KEY).await?).map_or(defaults.keep_last, |n| n as usize),
+            keep_daily: number(db.get_setting(KEEP_DAILY_KEY).await?).map_or(defaults.keep_daily, |n| n as usize),
+            keep_weekly: number(db.get_setting(KEEP_WEEKLY_KEY).await?).map_or(defaults.keep_weekly, |n| n as usize),
+        };
+
+        Ok(Self { interval, dir, retention })
+    }
+}
+
+pub fn default_backup_dir() -> Result<PathBuf> {
+    Ok(dirs::data_dir()
+        .ok_or_else(|| anyhow!("Could not find app data directory"))?
+        .join("com.minglog.desktop")
+        .join("backups"))
+}
+
+/// Runs automatic backups in the background
+pub struct BackupScheduler {
+    database: Arc<Mutex<Database>>,
+    reschedule: Notify,
+    shutdown: CancellationToken,
+}
+
+impl std::fmt::Debug for BackupScheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BackupScheduler")
+            .field("shutdown", &self.shutdown.is_cancelled())
+            .finish_non_exhaustive()
+    }
+}
+
+impl BackupScheduler {
+    pub fn new(database: Arc<Mutex<Database>>) -> Self {
+        Self {
+            database,
+            reschedule: Notify::new(),
+            shutdown: CancellationToken::new(),
+        }
+    }
+
+    /// Start the scheduling loop in the background
+    pub fn start(self: &Arc<Self>) -> tauri::async_runtime::JoinHandle<()> {
+        let scheduler = Arc::clone(self);
+        tauri::async_runtime::spawn(async move {
+            log::info!("Backup scheduler started");
+            scheduler.run().await;
+            log::info!("Backup scheduler stopped");
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// Re-read the backup settings and reschedule
+    pub fn notify_config_changed(&self) {
+        self.reschedule.notify_one();
+    }
+
+    /// Write an automatic backup now and prune old ones
+    pub async fn backup_now(&self) -> Result<BackupInfo> {
+        let db = self.database.lock().await;
+        let config = BackupConfig::load(&db).await?;
+        std::fs::create_dir_all(&config.dir)?;
+
+        let file_name = format!("{}{}.zip", AUTO_BACKUP_PREFIX, Utc::now().format("%Y%m%d-%H%M%S"));
+        let path = config.dir.join(&file_name);
+        let snapshot = FileOperations::snapshot_for_backup(&db, &path).await?;
+        // Zipping and hashing only read the snapshot, so other commands can go on meanwhile
+        drop(db);
+        let manifest = FileOperations::create_backup_from_snapshot(&snapshot, &path).await?;
+
+        let automatic: Vec<BackupInfo> = FileOperations::list_backups(&config.dir)?
+            .into_iter()
+            .filter(|backup| backup.file_name.starts_with(AUTO_BACKUP_PREFIX))
+            .collect();
+        for index in config.retention.prune(&automatic) {
+            log::info!("Removing old backup {}", automatic[index].file_name);
+            std::fs::remove_file(&automatic[index].path)?;
+        }
+
+        Ok(BackupInfo {
+            size: std::fs::metadata(&path)?.len(),
+            path: path.to_string_lossy().to_string(),
+            file_name,
+            created_at: manifest.created_at,
+            schema_version: manifest.schema_version,
+        })
+    }
+
+    async fn run(&self) {
+        loop {
+            let delay = match self.next_backup_delay().await {
+                Ok(Some(delay)) => delay,
+                Ok(None) => IDLE_POLL_INTERVAL,
+                Err(e) => {
+                    log::warn!("Failed to read backup settings: {}", e);
+                    IDLE_POLL_INTERVAL
+                }
+            };
+
+            tokio::select! {
+                _ = self.shutdown.cancelled() => return,
+                _ = self.reschedule.notified() => continue,
+                _ = tokio::time::sleep(delay) => {}
+            }
+
+            // Settings may have changed while sleeping
+            if !matches!(self.next_backup_delay().await, Ok(Some(delay)) if delay.is_zero()) {
+                continue;
+            }
+            if let Err(e) = self.backup_now().await {
+                log::warn!("Automatic backup failed: {}", e);
+                // Wait a full interval rather than retrying straight away
+                tokio::select! {
+                    _ = self.shutdown.cancelled() => return,
+                    _ = self.reschedule.notified() => {}
+                    _ = tokio::time::sleep(self.interval().await.unwrap_or(IDLE_POLL_INTERVAL)) => {}
+                }
+            }
+        }
+    }
+
+    async fn interval(&self) -> Option<Duration> {
+        let db = self.database.lock().await;
+        BackupConfig::load(&db).await.ok()?.interval
+    }
+
+    /// Time until the next automatic backup is due, `None` when they are off
+    async fn next_backup_delay(&self) -> Result<Option<Duration>> {
+        let config = {
+            let db = self.database.lock().await;
+            BackupConfig::load(&db).await?
+        };
+        let Some(interval) = config.interval else {
+            return Ok(None);
+        };
+
+        let last = FileOperations::list_backups(&config.dir)?
+            .into_iter()
+            .find(|backup| backup.file_name.starts_with(AUTO_BACKUP_PREFIX));
+        let delay = match last {
+            Some(last) => (last.created_at + chrono::Duration::from_std(interval)? - Utc::now())
+                .to_std()
+                .unwrap_or(Duration::ZERO),
+            None => Duration::ZERO,
+        };
+
+        Ok(Some(delay))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_backup_now_writes_and_prunes() {
+        let dir = tempdir().unwrap();
+        let backup_dir = dir.path().join("backups");
+        let db = Database::new_with_path(dir.path().join("graph.db").to_str().unwrap()).await.unwrap();
+        db.set_setting(DIR_KEY, backup_dir.to_str().unwrap()).await.unwrap();
+        db.set_setting(KEEP_LAST_KEY, "1").await.unwrap();
+        db.set_setting(KEEP_DAILY_KEY, "0").await.unwrap();
+        db.set_setting(KEEP_WEEKLY_KEY, "0").await.unwrap();
+
+        let config = BackupConfig::load(&db).await.unwrap();
+        assert_eq!(config.interval, None);
+        assert_eq!(config.retention, RetentionPolicy { keep_last: 1, keep_daily: 0, keep_weekly: 0 });
+
+        // An older automatic backup that retention should remove, and a manual one it must keep
+        std::fs::create_dir_all(&backup_dir).unwrap();
+        let old = backup_dir.join(format!("{}20000101-000000.zip", AUTO_BACKUP_PREFIX));
+        FileOperations::create_backup(&db, old.to_str().unwrap()).await.unwrap();
+        let manual = backup_dir.join("manual.zip");
+        FileOperations::create_backup(&db, manual.to_str().unwrap()).await.unwrap();
+
+        let scheduler = BackupScheduler::new(Arc::new(Mutex::new(db)));
+        let info = scheduler.backup_now().await.unwrap();
+        assert!(FileOperations::verify_backup(std::path::Path::new(&info.path)).unwrap().valid);
+
+        let remaining: Vec<String> = FileOperations::list_backups(&backup_dir).unwrap()
+            .into_iter()
+            .map(|backup| backup.file_name)
+            .collect();
+        assert_eq!(remaining.len(), 2, "{:?}", remaining);
+        assert!(remaining.contains(&info.file_name));
+        assert!(remaining.contains(&"manual.zip".to_string()));
+        assert!(!backup_dir.join(format!("{}.snapshot.db", info.file_name.trim_end_matches(".zip"))).exists());
+    }
+}
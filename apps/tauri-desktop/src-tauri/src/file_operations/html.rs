@@ -0,0 +1,609 @@
+//! Static HTML site export.
+//!
+//! Writes one HTML file per page under `pages/`, plus an index of all pages, a tag
+//! index, a journal archive and `search-index.json` for the bundled search script.
+//! Block content is rendered with `pulldown-cmark`; wikilinks and block references
+//! to exported pages become links, and links to pages left out of the export are
+//! reduced to plain text so private page names do not leak as URLs. Raw HTML in
+//! blocks is escaped, and only web, mail and relative link destinations are kept.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use pulldown_cmark::{html, CowStr, Event, LinkType, Options, Parser, Tag};
+use serde::{Deserialize, Serialize};
+
+use super::{ExportResult, FileOperations};
+use crate::database::Database;
+use crate::models::{Block, Page};
+
+const STYLE_CSS: &str = "body{font-family:system-ui,sans-serif;max-width:48rem;margin:0 auto;padding:1rem;line-height:1.6;color:#222}\
+nav{display:flex;gap:1rem;align-items:center;border-bottom:1px solid #ddd;padding-bottom:.5rem}\
+nav input{margin-left:auto}\
+ul.blocks{padding-left:1.25rem}\
+.tags a{margin-right:.5rem;color:#666}\
+section.backlinks{border-top:1px solid #ddd;margin-top:2rem}\
+.missing{color:#999}\
+#search-results{list-style:none;padding:0}\n";
+
+const SEARCH_JS: &str = r#"(function () {
+  var root = document.body.dataset.root || "";
+  var input = document.getElementById("search");
+  var results = document.getElementById("search-results");
+  if (!input || !results) return;
+  var index = null;
+  input.addEventListener("input", function () {
+    var query = input.value.trim().toLowerCase();
+    var show = function () {
+      results.innerHTML = "";
+      if (!query) return;
+      index.filter(function (entry) {
+        return entry.title.toLowerCase().indexOf(query) >= 0 || entry.text.toLowerCase().indexOf(query) >= 0;
+      }).slice(0, 20).forEach(function (entry) {
+        var item = document.createElement("li");
+        var link = document.createElement("a");
+        link.href = root + entry.url;
+        link.textContent = entry.title;
+        item.appendChild(link);
+        results.appendChild(item);
+      });
+    };
+    if (index) return show();
+    fetch(root + "search-index.json").then(function (response) { return response.json(); }).then(function (data) {
+      index = data;
+      show();
+    });
+  });
+})();
+"#;
+
+/// Which pages of a graph to publish
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HtmlExportFilter {
+    /// Only pages with a `public:: true` property
+    #[serde(default)]
+    pub public_only: bool,
+    /// Only pages carrying at least one of these tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl HtmlExportFilter {
+    pub fn matches(&self, page: &Page) -> bool {
+        if self.public_only && !is_public(page) {
+            return false;
+        }
+        self.tags.is_empty() || page_tags(page).iter().any(|tag| self.tags.iter().any(|wanted| wanted.eq_ignore_ascii_case(tag)))
+    }
+}
+
+/// An entry of `search-index.json`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchEntry {
+    pub title: String,
+    pub url: String,
+    pub tags: Vec<String>,
+    pub text: String,
+}
+
+struct SitePage<'a> {
+    page: &'a Page,
+    slug: String,
+    title: String,
+    tags: Vec<String>,
+    blocks: Vec<Block>,
+}
+
+#[allow(dead_code)]
+impl FileOperations {
+    /// Export the pages of a graph that pass `filter` as a static site in `out_dir`
+    pub async fn export_graph_to_html(
+        db: &Database,
+        graph_id: &str,
+        out_dir: &Path,
+        filter: &HtmlExportFilter,
+    ) -> Result<ExportResult> {
+        let pages: Vec<Page> = db.get_pages_by_graph(graph_id).await?
+            .into_iter()
+            .filter(|page| filter.matches(page))
+            .collect();
+
+        let mut used_slugs = HashSet::new();
+        let mut site = Vec::with_capacity(pages.len());
+        let mut names: HashMap<String, usize> = HashMap::new();
+        for page in &pages {
+            let index = site.len();
+            names.insert(page.name.to_lowercase(), index);
+            for alias in db.get_page_aliases(&page.id).await? {
+                names.entry(alias.to_lowercase()).or_insert(index);
+            }
+
+            let mut slug = slugify(&page.name);
+            let base = slug.clone();
+            let mut counter = 2;
+            while !used_slugs.insert(slug.clone()) {
+                slug = format!("{}-{}", base, counter);
+                counter += 1;
+            }
+            site.push(SitePage {
+                page,
+                slug,
+                title: page.title.clone().unwrap_or_else(|| page.name.clone()),
+                tags: page_tags(page),
+                blocks: db.get_blocks_by_page(&page.id).await?,
+            });
+        }
+
+        let resolver = Resolver::new(&site, names);
+        let backlinks = resolver.backlinks(&site);
+
+        fs::create_dir_all(out_dir.join("pages"))?;
+        let mut written = Written::default();
+
+        for (index, site_page) in site.iter().enumerate() {
+            let body = render_page(site_page, &resolver, &backlinks[index]);
+            written.write(&out_dir.join("pages").join(format!("{}.html", site_page.slug)), &layout(&site_page.title, "../", &body))?;
+        }
+
+        written.write(&out_dir.join("index.html"), &layout("All pages", "", &render_index(&site)))?;
+        written.write(&out_dir.join("tags.html"), &layout("Tags", "", &render_tags(&site)))?;
+        written.write(&out_dir.join("journals.html"), &layout("Journals", "", &render_journals(&site)))?;
+
+        let search_index: Vec<SearchEntry> = site.iter()
+            .map(|site_page| SearchEntry {
+                title: site_page.title.clone(),
+                url: format!("pages/{}.html", site_page.slug),
+                tags: site_page.tags.clone(),
+                // Indexed as rendered, so links to pages left out don't show up here either
+                text: site_page.blocks.iter().map(|block| plain_text(&resolver.rewrite(&block.content))).collect::<Vec<_>>().join("\n"),
+            })
+            .collect();
+        written.write(&out_dir.join("search-index.json"), &serde_json::to_string(&search_index)?)?;
+        written.write(&out_dir.join("style.css"), STYLE_CSS)?;
+        written.write(&out_dir.join("search.js"), SEARCH_JS)?;
+
+        Ok(ExportResult {
+            files_exported: written.files,
+            total_size: written.size,
+            export_path: out_dir.to_string_lossy().to_string(),
+        })
+    }
+}
+
+#[derive(Default)]
+struct Written {
+    files: usize,
+    size: u64,
+}
+
+impl Written {
+    fn write(&mut self, path: &Path, content: &str) -> Result<()> {
+        fs::write(path, content)?;
+        self.files += 1;
+        self.size += content.len() as u64;
+        Ok(())
+    }
+}
+
+/// Resolves wikilinks and block references against the exported pages
+struct Resolver {
+    names: HashMap<String, usize>,
+    slugs: Vec<String>,
+    titles: Vec<String>,
+    /// Block id to its page and first line as plain text
+    blocks: HashMap<String, (usize, String)>,
+}
+
+impl Resolver {
+    fn new(site: &[SitePage], names: HashMap<String, usize>) -> Self {
+        let mut blocks = HashMap::new();
+        for (index, site_page) in site.iter().enumerate() {
+            for block in &site_page.blocks {
+                let first_line = block.content.lines().next().unwrap_or_default().to_string();
+                blocks.insert(block.id.clone(), (index, first_line));
+            }
+        }
+        let mut resolver = Self {
+            names,
+            slugs: site.iter().map(|site_page| site_page.slug.clone()).collect(),
+            titles: site.iter().map(|site_page| site_page.title.clone()).collect(),
+            blocks,
+        };
+        // References show the referenced line as text, with its own links resolved first
+        let labels: Vec<(String, String)> = resolver.blocks.iter()
+            .map(|(id, (_, first_line))| (id.clone(), plain_text(&resolver.rewrite(first_line))))
+            .collect();
+        for (id, label) in labels {
+            if let Some((_, first_line)) = resolver.blocks.get_mut(&id) {
+                *first_line = label;
+            }
+        }
+        resolver
+    }
+
+    fn page(&self, name: &str) -> Option<usize> {
+        self.names.get(&name.trim().to_lowercase()).copied()
+    }
+
+    /// Blocks on other pages that link to each page, grouped by page
+    fn backlinks<'a>(&self, site: &'a [SitePage]) -> Vec<Vec<(usize, &'a Block)>> {
+        let mut backlinks = vec![Vec::new(); site.len()];
+        for (source, site_page) in site.iter().enumerate() {
+            for block in &site_page.blocks {
+                let mut targets: Vec<usize> = wikilink_targets(&block.content)
+                    .filter_map(|name| self.page(name))
+                    .filter(|target| *target != source)
+                    .collect();
+                targets.sort_unstable();
+                targets.dedup();
+                for target in targets {
+                    backlinks[target].push((source, block));
+                }
+            }
+        }
+        backlinks
+    }
+
+    /// Turn wikilinks and block references into Markdown links, relative to `pages/`
+    fn rewrite(&self, content: &str) -> String {
+        let mut out = String::with_capacity(content.len());
+        let mut rest = content;
+        loop {
+            let next = [rest.find("[["), rest.find("((")].into_iter().flatten().min();
+            let Some(start) = next else { break };
+            let is_page = rest[start..].starts_with("[[");
+            let close = if is_page { "]]" } else { "))" };
+            let Some(end) = rest[start + 2..].find(close).map(|end| end + start + 2) else { break };
+            let inner = &rest[start + 2..end];
+            if inner.contains('\n') {
+                out.push_str(&rest[..start + 2]);
+                rest = &rest[start + 2..];
+                continue;
+            }
+
+            // `#[[tag]]` is a link whose `#` is not part of the text
+            let prefix = &rest[..start];
+            out.push_str(prefix.strip_suffix('#').filter(|_| is_page).unwrap_or(prefix));
+            if is_page {
+                let (target, label) = inner.split_once('|').unwrap_or((inner, inner));
+                let target = target.split('#').next().unwrap_or_default();
+                match self.page(target) {
+                    Some(index) => out.push_str(&format!("[{}]({}.html)", escape_markdown(label), self.slugs[index])),
+                    None => out.push_str(&format!("<span class=\"missing\">{}</span>", escape_html(label))),
+                }
+            } else if let Some((index, text)) = self.blocks.get(inner) {
+                out.push_str(&format!("[{}]({}.html#block-{})", escape_markdown(text), self.slugs[*index], inner));
+            }
+            rest = &rest[end + 2..];
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+/// Names linked with `[[name]]`, `[[name|label]]` or `#[[name]]`
+fn wikilink_targets(content: &str) -> impl Iterator<Item = &str> {
+    super::delimited(content, "[[", "]]")
+        .map(|inner| inner.split('|').next().unwrap_or_default())
+        .map(|target| target.split('#').next().unwrap_or_default())
+}
+
+fn render_page(site_page: &SitePage, resolver: &Resolver, backlinks: &[(usize, &Block)]) -> String {
+    let mut body = format!("<h1>{}</h1>\n", escape_html(&site_page.title));
+    if let Some(date) = site_page.page.journal_date.as_deref().filter(|_| site_page.page.is_journal) {
+        body.push_str(&format!("<p class=\"journal-date\"><time datetime=\"{0}\">{0}</time></p>\n", escape_html(date)));
+    }
+    if !site_page.tags.is_empty() {
+        body.push_str("<p class=\"tags\">");
+        for tag in &site_page.tags {
+            body.push_str(&format!("<a href=\"../tags.html#tag-{}\">#{}</a>", slugify(tag), escape_html(tag)));
+        }
+        body.push_str("</p>\n");
+    }
+
+    let mut children: HashMap<Option<&str>, Vec<&Block>> = HashMap::new();
+    let ids: HashSet<&str> = site_page.blocks.iter().map(|block| block.id.as_str()).collect();
+    let mut sorted: Vec<&Block> = site_page.blocks.iter().collect();
+    sorted.sort_by(|a, b| (a.order, a.created_at, &a.id).cmp(&(b.order, b.created_at, &b.id)));
+    for block in sorted {
+        let parent = block.parent_id.as_deref().filter(|parent| ids.contains(parent) && *parent != block.id);
+        children.entry(parent).or_default().push(block);
+    }
+    render_blocks(&mut body, None, &children, resolver, &mut HashSet::new());
+
+    if !backlinks.is_empty() {
+        body.push_str("<section class=\"backlinks\">\n<h2>Backlinks</h2>\n");
+        let mut by_page: BTreeMap<usize, Vec<&Block>> = BTreeMap::new();
+        for (source, block) in backlinks {
+            by_page.entry(*source).or_default().push(block);
+        }
+        for (source, blocks) in by_page {
+            body.push_str(&format!(
+                "<h3><a href=\"{}.html\">{}</a></h3>\n<ul>\n",
+                resolver.slugs[source],
+                escape_html(&resolver.titles[source])
+            ));
+            for block in blocks {
+                body.push_str(&format!("<li>{}</li>\n", render_markdown(&resolver.rewrite(&block.content))));
+            }
+            body.push_str("</ul>\n");
+        }
+        body.push_str("</section>\n");
+    }
+    body
+}
+
+fn render_blocks<'a>(
+    out: &mut String,
+    parent: Option<&'a str>,
+    children: &HashMap<Option<&'a str>, Vec<&'a Block>>,
+    resolver: &Resolver,
+    visited: &mut HashSet<&'a str>,
+) {
+    let Some(blocks) = children.get(&parent) else { return };
+    out.push_str("<ul class=\"blocks\">\n");
+    for block in blocks {
+        if !visited.insert(block.id.as_str()) {
+            continue;
+        }
+        out.push_str(&format!("<li id=\"block-{}\">", escape_html(&block.id)));
+        out.push_str(&render_markdown(&resolver.rewrite(&block.content)));
+        render_blocks(out, Some(block.id.as_str()), children, resolver, visited);
+        out.push_str("</li>\n");
+    }
+    out.push_str("</ul>\n");
+}
+
+fn markdown_options() -> Options {
+    Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS
+}
+
+fn render_markdown(markdown: &str) -> String {
+    let parser = Parser::new_ext(markdown, markdown_options()).map(|event| match event {
+        // Keep the markup we generate for missing links, escape anything else
+        Event::Html(raw) if !is_missing_link(&raw) => Event::Text(raw),
+        Event::Start(Tag::Link(kind, destination, title)) => Event::Start(Tag::Link(kind, safe_destination(kind, destination), title)),
+        Event::Start(Tag::Image(kind, destination, title)) => Event::Start(Tag::Image(kind, safe_destination(kind, destination), title)),
+        event => event,
+    });
+    let mut rendered = String::new();
+    html::push_html(&mut rendered, parser);
+    rendered.trim_end().to_string()
+}
+
+fn is_missing_link(raw: &str) -> bool {
+    raw == "<span class=\"missing\">" || raw == "</span>"
+}
+
+/// Link destinations other than http, https, mailto and relative ones become `#`
+fn safe_destination(kind: LinkType, destination: CowStr<'_>) -> CowStr<'_> {
+    // Email autolinks carry the bare address, the renderer adds `mailto:`
+    if kind == LinkType::Email || is_safe_url(&destination) {
+        destination
+    } else {
+        CowStr::Borrowed("#")
+    }
+}
+
+fn is_safe_url(url: &str) -> bool {
+    // Browsers skip whitespace and control characters inside a scheme
+    let url: String = url.chars().filter(|c| !c.is_whitespace() && !c.is_control()).collect();
+    match url.find([':', '/', '?', '#']) {
+        Some(end) if url[end..].starts_with(':') => {
+            matches!(url[..end].to_ascii_lowercase().as_str(), "http" | "https" | "mailto")
+        }
+        _ => true,
+    }
+}
+
+/// The text a block shows once rendered, for the search index
+fn plain_text(markdown: &str) -> String {
+    let mut text = String::new();
+    for event in Parser::new_ext(markdown, markdown_options()) {
+        match event {
+            Event::Text(part) | Event::Code(part) => text.push_str(&part),
+            Event::SoftBreak | Event::HardBreak | Event::End(Tag::Paragraph | Tag::Heading(..) | Tag::Item) => text.push(' '),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn render_index(site: &[SitePage]) -> String {
+    let mut body = String::from("<h1>All pages</h1>\n<ul id=\"search-results\"></ul>\n<ul>\n");
+    let mut pages: Vec<&SitePage> = site.iter().filter(|site_page| !site_page.page.is_journal).collect();
+    pages.sort_by_key(|site_page| site_page.title.to_lowercase());
+    for site_page in pages {
+        body.push_str(&format!("<li><a href=\"pages/{}.html\">{}</a></li>\n", site_page.slug, escape_html(&site_page.title)));
+    }
+    body.push_str("</ul>\n");
+    body
+}
+
+fn render_tags(site: &[SitePage]) -> String {
+    let mut tags: BTreeMap<String, (String, Vec<&SitePage>)> = BTreeMap::new();
+    for site_page in site {
+        for tag in &site_page.tags {
+            tags.entry(tag.to_lowercase()).or_insert_with(|| (tag.clone(), Vec::new())).1.push(site_page);
+        }
+    }
+
+    let mut body = String::from("<h1>Tags</h1>\n");
+    for (tag, pages) in tags.values() {
+        body.push_str(&format!("<h2 id=\"tag-{}\">#{}</h2>\n<ul>\n", slugify(tag), escape_html(tag)));
+        for site_page in pages {
+            body.push_str(&format!("<li><a href=\"pages/{}.html\">{}</a></li>\n", site_page.slug, escape_html(&site_page.title)));
+        }
+        body.push_str("</ul>\n");
+    }
+    body
+}
+
+fn render_journals(site: &[SitePage]) -> String {
+    let mut journals: Vec<(&str, &SitePage)> = site.iter()
+        .filter(|site_page| site_page.page.is_journal)
+        .map(|site_page| (site_page.page.journal_date.as_deref().unwrap_or_default(), site_page))
+        .collect();
+    journals.sort_by(|a, b| b.0.cmp(a.0));
+
+    let mut body = String::from("<h1>Journals</h1>\n");
+    let mut month = None;
+    for (date, site_page) in journals {
+        let this_month = date.get(..7).unwrap_or(date);
+        if month != Some(this_month) {
+            if month.is_some() {
+                body.push_str("</ul>\n");
+            }
+            body.push_str(&format!("<h2>{}</h2>\n<ul>\n", escape_html(this_month)));
+            month = Some(this_month);
+        }
+        body.push_str(&format!("<li><a href=\"pages/{}.html\">{}</a></li>\n", site_page.slug, escape_html(&site_page.title)));
+    }
+    if month.is_some() {
+        body.push_str("</ul>\n");
+    }
+    body
+}
+
+fn layout(title: &str, root: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{title}</title>\n<link rel=\"stylesheet\" href=\"{root}style.css\">\n</head>\n<body data-root=\"{root}\">\n<nav><a href=\"{root}index.html\">All pages</a><a href=\"{root}journals.html\">Journals</a><a href=\"{root}tags.html\">Tags</a><input id=\"search\" type=\"search\" placeholder=\"Search\"></nav>\n<main>\n{body}</main>\n<script src=\"{root}search.js\"></script>\n</body>\n</html>\n",
+        title = escape_html(title),
+        root = root,
+        body = body,
+    )
+}
+
+fn is_public(page: &Page) -> bool {
+    let Some(properties) = page.properties.as_deref() else { return false };
+    match serde_json::from_str::<serde_json::Value>(properties).ok().as_ref().and_then(|value| value.get("public")) {
+        Some(serde_json::Value::Bool(public)) => *public,
+        Some(serde_json::Value::String(public)) => public.trim().eq_ignore_ascii_case("true"),
+        _ => false,
+    }
+}
+
+fn page_tags(page: &Page) -> Vec<String> {
+    serde_json::from_str(&page.tags).unwrap_or_default()
+}
+
+/// File name safe slug; namespaces are joined with `-`
+fn slugify(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+    for c in name.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() { "page".to_string() } else { slug.to_string() }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('[', "\\[").replace(']', "\\]")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreateBlockRequest, CreatePageRequest};
+    use tempfile::tempdir;
+
+    async fn page(db: &Database, name: &str, properties: Option<&str>, tags: &str, journal_date: Option<&str>) -> Page {
+        db.create_page(CreatePageRequest {
+            graph_id: "default".to_string(),
+            name: name.to_string(),
+            title: None,
+            properties: properties.map(str::to_string),
+            tags: Some(tags.to_string()),
+            is_journal: Some(journal_date.is_some()),
+            journal_date: journal_date.map(str::to_string),
+        }).await.unwrap()
+    }
+
+    async fn block(db: &Database, page: &Page, content: &str, parent_id: Option<String>) -> Block {
+        db.create_block(CreateBlockRequest {
+            graph_id: "default".to_string(),
+            page_id: page.id.clone(),
+            content: content.to_string(),
+            parent_id,
+            properties: None,
+            refs: None,
+            order: Some(0),
+        }).await.unwrap()
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Projects/Alpha Beta"), "projects-alpha-beta");
+        assert_eq!(slugify("Café & Co."), "café-co");
+        assert_eq!(slugify("???"), "page");
+    }
+
+    #[tokio::test]
+    async fn test_export_graph_to_html() {
+        let dir = tempdir().unwrap();
+        let db = Database::new_with_path(dir.path().join("site.db").to_str().unwrap()).await.unwrap();
+        let public = Some(r#"{"public":"true"}"#);
+
+        let home = page(&db, "Home", public, r#"["guide"]"#, None).await;
+        let notes = page(&db, "Notes", public, "[]", None).await;
+        let secret = page(&db, "Secret", None, "[]", None).await;
+        let journal = page(&db, "Jan 15th, 2024", public, "[]", Some("2024-01-15")).await;
+
+        let welcome = block(&db, &home, "Welcome to [[Notes]] and [[Secret]] <script>x</script>", None).await;
+        block(&db, &home, "[run](javascript:alert(1)) ![pic](  JavaScript:alert(2)) [site](https://example.com) [local](notes.html) <me@example.com>", None).await;
+        block(&db, &home, "nested **bold**", Some(welcome.id.clone())).await;
+        let reply = block(&db, &notes, "Back to [[home|the start]]", None).await;
+        block(&db, &secret, "Links to [[Home]]", None).await;
+        block(&db, &journal, &format!("See (({}))", reply.id), None).await;
+
+        let out_dir = dir.path().join("site");
+        let filter = HtmlExportFilter { public_only: true, ..Default::default() };
+        let result = FileOperations::export_graph_to_html(&db, "default", &out_dir, &filter).await.unwrap();
+        assert_eq!(result.files_exported, 3 + 6);
+        assert!(!out_dir.join("pages/secret.html").exists());
+
+        let home_html = fs::read_to_string(out_dir.join("pages/home.html")).unwrap();
+        assert!(home_html.contains("<a href=\"notes.html\">Notes</a>"));
+        assert!(home_html.contains("<span class=\"missing\">Secret</span>"));
+        assert!(home_html.contains("&lt;script&gt;"));
+        assert!(home_html.contains("<strong>bold</strong>"));
+        assert!(!home_html.to_lowercase().contains("javascript:"));
+        assert!(home_html.contains("<a href=\"#\">run</a>"));
+        assert!(home_html.contains("<a href=\"https://example.com\">site</a>"));
+        assert!(home_html.contains("<a href=\"notes.html\">local</a>"));
+        assert!(home_html.contains("<a href=\"mailto:me@example.com\">me@example.com</a>"));
+        assert!(home_html.contains("<a href=\"../tags.html#tag-guide\">#guide</a>"));
+        // Backlinks only come from exported pages
+        assert!(home_html.contains("<h2>Backlinks</h2>"));
+        assert!(home_html.contains("<a href=\"notes.html\">Notes</a></h3>"));
+        assert!(!home_html.contains("Links to"));
+
+        let journal_html = fs::read_to_string(out_dir.join("pages/jan-15th-2024.html")).unwrap();
+        assert!(journal_html.contains(&format!("<a href=\"notes.html#block-{}\">Back to the start</a>", reply.id)));
+
+        let journals = fs::read_to_string(out_dir.join("journals.html")).unwrap();
+        assert!(journals.contains("<h2>2024-01</h2>"));
+        let tags = fs::read_to_string(out_dir.join("tags.html")).unwrap();
+        assert!(tags.contains("<h2 id=\"tag-guide\">#guide</h2>"));
+
+        let search: Vec<SearchEntry> = serde_json::from_str(&fs::read_to_string(out_dir.join("search-index.json")).unwrap()).unwrap();
+        assert_eq!(search.len(), 3);
+        assert!(search.iter().any(|entry| entry.url == "pages/notes.html" && entry.text == "Back to the start"));
+        // Neither wikilink syntax nor block ids make it into the index
+        assert!(search.iter().all(|entry| !entry.text.contains("[[") && !entry.text.contains(&reply.id)));
+        assert!(search.iter().any(|entry| entry.text == "See Back to the start"));
+    }
+}
@@ -0,0 +1,859 @@
+//! iCalendar (RFC 5545) import and export.
+//!
+//! Tasks with a due date are written as `VTODO`s, stopped time entries and blocks
+//! with a `SCHEDULED:` line as `VEVENT`s. UIDs are `<id>@minglog`, so importing a
+//! file this app wrote skips what is already here. An imported `VTODO` becomes a
+//! task; a `VEVENT` becomes a block on the journal page of its start date, with a
+//! `SCHEDULED:` line so it is exported as an event again. Floating times and times
+//! with a `TZID` are read in the local zone, as there is no timezone database to
+//! resolve them; `SCHEDULED:` times are local, so they are written as floating.
+//! Imported components keep their UID, and importing one again skips it.
+//!
+//! The calendar feed is rewritten by `CalendarFeed` whenever tasks or blocks
+//! change, once it was created with `refresh_calendar_feed`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, Notify};
+use tokio_util::sync::CancellationToken;
+
+use super::logseq::{format_logseq_date, LogseqConfig};
+use super::{ExportResult, FileOperations};
+use crate::database::Database;
+use crate::models::{
+    Block, CreateBlockRequest, CreatePageRequest, CreateTaskRequest, Task, TaskPriority, TaskStatus, TimeEntry,
+    UpdateTaskRequest,
+};
+use crate::tasks::block_task;
+use crate::tasks::recurrence::{self, Recurrence};
+
+const UID_DOMAIN: &str = "@minglog";
+const TEMP_SUFFIX: &str = ".minglog-tmp";
+
+/// How long edits must settle before the calendar feed is rewritten
+const FEED_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// The feed is also rewritten this often, for changes that arrive by sync
+const FEED_MAX_AGE: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentKind {
+    Todo,
+    Event,
+}
+
+/// A `DATE` or a `DATE-TIME` value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IcsTime {
+    Date(NaiveDate),
+    DateTime(DateTime<Utc>),
+    /// A wall-clock time in the local zone, written without `Z`
+    Floating(NaiveDateTime),
+}
+
+impl IcsTime {
+    /// Dates start at midnight UTC
+    pub fn to_utc(self) -> DateTime<Utc> {
+        match self {
+            IcsTime::Date(date) => Utc.from_utc_datetime(&date.and_time(NaiveTime::MIN)),
+            IcsTime::DateTime(time) => time,
+            IcsTime::Floating(time) => recurrence::from_local(&Local, time)
+                .unwrap_or_else(|| Utc.from_utc_datetime(&time)),
+        }
+    }
+
+    /// Local date and time, `None` for dates
+    pub fn to_local(self) -> Option<NaiveDateTime> {
+        match self {
+            IcsTime::Date(_) => None,
+            IcsTime::DateTime(time) => Some(time.with_timezone(&Local).naive_local()),
+            IcsTime::Floating(time) => Some(time),
+        }
+    }
+
+    /// The day in the local zone
+    pub fn date(self) -> NaiveDate {
+        match self {
+            IcsTime::Date(date) => date,
+            _ => self.to_local().map(|time| time.date()).unwrap_or_default(),
+        }
+    }
+}
+
+/// A `VTODO` or `VEVENT`, with the properties this app reads and writes
+#[derive(Debug, Clone, PartialEq)]
+pub struct IcsComponent {
+    pub kind: ComponentKind,
+    pub uid: String,
+    pub summary: String,
+    pub description: Option<String>,
+    pub start: Option<IcsTime>,
+    pub end: Option<IcsTime>,
+    pub due: Option<IcsTime>,
+    pub completed: Option<DateTime<Utc>>,
+    pub status: Option<String>,
+    pub priority: Option<u8>,
+    pub categories: Vec<String>,
+    pub rrule: Option<String>,
+    pub created: Option<DateTime<Utc>>,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+impl IcsComponent {
+    pub fn new(kind: ComponentKind, uid: String, summary: String) -> Self {
+        Self {
+            kind,
+            uid,
+            summary,
+            description: None,
+            start: None,
+            end: None,
+            due: None,
+            completed: None,
+            status: None,
+            priority: None,
+            categories: Vec::new(),
+            rrule: None,
+            created: None,
+            last_modified: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IcsImportResult {
+    pub tasks_imported: usize,
+    pub blocks_imported: usize,
+    pub pages_created: usize,
+    /// Components whose task, block or time entry is already here
+    pub skipped: usize,
+    pub errors: Vec<String>,
+}
+
+/// Where the calendar feed other apps can subscribe to is written
+pub fn default_calendar_feed_path() -> Result<PathBuf> {
+    Ok(dirs::data_dir()
+        .ok_or_else(|| anyhow!("Could not find app data directory"))?
+        .join("com.minglog.desktop")
+        .join("calendar.ics"))
+}
+
+#[allow(dead_code)]
+impl FileOperations {
+    /// Tasks with a due date, stopped time entries and scheduled blocks
+    pub async fn calendar_components(db: &Database) -> Result<Vec<IcsComponent>> {
+        let tasks = db.get_tasks(None).await?;
+        let titles: HashMap<&str, &str> = tasks.iter().map(|task| (task.id.as_str(), task.title.as_str())).collect();
+
+        let mut components: Vec<IcsComponent> = tasks.iter().filter_map(task_to_todo).collect();
+        for entry in db.get_all_time_entries().await? {
+            if let Some(event) = time_entry_to_event(&entry, titles.get(entry.task_id.as_str()).copied()) {
+                components.push(event);
+            }
+        }
+        components.extend(db.get_scheduled_blocks().await?.iter().filter_map(block_to_event));
+        Ok(components)
+    }
+
+    /// Write the calendar to an `.ics` file, through a temporary file so apps
+    /// subscribed to it never read half a calendar
+    pub async fn export_ics(db: &Database, output_path: &Path) -> Result<ExportResult> {
+        let calendar = render_calendar(&Self::calendar_components(db).await?, Utc::now());
+        if let Some(parent) = output_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut temp_name = output_path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(TEMP_SUFFIX);
+        let temp_path = output_path.with_file_name(temp_name);
+        fs::write(&temp_path, &calendar)?;
+        fs::rename(&temp_path, output_path)?;
+
+        Ok(ExportResult {
+            files_exported: 1,
+            total_size: calendar.len() as u64,
+            export_path: output_path.to_string_lossy().to_string(),
+        })
+    }
+
+    /// Import to-dos as tasks and events as journal blocks
+    pub async fn import_ics(db: &Database, file_path: &Path, graph_id: &str) -> Result<IcsImportResult> {
+        let components = parse_calendar(&fs::read_to_string(file_path)?)?;
+        let mut result = IcsImportResult::default();
+
+        for component in &components {
+            if Self::exists_locally(db, &component.uid).await? {
+                result.skipped += 1;
+                continue;
+            }
+            let imported = match component.kind {
+                ComponentKind::Todo => Self::import_todo(db, component, &mut result).await,
+                ComponentKind::Event => Self::import_event(db, component, graph_id, &mut result).await,
+            };
+            if let Err(e) = imported {
+                result.errors.push(format!("Failed to import '{}': {}", component.summary, e));
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Exported from here, or imported before
+    async fn exists_locally(db: &Database, uid: &str) -> Result<bool> {
+        if uid.is_empty() {
+            return Ok(false);
+        }
+        if let Some(id) = uid.strip_suffix(UID_DOMAIN) {
+            if db.get_task(id).await.is_ok() || db.get_block(id).await.is_ok() || db.get_time_entry(id).await.is_ok() {
+                return Ok(true);
+            }
+        }
+        Ok(db.has_ics_uid(uid).await?)
+    }
+
+    async fn import_todo(db: &Database, todo: &IcsComponent, result: &mut IcsImportResult) -> Result<()> {
+        let status = todo.status.as_deref().map(todo_status);
+        let recurrence = match &todo.rrule {
+            // A finished series has nothing left to repeat
+            Some(_) if status == Some(TaskStatus::Done) => None,
+            Some(rrule) => match Recurrence::from_rrule(rrule) {
+                Ok(recurrence) => Some(recurrence),
+                Err(e) => {
+                    result.errors.push(format!("Ignored repeat rule of '{}': {}", todo.summary, e));
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let task = db.create_task(CreateTaskRequest {
+            title: non_empty(&todo.summary),
+            description: todo.description.clone(),
+            priority: todo.priority.and_then(task_priority),
+            due_date: todo.due.map(IcsTime::to_utc),
+            scheduled_date: todo.start.map(IcsTime::to_utc),
+            estimated_time: None,
+            project_id: None,
+            parent_task_id: None,
+            linked_notes: None,
+            linked_files: None,
+            tags: Some(todo.categories.clone()),
+            contexts: None,
+            recurrence,
+        }).await?;
+        if !todo.uid.is_empty() {
+            db.set_task_ics_uid(&task.id, &todo.uid).await?;
+        }
+        result.tasks_imported += 1;
+
+        if let Some(status) = status.filter(|status| *status != task.status) {
+            db.update_task(UpdateTaskRequest {
+                id: task.id,
+                title: None,
+                description: None,
+                status: Some(status),
+                priority: None,
+                due_date: None,
+                scheduled_date: None,
+                estimated_time: None,
+                project_id: None,
+                parent_task_id: None,
+                linked_notes: None,
+                linked_files: None,
+                tags: None,
+                contexts: None,
+                recurrence: None,
+            }, false).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn import_event(db: &Database, event: &IcsComponent, graph_id: &str, result: &mut IcsImportResult) -> Result<()> {
+        let start = event.start.ok_or_else(|| anyhow!("event has no start"))?;
+        // The journal page of the day the event starts here
+        let date = start.date();
+        let journal_date = date.format("%Y-%m-%d").to_string();
+
+        let page = match db.get_journal_page(graph_id, &journal_date).await? {
+            Some(page) => page,
+            None => {
+                let page = db.create_page(CreatePageRequest {
+                    name: format_logseq_date(&LogseqConfig::default().journal_title_format, date),
+                    title: None,
+                    graph_id: graph_id.to_string(),
+                    is_journal: Some(true),
+                    journal_date: Some(journal_date),
+                    tags: Some("[]".to_string()),
+                    properties: None,
+                }).await?;
+                result.pages_created += 1;
+                page
+            }
+        };
+
+        let time = start.to_local().map(|time| time.time());
+        let mut content = non_empty(&event.summary);
+        if let Some(description) = event.description.as_deref().map(str::trim).filter(|d| !d.is_empty()) {
+            content.push('\n');
+            content.push_str(description);
+        }
+        content.push_str("\nSCHEDULED: ");
+        content.push_str(&block_task::format_timestamp(date, time));
+
+        let order = db.get_blocks_by_page(&page.id).await?.len() as i32;
+        let block = db.create_block(CreateBlockRequest {
+            content,
+            parent_id: None,
+            properties: None,
+            refs: None,
+            order: Some(order),
+            page_id: page.id,
+            graph_id: graph_id.to_string(),
+        }).await?;
+        if !event.uid.is_empty() {
+            db.set_block_ics_uid(&block.id, &event.uid).await?;
+        }
+        result.blocks_imported += 1;
+
+        Ok(())
+    }
+}
+
+/// Rewrites the calendar feed in the background after tasks and blocks change
+pub struct CalendarFeed {
+    database: Arc<Mutex<Database>>,
+    path: Option<PathBuf>,
+    changed: Notify,
+    shutdown: CancellationToken,
+}
+
+impl std::fmt::Debug for CalendarFeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CalendarFeed")
+            .field("path", &self.path)
+            .field("shutdown", &self.shutdown.is_cancelled())
+            .finish_non_exhaustive()
+    }
+}
+
+impl CalendarFeed {
+    pub fn new(database: Arc<Mutex<Database>>) -> Self {
+        Self::with_path(database, default_calendar_feed_path().ok())
+    }
+
+    pub fn with_path(database: Arc<Mutex<Database>>, path: Option<PathBuf>) -> Self {
+        Self {
+            database,
+            path,
+            changed: Notify::new(),
+            shutdown: CancellationToken::new(),
+        }
+    }
+
+    /// Start the refresh loop in the background
+    pub fn start(self: &Arc<Self>) -> tauri::async_runtime::JoinHandle<()> {
+        let feed = Arc::clone(self);
+        tauri::async_runtime::spawn(async move {
+            log::info!("Calendar feed started");
+            feed.run().await;
+            log::info!("Calendar feed stopped");
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// Tasks, time entries or blocks were added, changed or removed
+    pub fn notify_changed(&self) {
+        self.changed.notify_one();
+    }
+
+    /// Rewrite the feed, unless nobody has created it yet
+    pub async fn refresh(&self) -> Result<bool> {
+        let Some(path) = self.path.as_deref().filter(|path| path.exists()) else {
+            return Ok(false);
+        };
+        let db = self.database.lock().await;
+        FileOperations::export_ics(&db, path).await?;
+        Ok(true)
+    }
+
+    async fn run(&self) {
+        loop {
+            // Catches up on changes made while the app was closed, too
+            if let Err(e) = self.refresh().await {
+                log::warn!("Failed to refresh calendar feed: {}", e);
+            }
+
+            tokio::select! {
+                _ = self.shutdown.cancelled() => return,
+                _ = self.changed.notified() => {}
+                _ = tokio::time::sleep(FEED_MAX_AGE) => continue,
+            }
+            // Wait for a burst of edits to settle before writing
+            loop {
+                tokio::select! {
+                    _ = self.shutdown.cancelled() => return,
+                    _ = self.changed.notified() => {}
+                    _ = tokio::time::sleep(FEED_DEBOUNCE) => break,
+                }
+            }
+        }
+    }
+}
+
+fn task_to_todo(task: &Task) -> Option<IcsComponent> {
+    let due = task.due_date?;
+    let mut todo = IcsComponent::new(ComponentKind::Todo, format!("{}{}", task.id, UID_DOMAIN), task.title.clone());
+    todo.description = task.description.clone().filter(|description| !description.is_empty());
+    todo.due = Some(IcsTime::DateTime(due));
+    // DTSTART must not come after DUE
+    todo.start = task.scheduled_date.filter(|scheduled| *scheduled <= due).map(IcsTime::DateTime);
+    todo.completed = task.completed_at;
+    todo.status = Some(match task.status {
+        TaskStatus::InProgress => "IN-PROCESS",
+        TaskStatus::Done => "COMPLETED",
+        TaskStatus::Cancelled => "CANCELLED",
+        _ => "NEEDS-ACTION",
+    }.to_string());
+    todo.priority = Some(match task.priority {
+        TaskPriority::Urgent => 1,
+        TaskPriority::High => 3,
+        TaskPriority::Medium => 5,
+        TaskPriority::Low => 9,
+    });
+    todo.categories = serde_json::from_str(&task.tags).unwrap_or_default();
+    todo.rrule = task.recurrence.as_ref().map(Recurrence::to_string);
+    todo.created = Some(task.created_at);
+    todo.last_modified = Some(task.updated_at);
+    Some(todo)
+}
+
+/// Running timers are left out until they stop
+fn time_entry_to_event(entry: &TimeEntry, task_title: Option<&str>) -> Option<IcsComponent> {
+    let end = entry.end_time?;
+    let mut event = IcsComponent::new(
+        ComponentKind::Event,
+        format!("{}{}", entry.id, UID_DOMAIN),
+        task_title.unwrap_or("Time entry").to_string(),
+    );
+    event.description = entry.description.clone().filter(|description| !description.is_empty());
+    event.start = Some(IcsTime::DateTime(entry.start_time));
+    event.end = Some(IcsTime::DateTime(end));
+    event.created = Some(entry.created_at);
+    Some(event)
+}
+
+fn block_to_event(block: &Block) -> Option<IcsComponent> {
+    let (date, time) = block_task::scheduled(&block.content)?;
+    let summary = match block_task::parse(&block.content) {
+        Some(task) => task.title,
+        None => block.content.lines().next().unwrap_or_default().trim().to_string(),
+    };
+    let description: Vec<&str> = block.content
+        .lines()
+        .skip(1)
+        .filter(|line| !line.trim_start().starts_with("SCHEDULED:") && !line.trim_start().starts_with("DEADLINE:"))
+        .collect();
+
+    let mut event = IcsComponent::new(ComponentKind::Event, format!("{}{}", block.id, UID_DOMAIN), summary);
+    event.description = Some(description.join("\n").trim().to_string()).filter(|description| !description.is_empty());
+    match time {
+        Some(time) => event.start = Some(IcsTime::Floating(date.and_time(time))),
+        None => {
+            // All-day events end the next day
+            event.start = Some(IcsTime::Date(date));
+            event.end = date.succ_opt().map(IcsTime::Date);
+        }
+    }
+    event.created = Some(block.created_at);
+    event.last_modified = Some(block.updated_at);
+    Some(event)
+}
+
+fn todo_status(status: &str) -> TaskStatus {
+    match status.to_ascii_uppercase().as_str() {
+        "IN-PROCESS" => TaskStatus::InProgress,
+        "COMPLETED" => TaskStatus::Done,
+        "CANCELLED" => TaskStatus::Cancelled,
+        _ => TaskStatus::Todo,
+    }
+}
+
+/// 1 is the highest priority and 9 the lowest; 0 means none
+fn task_priority(priority: u8) -> Option<TaskPriority> {
+    match priority {
+        1 => Some(TaskPriority::Urgent),
+        2..=4 => Some(TaskPriority::High),
+        5 => Some(TaskPriority::Medium),
+        6..=9 => Some(TaskPriority::Low),
+        _ => None,
+    }
+}
+
+fn non_empty(summary: &str) -> String {
+    match summary.trim() {
+        "" => "Untitled".to_string(),
+        summary => summary.to_string(),
+    }
+}
+
+/// Write a calendar with CRLF line endings and lines folded at 75 octets
+pub fn render_calendar(components: &[IcsComponent], stamp: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//MingLog//MingLog Desktop//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "X-WR-CALNAME:MingLog".to_string(),
+    ];
+    for component in components {
+        let name = match component.kind {
+            ComponentKind::Todo => "VTODO",
+            ComponentKind::Event => "VEVENT",
+        };
+        lines.push(format!("BEGIN:{}", name));
+        lines.push(format!("UID:{}", escape(&component.uid)));
+        lines.push(format!("DTSTAMP:{}", format_utc(stamp)));
+        lines.push(format!("SUMMARY:{}", escape(&component.summary)));
+        if let Some(description) = &component.description {
+            lines.push(format!("DESCRIPTION:{}", escape(description)));
+        }
+        for (property, time) in [("DTSTART", component.start), ("DTEND", component.end), ("DUE", component.due)] {
+            match time {
+                Some(IcsTime::Date(date)) => lines.push(format!("{};VALUE=DATE:{}", property, date.format("%Y%m%d"))),
+                Some(IcsTime::DateTime(time)) => lines.push(format!("{}:{}", property, format_utc(time))),
+                Some(IcsTime::Floating(time)) => lines.push(format!("{}:{}", property, time.format("%Y%m%dT%H%M%S"))),
+                None => {}
+            }
+        }
+        for (property, time) in [
+            ("COMPLETED", component.completed),
+            ("CREATED", component.created),
+            ("LAST-MODIFIED", component.last_modified),
+        ] {
+            if let Some(time) = time {
+                lines.push(format!("{}:{}", property, format_utc(time)));
+            }
+        }
+        if let Some(status) = &component.status {
+            lines.push(format!("STATUS:{}", status));
+        }
+        if let Some(priority) = component.priority {
+            lines.push(format!("PRIORITY:{}", priority));
+        }
+        if !component.categories.is_empty() {
+            let categories: Vec<String> = component.categories.iter().map(|category| escape(category)).collect();
+            lines.push(format!("CATEGORIES:{}", categories.join(",")));
+        }
+        if let Some(rrule) = &component.rrule {
+            lines.push(format!("RRULE:{}", rrule));
+        }
+        lines.push(format!("END:{}", name));
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut out = String::new();
+    for line in lines {
+        fold(&mut out, &line);
+    }
+    out
+}
+
+/// Read the to-dos and events of a calendar; other components are skipped
+pub fn parse_calendar(text: &str) -> Result<Vec<IcsComponent>> {
+    let mut components = Vec::new();
+    let mut current: Option<IcsComponent> = None;
+    // Depth of components nested in the current one, like VALARM
+    let mut nested = 0usize;
+    let mut in_calendar = false;
+
+    for line in unfold(text) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let ContentLine { name, params, value } = split_property(&line)
+            .ok_or_else(|| anyhow!("Invalid calendar line '{}'", line))?;
+
+        match (name.as_str(), current.as_mut()) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VCALENDAR") => in_calendar = true,
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VTODO") || value.eq_ignore_ascii_case("VEVENT") => {
+                let kind = if value.eq_ignore_ascii_case("VTODO") { ComponentKind::Todo } else { ComponentKind::Event };
+                current = Some(IcsComponent::new(kind, String::new(), String::new()));
+            }
+            ("BEGIN", _) => nested += 1,
+            ("END", _) if nested > 0 => nested -= 1,
+            ("END", Some(_)) => components.extend(current.take()),
+            ("END", None) => in_calendar = false,
+            (_, Some(component)) if nested == 0 => read_property(component, &name, &params, &value)?,
+            _ => {}
+        }
+    }
+    if current.is_some() || nested > 0 {
+        return Err(anyhow!("Calendar ends inside a component"));
+    }
+    if in_calendar {
+        return Err(anyhow!("Calendar is missing END:VCALENDAR"));
+    }
+
+    Ok(components)
+}
+
+fn read_property(component: &mut IcsComponent, name: &str, params: &[(String, String)], value: &str) -> Result<()> {
+    let time = || parse_time(params, value).ok_or_else(|| anyhow!("Invalid {} '{}'", name, value));
+    match name {
+        "UID" => component.uid = unescape(value),
+        "SUMMARY" => component.summary = unescape(value),
+        "DESCRIPTION" => component.description = Some(unescape(value)),
+        "DTSTART" => component.start = Some(time()?),
+        "DTEND" => component.end = Some(time()?),
+        "DUE" => component.due = Some(time()?),
+        "COMPLETED" => component.completed = Some(time()?.to_utc()),
+        "CREATED" => component.created = Some(time()?.to_utc()),
+        "LAST-MODIFIED" => component.last_modified = Some(time()?.to_utc()),
+        "STATUS" => component.status = Some(value.trim().to_string()),
+        "PRIORITY" => component.priority = value.trim().parse().ok(),
+        "CATEGORIES" => component.categories.extend(
+            split_unescaped(value, ',').iter().map(|category| unescape(category)).filter(|category| !category.is_empty()),
+        ),
+        "RRULE" => component.rrule = Some(value.trim().to_string()),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// `20261020`, `20261020T100000Z`, or a floating or `TZID` time read as local time
+fn parse_time(params: &[(String, String)], value: &str) -> Option<IcsTime> {
+    let value = value.trim();
+    let is_date = params.iter().any(|(name, param)| name == "VALUE" && param.eq_ignore_ascii_case("DATE"));
+    if is_date || value.len() == 8 {
+        return NaiveDate::parse_from_str(value, "%Y%m%d").ok().map(IcsTime::Date);
+    }
+    match value.strip_suffix('Z') {
+        Some(utc) => NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()
+            .map(|time| IcsTime::DateTime(Utc.from_utc_datetime(&time))),
+        None => NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok().map(IcsTime::Floating),
+    }
+}
+
+fn format_utc(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+struct ContentLine {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+/// Split `NAME;PARAM=value:VALUE`, where quoted parameter values may hold `:`
+fn split_property(line: &str) -> Option<ContentLine> {
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+
+    let mut parts = head.split(';');
+    let name = parts.next()?.trim().to_ascii_uppercase();
+    if name.is_empty() {
+        return None;
+    }
+    let params = parts
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, value)| (key.trim().to_ascii_uppercase(), value.trim_matches('"').to_string()))
+        .collect();
+    Some(ContentLine { name, params, value: value.to_string() })
+}
+
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Append a content line, folding it so no line is longer than 75 octets
+fn fold(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Split on `separator` where it isn't escaped
+fn split_unescaped(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == separator => {
+                parts.push(&text[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn at(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_calendar_round_trip() {
+        let mut todo = IcsComponent::new(ComponentKind::Todo, "a@minglog".to_string(), "Pay rent, water; gas".to_string());
+        todo.description = Some(format!("Line one\nback\\slash {}", "long ".repeat(20)));
+        todo.due = Some(IcsTime::DateTime(at("2026-10-31T17:00:00Z")));
+        todo.status = Some("NEEDS-ACTION".to_string());
+        todo.priority = Some(3);
+        todo.categories = vec!["home".to_string(), "bills, monthly".to_string()];
+        todo.rrule = Some("FREQ=MONTHLY".to_string());
+        let mut event = IcsComponent::new(ComponentKind::Event, "b@minglog".to_string(), "Offsite".to_string());
+        event.start = Some(IcsTime::Date(NaiveDate::from_ymd_opt(2026, 10, 20).unwrap()));
+        event.end = Some(IcsTime::Date(NaiveDate::from_ymd_opt(2026, 10, 21).unwrap()));
+
+        let calendar = render_calendar(&[todo.clone(), event.clone()], at("2026-10-18T00:00:00Z"));
+        assert!(calendar.lines().all(|line| line.len() <= 76), "{}", calendar);
+        assert!(calendar.contains("SUMMARY:Pay rent\\, water\\; gas\r\n"));
+        assert!(calendar.contains("DTSTART;VALUE=DATE:20261020\r\n"));
+        assert_eq!(parse_calendar(&calendar).unwrap(), vec![todo, event]);
+    }
+
+    #[test]
+    fn test_parse_foreign_calendar() {
+        let calendar = "BEGIN:VCALENDAR\nVERSION:2.0\nBEGIN:VTIMEZONE\nTZID:Europe/Berlin\nEND:VTIMEZONE\n\
+            BEGIN:VEVENT\nUID:123\nSUMMARY:Dentist\nDTSTART;TZID=\"Europe/Berlin\":20261022T083000\n\
+            DESCRIPTION:bring\n  card\nBEGIN:VALARM\nACTION:DISPLAY\nDESCRIPTION:Reminder\nEND:VALARM\nEND:VEVENT\n\
+            BEGIN:VTODO\nUID:456\nSUMMARY:Call back\nDUE;VALUE=DATE:20261023\nSTATUS:COMPLETED\nPRIORITY:1\nEND:VTODO\n\
+            END:VCALENDAR\n";
+        let components = parse_calendar(calendar).unwrap();
+        assert_eq!(components.len(), 2);
+        assert_eq!(components[0].description.as_deref(), Some("bring card"));
+        let local = NaiveDate::from_ymd_opt(2026, 10, 22).unwrap().and_hms_opt(8, 30, 0).unwrap();
+        assert_eq!(components[0].start, Some(IcsTime::Floating(local)));
+        assert_eq!(components[1].due.map(IcsTime::date), NaiveDate::from_ymd_opt(2026, 10, 23));
+        assert_eq!(components[1].priority.and_then(task_priority), Some(TaskPriority::Urgent));
+        assert!(parse_calendar("BEGIN:VCALENDAR\nBEGIN:VEVENT\nSUMMARY:x\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import_ics() {
+        let dir = tempdir().unwrap();
+        let db = Database::new_with_path(dir.path().join("ics.db").to_str().unwrap()).await.unwrap();
+        let source = dir.path().join("import.ics");
+        let mut todo = IcsComponent::new(ComponentKind::Todo, "todo-1@example.com".to_string(), "Renew passport".to_string());
+        todo.due = Some(IcsTime::DateTime(at("2026-11-01T12:00:00Z")));
+        todo.status = Some("IN-PROCESS".to_string());
+        let mut event = IcsComponent::new(ComponentKind::Event, "event-1@example.com".to_string(), "Standup".to_string());
+        let standup = NaiveDate::from_ymd_opt(2026, 10, 20).unwrap().and_hms_opt(9, 15, 0).unwrap();
+        event.start = Some(IcsTime::Floating(standup));
+        let mut broken = IcsComponent::new(ComponentKind::Event, "event-2@example.com".to_string(), "No start".to_string());
+        broken.description = Some("lost".to_string());
+        fs::write(&source, render_calendar(&[todo, event, broken], Utc::now())).unwrap();
+
+        let result = FileOperations::import_ics(&db, &source, "default").await.unwrap();
+        assert_eq!((result.tasks_imported, result.blocks_imported, result.pages_created), (1, 1, 1));
+        assert_eq!(result.errors.len(), 1, "{:?}", result.errors);
+        // Importing the same file again skips what came from it
+        let again = FileOperations::import_ics(&db, &source, "default").await.unwrap();
+        assert_eq!((again.tasks_imported, again.blocks_imported, again.skipped), (0, 0, 2));
+
+        let task = db.get_tasks(None).await.unwrap().remove(0);
+        assert_eq!((task.title.as_str(), task.status), ("Renew passport", TaskStatus::InProgress));
+        let page = db.get_journal_page("default", "2026-10-20").await.unwrap().unwrap();
+        assert_eq!(page.name, "Oct 20th, 2026");
+        let blocks = db.get_blocks_by_page(&page.id).await.unwrap();
+        assert_eq!(blocks[0].content, "Standup\nSCHEDULED: <2026-10-20 Tue 09:15>");
+
+        // Exporting writes both back, and importing that file again adds nothing
+        let exported = dir.path().join("feed").join("calendar.ics");
+        FileOperations::export_ics(&db, &exported).await.unwrap();
+        let components = parse_calendar(&fs::read_to_string(&exported).unwrap()).unwrap();
+        let summaries: Vec<(ComponentKind, &str)> = components.iter().map(|c| (c.kind, c.summary.as_str())).collect();
+        assert_eq!(summaries, [(ComponentKind::Todo, "Renew passport"), (ComponentKind::Event, "Standup")]);
+        // SCHEDULED times are local, so they are written as floating times
+        assert_eq!(components[1].start, Some(IcsTime::Floating(standup)));
+        assert!(fs::read_to_string(&exported).unwrap().contains("DTSTART:20261020T091500\r\n"));
+        assert!(!dir.path().join("feed").join("calendar.ics.minglog-tmp").exists());
+
+        let again = FileOperations::import_ics(&db, &exported, "default").await.unwrap();
+        assert_eq!((again.tasks_imported, again.blocks_imported, again.skipped), (0, 0, 2));
+    }
+
+    #[tokio::test]
+    async fn test_calendar_feed_refresh() {
+        let dir = tempdir().unwrap();
+        let db = Database::new_with_path(dir.path().join("feed.db").to_str().unwrap()).await.unwrap();
+        let path = dir.path().join("calendar.ics");
+        let feed = CalendarFeed::with_path(Arc::new(Mutex::new(db)), Some(path.clone()));
+
+        // Nothing is written until the feed was created
+        assert!(!feed.refresh().await.unwrap());
+        assert!(!path.exists());
+
+        fs::write(&path, "").unwrap();
+        assert!(feed.refresh().await.unwrap());
+        assert!(fs::read_to_string(&path).unwrap().starts_with("BEGIN:VCALENDAR\r\n"));
+    }
+}
@@ -0,0 +1,224 @@
+//! Common interface for the import formats.
+//!
+//! Each importer knows how to recognise its source (a file or a folder) and how to
+//! turn it into pages and blocks of a graph. Commands pick an importer by id, or
+//! let `detect` choose one from the path.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::{FileOperations, ImportResult};
+use crate::database::Database;
+
+/// Where imported content goes
+#[derive(Debug, Clone)]
+pub struct ImportContext {
+    pub graph_id: String,
+    /// Managed directory that attachments are copied into
+    pub assets_dir: PathBuf,
+}
+
+#[async_trait]
+pub trait Importer: Send + Sync {
+    /// Stable identifier used by the frontend, e.g. `"roam"`
+    fn id(&self) -> &'static str;
+
+    /// Whether `path` looks like something this importer reads
+    fn can_import(&self, path: &Path) -> bool;
+
+    async fn import(&self, db: &Database, path: &Path, context: &ImportContext) -> Result<ImportResult>;
+}
+
+/// A single Markdown file with optional YAML frontmatter
+pub struct MarkdownImporter;
+
+/// A Logseq graph folder
+pub struct LogseqImporter;
+
+/// An Obsidian vault
+pub struct ObsidianImporter;
+
+/// An OPML outline
+pub struct OpmlImporter;
+
+/// A Roam Research JSON export
+pub struct RoamImporter;
+
+/// A Notion "Markdown & CSV" export zip
+pub struct NotionImporter;
+
+#[async_trait]
+impl Importer for MarkdownImporter {
+    fn id(&self) -> &'static str {
+        "markdown"
+    }
+
+    fn can_import(&self, path: &Path) -> bool {
+        path.is_file() && has_extension(path, "md")
+    }
+
+    async fn import(&self, db: &Database, path: &Path, context: &ImportContext) -> Result<ImportResult> {
+        FileOperations::import_markdown_file(db, path, &context.graph_id).await
+    }
+}
+
+#[async_trait]
+impl Importer for LogseqImporter {
+    fn id(&self) -> &'static str {
+        "logseq"
+    }
+
+    fn can_import(&self, path: &Path) -> bool {
+        path.join("logseq").join("config.edn").is_file()
+    }
+
+    async fn import(&self, db: &Database, path: &Path, context: &ImportContext) -> Result<ImportResult> {
+        FileOperations::import_logseq_graph(db, path, &context.graph_id, &context.assets_dir).await
+    }
+}
+
+#[async_trait]
+impl Importer for ObsidianImporter {
+    fn id(&self) -> &'static str {
+        "obsidian"
+    }
+
+    fn can_import(&self, path: &Path) -> bool {
+        path.join(".obsidian").is_dir()
+    }
+
+    async fn import(&self, db: &Database, path: &Path, context: &ImportContext) -> Result<ImportResult> {
+        FileOperations::import_obsidian_vault(db, path, &context.graph_id, &context.assets_dir).await
+    }
+}
+
+#[async_trait]
+impl Importer for OpmlImporter {
+    fn id(&self) -> &'static str {
+        "opml"
+    }
+
+    fn can_import(&self, path: &Path) -> bool {
+        path.is_file() && has_extension(path, "opml")
+    }
+
+    async fn import(&self, db: &Database, path: &Path, context: &ImportContext) -> Result<ImportResult> {
+        FileOperations::import_opml(db, path, &context.graph_id).await
+    }
+}
+
+#[async_trait]
+impl Importer for RoamImporter {
+    fn id(&self) -> &'static str {
+        "roam"
+    }
+
+    fn can_import(&self, path: &Path) -> bool {
+        path.is_file() && has_extension(path, "json")
+    }
+
+    async fn import(&self, db: &Database, path: &Path, context: &ImportContext) -> Result<ImportResult> {
+        FileOperations::import_roam_json(db, path, &context.graph_id).await
+    }
+}
+
+#[async_trait]
+impl Importer for NotionImporter {
+    fn id(&self) -> &'static str {
+        "notion"
+    }
+
+    fn can_import(&self, path: &Path) -> bool {
+        path.is_file() && has_extension(path, "zip")
+    }
+
+    async fn import(&self, db: &Database, path: &Path, context: &ImportContext) -> Result<ImportResult> {
+        FileOperations::import_notion_export(db, path, &context.graph_id, &context.assets_dir).await
+    }
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map_or(false, |ext| ext.eq_ignore_ascii_case(extension))
+}
+
+/// Link to an attachment copied into the assets directory, served through Tauri's
+/// asset protocol the way `convertFileSrc` builds it on the frontend
+pub fn asset_url(path: &Path) -> String {
+    let mut encoded = String::new();
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    if cfg!(windows) {
+        format!("https://asset.localhost/{}", encoded)
+    } else {
+        format!("asset://localhost/{}", encoded)
+    }
+}
+
+/// All importers, most specific first
+pub fn importers() -> Vec<Box<dyn Importer>> {
+    vec![
+        Box::new(LogseqImporter),
+        Box::new(ObsidianImporter),
+        Box::new(RoamImporter),
+        Box::new(NotionImporter),
+        Box::new(OpmlImporter),
+        Box::new(MarkdownImporter),
+    ]
+}
+
+/// Look an importer up by its id
+pub fn find_importer(id: &str) -> Option<Box<dyn Importer>> {
+    importers().into_iter().find(|importer| importer.id() == id)
+}
+
+/// Pick the first importer that recognises `path`
+pub fn detect_importer(path: &Path) -> Option<Box<dyn Importer>> {
+    importers().into_iter().find(|importer| importer.can_import(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_detect_importer() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("graph/logseq")).unwrap();
+        std::fs::write(root.join("graph/logseq/config.edn"), "{}").unwrap();
+        std::fs::create_dir_all(root.join("vault/.obsidian")).unwrap();
+        for file in ["roam.json", "notion.zip", "outline.opml", "note.md", "other.txt"] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+
+        let detected = |name: &str| detect_importer(&root.join(name)).map(|importer| importer.id());
+        assert_eq!(detected("graph"), Some("logseq"));
+        assert_eq!(detected("vault"), Some("obsidian"));
+        assert_eq!(detected("roam.json"), Some("roam"));
+        assert_eq!(detected("notion.zip"), Some("notion"));
+        assert_eq!(detected("outline.opml"), Some("opml"));
+        assert_eq!(detected("note.md"), Some("markdown"));
+        assert_eq!(detected("other.txt"), None);
+        assert!(find_importer("notion").is_some());
+    }
+
+    #[test]
+    fn test_asset_url() {
+        let url = asset_url(Path::new("/data/assets/default/Chart (1).png"));
+        let expected = "%2Fdata%2Fassets%2Fdefault%2FChart%20%281%29.png";
+        if cfg!(windows) {
+            assert_eq!(url, format!("https://asset.localhost/{}", expected));
+        } else {
+            assert_eq!(url, format!("asset://localhost/{}", expected));
+        }
+    }
+}
@@ -0,0 +1,406 @@
+//! Importer for Logseq graph folders.
+//!
+//! Walks `pages/` and `journals/`, reads journal settings from `logseq/config.edn`
+//! and copies `assets/`. Page-level `key:: value` lines at the top of a file become
+//! page fields; everything else goes through the outline codec, so block
+//! properties such as `id::` are kept and `((uuid))` references still resolve.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use chrono::{Datelike, NaiveDate};
+
+use super::outline;
+use super::{FileOperations, ImportResult};
+use crate::database::Database;
+use crate::models::CreatePageRequest;
+
+/// Journal settings read from `logseq/config.edn`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogseqConfig {
+    pub journal_file_format: String,
+    pub journal_title_format: String,
+}
+
+impl Default for LogseqConfig {
+    fn default() -> Self {
+        Self {
+            journal_file_format: "yyyy_MM_dd".to_string(),
+            journal_title_format: "MMM do, yyyy".to_string(),
+        }
+    }
+}
+
+impl LogseqConfig {
+    /// Pick the journal formats out of `config.edn`, ignoring everything else
+    pub fn parse(edn: &str) -> Self {
+        let mut config = Self::default();
+        for line in edn.lines() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if let Some(value) = edn_string(line, ":journal/file-name-format") {
+                config.journal_file_format = value;
+            }
+            if let Some(value) = edn_string(line, ":journal/page-title-format") {
+                config.journal_title_format = value;
+            }
+        }
+        config
+    }
+}
+
+fn edn_string(line: &str, key: &str) -> Option<String> {
+    let rest = line.strip_prefix('{').unwrap_or(line).trim_start().strip_prefix(key)?;
+    let rest = rest.trim_start().strip_prefix('"')?;
+    rest.split_once('"').map(|(value, _)| value.to_string())
+}
+
+#[allow(dead_code)]
+impl FileOperations {
+    /// Import a Logseq graph folder, copying its assets into `assets_dir`
+    pub async fn import_logseq_graph(
+        db: &Database,
+        graph_dir: &Path,
+        graph_id: &str,
+        assets_dir: &Path,
+    ) -> Result<ImportResult> {
+        let pages_dir = graph_dir.join("pages");
+        let journals_dir = graph_dir.join("journals");
+        if !pages_dir.is_dir() && !journals_dir.is_dir() {
+            return Err(anyhow!("Not a Logseq graph: {}", graph_dir.display()));
+        }
+
+        let config = match fs::read_to_string(graph_dir.join("logseq").join("config.edn")) {
+            Ok(edn) => LogseqConfig::parse(&edn),
+            Err(_) => LogseqConfig::default(),
+        };
+
+        let mut result = ImportResult {
+            pages_imported: 0,
+            blocks_imported: 0,
+            errors: Vec::new(),
+        };
+
+        for (dir, is_journal) in [(pages_dir, false), (journals_dir, true)] {
+            let mut files: Vec<_> = match fs::read_dir(&dir) {
+                Ok(entries) => entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect(),
+                Err(_) => continue,
+            };
+            files.sort();
+
+            for path in files {
+                let relative = path.strip_prefix(graph_dir).unwrap_or(&path).display().to_string();
+                let file_name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+                if !path.is_file() || file_name.starts_with('.') {
+                    continue;
+                }
+                if path.extension().and_then(|ext| ext.to_str()) != Some("md") {
+                    result.errors.push(format!("{}: only Markdown pages are supported", relative));
+                    continue;
+                }
+
+                match Self::import_logseq_page(db, &path, graph_id, is_journal, &config).await {
+                    Ok(page_result) => {
+                        result.pages_imported += page_result.pages_imported;
+                        result.blocks_imported += page_result.blocks_imported;
+                        result.errors.extend(page_result.errors.into_iter().map(|e| format!("{}: {}", relative, e)));
+                    }
+                    Err(e) => result.errors.push(format!("{}: {}", relative, e)),
+                }
+            }
+        }
+
+        let assets = graph_dir.join("assets");
+        if assets.is_dir() {
+            if let Err(e) = copy_dir(&assets, assets_dir) {
+                result.errors.push(format!("assets: {}", e));
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Import one page or journal file of a Logseq graph
+    pub async fn import_logseq_page(
+        db: &Database,
+        file_path: &Path,
+        graph_id: &str,
+        is_journal: bool,
+        config: &LogseqConfig,
+    ) -> Result<ImportResult> {
+        let content = fs::read_to_string(file_path)?;
+        let (properties, body) = split_page_properties(&content);
+        let stem = file_path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+
+        let mut title = None;
+        let mut aliases = Vec::new();
+        let mut tags = Vec::new();
+        let mut page_properties = Vec::new();
+        for (key, value) in properties {
+            match key.to_lowercase().as_str() {
+                "title" => title = Some(value),
+                "alias" => aliases.extend(page_refs(&value)),
+                "tags" => tags.extend(page_refs(&value)),
+                _ => page_properties.push((key, value)),
+            }
+        }
+
+        let journal_date = if is_journal { parse_journal_file_name(&stem, &config.journal_file_format) } else { None };
+        let name = match (&title, journal_date) {
+            (Some(title), _) => title.clone(),
+            (None, Some(date)) => format_logseq_date(&config.journal_title_format, date),
+            (None, None) => decode_page_file_name(&stem),
+        };
+
+        let page_request = CreatePageRequest {
+            graph_id: graph_id.to_string(),
+            name,
+            title,
+            properties: outline::properties_to_json(&page_properties),
+            tags: Some(serde_json::to_string(&tags)?),
+            is_journal: Some(journal_date.is_some()),
+            journal_date: journal_date.map(|date| date.format("%Y-%m-%d").to_string()),
+        };
+
+        let mut result = ImportResult {
+            pages_imported: 0,
+            blocks_imported: 0,
+            errors: Vec::new(),
+        };
+        if let Some(page) = Self::import_outline_page(db, page_request, body, &mut result).await {
+            for alias in aliases {
+                if let Err(e) = db.add_page_alias(&page.id, &alias).await {
+                    result.errors.push(format!("Failed to add alias '{}': {}", alias, e));
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// Split leading `key:: value` lines (Logseq page properties) from the outline
+fn split_page_properties(content: &str) -> (Vec<(String, String)>, &str) {
+    let mut properties = Vec::new();
+    let mut rest = content.trim_start_matches('\u{feff}');
+    loop {
+        let (line, next) = rest.split_once('\n').unwrap_or((rest, ""));
+        match outline::property_line(line.trim_end_matches('\r')) {
+            Some((key, value)) => properties.push((key.to_string(), value.trim().to_string())),
+            None => break,
+        }
+        rest = next;
+    }
+    (properties, rest)
+}
+
+/// Page names in a comma separated property value, e.g. `[[Project A]], research, #todo`
+fn page_refs(value: &str) -> Vec<String> {
+    let mut refs = Vec::new();
+    let mut rest = value;
+    while !rest.trim().is_empty() {
+        let trimmed = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+        let (name, next) = if let Some(inner) = trimmed.strip_prefix("[[") {
+            match inner.split_once("]]") {
+                Some((name, next)) => (name, next),
+                None => (inner, ""),
+            }
+        } else {
+            trimmed.split_once(',').unwrap_or((trimmed, ""))
+        };
+        let name = name.trim().trim_start_matches('#').trim();
+        if !name.is_empty() && !refs.iter().any(|r: &String| r == name) {
+            refs.push(name.to_string());
+        }
+        rest = next;
+    }
+    refs
+}
+
+/// Undo Logseq's file name escaping: `___` is a namespace separator and `%XX` is percent-encoded
+fn decode_page_file_name(stem: &str) -> String {
+    super::percent_decode(&stem.replace("___", "/"))
+}
+
+/// Parse a journal file name like `2024_01_15` using the configured format
+fn parse_journal_file_name(stem: &str, format: &str) -> Option<NaiveDate> {
+    let mut pattern = String::new();
+    for token in date_tokens(format) {
+        match token {
+            DateToken::Year => pattern.push_str("%Y"),
+            DateToken::Month | DateToken::MonthPadded => pattern.push_str("%m"),
+            DateToken::Day | DateToken::DayPadded => pattern.push_str("%d"),
+            DateToken::Literal('%') => pattern.push_str("%%"),
+            DateToken::Literal(c) => pattern.push(c),
+            _ => return None,
+        }
+    }
+    NaiveDate::parse_from_str(stem, &pattern).ok()
+}
+
+/// Render a date with a Logseq (date-fns style) format such as `MMM do, yyyy`
+pub(super) fn format_logseq_date(format: &str, date: NaiveDate) -> String {
+    let mut out = String::new();
+    for token in date_tokens(format) {
+        match token {
+            DateToken::Year => out.push_str(&date.format("%Y").to_string()),
+            DateToken::ShortYear => out.push_str(&date.format("%y").to_string()),
+            DateToken::MonthName => out.push_str(&date.format("%B").to_string()),
+            DateToken::MonthShort => out.push_str(&date.format("%b").to_string()),
+            DateToken::MonthPadded => out.push_str(&date.format("%m").to_string()),
+            DateToken::Month => out.push_str(&date.month().to_string()),
+            DateToken::DayPadded => out.push_str(&date.format("%d").to_string()),
+            DateToken::Day => out.push_str(&date.day().to_string()),
+            DateToken::DayOrdinal => {
+                let day = date.day();
+                let suffix = match (day % 10, day % 100) {
+                    (_, 11..=13) => "th",
+                    (1, _) => "st",
+                    (2, _) => "nd",
+                    (3, _) => "rd",
+                    _ => "th",
+                };
+                out.push_str(&format!("{}{}", day, suffix));
+            }
+            DateToken::WeekdayName => out.push_str(&date.format("%A").to_string()),
+            DateToken::WeekdayShort => out.push_str(&date.format("%a").to_string()),
+            DateToken::Literal(c) => out.push(c),
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DateToken {
+    Year,
+    ShortYear,
+    MonthName,
+    MonthShort,
+    MonthPadded,
+    Month,
+    DayOrdinal,
+    DayPadded,
+    Day,
+    WeekdayName,
+    WeekdayShort,
+    Literal(char),
+}
+
+fn date_tokens(format: &str) -> Vec<DateToken> {
+    const TOKENS: [(&str, DateToken); 11] = [
+        ("yyyy", DateToken::Year),
+        ("EEEE", DateToken::WeekdayName),
+        ("MMMM", DateToken::MonthName),
+        ("EEE", DateToken::WeekdayShort),
+        ("MMM", DateToken::MonthShort),
+        ("yy", DateToken::ShortYear),
+        ("MM", DateToken::MonthPadded),
+        ("dd", DateToken::DayPadded),
+        ("do", DateToken::DayOrdinal),
+        ("M", DateToken::Month),
+        ("d", DateToken::Day),
+    ];
+
+    let mut tokens = Vec::new();
+    let mut rest = format;
+    'outer: while let Some(c) = rest.chars().next() {
+        for (pattern, token) in TOKENS {
+            if let Some(next) = rest.strip_prefix(pattern) {
+                tokens.push(token);
+                rest = next;
+                continue 'outer;
+            }
+        }
+        tokens.push(DateToken::Literal(c));
+        rest = &rest[c.len_utf8()..];
+    }
+    tokens
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_journal_names_and_config() {
+        let config = LogseqConfig::parse("{:meta/version 1\n ;; :journal/page-title-format \"ignored\"\n :journal/page-title-format \"EEE, MM/dd/yyyy\"\n :journal/file-name-format \"yyyy-MM-dd\"}");
+        assert_eq!(config.journal_file_format, "yyyy-MM-dd");
+        assert_eq!(config.journal_title_format, "EEE, MM/dd/yyyy");
+
+        let date = parse_journal_file_name("2024_03_02", &LogseqConfig::default().journal_file_format).unwrap();
+        assert_eq!(date, NaiveDate::from_ymd_opt(2024, 3, 2).unwrap());
+        assert_eq!(format_logseq_date("MMM do, yyyy", date), "Mar 2nd, 2024");
+        assert_eq!(format_logseq_date(&config.journal_title_format, date), "Sat, 03/02/2024");
+        assert!(parse_journal_file_name("notes", "yyyy_MM_dd").is_none());
+    }
+
+    #[test]
+    fn test_page_properties_and_refs() {
+        let (properties, body) = split_page_properties("title:: Project/Alpha\nalias:: [[PA]], alpha\ntags:: [[Big Idea]], #work\n\n- first\n");
+        assert_eq!(properties.len(), 3);
+        assert_eq!(body, "\n- first\n");
+        assert_eq!(page_refs(&properties[1].1), vec!["PA", "alpha"]);
+        assert_eq!(page_refs(&properties[2].1), vec!["Big Idea", "work"]);
+        assert_eq!(decode_page_file_name("Project___Alpha%3F"), "Project/Alpha?");
+    }
+
+    #[tokio::test]
+    async fn test_import_logseq_graph() {
+        let graph = tempdir().unwrap();
+        let root = graph.path();
+        fs::create_dir_all(root.join("pages")).unwrap();
+        fs::create_dir_all(root.join("journals")).unwrap();
+        fs::create_dir_all(root.join("logseq")).unwrap();
+        fs::create_dir_all(root.join("assets")).unwrap();
+        fs::write(root.join("logseq/config.edn"), "{:journal/page-title-format \"yyyy-MM-dd\"}").unwrap();
+        fs::write(root.join("assets/diagram.png"), [0u8, 1, 2]).unwrap();
+        fs::write(
+            root.join("pages/Project___Alpha.md"),
+            "alias:: PA, [[Alpha Project]]\ntags:: [[Work]], planning\nstatus:: active\n\n- Goals\n  id:: 6650a1b2-0000-4000-8000-000000000001\n\t- Ship **v1** ![diagram](../assets/diagram.png)\n- See ((6650a1b2-0000-4000-8000-000000000001))\n",
+        ).unwrap();
+        fs::write(root.join("journals/2024_01_15.md"), "- Met with [[Project/Alpha]]\n").unwrap();
+        fs::write(root.join("pages/notes.org"), "* org page").unwrap();
+
+        let data = tempdir().unwrap();
+        let db = Database::new_with_path(data.path().join("logseq.db").to_str().unwrap()).await.unwrap();
+        let assets_dir = data.path().join("assets");
+        let result = FileOperations::import_logseq_graph(&db, root, "default", &assets_dir).await.unwrap();
+
+        assert_eq!(result.pages_imported, 2);
+        assert_eq!(result.blocks_imported, 4);
+        assert_eq!(result.errors, vec!["pages/notes.org: only Markdown pages are supported".to_string()]);
+        assert!(assets_dir.join("diagram.png").exists());
+
+        let pages = db.get_all_pages().await.unwrap();
+        let project = pages.iter().find(|page| page.name == "Project/Alpha").unwrap();
+        assert_eq!(project.tags, r#"["Work","planning"]"#);
+        assert_eq!(project.properties.as_deref(), Some(r#"{"status":"active"}"#));
+        assert_eq!(db.get_page_aliases(&project.id).await.unwrap(), vec!["PA", "Alpha Project"]);
+
+        let journal = pages.iter().find(|page| page.is_journal).unwrap();
+        assert_eq!(journal.name, "2024-01-15");
+        assert_eq!(journal.journal_date.as_deref(), Some("2024-01-15"));
+
+        let blocks = db.get_blocks_by_page(&project.id).await.unwrap();
+        let goals = blocks.iter().find(|block| block.content == "Goals").unwrap();
+        assert_eq!(goals.properties.as_deref(), Some(r#"{"id":"6650a1b2-0000-4000-8000-000000000001"}"#));
+        let child = blocks.iter().find(|block| block.parent_id.as_ref() == Some(&goals.id)).unwrap();
+        assert_eq!(child.content, "Ship **v1** ![diagram](../assets/diagram.png)");
+        assert!(blocks.iter().any(|block| block.content == "See ((6650a1b2-0000-4000-8000-000000000001))"));
+    }
+}
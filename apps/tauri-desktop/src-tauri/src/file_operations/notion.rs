@@ -0,0 +1,491 @@
+//! Importer for Notion's "Markdown & CSV" export.
+//!
+//! Every `.md` file becomes a page named after its path with Notion's 32 digit ids
+//! removed, so sub-pages become namespaces (`Projects/Roadmap`). Each database
+//! export (`.csv`) becomes a page listing its rows, and every row becomes a page
+//! whose properties are the row's columns. Links between exported files are
+//! rewritten to `[[Page]]` references and recorded in the `links` table, and other
+//! files are copied into the managed assets directory.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+use super::importer::asset_url;
+use super::outline;
+use super::{delimited, percent_decode, FileOperations, ImportResult};
+use crate::database::Database;
+use crate::models::{CreateLinkRequest, CreatePageRequest};
+
+const CREATED_COLUMNS: [&str; 3] = ["created", "created time", "date created"];
+const UPDATED_COLUMNS: [&str; 3] = ["last edited time", "last edited", "updated"];
+
+/// `(title, properties)` for each row of a database
+type DatabaseRows = Vec<(String, Vec<(String, String)>)>;
+
+/// A file inside the export, with the wrapping `Export-…` folder removed from its path
+struct Entry {
+    path: String,
+    data: Vec<u8>,
+    modified: Option<DateTime<Utc>>,
+}
+
+/// A page about to be created
+struct PendingPage {
+    name: String,
+    title: Option<String>,
+    body: String,
+    properties: Vec<(String, String)>,
+    /// Export path that relative links in `body` are resolved against
+    source: String,
+    modified: Option<DateTime<Utc>>,
+}
+
+#[allow(dead_code)]
+impl FileOperations {
+    /// Import a Notion export zip, copying attachments into `assets_dir`
+    pub async fn import_notion_export(
+        db: &Database,
+        zip_path: &Path,
+        graph_id: &str,
+        assets_dir: &Path,
+    ) -> Result<ImportResult> {
+        let mut entries = Vec::new();
+        read_zip(fs::File::open(zip_path)?, &mut entries, true)?;
+        strip_export_folder(&mut entries);
+
+        let mut result = ImportResult {
+            pages_imported: 0,
+            blocks_imported: 0,
+            errors: Vec::new(),
+        };
+
+        // Notion writes both `Name.csv` (the current view) and `Name_all.csv` (every row)
+        let has_all_rows = |path: &str| {
+            path.strip_suffix(".csv")
+                .map_or(false, |stem| entries.iter().any(|entry| entry.path == format!("{}_all.csv", stem)))
+        };
+
+        // Export paths of pages and databases, to resolve links
+        let mut targets: HashMap<String, LinkTarget> = HashMap::new();
+        let mut pages: Vec<PendingPage> = Vec::new();
+        let mut row_properties: HashMap<String, Vec<(String, String)>> = HashMap::new();
+        let mut databases = Vec::new();
+
+        for entry in &entries {
+            if entry.path.ends_with(".csv") && !has_all_rows(&entry.path) {
+                let name = page_name(&entry.path);
+                targets.insert(entry.path.clone(), LinkTarget::Page(name.clone()));
+                targets.insert(entry.path.replace("_all.csv", ".csv"), LinkTarget::Page(name.clone()));
+                match read_database(&entry.data) {
+                    Ok((columns, rows)) => {
+                        let mut row_names = Vec::new();
+                        for (title, properties) in rows {
+                            let row_name = format!("{}/{}", name, title);
+                            row_properties.insert(row_name.clone(), properties);
+                            row_names.push(row_name);
+                        }
+                        databases.push((name, columns, row_names, entry));
+                    }
+                    Err(e) => result.errors.push(format!("{}: invalid database export: {}", entry.path, e)),
+                }
+            }
+        }
+
+        let mut attachments = Vec::new();
+        for entry in &entries {
+            if entry.path.ends_with(".md") {
+                let name = page_name(&entry.path);
+                targets.insert(entry.path.clone(), LinkTarget::Page(name.clone()));
+                let content = String::from_utf8_lossy(&entry.data);
+                let properties = row_properties.get(&name);
+                let (title, body) = split_title(&content, properties.map(Vec::as_slice).unwrap_or_default());
+                pages.push(PendingPage {
+                    name,
+                    title,
+                    body,
+                    properties: properties.cloned().unwrap_or_default(),
+                    source: entry.path.clone(),
+                    modified: entry.modified,
+                });
+            } else if !entry.path.ends_with(".csv") {
+                attachments.push(entry);
+            }
+        }
+
+        // Database pages list their rows; rows without a page of their own still get one
+        for (name, columns, row_names, entry) in databases {
+            let rows: String = row_names.iter().map(|row| format!("- [[{}]]\n", row)).collect();
+            let properties = vec![
+                ("notion-database".to_string(), "true".to_string()),
+                ("columns".to_string(), columns.join(", ")),
+            ];
+            match pages.iter_mut().find(|page| page.name == name) {
+                Some(page) => {
+                    page.body = format!("{}\n\n{}", page.body.trim_end(), rows);
+                    page.properties.extend(properties);
+                }
+                None => pages.push(PendingPage {
+                    name: name.clone(),
+                    title: None,
+                    body: rows,
+                    properties,
+                    source: entry.path.clone(),
+                    modified: entry.modified,
+                }),
+            }
+            for row in row_names {
+                if !pages.iter().any(|page| page.name == row) {
+                    pages.push(PendingPage {
+                        properties: row_properties.remove(&row).unwrap_or_default(),
+                        name: row,
+                        title: None,
+                        body: String::new(),
+                        source: entry.path.clone(),
+                        modified: entry.modified,
+                    });
+                }
+            }
+        }
+
+        for entry in attachments {
+            let target = assets_dir.join(&entry.path);
+            let copied = target.parent().map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(&target, &entry.data));
+            match copied {
+                Ok(_) => {
+                    targets.insert(entry.path.clone(), LinkTarget::Asset(asset_url(&target)));
+                }
+                Err(e) => result.errors.push(format!("{}: failed to copy attachment: {}", entry.path, e)),
+            }
+        }
+
+        let mut page_ids: HashMap<String, String> = HashMap::new();
+        for page in &pages {
+            let page_request = CreatePageRequest {
+                graph_id: graph_id.to_string(),
+                name: page.name.clone(),
+                title: page.title.clone().filter(|title| !page.name.ends_with(title.as_str())),
+                properties: outline::properties_to_json(&page.properties),
+                tags: Some("[]".to_string()),
+                is_journal: Some(false),
+                journal_date: None,
+            };
+            match db.create_page(page_request).await {
+                Ok(created) => {
+                    result.pages_imported += 1;
+                    page_ids.insert(page.name.clone(), created.id);
+                }
+                Err(e) => result.errors.push(format!("{}: failed to create page: {}", page.source, e)),
+            }
+        }
+
+        for page in &pages {
+            let Some(page_id) = page_ids.get(&page.name) else { continue };
+            let body = rewrite_links(&page.body, &page.source, &targets);
+            let blocks = outline::nest_sections(outline::parse(&outline::normalize_markdown(&body)));
+
+            let mut page_result = ImportResult {
+                pages_imported: 0,
+                blocks_imported: 0,
+                errors: Vec::new(),
+            };
+            let created = Self::create_outline_blocks(db, graph_id, page_id, &blocks, &mut page_result).await;
+            result.blocks_imported += page_result.blocks_imported;
+            result.errors.extend(page_result.errors.into_iter().map(|e| format!("{}: {}", page.source, e)));
+
+            for block in created {
+                let linked = delimited(&block.content, "[[", "]]")
+                    .filter_map(|inner| page_ids.get(inner.split('|').next().unwrap_or_default()));
+                for (position, target_id) in linked.enumerate() {
+                    let request = CreateLinkRequest {
+                        source_type: "block".to_string(),
+                        source_id: block.id.clone(),
+                        target_type: "page".to_string(),
+                        target_id: target_id.clone(),
+                        link_type: "page-reference".to_string(),
+                        context: None,
+                        position: Some(position as i32),
+                    };
+                    if let Err(e) = db.create_link(request).await {
+                        result.errors.push(format!("{}: failed to record link: {}", page.source, e));
+                    }
+                }
+            }
+
+            let created_at = property_time(&page.properties, &CREATED_COLUMNS).or(page.modified);
+            let updated_at = property_time(&page.properties, &UPDATED_COLUMNS).or(page.modified);
+            Self::restore_timestamps(db, "pages", page_id, created_at, updated_at).await?;
+        }
+
+        Ok(result)
+    }
+}
+
+/// Collect the files of a zip, unpacking the nested part zips of large exports
+fn read_zip<R: Read + Seek>(reader: R, entries: &mut Vec<Entry>, unpack_nested: bool) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(reader)?;
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        // `enclosed_name` rejects paths that would escape the export
+        let Some(path) = file.enclosed_name().map(|path| path.to_string_lossy().replace('\\', "/")) else { continue };
+        if file.is_dir() {
+            continue;
+        }
+        let modified = file.last_modified();
+        let modified = NaiveDate::from_ymd_opt(modified.year().into(), modified.month().into(), modified.day().into())
+            .and_then(|date| date.and_hms_opt(modified.hour().into(), modified.minute().into(), modified.second().into()))
+            .map(|time| DateTime::<Utc>::from_naive_utc_and_offset(time, Utc));
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        if unpack_nested && path.ends_with(".zip") {
+            drop(file);
+            read_zip(Cursor::new(data), entries, false)?;
+        } else {
+            entries.push(Entry { path, data, modified });
+        }
+    }
+    Ok(())
+}
+
+/// Drop the `Export-<id>/` folder that wraps the whole export
+fn strip_export_folder(entries: &mut [Entry]) {
+    let root = entries.first()
+        .and_then(|entry| entry.path.split_once('/'))
+        .map(|(root, _)| format!("{}/", root));
+    if let Some(root) = root.filter(|root| root.starts_with("Export-")) {
+        if entries.iter().all(|entry| entry.path.starts_with(&root)) {
+            for entry in entries {
+                entry.path = entry.path[root.len()..].to_string();
+            }
+        }
+    }
+}
+
+/// Page name for an export path: `Projects 1a2b…/Roadmap 3c4d….md` becomes `Projects/Roadmap`
+fn page_name(path: &str) -> String {
+    let stem = path.strip_suffix("_all.csv")
+        .or_else(|| path.strip_suffix(".csv"))
+        .or_else(|| path.strip_suffix(".md"))
+        .unwrap_or(path);
+    stem.split('/').map(strip_notion_id).collect::<Vec<_>>().join("/")
+}
+
+fn strip_notion_id(segment: &str) -> &str {
+    match segment.rsplit_once(' ') {
+        Some((title, id)) if id.len() == 32 && id.chars().all(|c| c.is_ascii_hexdigit()) => title,
+        _ => segment,
+    }
+}
+
+/// Columns and rows of a database export; the first column is the title
+fn read_database(data: &[u8]) -> Result<(Vec<String>, DatabaseRows)> {
+    let data = data.strip_prefix("\u{feff}".as_bytes()).unwrap_or(data);
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(data);
+    let columns: Vec<String> = reader.headers()?.iter().map(str::to_string).collect();
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let title = record.get(0).unwrap_or_default().trim();
+        if title.is_empty() {
+            continue;
+        }
+        let properties = columns.iter()
+            .zip(record.iter())
+            .skip(1)
+            .map(|(column, value)| (column.clone(), value.to_string()))
+            .collect();
+        rows.push((title.to_string(), properties));
+    }
+    Ok((columns, rows))
+}
+
+/// Take the `# Title` line off a page, and for database rows also the `Key: Value`
+/// lines Notion repeats under it
+fn split_title(content: &str, properties: &[(String, String)]) -> (Option<String>, String) {
+    let Some(first) = content.lines().next().and_then(|line| line.strip_prefix("# ")) else {
+        return (None, content.to_string());
+    };
+    let title = first.trim().to_string();
+    let mut body = content.split_once('\n').map_or("", |(_, rest)| rest).trim_start_matches('\n');
+
+    if !properties.is_empty() {
+        let (paragraph, rest) = body.split_once("\n\n").unwrap_or((body, ""));
+        let is_property = |line: &str| {
+            line.split_once(": ").map_or(false, |(key, _)| properties.iter().any(|(column, _)| column == key))
+        };
+        if paragraph.lines().all(is_property) {
+            body = rest.trim_start_matches('\n');
+        }
+    }
+    (Some(title), body.to_string())
+}
+
+/// What a link to an exported file points at once imported
+enum LinkTarget {
+    Page(String),
+    Asset(String),
+}
+
+/// Point links between exported files at pages, and links to attachments at the copies
+fn rewrite_links(body: &str, source: &str, targets: &HashMap<String, LinkTarget>) -> String {
+    let base = source.rsplit_once('/').map_or("", |(dir, _)| dir);
+    let mut out = String::with_capacity(body.len());
+    let mut rest = body;
+
+    while let Some(middle) = rest.find("](") {
+        let (Some(open), Some(close)) = (rest[..middle].rfind('['), rest[middle + 2..].find(')')) else { break };
+        let close = middle + 2 + close;
+        let destination = &rest[middle + 2..close];
+        let text = &rest[open + 1..middle];
+        let image = open > 0 && rest.as_bytes()[open - 1] == b'!';
+
+        let resolved = (!destination.contains("://") && !destination.contains('\n'))
+            .then(|| resolve_path(base, &percent_decode(destination)))
+            .and_then(|path| targets.get(&path));
+        match resolved {
+            Some(LinkTarget::Asset(url)) => {
+                out.push_str(&rest[..middle + 2]);
+                out.push_str(url);
+                out.push(')');
+            }
+            Some(LinkTarget::Page(name)) => {
+                out.push_str(&rest[..if image { open - 1 } else { open }]);
+                let label = name.rsplit('/').next().unwrap_or_default();
+                if text == label || text.is_empty() {
+                    out.push_str(&format!("[[{}]]", name));
+                } else {
+                    out.push_str(&format!("[[{}|{}]]", name, text));
+                }
+            }
+            None => out.push_str(&rest[..=close]),
+        }
+        rest = &rest[close + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Join a relative link onto the directory of the file it appears in
+fn resolve_path(base: &str, relative: &str) -> String {
+    let mut segments: Vec<&str> = base.split('/').filter(|segment| !segment.is_empty()).collect();
+    for segment in relative.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+/// Notion writes dates like `October 5, 2023 3:12 PM`
+fn property_time(properties: &[(String, String)], columns: &[&str]) -> Option<DateTime<Utc>> {
+    let (_, value) = properties.iter().find(|(key, _)| columns.contains(&key.to_lowercase().as_str()))?;
+    NaiveDateTime::parse_from_str(value, "%B %d, %Y %I:%M %p")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%B %d, %Y").map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default()))
+        .ok()
+        .map(|time| DateTime::<Utc>::from_naive_utc_and_offset(time, Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    const PROJECTS: &str = "Projects 0123456789abcdef0123456789abcdef";
+    const TASKS: &str = "Tasks aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+
+    #[tokio::test]
+    async fn test_import_notion_export() {
+        let dir = tempdir().unwrap();
+        let zip_path = dir.path().join("export.zip");
+        let mut writer = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+        let files: Vec<(String, Vec<u8>)> = vec![
+            (
+                format!("{}.md", PROJECTS),
+                format!(
+                    "# Projects\n\nOverview of [Roadmap]({}/Roadmap%20fedcba9876543210fedcba9876543210.md) and [the tasks]({}.csv)\n\n![diagram]({}/diagram.png)\n\n## Goals\n\n- Ship\n    - Soon\n",
+                    PROJECTS.replace(' ', "%20"), TASKS.replace(' ', "%20"), PROJECTS.replace(' ', "%20"),
+                ).into_bytes(),
+            ),
+            (
+                format!("{}/Roadmap fedcba9876543210fedcba9876543210.md", PROJECTS),
+                format!("# Roadmap\n\nBack to [Projects](../{}.md)\n", PROJECTS.replace(' ', "%20")).into_bytes(),
+            ),
+            (format!("{}/diagram.png", PROJECTS), vec![0, 1, 2]),
+            (
+                format!("{}.csv", TASKS),
+                "\u{feff}Name,Status,Created\nWrite docs,Done,\"October 5, 2023 3:12 PM\"\nFix bug,Todo,\"October 6, 2023 9:00 AM\"\n".as_bytes().to_vec(),
+            ),
+            (
+                format!("{}/Write docs bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb.md", TASKS),
+                b"# Write docs\n\nStatus: Done\nCreated: October 5, 2023 3:12 PM\n\nDraft the guide\n".to_vec(),
+            ),
+        ];
+        for (path, data) in files {
+            writer.start_file(format!("Export-1234/{}", path), zip::write::FileOptions::default()).unwrap();
+            writer.write_all(&data).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let db = Database::new_with_path(dir.path().join("notion.db").to_str().unwrap()).await.unwrap();
+        let assets_dir = dir.path().join("assets");
+        let result = FileOperations::import_notion_export(&db, &zip_path, "default", &assets_dir).await.unwrap();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.pages_imported, 5);
+        assert!(assets_dir.join(PROJECTS).join("diagram.png").exists());
+
+        let pages = db.get_all_pages().await.unwrap();
+        let page = |name: &str| pages.iter().find(|page| page.name == name).unwrap_or_else(|| panic!("missing page {}", name));
+
+        let projects = page("Projects");
+        let blocks = db.get_blocks_by_page(&projects.id).await.unwrap();
+        let contents: Vec<&str> = blocks.iter().map(|block| block.content.as_str()).collect();
+        assert!(contents.contains(&"Overview of [[Projects/Roadmap]] and [[Tasks|the tasks]]"), "{:?}", contents);
+        let image = format!("![diagram]({})", asset_url(&assets_dir.join(PROJECTS).join("diagram.png")));
+        assert!(contents.contains(&image.as_str()), "{:?}", contents);
+        let goals = blocks.iter().find(|block| block.content == "## Goals").unwrap();
+        let ship = blocks.iter().find(|block| block.content == "Ship").unwrap();
+        assert_eq!(ship.parent_id.as_deref(), Some(goals.id.as_str()));
+
+        // Database rows are pages with the row's columns as properties
+        let row = page("Tasks/Write docs");
+        let properties = outline::properties_from_json(row.properties.as_deref().unwrap_or("{}"));
+        assert_eq!(properties, vec![
+            ("Status".to_string(), "Done".to_string()),
+            ("Created".to_string(), "October 5, 2023 3:12 PM".to_string()),
+        ]);
+        assert_eq!(row.created_at.to_rfc3339(), "2023-10-05T15:12:00+00:00");
+        let row_blocks = db.get_blocks_by_page(&row.id).await.unwrap();
+        assert_eq!(row_blocks.len(), 1);
+        assert_eq!(row_blocks[0].content, "Draft the guide");
+        page("Tasks/Fix bug");
+
+        let tasks = page("Tasks");
+        let task_blocks = db.get_blocks_by_page(&tasks.id).await.unwrap();
+        let rows: Vec<&str> = task_blocks.iter().map(|block| block.content.as_str()).collect();
+        assert_eq!(rows, vec!["[[Tasks/Write docs]]", "[[Tasks/Fix bug]]"]);
+
+        assert_eq!(db.get_links_to("page", &page("Projects/Roadmap").id).await.unwrap().len(), 1);
+        assert_eq!(db.get_links_to("page", &projects.id).await.unwrap().len(), 1);
+        assert_eq!(db.get_links_to("page", &row.id).await.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_page_names_and_paths() {
+        assert_eq!(page_name(&format!("{}/Roadmap fedcba9876543210fedcba9876543210.md", PROJECTS)), "Projects/Roadmap");
+        assert_eq!(page_name(&format!("{}_all.csv", TASKS)), "Tasks");
+        assert_eq!(page_name("Notes.md"), "Notes");
+        assert_eq!(resolve_path("a/b", "../c d.md"), "a/c d.md");
+    }
+}
@@ -0,0 +1,458 @@
+//! Importer for Obsidian vaults.
+//!
+//! Notes become pages named after their path inside the vault, so folders act as
+//! namespaces (`Projects/Alpha.md` becomes `Projects/Alpha`). Headings become parent
+//! blocks of the content below them. Wikilinks are rewritten to the full page name
+//! and recorded in the `links` table, and attachments are copied into the managed
+//! assets directory with their embeds pointing at the copies.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+use super::importer::asset_url;
+use super::outline::{self, OutlineBlock};
+use super::{FileOperations, ImportResult};
+use crate::database::Database;
+use crate::models::{CreateLinkRequest, CreatePageRequest};
+
+const IMAGE_EXTENSIONS: [&str; 8] = ["png", "jpg", "jpeg", "gif", "svg", "webp", "bmp", "avif"];
+
+/// A note read from the vault, before its page is created
+struct Note {
+    path: String,
+    name: String,
+    body: String,
+    tags: Vec<String>,
+    aliases: Vec<String>,
+    properties: Vec<(String, String)>,
+}
+
+/// Lookup tables used to resolve wikilink targets the way Obsidian does
+#[derive(Default)]
+struct Vault {
+    by_path: HashMap<String, String>,
+    by_name: HashMap<String, String>,
+    by_alias: HashMap<String, String>,
+    attachments: HashMap<String, String>,
+}
+
+impl Vault {
+    fn resolve_page(&self, target: &str) -> Option<&str> {
+        let key = target.trim().trim_end_matches(".md").to_lowercase();
+        self.by_path.get(&key)
+            .or_else(|| self.by_name.get(&key))
+            .or_else(|| self.by_alias.get(&key))
+            .map(String::as_str)
+    }
+
+    fn resolve_attachment(&self, target: &str) -> Option<&str> {
+        let key = target.trim().to_lowercase();
+        self.attachments.get(&key)
+            .or_else(|| self.attachments.get(key.rsplit('/').next().unwrap_or_default()))
+            .map(String::as_str)
+    }
+}
+
+/// A `[[target#section|alias]]` or `![[embed]]` occurrence
+struct WikiLink<'a> {
+    start: usize,
+    end: usize,
+    embed: bool,
+    target: &'a str,
+    section: &'a str,
+    alias: Option<&'a str>,
+}
+
+#[allow(dead_code)]
+impl FileOperations {
+    /// Import an Obsidian vault, copying attachments into `assets_dir`
+    pub async fn import_obsidian_vault(
+        db: &Database,
+        vault_dir: &Path,
+        graph_id: &str,
+        assets_dir: &Path,
+    ) -> Result<ImportResult> {
+        if !vault_dir.is_dir() {
+            return Err(anyhow!("Not a directory: {}", vault_dir.display()));
+        }
+
+        let mut result = ImportResult {
+            pages_imported: 0,
+            blocks_imported: 0,
+            errors: Vec::new(),
+        };
+
+        let mut files = Vec::new();
+        collect_files(vault_dir, vault_dir, &mut files)?;
+        files.sort();
+
+        // Copy attachments and read notes
+        let mut vault = Vault::default();
+        let mut notes = Vec::new();
+        for relative in files {
+            let path = relative.to_string_lossy().replace('\\', "/");
+            let source = vault_dir.join(&relative);
+            if relative.extension().and_then(|ext| ext.to_str()) != Some("md") {
+                let target = assets_dir.join(&relative);
+                let copied = target.parent().map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|_| fs::copy(&source, &target));
+                match copied {
+                    Ok(_) => {
+                        let file_name = path.rsplit('/').next().unwrap_or_default().to_lowercase();
+                        vault.attachments.entry(file_name).or_insert_with(|| path.clone());
+                        vault.attachments.insert(path.to_lowercase(), path);
+                    }
+                    Err(e) => result.errors.push(format!("{}: failed to copy attachment: {}", path, e)),
+                }
+                continue;
+            }
+
+            match fs::read_to_string(&source) {
+                Ok(content) => notes.push(read_note(&path, &content, &mut result.errors)),
+                Err(e) => result.errors.push(format!("{}: {}", path, e)),
+            }
+        }
+
+        // Shorter paths win when several notes share a file name
+        let mut by_length: Vec<&Note> = notes.iter().collect();
+        by_length.sort_by_key(|note| (note.name.matches('/').count(), note.name.clone()));
+        for note in by_length {
+            vault.by_path.insert(note.name.to_lowercase(), note.name.clone());
+            let file_name = note.name.rsplit('/').next().unwrap_or_default().to_lowercase();
+            vault.by_name.entry(file_name).or_insert_with(|| note.name.clone());
+            for alias in &note.aliases {
+                vault.by_alias.entry(alias.to_lowercase()).or_insert_with(|| note.name.clone());
+            }
+        }
+
+        // Create every page first so links can point at notes imported later
+        let mut page_ids: HashMap<String, String> = HashMap::new();
+        for note in &notes {
+            let page_request = CreatePageRequest {
+                graph_id: graph_id.to_string(),
+                name: note.name.clone(),
+                title: None,
+                properties: outline::properties_to_json(&note.properties),
+                tags: Some(serde_json::to_string(&note.tags)?),
+                is_journal: Some(false),
+                journal_date: None,
+            };
+            match db.create_page(page_request).await {
+                Ok(page) => {
+                    result.pages_imported += 1;
+                    for alias in &note.aliases {
+                        if let Err(e) = db.add_page_alias(&page.id, alias).await {
+                            result.errors.push(format!("{}: failed to add alias '{}': {}", note.path, alias, e));
+                        }
+                    }
+                    page_ids.insert(note.name.clone(), page.id);
+                }
+                Err(e) => result.errors.push(format!("{}: failed to create page: {}", note.path, e)),
+            }
+        }
+
+        for note in &notes {
+            let Some(page_id) = page_ids.get(&note.name) else { continue };
+            let body = rewrite_links(&note.body, &vault, &note.name, assets_dir);
+            let blocks = outline::nest_sections(outline::parse(&outline::normalize_markdown(&body)));
+
+            let mut note_result = ImportResult {
+                pages_imported: 0,
+                blocks_imported: 0,
+                errors: Vec::new(),
+            };
+            let created = Self::create_outline_blocks(db, graph_id, page_id, &blocks, &mut note_result).await;
+            result.blocks_imported += note_result.blocks_imported;
+            result.errors.extend(note_result.errors.into_iter().map(|e| format!("{}: {}", note.path, e)));
+
+            for block in created {
+                for (position, link) in wikilinks(&block.content).iter().enumerate() {
+                    let target = if link.target.is_empty() { Some(note.name.as_str()) } else { vault.resolve_page(link.target) };
+                    let Some(target_id) = target.and_then(|name| page_ids.get(name)) else { continue };
+                    let request = CreateLinkRequest {
+                        source_type: "block".to_string(),
+                        source_id: block.id.clone(),
+                        target_type: "page".to_string(),
+                        target_id: target_id.clone(),
+                        link_type: "page-reference".to_string(),
+                        context: Some(block.content[link.start..link.end].to_string()),
+                        position: Some(position as i32),
+                    };
+                    if let Err(e) = db.create_link(request).await {
+                        result.errors.push(format!("{}: failed to record link: {}", note.path, e));
+                    }
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            files.push(relative.to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+/// Split a note into frontmatter fields and body
+fn read_note(path: &str, content: &str, errors: &mut Vec<String>) -> Note {
+    let (frontmatter, body) = FileOperations::split_frontmatter(content);
+    let mut note = Note {
+        path: path.to_string(),
+        name: path.trim_end_matches(".md").to_string(),
+        body: body.to_string(),
+        tags: Vec::new(),
+        aliases: Vec::new(),
+        properties: Vec::new(),
+    };
+
+    let mapping = match frontmatter.map(serde_yaml::from_str::<serde_yaml::Value>) {
+        Some(Ok(serde_yaml::Value::Mapping(mapping))) => mapping,
+        Some(Ok(serde_yaml::Value::Null)) | None => serde_yaml::Mapping::new(),
+        Some(Ok(_)) | Some(Err(_)) => {
+            errors.push(format!("{}: invalid frontmatter", path));
+            serde_yaml::Mapping::new()
+        }
+    };
+    for (key, value) in mapping {
+        let Some(key) = key.as_str().map(str::to_string) else { continue };
+        match key.as_str() {
+            "tags" | "tag" => note.tags.extend(yaml_list(&value, &[',', ' ']).into_iter().map(|tag| tag.trim_start_matches('#').to_string())),
+            "aliases" | "alias" => note.aliases.extend(yaml_list(&value, &[','])),
+            _ => {
+                let value = match value {
+                    serde_yaml::Value::String(value) => value,
+                    other => serde_json::to_value(&other).map(|json| json.to_string()).unwrap_or_default(),
+                };
+                note.properties.push((key, value));
+            }
+        }
+    }
+
+    for tag in inline_tags(&note.body) {
+        note.tags.push(tag);
+    }
+    let mut seen = std::collections::HashSet::new();
+    note.tags.retain(|tag| !tag.is_empty() && seen.insert(tag.clone()));
+    note
+}
+
+/// Frontmatter lists may also be written as a single string of `separators` separated items
+fn yaml_list(value: &serde_yaml::Value, separators: &[char]) -> Vec<String> {
+    match value {
+        serde_yaml::Value::Sequence(items) => items.iter().flat_map(|item| yaml_list(item, &[])).collect(),
+        serde_yaml::Value::String(text) => text
+            .split(separators)
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect(),
+        serde_yaml::Value::Number(number) => vec![number.to_string()],
+        _ => Vec::new(),
+    }
+}
+
+/// `#tag` and `#nested/tag` outside code
+fn inline_tags(body: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    for line in outline::lines_outside_fences(body) {
+        let mut in_code = false;
+        let mut previous = ' ';
+        for (index, c) in line.char_indices() {
+            if c == '`' {
+                in_code = !in_code;
+            } else if c == '#' && !in_code && (previous.is_whitespace() || previous == '(') {
+                let tag: String = line[index + 1..]
+                    .chars()
+                    .take_while(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '/'))
+                    .collect();
+                let tag = tag.trim_end_matches('/');
+                if tag.chars().any(|c| !c.is_ascii_digit()) && !tags.iter().any(|t: &String| t == tag) {
+                    tags.push(tag.to_string());
+                }
+            }
+            previous = c;
+        }
+    }
+    tags
+}
+
+fn wikilinks(text: &str) -> Vec<WikiLink<'_>> {
+    let mut links = Vec::new();
+    let mut offset = 0;
+    while let Some(open) = text[offset..].find("[[") {
+        let start = offset + open;
+        let inner_start = start + 2;
+        let Some(close) = text[inner_start..].find("]]") else { break };
+        let inner = &text[inner_start..inner_start + close];
+        offset = inner_start + close + 2;
+        if inner.contains('\n') || inner.contains("[[") {
+            offset = inner_start;
+            continue;
+        }
+
+        let embed = start > 0 && text.as_bytes()[start - 1] == b'!';
+        let (reference, alias) = match inner.split_once('|') {
+            Some((reference, alias)) => (reference, Some(alias)),
+            None => (inner, None),
+        };
+        let (target, section) = match reference.find('#') {
+            Some(index) => reference.split_at(index),
+            None => (reference, ""),
+        };
+        links.push(WikiLink {
+            start: if embed { start - 1 } else { start },
+            end: offset,
+            embed,
+            target,
+            section,
+            alias,
+        });
+    }
+    links
+}
+
+/// Point wikilinks at full page names and embeds of attachments at the managed copies
+fn rewrite_links(body: &str, vault: &Vault, current: &str, assets_dir: &Path) -> String {
+    let mut out = String::with_capacity(body.len());
+    let mut fenced = false;
+    for line in body.split_inclusive('\n') {
+        if line.trim_start().starts_with("```") {
+            fenced = !fenced;
+        }
+        if fenced || !line.contains("[[") {
+            out.push_str(line);
+            continue;
+        }
+
+        let mut last = 0;
+        for link in wikilinks(line) {
+            out.push_str(&line[last..link.start]);
+            last = link.end;
+
+            let attachment = link.embed.then(|| vault.resolve_attachment(link.target)).flatten();
+            if let Some(path) = attachment.filter(|_| !link.target.ends_with(".md")) {
+                let file_name = path.rsplit('/').next().unwrap_or_default();
+                let label = link.alias.unwrap_or(file_name);
+                let is_image = file_name.rsplit('.').next()
+                    .map_or(false, |ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+                out.push_str(&format!(
+                    "{}[{}]({})",
+                    if is_image { "!" } else { "" },
+                    label,
+                    asset_url(&assets_dir.join(path))
+                ));
+                continue;
+            }
+
+            let target = if link.target.is_empty() { Some(current) } else { vault.resolve_page(link.target) };
+            match target {
+                Some(name) => {
+                    out.push_str(if link.embed { "![[" } else { "[[" });
+                    out.push_str(if link.target.is_empty() { "" } else { name });
+                    out.push_str(link.section);
+                    if let Some(alias) = link.alias {
+                        out.push('|');
+                        out.push_str(alias);
+                    }
+                    out.push_str("]]");
+                }
+                None => out.push_str(&line[link.start..link.end]),
+            }
+        }
+        out.push_str(&line[last..]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_sections_and_lists() {
+        let body = "Intro\n\n# Plan\n* first\n    * nested\n## Details\nText #project/alpha\n# Notes\n```\n#not-a-tag\n```\n";
+        let blocks = outline::nest_sections(outline::parse(&outline::normalize_markdown(body)));
+
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0].content, "Intro");
+        let plan = &blocks[1];
+        assert_eq!(plan.content, "# Plan");
+        assert_eq!(plan.children[0].content, "first");
+        assert_eq!(plan.children[0].children[0].content, "nested");
+        assert_eq!(plan.children[1].content, "## Details");
+        assert_eq!(plan.children[1].children[0].content, "Text #project/alpha");
+        assert_eq!(blocks[2].children[0].content, "```\n#not-a-tag\n```");
+        assert_eq!(inline_tags(body), vec!["project/alpha"]);
+    }
+
+    #[tokio::test]
+    async fn test_import_obsidian_vault() {
+        let vault = tempdir().unwrap();
+        let root = vault.path();
+        fs::create_dir_all(root.join("Projects")).unwrap();
+        fs::create_dir_all(root.join("attachments")).unwrap();
+        fs::create_dir_all(root.join(".obsidian")).unwrap();
+        fs::write(root.join(".obsidian/app.json"), "{}").unwrap();
+        fs::write(root.join("attachments/Chart 1.png"), [0u8, 1]).unwrap();
+        fs::write(
+            root.join("Projects/Alpha.md"),
+            "---\naliases: [Project Alpha]\ntags: [work, \"#research/ml\"]\nstatus: active\npriority: 2\n---\n# Overview\nBuilt with [[Beta|the beta]] and #planning\n![[Chart 1.png]]\n## Risks\n- see [[#Overview]]\n",
+        ).unwrap();
+        fs::write(root.join("Beta.md"), "Depends on [[Project Alpha]] and [[Missing]]\n\n![[Alpha#Overview]]\n").unwrap();
+
+        let data = tempdir().unwrap();
+        let db = Database::new_with_path(data.path().join("vault.db").to_str().unwrap()).await.unwrap();
+        let assets_dir = data.path().join("assets");
+        let result = FileOperations::import_obsidian_vault(&db, root, "default", &assets_dir).await.unwrap();
+
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.pages_imported, 2);
+        assert!(assets_dir.join("attachments/Chart 1.png").exists());
+
+        let pages = db.get_all_pages().await.unwrap();
+        let alpha = pages.iter().find(|page| page.name == "Projects/Alpha").unwrap();
+        let beta = pages.iter().find(|page| page.name == "Beta").unwrap();
+        let tags: Vec<String> = serde_json::from_str(&alpha.tags).unwrap();
+        assert_eq!(tags, vec!["work", "research/ml", "planning"]);
+        let properties = outline::properties_from_json(alpha.properties.as_deref().unwrap_or("{}"));
+        assert_eq!(properties, vec![("status".to_string(), "active".to_string()), ("priority".to_string(), "2".to_string())]);
+        assert_eq!(db.get_page_aliases(&alpha.id).await.unwrap(), vec!["Project Alpha"]);
+
+        // Headings own the blocks below them
+        let blocks = db.get_blocks_by_page(&alpha.id).await.unwrap();
+        let overview = blocks.iter().find(|block| block.content == "# Overview").unwrap();
+        let text = blocks.iter().find(|block| block.content.starts_with("Built with")).unwrap();
+        assert_eq!(text.content, format!("Built with [[Beta|the beta]] and #planning\n![Chart 1.png]({})", asset_url(&assets_dir.join("attachments/Chart 1.png"))));
+        assert_eq!(text.parent_id.as_deref(), Some(overview.id.as_str()));
+        let risks = blocks.iter().find(|block| block.content == "## Risks").unwrap();
+        assert_eq!(risks.parent_id.as_deref(), Some(overview.id.as_str()));
+
+        // Alias and embed links resolve to the full page name
+        let beta_blocks = db.get_blocks_by_page(&beta.id).await.unwrap();
+        assert_eq!(beta_blocks[0].content, "Depends on [[Projects/Alpha]] and [[Missing]]");
+        assert_eq!(beta_blocks[1].content, "![[Projects/Alpha#Overview]]");
+
+        let to_alpha = db.get_links_to("page", &alpha.id).await.unwrap();
+        assert_eq!(to_alpha.len(), 3);
+        assert!(to_alpha.iter().any(|link| link.context.as_deref() == Some("[[#Overview]]")));
+        let to_beta = db.get_links_to("page", &beta.id).await.unwrap();
+        assert_eq!(to_beta.len(), 1);
+        assert_eq!(to_beta[0].source_id, text.id);
+    }
+}
@@ -0,0 +1,406 @@
+//! OPML import and export.
+//!
+//! Each block is an `<outline>` element nested like the block tree. The first line
+//! of a block goes in `text` and any further lines in `_note`; block properties
+//! become extra attributes. Collapsed state is written as the standard
+//! `<expansionState>` in the head, listing the blocks with children that are open.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+
+use super::outline::{self, OutlineBlock};
+use super::{FileOperations, ImportResult};
+use crate::database::Database;
+use crate::encoding::unescape_xml;
+use crate::models::{Block, CreatePageRequest, Page};
+
+/// Contents of an OPML file
+#[derive(Debug, Default, PartialEq)]
+pub struct OpmlDocument {
+    pub title: Option<String>,
+    pub date_created: Option<DateTime<Utc>>,
+    pub date_modified: Option<DateTime<Utc>>,
+    pub outlines: Vec<OutlineBlock>,
+}
+
+/// Attributes with a meaning of their own; everything else is a block property
+const RESERVED_ATTRIBUTES: [&str; 2] = ["text", "_note"];
+
+#[allow(dead_code)]
+impl FileOperations {
+    /// Convert a page and its blocks to an OPML document
+    pub fn page_to_opml(page: &Page, blocks: &[Block]) -> String {
+        let document = OpmlDocument {
+            title: Some(page.name.clone()),
+            date_created: Some(page.created_at),
+            date_modified: Some(page.updated_at),
+            outlines: outline::blocks_to_outline(blocks),
+        };
+        render_opml(&document)
+    }
+
+    /// Export page to OPML file
+    pub async fn export_page_to_opml(
+        db: &Database,
+        page_id: &str,
+        output_dir: &Path,
+    ) -> Result<PathBuf> {
+        let page = db.get_page(page_id).await?;
+        let blocks = db.get_blocks_by_page(page_id).await?;
+
+        let opml_content = Self::page_to_opml(&page, &blocks);
+
+        // Create safe filename
+        let filename = format!("{}.opml", page.name.replace(['/', '\\', ':', '*', '?', '"', '<', '>', '|'], "_"));
+        let file_path = output_dir.join(filename);
+
+        fs::write(&file_path, opml_content)?;
+
+        Ok(file_path)
+    }
+
+    /// Import an OPML file as one page
+    pub async fn import_opml(
+        db: &Database,
+        file_path: &Path,
+        graph_id: &str,
+    ) -> Result<ImportResult> {
+        let document = parse_opml(&fs::read_to_string(file_path)?)?;
+
+        let mut result = ImportResult {
+            pages_imported: 0,
+            blocks_imported: 0,
+            errors: Vec::new(),
+        };
+
+        let page_name = document.title
+            .clone()
+            .filter(|title| !title.trim().is_empty())
+            .or_else(|| file_path.file_stem().map(|s| s.to_string_lossy().to_string()))
+            .unwrap_or_else(|| "Untitled".to_string());
+
+        let page_request = CreatePageRequest {
+            graph_id: graph_id.to_string(),
+            name: page_name,
+            title: None,
+            properties: None,
+            tags: Some("[]".to_string()),
+            is_journal: Some(false),
+            journal_date: None,
+        };
+
+        match db.create_page(page_request).await {
+            Ok(page) => {
+                result.pages_imported += 1;
+                Self::create_outline_blocks(db, graph_id, &page.id, &document.outlines, &mut result).await;
+                Self::restore_timestamps(db, "pages", &page.id, document.date_created, document.date_modified).await?;
+            }
+            Err(e) => result.errors.push(format!("Failed to create page: {}", e)),
+        }
+
+        Ok(result)
+    }
+}
+
+/// Write an OPML 2.0 document
+pub fn render_opml(document: &OpmlDocument) -> String {
+    let mut expanded = Vec::new();
+    let mut index = 0;
+    let mut body = String::new();
+    for block in &document.outlines {
+        render_outline(&mut body, block, 2, &mut index, &mut expanded);
+    }
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<opml version=\"2.0\">\n  <head>\n");
+    if let Some(title) = &document.title {
+        out.push_str(&format!("    <title>{}</title>\n", escape(title)));
+    }
+    if let Some(created) = document.date_created {
+        out.push_str(&format!("    <dateCreated>{}</dateCreated>\n", created.to_rfc2822()));
+    }
+    if let Some(modified) = document.date_modified {
+        out.push_str(&format!("    <dateModified>{}</dateModified>\n", modified.to_rfc2822()));
+    }
+    let expanded: Vec<String> = expanded.iter().map(usize::to_string).collect();
+    out.push_str(&format!("    <expansionState>{}</expansionState>\n", expanded.join(",")));
+    out.push_str("  </head>\n  <body>\n");
+    out.push_str(&body);
+    out.push_str("  </body>\n</opml>\n");
+    out
+}
+
+fn render_outline(out: &mut String, block: &OutlineBlock, depth: usize, index: &mut usize, expanded: &mut Vec<usize>) {
+    if !block.children.is_empty() && !block.collapsed {
+        expanded.push(*index);
+    }
+    *index += 1;
+
+    let (text, note) = block.content.split_once('\n').unwrap_or((&block.content, ""));
+    out.push_str(&"  ".repeat(depth));
+    out.push_str(&format!("<outline text=\"{}\"", escape(text)));
+    if !note.is_empty() {
+        out.push_str(&format!(" _note=\"{}\"", escape(note)));
+    }
+    for (key, value) in &block.properties {
+        if is_attribute_name(key) && !RESERVED_ATTRIBUTES.contains(&key.as_str()) {
+            out.push_str(&format!(" {}=\"{}\"", key, escape(value)));
+        }
+    }
+
+    if block.children.is_empty() {
+        out.push_str("/>\n");
+        return;
+    }
+    out.push_str(">\n");
+    for child in &block.children {
+        render_outline(out, child, depth + 1, index, expanded);
+    }
+    out.push_str(&"  ".repeat(depth));
+    out.push_str("</outline>\n");
+}
+
+fn is_attribute_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\n' => out.push_str("&#10;"),
+            '\r' => out.push_str("&#13;"),
+            '\t' => out.push_str("&#9;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Read an OPML document
+pub fn parse_opml(xml: &str) -> Result<OpmlDocument> {
+    let mut document = OpmlDocument::default();
+    let mut expansion_state: Option<Vec<usize>> = None;
+    let mut open: Vec<OutlineBlock> = Vec::new();
+    let mut text_element: Option<String> = None;
+    let mut text = String::new();
+    let mut rest = xml;
+
+    fn attach(open: &mut [OutlineBlock], roots: &mut Vec<OutlineBlock>, block: OutlineBlock) {
+        match open.last_mut() {
+            Some(parent) => parent.children.push(block),
+            None => roots.push(block),
+        }
+    }
+
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(after) = rest.strip_prefix("<!--") {
+            let end = after.find("-->").ok_or_else(|| anyhow!("Unterminated comment"))?;
+            rest = &after[end + 3..];
+            continue;
+        }
+        if rest.starts_with("<?") || rest.starts_with("<!") {
+            let end = rest.find('>').ok_or_else(|| anyhow!("Unterminated declaration"))?;
+            rest = &rest[end + 1..];
+            continue;
+        }
+
+        let end = tag_end(rest).ok_or_else(|| anyhow!("Unterminated tag"))?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim();
+            if name == "outline" {
+                let block = open.pop().ok_or_else(|| anyhow!("Unexpected </outline>"))?;
+                attach(&mut open, &mut document.outlines, block);
+            } else if text_element.as_deref() == Some(name) {
+                let value = unescape_xml(text.trim());
+                match name {
+                    "title" => document.title = Some(value),
+                    "dateCreated" => document.date_created = parse_date(&value),
+                    "dateModified" => document.date_modified = parse_date(&value),
+                    "expansionState" => {
+                        expansion_state = Some(value.split(',').filter_map(|item| item.trim().parse().ok()).collect());
+                    }
+                    _ => {}
+                }
+                text_element = None;
+            }
+            continue;
+        }
+
+        let self_closing = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let (name, attributes) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        if name != "outline" {
+            if !self_closing {
+                text_element = Some(name.to_string());
+                text.clear();
+            }
+            continue;
+        }
+
+        let mut block = OutlineBlock::default();
+        let mut note = None;
+        for (key, value) in parse_attributes(attributes) {
+            match key {
+                "text" => block.content = value,
+                "_note" => note = Some(value),
+                _ => block.properties.push((key.to_string(), value)),
+            }
+        }
+        if let Some(note) = note.filter(|note| !note.is_empty()) {
+            block.content = format!("{}\n{}", block.content, note);
+        }
+
+        if self_closing {
+            attach(&mut open, &mut document.outlines, block);
+        } else {
+            open.push(block);
+        }
+    }
+
+    if !open.is_empty() {
+        return Err(anyhow!("Unclosed <outline> element"));
+    }
+
+    // Outlines with children that are not listed as expanded were collapsed
+    if let Some(expanded) = expansion_state {
+        let mut counter = 0;
+        for block in &mut document.outlines {
+            mark_collapsed(block, &mut counter, &expanded);
+        }
+    }
+    Ok(document)
+}
+
+fn mark_collapsed(block: &mut OutlineBlock, index: &mut usize, expanded: &[usize]) {
+    block.collapsed = !block.children.is_empty() && !expanded.contains(index);
+    *index += 1;
+    for child in &mut block.children {
+        mark_collapsed(child, index, expanded);
+    }
+}
+
+/// Position of the `>` closing a tag, skipping quoted attribute values
+fn tag_end(tag: &str) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in tag.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (None, '>') => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_attributes(mut attributes: &str) -> Vec<(&str, String)> {
+    let mut parsed = Vec::new();
+    loop {
+        attributes = attributes.trim_start();
+        let Some(equals) = attributes.find('=') else { break };
+        let key = attributes[..equals].trim();
+        let value = attributes[equals + 1..].trim_start();
+        let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') else { break };
+        let Some(end) = value[1..].find(quote) else { break };
+        parsed.push((key, unescape_xml(&value[1..end + 1])));
+        attributes = &value[end + 2..];
+    }
+    parsed
+}
+
+/// OPML dates are RFC 822; accept RFC 3339 as well
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value)
+        .or_else(|_| DateTime::parse_from_rfc3339(value))
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn block(content: &str, collapsed: bool, children: Vec<OutlineBlock>) -> OutlineBlock {
+        OutlineBlock { content: content.to_string(), children, collapsed, ..Default::default() }
+    }
+
+    #[test]
+    fn test_opml_round_trip() {
+        let mut tagged = block("Tom & \"Jerry\" <3", false, Vec::new());
+        tagged.properties.push(("_complete".to_string(), "true".to_string()));
+        let document = OpmlDocument {
+            title: Some("Plans".to_string()),
+            date_created: DateTime::parse_from_rfc3339("2024-01-02T03:04:05Z").ok().map(|time| time.with_timezone(&Utc)),
+            date_modified: None,
+            outlines: vec![
+                block("Project\nwith a note\n\tand a tab", false, vec![
+                    block("Hidden", true, vec![block("Deep", false, Vec::new())]),
+                    tagged,
+                ]),
+                block("Closed", true, vec![block("Child", false, Vec::new())]),
+            ],
+        };
+
+        let opml = render_opml(&document);
+        assert!(opml.contains("<outline text=\"Project\" _note=\"with a note&#10;&#9;and a tab\">"));
+        assert!(opml.contains("<expansionState>0</expansionState>"));
+        assert_eq!(parse_opml(&opml).unwrap(), document);
+    }
+
+    #[test]
+    fn test_parse_foreign_opml() {
+        let xml = r#"<?xml version="1.0"?>
+<!-- exported elsewhere -->
+<opml version="1.0"><head><title>Inbox</title></head>
+<body><outline text='a &gt; b' _note=""><outline text="child"/></outline><outline text="x &#x1F600;"/></body></opml>"#;
+        let document = parse_opml(xml).unwrap();
+        assert_eq!(document.title.as_deref(), Some("Inbox"));
+        assert_eq!(document.outlines.len(), 2);
+        assert_eq!(document.outlines[0].content, "a > b");
+        assert!(!document.outlines[0].collapsed);
+        assert_eq!(document.outlines[0].children[0].content, "child");
+        assert_eq!(document.outlines[1].content, "x \u{1F600}");
+        assert!(parse_opml("<opml><body><outline text=\"open\"></body></opml>").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import_opml() {
+        let dir = tempdir().unwrap();
+        let db = Database::new_with_path(dir.path().join("opml.db").to_str().unwrap()).await.unwrap();
+        let source = dir.path().join("Reading.opml");
+        fs::write(&source, render_opml(&OpmlDocument {
+            title: Some("Reading".to_string()),
+            outlines: vec![block("Books\nto finish", true, vec![block("SICP", false, Vec::new())])],
+            ..Default::default()
+        })).unwrap();
+
+        let result = FileOperations::import_opml(&db, &source, "default").await.unwrap();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!((result.pages_imported, result.blocks_imported), (1, 2));
+
+        let page = db.get_all_pages().await.unwrap().into_iter().find(|page| page.name == "Reading").unwrap();
+        let blocks = db.get_blocks_by_page(&page.id).await.unwrap();
+        let books = blocks.iter().find(|block| block.content == "Books\nto finish").unwrap();
+        assert!(books.collapsed);
+
+        let exported = FileOperations::export_page_to_opml(&db, &page.id, dir.path()).await.unwrap();
+        let reparsed = parse_opml(&fs::read_to_string(exported).unwrap()).unwrap();
+        assert_eq!(reparsed.outlines, vec![block("Books\nto finish", true, vec![block("SICP", false, Vec::new())])]);
+    }
+}
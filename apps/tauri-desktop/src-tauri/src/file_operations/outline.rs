@@ -0,0 +1,461 @@
+//! Outliner-style Markdown codec.
+//!
+//! Blocks are written as `- ` bullets nested with one tab per level. Continuation
+//! lines are indented by the block's tabs plus two spaces, and `key:: value` lines
+//! directly after the first line are block properties. Inline Markdown is kept
+//! verbatim, so any document in this form survives `render(parse(text))` byte for
+//! byte. Other Markdown (plain paragraphs, headings) is imported as top-level
+//! blocks and normalized to bullets on export.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use serde::de::{Deserializer, MapAccess, Visitor};
+use serde::Deserialize;
+
+use crate::models::Block;
+
+/// A block and its children as they appear in an outline
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OutlineBlock {
+    pub content: String,
+    pub properties: Vec<(String, String)>,
+    pub children: Vec<OutlineBlock>,
+    /// Not written to Markdown; carried for formats such as OPML that keep it
+    pub collapsed: bool,
+}
+
+#[derive(Clone, Copy)]
+enum Current {
+    None,
+    Bullet,
+    Paragraph { open: bool, fenced: bool },
+}
+
+/// Parse an outline document into a block tree
+pub fn parse(markdown: &str) -> Vec<OutlineBlock> {
+    let mut roots: Vec<OutlineBlock> = Vec::new();
+    let mut path: Vec<usize> = Vec::new();
+    let mut current = Current::None;
+    let mut in_properties = false;
+
+    for line in markdown.lines() {
+        let fenced = matches!(current, Current::Paragraph { fenced: true, .. });
+
+        if !fenced {
+            if let Some((depth, first)) = bullet_line(line) {
+                let depth = match current {
+                    Current::None => 0,
+                    _ => depth.min(path.len()),
+                };
+                path.truncate(depth);
+                let siblings = children_at(&mut roots, &path);
+                siblings.push(OutlineBlock { content: first.to_string(), ..Default::default() });
+                path.push(siblings.len() - 1);
+                current = Current::Bullet;
+                in_properties = true;
+                continue;
+            }
+        }
+
+        if let Current::Bullet = current {
+            let prefix = format!("{}  ", "\t".repeat(path.len() - 1));
+            let text = if let Some(rest) = line.strip_prefix(prefix.as_str()) {
+                Some(rest)
+            } else if line.trim().is_empty() {
+                Some("")
+            } else if line.starts_with([' ', '\t']) {
+                Some(line.trim_start())
+            } else {
+                None
+            };
+            if let Some(text) = text {
+                push_line(node_at(&mut roots, &path), text, &mut in_properties);
+                continue;
+            }
+        }
+
+        // Anything else is a top-level paragraph
+        if let Current::Paragraph { open: true, fenced } = current {
+            if fenced || !line.trim().is_empty() {
+                push_line(node_at(&mut roots, &path), line, &mut in_properties);
+                let fence = line.trim_start().starts_with("```");
+                current = Current::Paragraph { open: true, fenced: fenced != fence };
+                continue;
+            }
+        }
+        if line.trim().is_empty() {
+            current = Current::None;
+            continue;
+        }
+        roots.push(OutlineBlock { content: line.to_string(), ..Default::default() });
+        path = vec![roots.len() - 1];
+        in_properties = true;
+        current = Current::Paragraph {
+            open: !line.starts_with('#'),
+            fenced: line.trim_start().starts_with("```"),
+        };
+    }
+
+    roots
+}
+
+/// Render a block tree as an outline document
+pub fn render(blocks: &[OutlineBlock]) -> String {
+    let mut out = String::new();
+    for block in blocks {
+        render_block(&mut out, block, 0);
+    }
+    out
+}
+
+fn render_block(out: &mut String, block: &OutlineBlock, depth: usize) {
+    let indent = "\t".repeat(depth);
+    let mut lines = block.content.split('\n');
+    out.push_str(&indent);
+    out.push_str("- ");
+    out.push_str(lines.next().unwrap_or_default());
+    out.push('\n');
+
+    for (key, value) in &block.properties {
+        out.push_str(&format!("{}  {}:: {}\n", indent, key, value));
+    }
+    for line in lines {
+        if !line.is_empty() {
+            out.push_str(&indent);
+            out.push_str("  ");
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    for child in &block.children {
+        render_block(out, child, depth + 1);
+    }
+}
+
+/// Build the outline tree of a page from its blocks, ordered by `order`
+pub fn blocks_to_outline(blocks: &[Block]) -> Vec<OutlineBlock> {
+    let ids: HashSet<&str> = blocks.iter().map(|block| block.id.as_str()).collect();
+    let mut sorted: Vec<&Block> = blocks.iter().collect();
+    sorted.sort_by(|a, b| (a.order, a.created_at, &a.id).cmp(&(b.order, b.created_at, &b.id)));
+
+    let mut children: HashMap<Option<&str>, Vec<&Block>> = HashMap::new();
+    for block in sorted {
+        let parent = block.parent_id.as_deref().filter(|parent| ids.contains(parent) && *parent != block.id);
+        children.entry(parent).or_default().push(block);
+    }
+
+    let mut visited = HashSet::new();
+    build_outline(None, &children, &mut visited)
+}
+
+fn build_outline<'a>(
+    parent: Option<&'a str>,
+    children: &HashMap<Option<&'a str>, Vec<&'a Block>>,
+    visited: &mut HashSet<&'a str>,
+) -> Vec<OutlineBlock> {
+    let mut blocks = Vec::new();
+    for block in children.get(&parent).into_iter().flatten() {
+        if !visited.insert(block.id.as_str()) {
+            continue;
+        }
+        blocks.push(OutlineBlock {
+            content: block.content.clone(),
+            properties: block.properties.as_deref().map(properties_from_json).unwrap_or_default(),
+            children: build_outline(Some(block.id.as_str()), children, visited),
+            collapsed: block.collapsed,
+        });
+    }
+    blocks
+}
+
+/// Serialize properties as a JSON object, keeping their order
+pub fn properties_to_json(properties: &[(String, String)]) -> Option<String> {
+    if properties.is_empty() {
+        return None;
+    }
+    let fields: Vec<String> = properties
+        .iter()
+        .map(|(key, value)| format!("{}:{}", serde_json::Value::from(key.as_str()), serde_json::Value::from(value.as_str())))
+        .collect();
+    Some(format!("{{{}}}", fields.join(",")))
+}
+
+/// Read block properties from JSON in document order; non-string values keep their JSON text
+pub fn properties_from_json(json: &str) -> Vec<(String, String)> {
+    serde_json::from_str::<OrderedProperties>(json)
+        .map(|properties| properties.0)
+        .unwrap_or_default()
+}
+
+struct OrderedProperties(Vec<(String, String)>);
+
+impl<'de> Deserialize<'de> for OrderedProperties {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PropertiesVisitor;
+
+        impl<'de> Visitor<'de> for PropertiesVisitor {
+            type Value = OrderedProperties;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut properties = Vec::new();
+                while let Some((key, value)) = map.next_entry::<String, serde_json::Value>()? {
+                    let value = match value {
+                        serde_json::Value::String(value) => value,
+                        other => other.to_string(),
+                    };
+                    properties.push((key, value));
+                }
+                Ok(OrderedProperties(properties))
+            }
+        }
+
+        deserializer.deserialize_map(PropertiesVisitor)
+    }
+}
+
+fn bullet_line(line: &str) -> Option<(usize, &str)> {
+    let rest = line.trim_start_matches('\t');
+    let depth = line.len() - rest.len();
+    if rest == "-" {
+        Some((depth, ""))
+    } else {
+        rest.strip_prefix("- ").map(|first| (depth, first))
+    }
+}
+
+/// `key:: value` where the key has no spaces
+pub fn property_line(text: &str) -> Option<(&str, &str)> {
+    let (key, rest) = text.split_once("::")?;
+    let valid_key = !key.is_empty()
+        && key.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+    if !valid_key {
+        return None;
+    }
+    let value = if rest.is_empty() { rest } else { rest.strip_prefix(' ')? };
+    Some((key, value))
+}
+
+fn push_line(block: &mut OutlineBlock, text: &str, in_properties: &mut bool) {
+    if *in_properties {
+        if let Some((key, value)) = property_line(text) {
+            block.properties.push((key.to_string(), value.to_string()));
+            return;
+        }
+        *in_properties = false;
+    }
+    block.content.push('\n');
+    block.content.push_str(text);
+}
+
+fn children_at<'a>(roots: &'a mut Vec<OutlineBlock>, path: &[usize]) -> &'a mut Vec<OutlineBlock> {
+    if path.is_empty() {
+        roots
+    } else {
+        &mut node_at(roots, path).children
+    }
+}
+
+fn node_at<'a>(roots: &'a mut [OutlineBlock], path: &[usize]) -> &'a mut OutlineBlock {
+    let (first, rest) = path.split_first().expect("path is never empty");
+    rest.iter().fold(&mut roots[*first], |node, index| &mut node.children[*index])
+}
+
+/// Lines of `text` that are not inside or delimiting a fenced code block
+pub fn lines_outside_fences(text: &str) -> impl Iterator<Item = &str> {
+    let mut fenced = false;
+    text.lines().filter(move |line| {
+        if line.trim_start().starts_with("```") {
+            fenced = !fenced;
+            return false;
+        }
+        !fenced
+    })
+}
+
+/// Prepare Markdown written by other tools for `parse`: space-indented and `*`/`+` lists
+/// become tab-indented bullets, and every heading starts a new block
+pub fn normalize_markdown(body: &str) -> String {
+    let list_item = |line: &str| {
+        let rest = line.trim_start_matches([' ', '\t']);
+        (rest.starts_with("- ") || rest.starts_with("* ") || rest.starts_with("+ "))
+            .then(|| line.len() - rest.len())
+    };
+    let unit = lines_outside_fences(body)
+        .filter(|line| !line.starts_with('\t'))
+        .filter_map(list_item)
+        .filter(|indent| *indent > 0)
+        .min()
+        .unwrap_or(4);
+
+    let mut out = String::with_capacity(body.len());
+    let mut fenced = false;
+    let mut in_paragraph = false;
+    for line in body.split_inclusive('\n') {
+        let was_fenced = fenced;
+        if line.trim_start().starts_with("```") {
+            fenced = !fenced;
+        }
+        if !was_fenced && in_paragraph && heading_level(line.trim_end()).is_some() {
+            out.push('\n');
+        }
+        in_paragraph = was_fenced || !(line.trim().is_empty() || line.starts_with([' ', '\t']) || list_item(line).is_some());
+        match list_item(line).filter(|_| !fenced) {
+            Some(indent) => {
+                let whitespace = &line[..indent];
+                let depth = whitespace.matches('\t').count() + whitespace.matches(' ').count() / unit;
+                out.push_str(&"\t".repeat(depth));
+                out.push_str("- ");
+                out.push_str(&line[indent + 2..]);
+            }
+            None => out.push_str(line),
+        }
+    }
+    out
+}
+
+/// Level of an ATX heading such as `## Title`
+pub fn heading_level(content: &str) -> Option<usize> {
+    let level = content.chars().take_while(|c| *c == '#').count();
+    let rest = &content[level..];
+    ((1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(' '))).then(|| level)
+}
+
+/// Make each heading the parent of the blocks up to the next heading of the same or higher level
+pub fn nest_sections(blocks: Vec<OutlineBlock>) -> Vec<OutlineBlock> {
+    let mut roots = Vec::new();
+    let mut open: Vec<(usize, OutlineBlock)> = Vec::new();
+
+    fn close(open: &mut Vec<(usize, OutlineBlock)>, roots: &mut Vec<OutlineBlock>) {
+        if let Some((_, heading)) = open.pop() {
+            match open.last_mut() {
+                Some((_, parent)) => parent.children.push(heading),
+                None => roots.push(heading),
+            }
+        }
+    }
+
+    for block in blocks {
+        match heading_level(&block.content) {
+            Some(level) => {
+                while open.last().map_or(false, |(open_level, _)| *open_level >= level) {
+                    close(&mut open, &mut roots);
+                }
+                open.push((level, block));
+            }
+            None => match open.last_mut() {
+                Some((_, heading)) => heading.children.push(block),
+                None => roots.push(block),
+            },
+        }
+    }
+    while !open.is_empty() {
+        close(&mut open, &mut roots);
+    }
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn block(content: &str, properties: &[(&str, &str)], children: Vec<OutlineBlock>) -> OutlineBlock {
+        OutlineBlock {
+            content: content.to_string(),
+            properties: properties.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            children,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_nested_outline_with_properties() {
+        let markdown = "- **Project** [site](https://example.com)\n  status:: active\n  owner:: 小明\n  ![diagram](assets/a.png)\n\t- | a | b |\n\t  |---|---|\n\t\t- `code` and _emphasis_\n- second\n";
+        let blocks = parse(markdown);
+
+        assert_eq!(blocks, vec![
+            block(
+                "**Project** [site](https://example.com)\n![diagram](assets/a.png)",
+                &[("status", "active"), ("owner", "小明")],
+                vec![block("| a | b |\n|---|---|", &[], vec![block("`code` and _emphasis_", &[], vec![])])],
+            ),
+            block("second", &[], vec![]),
+        ]);
+        assert_eq!(render(&blocks), markdown);
+    }
+
+    #[test]
+    fn test_plain_markdown_becomes_top_level_blocks() {
+        let markdown = "# Title\nIntro line\nstill intro\n\n```rust\nfn main() {}\n\n```\n- item\n";
+        let blocks = parse(markdown);
+
+        let contents: Vec<&str> = blocks.iter().map(|b| b.content.as_str()).collect();
+        assert_eq!(contents, vec!["# Title", "Intro line\nstill intro", "```rust\nfn main() {}\n\n```", "item"]);
+        let normalized = render(&blocks);
+        assert_eq!(render(&parse(&normalized)), normalized);
+    }
+
+    #[test]
+    fn test_properties_json_keeps_order() {
+        let properties = vec![("zeta".to_string(), "1".to_string()), ("alpha".to_string(), "\"quoted\"".to_string())];
+        let json = properties_to_json(&properties).unwrap();
+        assert_eq!(properties_from_json(&json), properties);
+        assert_eq!(properties_from_json(r#"{"done": true, "n": 2}"#), vec![
+            ("done".to_string(), "true".to_string()),
+            ("n".to_string(), "2".to_string()),
+        ]);
+        assert!(properties_to_json(&[]).is_none());
+    }
+
+    fn line() -> impl Strategy<Value = String> {
+        prop_oneof![
+            "[^\n\r]{0,24}",
+            "[a-z #*_`!|:/.()\\[\\]-]{0,32}",
+            Just("**bold** *em* [link](https://example.com/a?b=c) ![img](x.png)".to_string()),
+            Just("| col | col |".to_string()),
+        ]
+    }
+
+    fn content() -> impl Strategy<Value = String> {
+        (line(), prop::collection::vec(line(), 0..4))
+            .prop_filter("second line would read as a property", |(_, rest)| {
+                rest.first().map_or(true, |second| property_line(second).is_none())
+            })
+            .prop_map(|(first, rest)| std::iter::once(first).chain(rest).collect::<Vec<_>>().join("\n"))
+    }
+
+    fn outline() -> impl Strategy<Value = Vec<OutlineBlock>> {
+        let property = ("[a-z][a-z0-9_-]{0,8}", "[^\n\r]{0,16}");
+        let leaf = (content(), prop::collection::vec(property, 0..3))
+            .prop_map(|(content, properties)| OutlineBlock { content, properties, ..Default::default() });
+        let tree = leaf.prop_recursive(4, 32, 4, |inner| {
+            (inner.clone(), prop::collection::vec(inner, 0..4))
+                .prop_map(|(mut block, children)| {
+                    block.children = children;
+                    block
+                })
+        });
+        prop::collection::vec(tree, 0..5)
+    }
+
+    proptest! {
+        #[test]
+        fn prop_outline_round_trips(blocks in outline()) {
+            let markdown = render(&blocks);
+            let parsed = parse(&markdown);
+            prop_assert_eq!(&parsed, &blocks);
+            prop_assert_eq!(render(&parsed), markdown);
+        }
+
+        #[test]
+        fn prop_normalization_is_stable(markdown in "[-\t\n #`:ab*]{0,120}") {
+            let normalized = render(&parse(&markdown));
+            prop_assert_eq!(render(&parse(&normalized)), normalized);
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Importer for Roam Research JSON exports.
+//!
+//! The export is an array of pages, each with a tree of `children` blocks. Roam's
+//! `uid`s are only used while importing: `((uid))` block references are rewritten
+//! to the new block ids, and `:block/refs` plus inline `[[Page]]` references are
+//! recorded in the `links` table. Daily notes (uid `MM-DD-YYYY`) become journal
+//! pages, and `create-time`/`edit-time` are kept on pages and blocks.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use serde::Deserialize;
+
+use super::{delimited, FileOperations, ImportResult};
+use crate::database::Database;
+use crate::models::{CreateBlockRequest, CreateLinkRequest, CreatePageRequest, UpdateBlockRequest};
+
+#[derive(Debug, Deserialize)]
+struct RoamPage {
+    title: String,
+    uid: Option<String>,
+    #[serde(rename = "create-time")]
+    create_time: Option<i64>,
+    #[serde(rename = "edit-time")]
+    edit_time: Option<i64>,
+    #[serde(default)]
+    children: Vec<RoamBlock>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RoamBlock {
+    string: String,
+    uid: Option<String>,
+    #[serde(rename = "create-time")]
+    create_time: Option<i64>,
+    #[serde(rename = "edit-time")]
+    edit_time: Option<i64>,
+    heading: Option<usize>,
+    #[serde(rename = ":block/refs", alias = "refs", default)]
+    refs: Vec<RoamRef>,
+    #[serde(default)]
+    children: Vec<RoamBlock>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RoamRef {
+    #[serde(rename = ":block/uid", alias = "uid")]
+    uid: String,
+}
+
+/// What a Roam uid or title points at after import
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Target<'a> {
+    Page(&'a str),
+    Block(&'a str),
+}
+
+#[allow(dead_code)]
+impl FileOperations {
+    /// Import a Roam Research JSON export
+    pub async fn import_roam_json(
+        db: &Database,
+        file_path: &Path,
+        graph_id: &str,
+    ) -> Result<ImportResult> {
+        let pages: Vec<RoamPage> = serde_json::from_str(&fs::read_to_string(file_path)?)?;
+
+        let mut result = ImportResult {
+            pages_imported: 0,
+            blocks_imported: 0,
+            errors: Vec::new(),
+        };
+
+        // Page ids by Roam uid and by lowercase title
+        let mut page_ids: HashMap<String, String> = HashMap::new();
+        let mut page_titles: HashMap<String, String> = HashMap::new();
+        let mut created_pages = Vec::new();
+        for page in &pages {
+            let journal_date = page.uid.as_deref().and_then(|uid| NaiveDate::parse_from_str(uid, "%m-%d-%Y").ok());
+            let page_request = CreatePageRequest {
+                graph_id: graph_id.to_string(),
+                name: page.title.clone(),
+                title: None,
+                properties: None,
+                tags: Some("[]".to_string()),
+                is_journal: Some(journal_date.is_some()),
+                journal_date: journal_date.map(|date| date.format("%Y-%m-%d").to_string()),
+            };
+            match db.create_page(page_request).await {
+                Ok(created) => {
+                    result.pages_imported += 1;
+                    if let Some(uid) = &page.uid {
+                        page_ids.insert(uid.clone(), created.id.clone());
+                    }
+                    page_titles.insert(page.title.to_lowercase(), created.id.clone());
+                    created_pages.push((created.id, page));
+                }
+                Err(e) => result.errors.push(format!("{}: failed to create page: {}", page.title, e)),
+            }
+        }
+
+        // Create the block trees, parents first
+        let mut block_ids: HashMap<String, String> = HashMap::new();
+        let mut created_blocks: Vec<(String, &RoamPage, &RoamBlock)> = Vec::new();
+        for (page_id, page) in &created_pages {
+            let mut pending: Vec<(Option<String>, usize, &RoamBlock)> = page.children.iter()
+                .enumerate()
+                .rev()
+                .map(|(index, block)| (None, index, block))
+                .collect();
+
+            while let Some((parent_id, index, block)) = pending.pop() {
+                let content = match block.heading {
+                    Some(level) if (1..=6).contains(&level) => format!("{} {}", "#".repeat(level), block.string),
+                    _ => block.string.clone(),
+                };
+                let block_request = CreateBlockRequest {
+                    graph_id: graph_id.to_string(),
+                    page_id: page_id.clone(),
+                    content,
+                    parent_id,
+                    properties: None,
+                    refs: Some("[]".to_string()),
+                    order: Some(index as i32),
+                };
+                match db.create_block(block_request).await {
+                    Ok(created) => {
+                        result.blocks_imported += 1;
+                        if let Some(uid) = &block.uid {
+                            block_ids.insert(uid.clone(), created.id.clone());
+                        }
+                        pending.extend(block.children.iter()
+                            .enumerate()
+                            .rev()
+                            .map(|(index, child)| (Some(created.id.clone()), index, child)));
+                        created_blocks.push((created.id, page, block));
+                    }
+                    Err(e) => result.errors.push(format!("{}: failed to create block: {}", page.title, e)),
+                }
+            }
+        }
+
+        let resolve_uid = |uid: &str| {
+            block_ids.get(uid).map(|id| Target::Block(id))
+                .or_else(|| page_ids.get(uid).map(|id| Target::Page(id)))
+        };
+
+        // With every block created, references can point at their new ids
+        for (block_id, page, block) in &created_blocks {
+            let rewritten = rewrite_block_refs(&block.string, &block_ids);
+            if rewritten != block.string {
+                let content = match block.heading {
+                    Some(level) if (1..=6).contains(&level) => format!("{} {}", "#".repeat(level), rewritten),
+                    _ => rewritten,
+                };
+                let update = UpdateBlockRequest {
+                    id: block_id.clone(),
+                    content: Some(content),
+                    parent_id: None,
+                    properties: None,
+                    refs: None,
+                    order: None,
+                    collapsed: None,
+                };
+                if let Err(e) = db.update_block(update).await {
+                    result.errors.push(format!("{}: failed to update block references: {}", page.title, e));
+                }
+            }
+
+            let mut targets: Vec<Target> = Vec::new();
+            let referenced = block.refs.iter().filter_map(|roam_ref| resolve_uid(&roam_ref.uid))
+                .chain(delimited(&block.string, "((", "))").filter_map(&resolve_uid))
+                .chain(delimited(&block.string, "[[", "]]").filter_map(|title| {
+                    page_titles.get(&title.to_lowercase()).map(|id| Target::Page(id))
+                }));
+            for target in referenced {
+                if !targets.contains(&target) {
+                    targets.push(target);
+                }
+            }
+
+            for (position, target) in targets.into_iter().enumerate() {
+                let (target_type, target_id, link_type) = match target {
+                    Target::Page(id) => ("page", id, "page-reference"),
+                    Target::Block(id) => ("block", id, "block-reference"),
+                };
+                let request = CreateLinkRequest {
+                    source_type: "block".to_string(),
+                    source_id: block_id.clone(),
+                    target_type: target_type.to_string(),
+                    target_id: target_id.to_string(),
+                    link_type: link_type.to_string(),
+                    context: None,
+                    position: Some(position as i32),
+                };
+                if let Err(e) = db.create_link(request).await {
+                    result.errors.push(format!("{}: failed to record link: {}", page.title, e));
+                }
+            }
+
+            Self::restore_timestamps(db, "blocks", block_id, roam_time(block.create_time), roam_time(block.edit_time)).await?;
+        }
+
+        for (page_id, page) in &created_pages {
+            Self::restore_timestamps(db, "pages", page_id, roam_time(page.create_time), roam_time(page.edit_time)).await?;
+        }
+
+        Ok(result)
+    }
+}
+
+/// Roam stores times as milliseconds since the epoch
+fn roam_time(millis: Option<i64>) -> Option<DateTime<Utc>> {
+    millis.and_then(|millis| Utc.timestamp_millis_opt(millis).single())
+}
+
+fn rewrite_block_refs(text: &str, block_ids: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("((") {
+        let Some(end) = rest[start + 2..].find("))").map(|end| end + start + 2) else { break };
+        out.push_str(&rest[..start + 2]);
+        let uid = &rest[start + 2..end];
+        out.push_str(block_ids.get(uid).map_or(uid, String::as_str));
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const EXPORT: &str = r#"[
+      {
+        "title": "Reading List",
+        "uid": "page-reading",
+        "create-time": 1600000000000,
+        "edit-time": 1600000500000,
+        "children": [
+          {
+            "string": "Books",
+            "uid": "blk-books",
+            "heading": 2,
+            "create-time": 1600000100000,
+            "children": [
+              { "string": "The Pragmatic Programmer", "uid": "blk-prag", "create-time": 1600000200000 },
+              { "string": "SICP", "uid": "blk-sicp" }
+            ]
+          }
+        ]
+      },
+      {
+        "title": "October 18th, 2026",
+        "uid": "10-18-2026",
+        "children": [
+          {
+            "string": "Started ((blk-prag)) from [[Reading List]]",
+            "uid": "blk-daily",
+            ":block/refs": [{ ":block/uid": "blk-prag" }, { ":block/uid": "page-reading" }]
+          }
+        ]
+      }
+    ]"#;
+
+    #[tokio::test]
+    async fn test_import_roam_json() {
+        let dir = tempdir().unwrap();
+        let export_path = dir.path().join("roam.json");
+        fs::write(&export_path, EXPORT).unwrap();
+        let db = Database::new_with_path(dir.path().join("roam.db").to_str().unwrap()).await.unwrap();
+
+        let result = FileOperations::import_roam_json(&db, &export_path, "default").await.unwrap();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.pages_imported, 2);
+        assert_eq!(result.blocks_imported, 4);
+
+        let pages = db.get_all_pages().await.unwrap();
+        let reading = pages.iter().find(|page| page.name == "Reading List").unwrap();
+        assert_eq!(reading.created_at.timestamp_millis(), 1600000000000);
+        assert_eq!(reading.updated_at.timestamp_millis(), 1600000500000);
+        let daily = pages.iter().find(|page| page.name == "October 18th, 2026").unwrap();
+        assert!(daily.is_journal);
+        assert_eq!(daily.journal_date.as_deref(), Some("2026-10-18"));
+
+        let blocks = db.get_blocks_by_page(&reading.id).await.unwrap();
+        let books = blocks.iter().find(|block| block.content == "## Books").unwrap();
+        let prag = blocks.iter().find(|block| block.content == "The Pragmatic Programmer").unwrap();
+        assert_eq!(prag.parent_id.as_deref(), Some(books.id.as_str()));
+        assert_eq!(prag.created_at.timestamp_millis(), 1600000200000);
+
+        let daily_block = &db.get_blocks_by_page(&daily.id).await.unwrap()[0];
+        assert_eq!(daily_block.content, format!("Started (({})) from [[Reading List]]", prag.id));
+
+        let to_prag = db.get_links_to("block", &prag.id).await.unwrap();
+        assert_eq!(to_prag.len(), 1);
+        assert_eq!(to_prag[0].link_type, "block-reference");
+        assert_eq!(to_prag[0].source_id, daily_block.id);
+        assert_eq!(db.get_links_to("page", &reading.id).await.unwrap().len(), 1);
+    }
+}
@@ -385,4 +385,33 @@ No frontmatter here.
         // let result = restore_backup(&db, "/non/existent/backup.zip").await;
         // assert!(result.is_err(), "Restore from non-existent backup should fail");
     }
+
+    #[tokio::test]
+    async fn test_markdown_import_export_is_lossless() {
+        let (db, _temp_dir, graph_id) = create_test_database().await.unwrap();
+
+        let markdown_content = "---\ntitle: \"Round Trip\"\ncreated: \"2024-01-02 03:04:05\"\nupdated: \"2024-01-03 04:05:06\"\n---\n\n- **Plan** for [MingLog](https://example.com) with ![logo](assets/logo.png)\n  status:: active\n  owner:: 小明\n  | step | done |\n  |------|------|\n\t- first *child*\n\t\t- `nested` grandchild\n\t- second child\n\t  priority:: high\n- Closing thoughts\n";
+        let temp_dir = tempdir().unwrap();
+        let source = temp_dir.path().join("Round Trip.md");
+        fs::write(&source, markdown_content).unwrap();
+
+        let result = crate::file_operations::FileOperations::import_markdown_file(&db, &source, &graph_id).await.unwrap();
+        assert_eq!(result.pages_imported, 1);
+        assert_eq!(result.blocks_imported, 5);
+
+        let page = db.get_all_pages().await.unwrap()
+            .into_iter()
+            .find(|page| page.name == "Round Trip")
+            .unwrap();
+        let blocks = db.get_blocks_by_page(&page.id).await.unwrap();
+        let child = blocks.iter().find(|block| block.content == "second child").unwrap();
+        let parent = blocks.iter().find(|block| Some(&block.id) == child.parent_id.as_ref()).unwrap();
+        assert!(parent.content.starts_with("**Plan**"));
+        assert_eq!(child.order, 1);
+        assert_eq!(child.properties.as_deref(), Some(r#"{"priority":"high"}"#));
+
+        let export_dir = tempdir().unwrap();
+        let exported = crate::file_operations::FileOperations::export_page_to_markdown(&db, &page.id, export_dir.path()).await.unwrap();
+        assert_eq!(fs::read_to_string(exported).unwrap(), markdown_content);
+    }
 }
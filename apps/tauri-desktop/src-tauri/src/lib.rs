@@ -4,6 +4,11 @@
 pub mod database;
 pub mod models;
 pub mod error;
+// Modules that database.rs and models.rs depend on
+pub mod cards;
+pub mod encoding;
+pub mod sync;
+pub mod tasks;
 // pub mod monitoring; // 暂时禁用监控模块，避免依赖问题
 
 // Re-export commonly used types
@@ -1,8 +1,10 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod cards;
 mod commands;
 mod database;
+mod encoding;
 mod error;
 // mod error_reporting; // 暂时禁用，避免Sentry依赖问题
 // mod error_testing; // 暂时禁用，避免Sentry依赖问题
@@ -12,13 +14,13 @@ mod models;
 mod state;
 mod file_operations;
 mod sync;
+mod tasks;
 
 use commands::*;
 use database::Database;
 use state::AppState;
 use std::sync::Arc;
 use tauri::{Manager, SystemTray, SystemTrayMenu, SystemTrayMenuItem, SystemTrayEvent, CustomMenuItem};
-use tokio::sync::Mutex;
 
 #[tokio::main]
 async fn main() {
@@ -44,10 +46,27 @@ async fn main() {
                         // 预加载关键数据（最近访问的页面）
                         let _ = db.get_recent_pages(5).await;
 
-                        let state = AppState {
-                            db: Arc::new(Mutex::new(db)),
-                            sync_manager: Arc::new(Mutex::new(sync::WebDAVSyncManager::new())),
-                        };
+                        let state = AppState::from_database(db);
+
+                        // 启动后台自动同步
+                        state.sync_scheduler.set_listener(Arc::new(
+                            sync::TauriSyncEventListener::new(app_handle.clone()),
+                        ));
+                        state.sync_scheduler.start();
+
+                        // 启动自动备份
+                        state.backup_scheduler.start();
+                        state.pitr_scheduler.start();
+
+                        // 启动任务提醒，补发离线期间错过的提醒
+                        state.reminder_scheduler.set_listener(Arc::new(
+                            tasks::reminders::TauriReminderListener::new(app_handle.clone()),
+                        ));
+                        state.reminder_scheduler.start();
+
+                        // 任务和块变化后刷新已订阅的日历文件
+                        state.calendar_feed.start();
+
                         app_handle.manage(state);
 
                         let startup_time = startup_start.elapsed();
@@ -149,11 +168,82 @@ async fn main() {
             get_graph_data,
             create_sample_graph_data,
 
+            // Task commands
+            commands::tasks::create_task,
+            commands::tasks::get_task,
+            commands::tasks::get_tasks,
+            commands::tasks::update_task,
+            commands::tasks::delete_task,
+            commands::tasks::search_tasks,
+            commands::tasks::preview_occurrences,
+            commands::tasks::preview_task_occurrences,
+
+            // Task dependency commands
+            commands::tasks::add_task_dependency,
+            commands::tasks::remove_task_dependency,
+            commands::tasks::get_task_blockers,
+            commands::tasks::get_blocked_tasks,
+            commands::tasks::get_project_schedule,
+
+            // Reminder commands
+            commands::tasks::add_task_reminder,
+            commands::tasks::get_task_reminders,
+            commands::tasks::delete_reminder,
+            commands::tasks::snooze_reminder,
+
+            // Flashcard commands
+            commands::cards::get_due_cards,
+            commands::cards::grade_card,
+            commands::cards::get_card_stats,
+
+            // GTD commands
+            commands::tasks::get_inbox_tasks,
+            commands::tasks::get_next_actions,
+            commands::tasks::get_waiting_tasks,
+            commands::tasks::get_overdue_tasks,
+            commands::tasks::get_tasks_due_this_week,
+            commands::tasks::get_someday_tasks,
+            commands::tasks::get_weekly_review,
+
+            // Project commands
+            commands::tasks::create_project,
+            commands::tasks::get_project,
+            commands::tasks::get_projects,
+            commands::tasks::update_project,
+            commands::tasks::delete_project,
+            commands::tasks::search_projects,
+            commands::tasks::recompute_project_stats,
+
+            // Time tracking commands
+            commands::tasks::create_time_entry,
+            commands::tasks::get_time_entries,
+            commands::tasks::stop_time_entry,
+            commands::tasks::delete_time_entry,
+            commands::tasks::start_timer,
+            commands::tasks::stop_timer,
+            commands::tasks::get_running_timer,
+            commands::tasks::get_time_report,
+            commands::tasks::export_time_report_csv,
+
             // File operations commands
             import_markdown_file,
+            import_logseq_graph,
+            import_obsidian_vault,
+            import_from_path,
             export_page_to_markdown,
+            export_page_to_opml,
+            export_graph_to_html,
+            import_opml,
+            export_ics,
+            import_ics,
+            refresh_calendar_feed,
             bulk_export_pages,
             create_backup,
+            verify_backup,
+            restore_backup,
+            list_backups,
+            backup_now,
+            restore_to,
 
             // File dialog commands
             open_file_dialog,
@@ -166,9 +256,24 @@ async fn main() {
             // WebDAV sync commands
             configure_webdav_sync,
             get_webdav_config,
+            configure_folder_sync,
+            get_folder_sync_config,
+            configure_s3_sync,
+            get_s3_sync_config,
             test_webdav_connection,
             start_webdav_sync,
             stop_webdav_sync,
+            get_block_version_vector,
+            get_block_ops_since,
+            apply_block_ops,
+            exchange_block_ops,
+            start_p2p_sync,
+            stop_p2p_sync,
+            begin_p2p_pairing,
+            discover_p2p_peers,
+            sync_p2p_peer,
+            get_p2p_peers,
+            remove_p2p_peer,
             get_sync_status,
             get_sync_conflicts,
             resolve_sync_conflict,
@@ -3,6 +3,8 @@ use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, Row};
 use uuid::Uuid;
 
+use crate::tasks::recurrence::Recurrence;
+
 // Graph model - represents a workspace/knowledge graph
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Graph {
@@ -523,25 +525,197 @@ impl Tag {
     }
 }
 
+// Task status values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TaskStatus {
+    Inbox,
+    Todo,
+    InProgress,
+    Waiting,
+    Someday,
+    Done,
+    Cancelled,
+}
+
+impl TaskStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskStatus::Inbox => "inbox",
+            TaskStatus::Todo => "todo",
+            TaskStatus::InProgress => "in-progress",
+            TaskStatus::Waiting => "waiting",
+            TaskStatus::Someday => "someday",
+            TaskStatus::Done => "done",
+            TaskStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl Default for TaskStatus {
+    fn default() -> Self {
+        TaskStatus::Todo
+    }
+}
+
+impl std::str::FromStr for TaskStatus {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "inbox" => Ok(TaskStatus::Inbox),
+            "todo" => Ok(TaskStatus::Todo),
+            "in-progress" => Ok(TaskStatus::InProgress),
+            "waiting" => Ok(TaskStatus::Waiting),
+            "someday" => Ok(TaskStatus::Someday),
+            "done" => Ok(TaskStatus::Done),
+            "cancelled" => Ok(TaskStatus::Cancelled),
+            _ => Err(format!("Unknown task status '{}'", value)),
+        }
+    }
+}
+
+// Task priority values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskPriority {
+    Low,
+    Medium,
+    High,
+    Urgent,
+}
+
+impl TaskPriority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskPriority::Low => "low",
+            TaskPriority::Medium => "medium",
+            TaskPriority::High => "high",
+            TaskPriority::Urgent => "urgent",
+        }
+    }
+}
+
+impl Default for TaskPriority {
+    fn default() -> Self {
+        TaskPriority::Medium
+    }
+}
+
+impl std::str::FromStr for TaskPriority {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "low" => Ok(TaskPriority::Low),
+            "medium" => Ok(TaskPriority::Medium),
+            "high" => Ok(TaskPriority::High),
+            "urgent" => Ok(TaskPriority::Urgent),
+            _ => Err(format!("Unknown task priority '{}'", value)),
+        }
+    }
+}
+
+// Project status values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProjectStatus {
+    Active,
+    OnHold,
+    Completed,
+    Cancelled,
+}
+
+impl ProjectStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProjectStatus::Active => "active",
+            ProjectStatus::OnHold => "on-hold",
+            ProjectStatus::Completed => "completed",
+            ProjectStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl Default for ProjectStatus {
+    fn default() -> Self {
+        ProjectStatus::Active
+    }
+}
+
+impl std::str::FromStr for ProjectStatus {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "active" => Ok(ProjectStatus::Active),
+            "on-hold" => Ok(ProjectStatus::OnHold),
+            "completed" => Ok(ProjectStatus::Completed),
+            "cancelled" => Ok(ProjectStatus::Cancelled),
+            _ => Err(format!("Unknown project status '{}'", value)),
+        }
+    }
+}
+
+// Decode a text column into one of the enums above. Values written by an older
+// or newer version fall back to the column default rather than failing the query
+fn decode_enum<T: std::str::FromStr<Err = String> + Default>(row: &sqlx::sqlite::SqliteRow, column: &str) -> Result<T, sqlx::Error> {
+    let value: String = row.try_get(column)?;
+    Ok(value.parse().unwrap_or_else(|e: String| {
+        log::warn!("{}, using the default", e);
+        T::default()
+    }))
+}
+
+// Decode an RFC 3339 text column
+pub(crate) fn decode_datetime(row: &sqlx::sqlite::SqliteRow, column: &str) -> Result<DateTime<Utc>, sqlx::Error> {
+    let value: String = row.try_get(column)?;
+    DateTime::parse_from_rfc3339(&value)
+        .map(|value| value.with_timezone(&Utc))
+        .map_err(|e| sqlx::Error::ColumnDecode {
+            index: column.to_string(),
+            source: Box::new(e),
+        })
+}
+
+pub(crate) fn decode_optional_datetime(row: &sqlx::sqlite::SqliteRow, column: &str) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    let value: Option<String> = row.try_get(column)?;
+    value.map(|_| decode_datetime(row, column)).transpose()
+}
+
+// Decode the recurrence column, ignoring rules this version can't interpret
+fn decode_recurrence(row: &sqlx::sqlite::SqliteRow) -> Result<Option<Recurrence>, sqlx::Error> {
+    let value: Option<String> = row.try_get("recurrence")?;
+    Ok(value.filter(|value| !value.trim().is_empty()).and_then(|value| match Recurrence::parse(&value) {
+        Ok(recurrence) => Some(recurrence),
+        Err(e) => {
+            log::warn!("Ignoring task recurrence '{}': {}", value, e);
+            None
+        }
+    }))
+}
+
 // Task model - represents a task in the task management system
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
     pub id: String,
     pub title: String,
     pub description: Option<String>,
-    pub status: String, // inbox, todo, in-progress, waiting, someday, done, cancelled
-    pub priority: String, // low, medium, high, urgent
+    pub status: TaskStatus,
+    pub priority: TaskPriority,
     pub due_date: Option<DateTime<Utc>>,
+    pub scheduled_date: Option<DateTime<Utc>>,
     pub completed_at: Option<DateTime<Utc>>,
     pub estimated_time: Option<i32>, // minutes
-    pub actual_time: Option<i32>, // minutes
+    pub actual_time: Option<i32>, // minutes, rolled up from time entries
     pub project_id: Option<String>,
     pub parent_task_id: Option<String>,
     pub linked_notes: String, // JSON array of note IDs
     pub linked_files: String, // JSON array of file IDs
     pub tags: String, // JSON array of tags
     pub contexts: String, // JSON array of GTD contexts
-    pub recurrence: Option<String>, // JSON object for recurrence settings
+    pub recurrence: Option<Recurrence>,
+    pub block_id: Option<String>, // block the task is written in, if any
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub created_by: Option<String>,
@@ -553,7 +727,7 @@ pub struct Project {
     pub id: String,
     pub name: String,
     pub description: Option<String>,
-    pub status: String, // active, on-hold, completed, cancelled
+    pub status: ProjectStatus,
     pub color: Option<String>,
     pub start_date: Option<DateTime<Utc>>,
     pub due_date: Option<DateTime<Utc>>,
@@ -580,6 +754,26 @@ pub struct TimeEntry {
     pub created_at: DateTime<Utc>,
 }
 
+// Reminder model - a notification `offset_minutes` before a task is due
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reminder {
+    pub id: String,
+    pub task_id: String,
+    pub offset_minutes: i32,
+    pub remind_at: DateTime<Utc>,
+    pub snoozed_until: Option<DateTime<Utc>>,
+    pub fired_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Reminder {
+    /// When the reminder fires next, taking snoozing into account
+    pub fn next_at(&self) -> DateTime<Utc> {
+        self.snoozed_until.unwrap_or(self.remind_at)
+    }
+}
+
 // FromRow implementations for Task
 impl FromRow<'_, sqlx::sqlite::SqliteRow> for Task {
     fn from_row(row: &sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
@@ -606,6 +800,12 @@ impl FromRow<'_, sqlx::sqlite::SqliteRow> for Task {
             None
         };
 
+        let scheduled_date = if let Ok(scheduled_date_str) = row.try_get::<Option<String>, _>("scheduled_date") {
+            scheduled_date_str.and_then(|s| DateTime::parse_from_rfc3339(&s).ok().map(|dt| dt.with_timezone(&Utc)))
+        } else {
+            None
+        };
+
         let completed_at = if let Ok(completed_at_str) = row.try_get::<Option<String>, _>("completed_at") {
             completed_at_str.and_then(|s| DateTime::parse_from_rfc3339(&s).ok().map(|dt| dt.with_timezone(&Utc)))
         } else {
@@ -616,9 +816,10 @@ impl FromRow<'_, sqlx::sqlite::SqliteRow> for Task {
             id: row.try_get("id")?,
             title: row.try_get("title")?,
             description: row.try_get("description")?,
-            status: row.try_get("status")?,
-            priority: row.try_get("priority")?,
+            status: decode_enum(row, "status")?,
+            priority: decode_enum(row, "priority")?,
             due_date,
+            scheduled_date,
             completed_at,
             estimated_time: row.try_get("estimated_time")?,
             actual_time: row.try_get("actual_time")?,
@@ -628,7 +829,8 @@ impl FromRow<'_, sqlx::sqlite::SqliteRow> for Task {
             linked_files: row.try_get("linked_files").unwrap_or_else(|_| "[]".to_string()),
             tags: row.try_get("tags").unwrap_or_else(|_| "[]".to_string()),
             contexts: row.try_get("contexts").unwrap_or_else(|_| "[]".to_string()),
-            recurrence: row.try_get("recurrence")?,
+            recurrence: decode_recurrence(row)?,
+            block_id: row.try_get("block_id")?,
             created_at,
             updated_at,
             created_by: row.try_get("created_by")?,
@@ -678,7 +880,7 @@ impl FromRow<'_, sqlx::sqlite::SqliteRow> for Project {
             id: row.try_get("id")?,
             name: row.try_get("name")?,
             description: row.try_get("description")?,
-            status: row.try_get("status")?,
+            status: decode_enum(row, "status")?,
             color: row.try_get("color")?,
             start_date,
             due_date,
@@ -733,13 +935,29 @@ impl FromRow<'_, sqlx::sqlite::SqliteRow> for TimeEntry {
     }
 }
 
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for Reminder {
+    fn from_row(row: &sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Reminder {
+            id: row.try_get("id")?,
+            task_id: row.try_get("task_id")?,
+            offset_minutes: row.try_get("offset_minutes")?,
+            remind_at: decode_datetime(row, "remind_at")?,
+            snoozed_until: decode_optional_datetime(row, "snoozed_until")?,
+            fired_at: decode_optional_datetime(row, "fired_at")?,
+            created_at: decode_datetime(row, "created_at")?,
+            updated_at: decode_datetime(row, "updated_at")?,
+        })
+    }
+}
+
 // Request structures for task management
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateTaskRequest {
     pub title: String,
     pub description: Option<String>,
-    pub priority: Option<String>,
+    pub priority: Option<TaskPriority>,
     pub due_date: Option<DateTime<Utc>>,
+    pub scheduled_date: Option<DateTime<Utc>>,
     pub estimated_time: Option<i32>,
     pub project_id: Option<String>,
     pub parent_task_id: Option<String>,
@@ -747,6 +965,7 @@ pub struct CreateTaskRequest {
     pub linked_files: Option<Vec<String>>,
     pub tags: Option<Vec<String>>,
     pub contexts: Option<Vec<String>>,
+    pub recurrence: Option<Recurrence>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -754,17 +973,18 @@ pub struct UpdateTaskRequest {
     pub id: String,
     pub title: Option<String>,
     pub description: Option<String>,
-    pub status: Option<String>,
-    pub priority: Option<String>,
+    pub status: Option<TaskStatus>,
+    pub priority: Option<TaskPriority>,
     pub due_date: Option<DateTime<Utc>>,
+    pub scheduled_date: Option<DateTime<Utc>>,
     pub estimated_time: Option<i32>,
-    pub actual_time: Option<i32>,
     pub project_id: Option<String>,
     pub parent_task_id: Option<String>,
     pub linked_notes: Option<Vec<String>>,
     pub linked_files: Option<Vec<String>>,
     pub tags: Option<Vec<String>>,
     pub contexts: Option<Vec<String>>,
+    pub recurrence: Option<Recurrence>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -783,7 +1003,7 @@ pub struct UpdateProjectRequest {
     pub id: String,
     pub name: Option<String>,
     pub description: Option<String>,
-    pub status: Option<String>,
+    pub status: Option<ProjectStatus>,
     pub color: Option<String>,
     pub start_date: Option<DateTime<Utc>>,
     pub due_date: Option<DateTime<Utc>>,
@@ -798,3 +1018,63 @@ pub struct CreateTimeEntryRequest {
     pub end_time: Option<DateTime<Utc>>,
     pub description: Option<String>,
 }
+
+// Link model - a reference from a page or block to another page or block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Link {
+    pub id: String,
+    pub source_type: String, // "page" or "block"
+    pub source_id: String,
+    pub target_type: String, // "page" or "block"
+    pub target_id: String,
+    pub link_type: String, // "page-reference" or "block-reference"
+    pub context: Option<String>,
+    pub position: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for Link {
+    fn from_row(row: &sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        let created_at_str: String = row.try_get("created_at")?;
+        let updated_at_str: String = row.try_get("updated_at")?;
+
+        let created_at = DateTime::parse_from_rfc3339(&created_at_str)
+            .map_err(|e| sqlx::Error::ColumnDecode {
+                index: "created_at".to_string(),
+                source: Box::new(e),
+            })?
+            .with_timezone(&Utc);
+
+        let updated_at = DateTime::parse_from_rfc3339(&updated_at_str)
+            .map_err(|e| sqlx::Error::ColumnDecode {
+                index: "updated_at".to_string(),
+                source: Box::new(e),
+            })?
+            .with_timezone(&Utc);
+
+        Ok(Link {
+            id: row.try_get("id")?,
+            source_type: row.try_get("source_type")?,
+            source_id: row.try_get("source_id")?,
+            target_type: row.try_get("target_type")?,
+            target_id: row.try_get("target_id")?,
+            link_type: row.try_get("link_type")?,
+            context: row.try_get("context")?,
+            position: row.try_get("position")?,
+            created_at,
+            updated_at,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateLinkRequest {
+    pub source_type: String,
+    pub source_id: String,
+    pub target_type: String,
+    pub target_id: String,
+    pub link_type: String,
+    pub context: Option<String>,
+    pub position: Option<i32>,
+}
@@ -1,5 +1,9 @@
 use crate::database::Database;
-use crate::sync::WebDAVSyncManager;
+use crate::file_operations::backup::pitr::PitrScheduler;
+use crate::file_operations::backup::scheduler::BackupScheduler;
+use crate::file_operations::ics::CalendarFeed;
+use crate::sync::{P2PServer, SyncScheduler, WebDAVSyncManager};
+use crate::tasks::reminders::ReminderScheduler;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -7,16 +11,38 @@ use tokio::sync::Mutex;
 pub struct AppState {
     pub db: Arc<Mutex<Database>>,
     pub sync_manager: Arc<Mutex<WebDAVSyncManager>>,
+    pub sync_scheduler: Arc<SyncScheduler>,
+    pub backup_scheduler: Arc<BackupScheduler>,
+    pub pitr_scheduler: Arc<PitrScheduler>,
+    pub reminder_scheduler: Arc<ReminderScheduler>,
+    pub calendar_feed: Arc<CalendarFeed>,
+    pub p2p_server: Mutex<Option<P2PServer>>,
 }
 
 impl AppState {
     #[allow(dead_code)]
     pub async fn new() -> crate::error::Result<Self> {
         let db = Database::new().await?;
-        let sync_manager = WebDAVSyncManager::new();
-        Ok(Self {
-            db: Arc::new(Mutex::new(db)),
-            sync_manager: Arc::new(Mutex::new(sync_manager)),
-        })
+        Ok(Self::from_database(db))
+    }
+
+    pub fn from_database(db: Database) -> Self {
+        let db = Arc::new(Mutex::new(db));
+        let sync_manager = Arc::new(Mutex::new(WebDAVSyncManager::new()));
+        let sync_scheduler = Arc::new(SyncScheduler::new(sync_manager.clone()).with_database(db.clone()));
+        let backup_scheduler = Arc::new(BackupScheduler::new(db.clone()));
+        let pitr_scheduler = Arc::new(PitrScheduler::new(db.clone()));
+        let reminder_scheduler = Arc::new(ReminderScheduler::new(db.clone()));
+        let calendar_feed = Arc::new(CalendarFeed::new(db.clone()));
+        Self {
+            db,
+            sync_manager,
+            sync_scheduler,
+            backup_scheduler,
+            pitr_scheduler,
+            reminder_scheduler,
+            calendar_feed,
+            p2p_server: Mutex::new(None),
+        }
     }
 }
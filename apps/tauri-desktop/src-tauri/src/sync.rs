@@ -1,8 +1,47 @@
+use crate::database::Database;
 use crate::error::{AppError, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use reqwest::{Client, Method};
+use std::path::PathBuf;
+use std::sync::Arc;
+use reqwest::Client;
+use tokio_util::sync::CancellationToken;
+
+mod backend;
+pub mod crdt;
+mod engine;
+mod local;
+mod op_exchange;
+mod p2p;
+mod s3;
+mod scheduler;
+mod webdav;
+mod xml;
+
+pub use backend::{RemoteEntry, SyncBackend};
+pub use local::{FolderSyncConfig, LocalFolderBackend};
+pub use op_exchange::{exchange_block_ops, OpExchangeResult};
+pub use p2p::{
+    discover_peers, get_paired_peers, remove_paired_peer, sync_with_peer, DiscoveredPeer, P2PServer, PairedPeer,
+    DEFAULT_P2P_PORT,
+};
+pub use s3::{S3Backend, S3Config};
+pub use scheduler::{SyncScheduler, TauriSyncEventListener};
+pub use webdav::WebDAVBackend;
+
+/// 只属于本设备的同步状态在 settings 表中的键：设备ID（HLC 节点ID）、已配对设备和
+/// 操作上传进度。复制到另一台设备会让两台设备共用节点ID、共用配对密钥，
+/// 所以备份、恢复、时间点恢复和数据导出都跳过它们
+pub const DEVICE_LOCAL_SETTINGS: [&str; 3] = [
+    crate::database::DEVICE_ID_KEY,
+    p2p::PEERS_KEY,
+    op_exchange::UPLOADED_UNTIL_KEY,
+];
+
+pub fn is_device_local_setting(key: &str) -> bool {
+    DEVICE_LOCAL_SETTINGS.contains(&key)
+}
 
 /// WebDAV同步配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -78,9 +117,15 @@ pub struct SyncResult {
 }
 
 /// WebDAV同步管理器
+///
+/// 同步目标通过 `SyncBackend` 抽象，WebDAV 与本地文件夹共用同一套比较/合并引擎。
 #[derive(Debug)]
 pub struct WebDAVSyncManager {
     config: Option<WebDAVConfig>,
+    folder_config: Option<FolderSyncConfig>,
+    s3_config: Option<S3Config>,
+    backend: Option<Arc<dyn SyncBackend>>,
+    local_dir: PathBuf,
     sync_status: SyncStatus,
     last_sync: Option<DateTime<Utc>>,
     file_sync_info: HashMap<String, FileSyncInfo>,
@@ -95,8 +140,17 @@ impl WebDAVSyncManager {
             .build()
             .unwrap_or_else(|_| Client::new());
 
+        let local_dir = dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("com.minglog.desktop")
+            .join("sync");
+
         Self {
             config: None,
+            folder_config: None,
+            s3_config: None,
+            backend: None,
+            local_dir,
             sync_status: SyncStatus::Idle,
             last_sync: None,
             file_sync_info: HashMap::new(),
@@ -108,6 +162,7 @@ impl WebDAVSyncManager {
     pub fn set_config(&mut self, config: WebDAVConfig) -> Result<()> {
         // 验证配置
         self.validate_config(&config)?;
+        self.set_backend(Arc::new(WebDAVBackend::new(config.clone(), self.http_client.clone())));
         self.config = Some(config);
         Ok(())
     }
@@ -117,6 +172,68 @@ impl WebDAVSyncManager {
         self.config.as_ref()
     }
 
+    /// 设置本地文件夹同步配置
+    pub fn set_folder_config(&mut self, config: FolderSyncConfig) -> Result<()> {
+        if config.folder_path.trim().is_empty() {
+            return Err(AppError::Sync("Folder path cannot be empty".to_string()));
+        }
+        let folder = PathBuf::from(&config.folder_path);
+        if folder == self.local_dir {
+            return Err(AppError::Sync("Sync folder must differ from the local sync directory".to_string()));
+        }
+        self.set_backend(Arc::new(LocalFolderBackend::new(folder)));
+        self.folder_config = Some(config);
+        Ok(())
+    }
+
+    /// 获取本地文件夹同步配置
+    pub fn get_folder_config(&self) -> Option<&FolderSyncConfig> {
+        self.folder_config.as_ref()
+    }
+
+    /// 设置S3兼容对象存储配置
+    pub fn set_s3_config(&mut self, config: S3Config) -> Result<()> {
+        S3Backend::validate_config(&config)?;
+        self.set_backend(Arc::new(S3Backend::new(config.clone(), self.http_client.clone())));
+        self.s3_config = Some(config);
+        Ok(())
+    }
+
+    /// 获取S3配置
+    pub fn get_s3_config(&self) -> Option<&S3Config> {
+        self.s3_config.as_ref()
+    }
+
+    /// 切换同步后端，同步记录只对原后端有效
+    fn set_backend(&mut self, backend: Arc<dyn SyncBackend>) {
+        self.config = None;
+        self.folder_config = None;
+        self.s3_config = None;
+        self.file_sync_info.clear();
+        self.backend = Some(backend);
+    }
+
+    /// 当前启用的自动同步间隔（秒）
+    pub fn auto_sync_interval(&self) -> Option<u64> {
+        let webdav = self.config.as_ref()
+            .filter(|config| config.enabled)
+            .and_then(|config| config.auto_sync_interval);
+        let folder = self.folder_config.as_ref()
+            .filter(|config| config.enabled)
+            .and_then(|config| config.auto_sync_interval);
+        let s3 = self.s3_config.as_ref()
+            .filter(|config| config.enabled)
+            .and_then(|config| config.auto_sync_interval);
+        webdav.or(folder).or(s3).filter(|secs| *secs > 0)
+    }
+
+    /// 设置本地同步目录
+    #[allow(dead_code)]
+    pub fn set_local_dir(&mut self, local_dir: impl Into<PathBuf>) {
+        self.local_dir = local_dir.into();
+        self.file_sync_info.clear();
+    }
+
     /// 验证WebDAV配置
     fn validate_config(&self, config: &WebDAVConfig) -> Result<()> {
         if config.server_url.is_empty() {
@@ -131,103 +248,92 @@ impl WebDAVSyncManager {
         Ok(())
     }
 
-    /// 测试WebDAV连接
+    /// 当前同步后端，供块操作交换等流程复用
+    pub fn backend(&self) -> Result<Arc<dyn SyncBackend>> {
+        self.backend.clone()
+            .ok_or_else(|| AppError::Sync("No sync backend configured".to_string()))
+    }
+
+    /// 测试同步后端连接
     pub async fn test_connection(&self) -> Result<bool> {
-        let config = self.config.as_ref()
-            .ok_or_else(|| AppError::Sync("No WebDAV configuration found".to_string()))?;
-
-        log::info!("Testing WebDAV connection to: {}", config.server_url);
-
-        // 构建测试URL
-        let test_url = format!("{}{}", config.server_url.trim_end_matches('/'), config.remote_path);
-
-        // 发送PROPFIND请求测试连接
-        let response = self.http_client
-            .request(Method::from_bytes(b"PROPFIND").unwrap(), &test_url)
-            .basic_auth(&config.username, Some(&config.password))
-            .header("Depth", "0")
-            .header("Content-Type", "application/xml")
-            .body(r#"<?xml version="1.0" encoding="utf-8"?>
-                <D:propfind xmlns:D="DAV:">
-                    <D:prop>
-                        <D:resourcetype/>
-                    </D:prop>
-                </D:propfind>"#)
-            .send()
-            .await
-            .map_err(|e| AppError::Sync(format!("Connection failed: {}", e)))?;
-
-        let is_success = response.status().is_success() || response.status().as_u16() == 207; // 207 Multi-Status
+        let backend = self.backend()?;
+        log::info!("Testing {} sync backend connection", backend.name());
 
+        let is_success = backend.test_connection().await?;
         if is_success {
-            log::info!("WebDAV connection test successful");
+            log::info!("Sync backend connection test successful");
         } else {
-            log::warn!("WebDAV connection test failed with status: {}", response.status());
+            log::warn!("Sync backend connection test failed");
         }
 
         Ok(is_success)
     }
 
     /// 开始同步
+    #[allow(dead_code)]
     pub async fn start_sync(&mut self, direction: SyncDirection) -> Result<SyncResult> {
+        self.start_sync_with_cancellation(direction, &CancellationToken::new()).await
+    }
+
+    /// 开始同步，`cancel` 被取消时中止正在进行的请求
+    pub async fn start_sync_with_cancellation(
+        &mut self,
+        direction: SyncDirection,
+        cancel: &CancellationToken,
+    ) -> Result<SyncResult> {
+        let mut job = self.begin_sync(&direction)?;
+        let outcome = job.run(direction, cancel).await;
+        self.finish_sync(job, outcome, cancel)
+    }
+
+    /// 标记为同步中并取出同步所需的状态，之后可以释放管理器的锁再执行 `SyncJob::run`
+    pub fn begin_sync(&mut self, direction: &SyncDirection) -> Result<SyncJob> {
         if self.sync_status == SyncStatus::Syncing {
             return Err(AppError::Sync("Sync already in progress".to_string()));
         }
 
-        let config = self.config.as_ref()
-            .ok_or_else(|| AppError::Sync("No WebDAV configuration found".to_string()))?;
-
+        let backend = self.backend()?;
+        log::info!("Starting {:?} sync with {} backend", direction, backend.name());
         self.sync_status = SyncStatus::Syncing;
-        let start_time = Utc::now();
-
-        let mut result = SyncResult {
-            status: SyncStatus::Syncing,
-            files_uploaded: 0,
-            files_downloaded: 0,
-            files_deleted: 0,
-            conflicts: Vec::new(),
-            errors: Vec::new(),
-            start_time,
-            end_time: None,
-        };
 
-        // 执行实际同步逻辑
-        match direction {
-            SyncDirection::Upload => {
-                log::info!("Starting upload sync to: {}", config.server_url);
-                result = self.upload_files(&mut result).await?;
-            }
-            SyncDirection::Download => {
-                log::info!("Starting download sync from: {}", config.server_url);
-                result = self.download_files(&mut result).await?;
-            }
-            SyncDirection::Bidirectional => {
-                log::info!("Starting bidirectional sync with: {}", config.server_url);
-                // 先下载，再上传，最后处理冲突
-                result = self.download_files(&mut result).await?;
-                result = self.upload_files(&mut result).await?;
-            }
+        Ok(SyncJob {
+            backend,
+            local_dir: self.local_dir.clone(),
+            file_sync_info: std::mem::take(&mut self.file_sync_info),
+            result: SyncResult {
+                status: SyncStatus::Syncing,
+                files_uploaded: 0,
+                files_downloaded: 0,
+                files_deleted: 0,
+                conflicts: Vec::new(),
+                errors: Vec::new(),
+                start_time: Utc::now(),
+                end_time: None,
+            },
+        })
+    }
+
+    /// 写回同步记录并更新同步状态
+    pub fn finish_sync(&mut self, job: SyncJob, outcome: Result<()>, cancel: &CancellationToken) -> Result<SyncResult> {
+        // 同步期间切换了后端时，旧后端的同步记录作废
+        if self.backend.as_ref().map_or(false, |backend| Arc::ptr_eq(backend, &job.backend)) {
+            self.file_sync_info = job.file_sync_info;
         }
 
-        result.status = SyncStatus::Success;
+        if let Err(e) = outcome {
+            self.sync_status = if cancel.is_cancelled() { SyncStatus::Idle } else { SyncStatus::Failed };
+            return Err(e);
+        }
+
+        let mut result = job.result;
+        result.status = if result.conflicts.is_empty() { SyncStatus::Success } else { SyncStatus::Conflict };
         result.end_time = Some(Utc::now());
-        self.sync_status = SyncStatus::Success;
+        self.sync_status = result.status.clone();
         self.last_sync = Some(Utc::now());
 
         Ok(result)
     }
 
-    /// 停止同步
-    pub fn stop_sync(&mut self) -> Result<()> {
-        if self.sync_status != SyncStatus::Syncing {
-            return Err(AppError::Sync("No sync in progress".to_string()));
-        }
-
-        self.sync_status = SyncStatus::Idle;
-        log::info!("Sync stopped by user");
-        Ok(())
-    }
-
     /// 获取同步状态
     pub fn get_sync_status(&self) -> SyncStatus {
         self.sync_status.clone()
@@ -241,17 +347,23 @@ impl WebDAVSyncManager {
 
     /// 解决同步冲突
     pub async fn resolve_conflict(&mut self, file_path: &str, resolution: ConflictResolution) -> Result<()> {
-        // TODO: 实现冲突解决逻辑
         log::info!("Resolving conflict for file: {} with resolution: {:?}", file_path, resolution);
-        
-        // 从冲突列表中移除已解决的冲突
-        if let Some(sync_info) = self.file_sync_info.get_mut(file_path) {
-            sync_info.sync_status = SyncStatus::Success;
-        }
 
+        let backend = self.backend()?;
+        let local = LocalFolderBackend::new(self.local_dir.clone());
+        engine::resolve(&local, backend.as_ref(), &mut self.file_sync_info, file_path, &resolution).await?;
+
+        if self.sync_status == SyncStatus::Conflict && self.get_conflicts().is_empty() {
+            self.sync_status = SyncStatus::Success;
+        }
         Ok(())
     }
 
+    /// 把当前后端的同步记录写回数据库
+    pub async fn save_file_sync_info(&self, database: &Database) -> Result<()> {
+        database.save_file_sync_info(&self.backend()?.location(), &self.file_sync_info).await
+    }
+
     /// 获取文件同步信息
     #[allow(dead_code)]
     pub fn get_file_sync_info(&self, file_path: &str) -> Option<&FileSyncInfo> {
@@ -273,77 +385,40 @@ impl WebDAVSyncManager {
         self.file_sync_info.clear();
         log::info!("Sync cache cleared");
     }
+}
 
+/// 一次进行中的文件同步，执行时不需要持有同步管理器的锁
+pub struct SyncJob {
+    backend: Arc<dyn SyncBackend>,
+    local_dir: PathBuf,
+    file_sync_info: HashMap<String, FileSyncInfo>,
+    result: SyncResult,
+}
 
-
-    /// 上传文件到WebDAV服务器
-    async fn upload_files(&self, result: &mut SyncResult) -> Result<SyncResult> {
-        let config = self.config.as_ref().unwrap();
-
-        // 这里应该从数据库获取需要上传的文件
-        // 为了演示，我们创建一个示例文件
-        let test_content = r#"{"version": "1.0", "type": "minglog_backup", "timestamp": "2024-01-01T00:00:00Z"}"#;
-        let remote_url = format!("{}{}/test_backup.json",
-            config.server_url.trim_end_matches('/'),
-            config.remote_path.trim_end_matches('/')
-        );
-
-        log::info!("Uploading test file to: {}", remote_url);
-
-        let response = self.http_client
-            .put(&remote_url)
-            .basic_auth(&config.username, Some(&config.password))
-            .header("Content-Type", "application/json")
-            .body(test_content)
-            .send()
-            .await
-            .map_err(|e| AppError::Sync(format!("Upload failed: {}", e)))?;
-
-        if response.status().is_success() || response.status().as_u16() == 201 {
-            result.files_uploaded += 1;
-            log::info!("File uploaded successfully");
-        } else {
-            let error_msg = format!("Upload failed with status: {}", response.status());
-            result.errors.push(error_msg.clone());
-            log::error!("{}", error_msg);
-        }
-
-        Ok(result.clone())
+impl SyncJob {
+    /// 当前同步使用的后端
+    pub fn backend(&self) -> Arc<dyn SyncBackend> {
+        self.backend.clone()
     }
 
-    /// 从WebDAV服务器下载文件
-    async fn download_files(&self, result: &mut SyncResult) -> Result<SyncResult> {
-        let config = self.config.as_ref().unwrap();
-
-        let remote_url = format!("{}{}/test_backup.json",
-            config.server_url.trim_end_matches('/'),
-            config.remote_path.trim_end_matches('/')
-        );
-
-        log::info!("Downloading file from: {}", remote_url);
-
-        let response = self.http_client
-            .get(&remote_url)
-            .basic_auth(&config.username, Some(&config.password))
-            .send()
-            .await
-            .map_err(|e| AppError::Sync(format!("Download failed: {}", e)))?;
+    /// 读取数据库中当前后端的同步记录，重启后据此区分远端删除和本地新建的文件
+    pub async fn load_file_sync_info(&mut self, database: &Database) -> Result<()> {
+        self.file_sync_info = database.get_file_sync_info(&self.backend.location()).await?;
+        Ok(())
+    }
 
-        if response.status().is_success() {
-            let _content = response.text().await
-                .map_err(|e| AppError::Sync(format!("Failed to read response: {}", e)))?;
+    /// 把同步记录写回数据库
+    pub async fn save_file_sync_info(&self, database: &Database) -> Result<()> {
+        database.save_file_sync_info(&self.backend.location(), &self.file_sync_info).await
+    }
 
-            result.files_downloaded += 1;
-            log::info!("File downloaded successfully");
-        } else if response.status().as_u16() == 404 {
-            log::info!("Remote file not found, skipping download");
-        } else {
-            let error_msg = format!("Download failed with status: {}", response.status());
-            result.errors.push(error_msg.clone());
-            log::error!("{}", error_msg);
+    /// 执行同步，取消时丢弃进行中的请求
+    pub async fn run(&mut self, direction: SyncDirection, cancel: &CancellationToken) -> Result<()> {
+        let local = LocalFolderBackend::new(self.local_dir.clone());
+        tokio::select! {
+            outcome = engine::sync(&local, self.backend.as_ref(), &direction, &mut self.file_sync_info, &mut self.result) => outcome,
+            _ = cancel.cancelled() => Err(AppError::Sync("Sync cancelled".to_string())),
         }
-
-        Ok(result.clone())
     }
 }
 
@@ -370,8 +445,7 @@ pub enum SyncEvent {
 }
 
 /// 同步事件监听器
-#[allow(dead_code)]
-pub trait SyncEventListener {
+pub trait SyncEventListener: Send + Sync {
     fn on_sync_event(&self, event: SyncEvent);
 }
 
@@ -428,6 +502,54 @@ mod tests {
         assert!(manager.get_config().is_none());
     }
 
+    #[tokio::test]
+    async fn test_folder_backend_sync_round_trip() {
+        let local_dir = tempfile::tempdir().unwrap();
+        let folder = tempfile::tempdir().unwrap();
+
+        let mut manager = WebDAVSyncManager::new();
+        manager.set_local_dir(local_dir.path());
+        manager.set_folder_config(FolderSyncConfig {
+            folder_path: folder.path().to_string_lossy().to_string(),
+            enabled: true,
+            auto_sync_interval: Some(60),
+        }).unwrap();
+        assert_eq!(manager.auto_sync_interval(), Some(60));
+        assert!(manager.test_connection().await.unwrap());
+
+        std::fs::write(local_dir.path().join("page.md"), "local").unwrap();
+        std::fs::create_dir_all(folder.path().join("journals")).unwrap();
+        std::fs::write(folder.path().join("journals").join("2024_01_01.md"), "remote").unwrap();
+
+        let result = manager.start_sync(SyncDirection::Bidirectional).await.unwrap();
+        assert_eq!(result.status, SyncStatus::Success);
+        assert_eq!(result.files_uploaded, 1);
+        assert_eq!(result.files_downloaded, 1);
+        assert_eq!(std::fs::read_to_string(folder.path().join("page.md")).unwrap(), "local");
+        assert_eq!(
+            std::fs::read_to_string(local_dir.path().join("journals").join("2024_01_01.md")).unwrap(),
+            "remote"
+        );
+
+        // 两端同时修改产生冲突，选择本地版本解决
+        std::fs::write(local_dir.path().join("page.md"), "local v2").unwrap();
+        std::fs::write(folder.path().join("page.md"), "remote v2").unwrap();
+        let result = manager.start_sync(SyncDirection::Bidirectional).await.unwrap();
+        assert_eq!(result.status, SyncStatus::Conflict);
+        assert_eq!(manager.get_conflicts(), vec!["page.md".to_string()]);
+
+        manager.resolve_conflict("page.md", ConflictResolution::UseLocal).await.unwrap();
+        assert!(manager.get_conflicts().is_empty());
+        assert_eq!(std::fs::read_to_string(folder.path().join("page.md")).unwrap(), "local v2");
+    }
+
+    #[tokio::test]
+    async fn test_sync_without_backend_fails() {
+        let mut manager = WebDAVSyncManager::new();
+        assert!(manager.start_sync(SyncDirection::Upload).await.is_err());
+        assert!(manager.test_connection().await.is_err());
+    }
+
     #[test]
     fn test_config_validation() {
         assert!(SyncConfigValidator::validate_server_url("https://example.com").is_ok());
@@ -0,0 +1,112 @@
+use crate::error::{AppError, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// 远端文件条目
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RemoteEntry {
+    pub path: String,                        // 相对同步根目录的路径，使用 `/` 分隔
+    pub etag: String,                        // 内容版本标识，内容变化时必须改变
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+}
+
+/// 写入时对目标当前版本的要求
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteCondition<'a> {
+    /// 无条件覆盖
+    Overwrite,
+    /// 仅在文件不存在时创建，两台设备同时创建同一文件时只有一方成功
+    Create,
+    /// 仅在当前 etag 匹配时写入
+    IfMatch(&'a str),
+}
+
+/// 同步存储后端
+///
+/// 所有路径都是相对同步根目录、以 `/` 分隔的文件路径，不包含目录条目。
+#[async_trait]
+pub trait SyncBackend: Send + Sync + std::fmt::Debug {
+    /// 后端名称，用于日志
+    fn name(&self) -> &str;
+
+    /// 同步目标的标识，不同目标的同步记录分开保存
+    fn location(&self) -> String;
+
+    /// 递归列出所有文件
+    async fn list(&self) -> Result<Vec<RemoteEntry>>;
+
+    /// 读取文件内容
+    async fn get(&self, path: &str) -> Result<Vec<u8>>;
+
+    /// 写入文件，`condition` 不满足时返回 [`precondition_failed`]
+    async fn put(&self, path: &str, data: &[u8], condition: WriteCondition<'_>) -> Result<RemoteEntry>;
+
+    /// 删除文件，文件不存在时视为成功
+    async fn delete(&self, path: &str) -> Result<()>;
+
+    /// 获取单个文件信息，不存在时返回 None
+    async fn stat(&self, path: &str) -> Result<Option<RemoteEntry>>;
+
+    /// 测试后端是否可用
+    async fn test_connection(&self) -> Result<bool> {
+        self.list().await.map(|_| true)
+    }
+}
+
+/// 计算内容哈希（十六进制 SHA-256）
+pub fn content_hash(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// 校验并规范化同步路径，拒绝 `..` 和 `C:` 这样的盘符前缀
+pub fn normalize_path(path: &str) -> Result<String> {
+    let mut segments = Vec::new();
+    for segment in path.split(['/', '\\']) {
+        let drive = segments.is_empty()
+            && segment.len() >= 2
+            && segment.as_bytes()[0].is_ascii_alphabetic()
+            && segment.as_bytes()[1] == b':';
+        match segment {
+            "" | "." => continue,
+            ".." => return Err(AppError::InvalidInput(format!("Invalid sync path: {}", path))),
+            _ if drive => return Err(AppError::InvalidInput(format!("Invalid sync path: {}", path))),
+            segment => segments.push(segment),
+        }
+    }
+    if segments.is_empty() {
+        return Err(AppError::InvalidInput(format!("Invalid sync path: {}", path)));
+    }
+    Ok(segments.join("/"))
+}
+
+/// 写入条件不满足时返回的错误
+pub fn precondition_failed(path: &str) -> AppError {
+    AppError::Sync(format!("Precondition failed: {} was modified concurrently", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path("pages/a.md").unwrap(), "pages/a.md");
+        assert_eq!(normalize_path("/pages//a.md").unwrap(), "pages/a.md");
+        assert_eq!(normalize_path("pages\\a.md").unwrap(), "pages/a.md");
+        assert!(normalize_path("../etc/passwd").is_err());
+        assert!(normalize_path("/").is_err());
+        assert!(normalize_path("C:/Windows/win.ini").is_err());
+        assert!(normalize_path("c:evil.md").is_err());
+    }
+
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(
+            content_hash(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
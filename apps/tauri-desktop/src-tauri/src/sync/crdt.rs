@@ -0,0 +1,678 @@
+use crate::error::{AppError, Result};
+use crate::models::Page;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+/// 混合逻辑时钟时间戳，按 (wall, counter, node) 全序比较
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Hlc {
+    pub wall: i64,       // 毫秒级物理时间
+    pub counter: u32,    // 同一毫秒内的逻辑计数
+    pub node: String,    // 设备ID，用于打破平局
+}
+
+impl Hlc {
+    /// 某设备的初始时间戳
+    pub fn zero(node: &str) -> Self {
+        Self { wall: 0, counter: 0, node: node.to_string() }
+    }
+}
+
+/// 字符串形式按字典序排序与时间戳顺序一致，可直接作为数据库主键
+impl fmt::Display for Hlc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:013}-{:010}-{}", self.wall, self.counter, self.node)
+    }
+}
+
+impl FromStr for Hlc {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || AppError::InvalidInput(format!("Invalid HLC timestamp: {}", s));
+        let mut parts = s.splitn(3, '-');
+        let wall = parts.next().and_then(|p| p.parse().ok()).ok_or_else(invalid)?;
+        let counter = parts.next().and_then(|p| p.parse().ok()).ok_or_else(invalid)?;
+        let node = parts.next().filter(|p| !p.is_empty()).ok_or_else(invalid)?;
+        Ok(Self { wall, counter, node: node.to_string() })
+    }
+}
+
+impl Serialize for Hlc {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Hlc {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// 本设备的混合逻辑时钟
+#[derive(Debug, Clone)]
+pub struct HybridClock {
+    last: Hlc,
+}
+
+impl HybridClock {
+    /// 从本设备已知的最大时间戳恢复时钟
+    pub fn new(node: &str, last_seen: Option<&Hlc>) -> Self {
+        let mut clock = Self { last: Hlc::zero(node) };
+        if let Some(last_seen) = last_seen {
+            clock.observe(last_seen);
+        }
+        clock
+    }
+
+    /// 生成下一个本地时间戳
+    pub fn tick(&mut self, now_ms: i64) -> Hlc {
+        if now_ms > self.last.wall {
+            self.last.wall = now_ms;
+            self.last.counter = 0;
+        } else {
+            self.last.counter += 1;
+        }
+        self.last.clone()
+    }
+
+    /// 使用当前系统时间生成时间戳
+    pub fn now(&mut self) -> Hlc {
+        self.tick(chrono::Utc::now().timestamp_millis())
+    }
+
+    /// 收到远端时间戳后推进时钟，保证之后的本地时间戳更大
+    pub fn observe(&mut self, remote: &Hlc) {
+        if (remote.wall, remote.counter) > (self.last.wall, self.last.counter) {
+            self.last.wall = remote.wall;
+            self.last.counter = remote.counter;
+        }
+    }
+}
+
+/// 文本中单个字符的唯一ID：插入操作的时间戳 + 在该次插入中的偏移
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct CharId {
+    pub op: Hlc,
+    pub offset: u32,
+}
+
+/// 使用最后写入者胜出（LWW）合并的块字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockField {
+    Properties,
+    Refs,
+    Collapsed,
+}
+
+/// 块操作类型
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BlockOpKind {
+    Create { graph_id: String },
+    /// 移动到 `parent_id` 下、紧跟在兄弟块 `after` 之后（None 表示第一个）
+    Move { parent_id: Option<String>, after: Option<String> },
+    /// 在字符 `after` 之后插入一段连续文本（RGA）
+    InsertText { after: Option<CharId>, text: String },
+    /// 删除若干段连续字符，每段为 (起始字符, 长度)
+    DeleteText { ranges: Vec<(CharId, u32)> },
+    SetField { field: BlockField, value: Option<String> },
+    Delete,
+}
+
+/// 单个块操作
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockOp {
+    pub id: Hlc,
+    pub block_id: String,
+    pub page_id: String,
+    #[serde(flatten)]
+    pub kind: BlockOpKind,
+}
+
+/// 设备之间交换的操作批次
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OpBatch {
+    pub device_id: String,
+    /// 操作所属的页面，接收方据此创建缺少的页面并同步改名
+    #[serde(default)]
+    pub pages: Vec<Page>,
+    pub ops: Vec<BlockOp>,
+}
+
+/// 各设备已知的最大操作时间戳
+pub type VersionVector = HashMap<String, Hlc>;
+
+/// 合并后的块状态
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockSnapshot {
+    pub id: String,
+    pub page_id: String,
+    pub graph_id: String,
+    pub parent_id: Option<String>,
+    pub after: Option<String>,       // 前一个未删除的兄弟块
+    pub order: i32,
+    pub content: String,
+    pub properties: Option<String>,
+    pub refs: String,
+    pub collapsed: bool,
+    pub deleted: bool,
+    pub created: Hlc,
+    pub updated: Hlc,
+}
+
+/// 块的目标状态，用于从本地编辑生成操作
+#[derive(Debug, Clone)]
+pub struct BlockTarget {
+    pub id: String,
+    pub page_id: String,
+    pub graph_id: String,
+    pub parent_id: Option<String>,
+    pub after: Option<String>,
+    pub content: String,
+    pub properties: Option<String>,
+    pub refs: String,
+    pub collapsed: bool,
+}
+
+/// 合并一个页面的全部操作，得到确定的块状态（与操作到达顺序无关）
+pub fn materialize(ops: &[BlockOp]) -> Vec<BlockSnapshot> {
+    let mut sorted: Vec<&BlockOp> = ops.iter().collect();
+    sorted.sort_by(|a, b| a.id.cmp(&b.id));
+    sorted.dedup_by(|a, b| a.id == b.id);
+
+    let mut blocks: BTreeMap<String, BlockSnapshot> = BTreeMap::new();
+    let mut text_ops: HashMap<String, Vec<&BlockOp>> = HashMap::new();
+    let mut moves: Vec<&BlockOp> = Vec::new();
+
+    for op in &sorted {
+        let snapshot = blocks.entry(op.block_id.clone()).or_insert_with(|| BlockSnapshot {
+            id: op.block_id.clone(),
+            page_id: op.page_id.clone(),
+            graph_id: String::new(),
+            parent_id: None,
+            after: None,
+            order: 0,
+            content: String::new(),
+            properties: None,
+            refs: String::new(),
+            collapsed: false,
+            deleted: false,
+            created: op.id.clone(),
+            updated: op.id.clone(),
+        });
+        snapshot.updated = op.id.clone();
+
+        match &op.kind {
+            BlockOpKind::Create { graph_id } => snapshot.graph_id = graph_id.clone(),
+            BlockOpKind::Move { .. } => moves.push(op),
+            BlockOpKind::InsertText { .. } | BlockOpKind::DeleteText { .. } => {
+                text_ops.entry(op.block_id.clone()).or_default().push(op);
+            }
+            BlockOpKind::SetField { field, value } => match field {
+                BlockField::Properties => snapshot.properties = value.clone(),
+                BlockField::Refs => snapshot.refs = value.clone().unwrap_or_default(),
+                BlockField::Collapsed => snapshot.collapsed = value.as_deref() == Some("true"),
+            },
+            BlockOpKind::Delete => snapshot.deleted = true,
+        }
+    }
+
+    // 没有创建操作的块（只收到了部分操作）不输出
+    blocks.retain(|_, snapshot| !snapshot.graph_id.is_empty());
+
+    for (block_id, ops) in &text_ops {
+        if let Some(snapshot) = blocks.get_mut(block_id) {
+            snapshot.content = visible_text(ops).into_iter().map(|(_, ch)| ch).collect();
+        }
+    }
+
+    let placements = resolve_moves(&blocks, &moves);
+    for (block_id, placement) in &placements {
+        if let Some(snapshot) = blocks.get_mut(block_id) {
+            snapshot.parent_id = placement.parent_id.clone();
+        }
+    }
+
+    // 祖先被删除的块同样视为删除
+    let ids: Vec<String> = blocks.keys().cloned().collect();
+    for id in &ids {
+        let mut current = blocks[id].parent_id.clone();
+        let mut seen = HashSet::new();
+        while let Some(parent) = current {
+            if !seen.insert(parent.clone()) {
+                break;
+            }
+            match blocks.get(&parent) {
+                Some(parent_block) if parent_block.deleted => {
+                    blocks.get_mut(id).unwrap().deleted = true;
+                    break;
+                }
+                Some(parent_block) => current = parent_block.parent_id.clone(),
+                None => break,
+            }
+        }
+    }
+
+    order_siblings(&mut blocks, &placements);
+    blocks.into_values().collect()
+}
+
+#[derive(Debug, Clone)]
+struct Placement {
+    parent_id: Option<String>,
+    after: Option<String>,
+    stamp: Hlc,
+}
+
+/// 按时间戳顺序重放移动操作，跳过会形成环的移动
+fn resolve_moves(blocks: &BTreeMap<String, BlockSnapshot>, moves: &[&BlockOp]) -> HashMap<String, Placement> {
+    let mut placements: HashMap<String, Placement> = blocks.values()
+        .map(|b| (b.id.clone(), Placement { parent_id: None, after: None, stamp: b.created.clone() }))
+        .collect();
+
+    for op in moves {
+        let BlockOpKind::Move { parent_id, after } = &op.kind else { continue };
+        if !placements.contains_key(&op.block_id) {
+            continue;
+        }
+
+        let mut ancestor = parent_id.clone();
+        let mut creates_cycle = false;
+        let mut seen = HashSet::new();
+        while let Some(current) = ancestor {
+            if current == op.block_id || !seen.insert(current.clone()) {
+                creates_cycle = true;
+                break;
+            }
+            ancestor = placements.get(&current).and_then(|p| p.parent_id.clone());
+        }
+        if creates_cycle {
+            continue;
+        }
+
+        placements.insert(op.block_id.clone(), Placement {
+            parent_id: parent_id.clone(),
+            after: after.clone(),
+            stamp: op.id.clone(),
+        });
+    }
+
+    placements
+}
+
+/// 对每组兄弟块按 RGA 规则排序：锚点之后按移动时间戳倒序
+fn order_siblings(blocks: &mut BTreeMap<String, BlockSnapshot>, placements: &HashMap<String, Placement>) {
+    let mut groups: BTreeMap<(String, Option<String>), Vec<String>> = BTreeMap::new();
+    for block in blocks.values() {
+        groups.entry((block.page_id.clone(), block.parent_id.clone()))
+            .or_default()
+            .push(block.id.clone());
+    }
+
+    for siblings in groups.values() {
+        let members: HashSet<&String> = siblings.iter().collect();
+        let mut anchors: HashMap<String, Option<String>> = siblings.iter()
+            .map(|id| {
+                let anchor = placements[id].after.clone().filter(|a| a != id && members.contains(a));
+                (id.clone(), anchor)
+            })
+            .collect();
+
+        let ordered = loop {
+            let mut children: HashMap<Option<String>, Vec<&String>> = HashMap::new();
+            for id in siblings {
+                children.entry(anchors[id].clone()).or_default().push(id);
+            }
+            for list in children.values_mut() {
+                list.sort_by_key(|id| Reverse((&placements[*id].stamp, *id)));
+            }
+
+            let mut ordered = Vec::with_capacity(siblings.len());
+            let mut stack: Vec<&String> = children.get(&None).map(|c| c.iter().rev().copied().collect()).unwrap_or_default();
+            while let Some(id) = stack.pop() {
+                ordered.push(id.clone());
+                if let Some(list) = children.get(&Some(id.clone())) {
+                    stack.extend(list.iter().rev().copied());
+                }
+            }
+            if ordered.len() == siblings.len() {
+                break ordered;
+            }
+
+            // 锚点形成环：断开其中最早的一个
+            let visited: HashSet<&String> = ordered.iter().collect();
+            let breaker = siblings.iter()
+                .filter(|id| !visited.contains(id))
+                .min_by_key(|id| (&placements[*id].stamp, *id))
+                .unwrap()
+                .clone();
+            anchors.insert(breaker, None);
+        };
+
+        let mut previous: Option<String> = None;
+        for (order, id) in ordered.into_iter().enumerate() {
+            let block = blocks.get_mut(&id).unwrap();
+            block.order = order as i32;
+            block.after = previous.clone();
+            if !block.deleted {
+                previous = Some(id);
+            }
+        }
+    }
+}
+
+/// 按 RGA 规则计算可见字符及其ID
+pub fn visible_text(ops: &[&BlockOp]) -> Vec<(CharId, char)> {
+    let mut chars: HashMap<CharId, char> = HashMap::new();
+    let mut children: HashMap<Option<CharId>, Vec<CharId>> = HashMap::new();
+    let mut deleted: HashSet<CharId> = HashSet::new();
+
+    for op in ops {
+        match &op.kind {
+            BlockOpKind::InsertText { after, text } => {
+                let mut anchor = after.clone();
+                for (offset, ch) in text.chars().enumerate() {
+                    let id = CharId { op: op.id.clone(), offset: offset as u32 };
+                    chars.insert(id.clone(), ch);
+                    children.entry(anchor).or_default().push(id.clone());
+                    anchor = Some(id);
+                }
+            }
+            BlockOpKind::DeleteText { ranges } => {
+                for (start, len) in ranges {
+                    for offset in start.offset..start.offset.saturating_add(*len) {
+                        deleted.insert(CharId { op: start.op.clone(), offset });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for list in children.values_mut() {
+        list.sort_by(|a, b| b.cmp(a));
+    }
+
+    // 迭代先序遍历，避免长文本导致递归过深
+    let mut visible = Vec::with_capacity(chars.len());
+    let mut stack: Vec<CharId> = children.get(&None).map(|c| c.iter().rev().cloned().collect()).unwrap_or_default();
+    while let Some(id) = stack.pop() {
+        if let Some(list) = children.get(&Some(id.clone())) {
+            stack.extend(list.iter().rev().cloned());
+        }
+        if !deleted.contains(&id) {
+            visible.push((id.clone(), chars[&id]));
+        }
+    }
+    visible
+}
+
+/// 根据块的当前合并状态和目标状态生成操作
+pub fn diff_block(
+    clock: &mut HybridClock,
+    current: Option<&BlockSnapshot>,
+    current_text: &[(CharId, char)],
+    target: &BlockTarget,
+) -> Vec<BlockOp> {
+    let mut ops = Vec::new();
+    let mut push = |clock: &mut HybridClock, kind: BlockOpKind| {
+        ops.push(BlockOp {
+            id: clock.now(),
+            block_id: target.id.clone(),
+            page_id: target.page_id.clone(),
+            kind,
+        });
+    };
+
+    if current.is_none() {
+        push(clock, BlockOpKind::Create { graph_id: target.graph_id.clone() });
+    }
+
+    let moved = current.map_or(true, |c| c.parent_id != target.parent_id || c.after != target.after);
+    if moved && (current.is_some() || target.parent_id.is_some() || target.after.is_some()) {
+        push(clock, BlockOpKind::Move { parent_id: target.parent_id.clone(), after: target.after.clone() });
+    }
+
+    // 文本：只替换公共前后缀之间的部分
+    let new_chars: Vec<char> = target.content.chars().collect();
+    let prefix = current_text.iter()
+        .zip(&new_chars)
+        .take_while(|((_, a), b)| a == *b)
+        .count();
+    let suffix = current_text[prefix..].iter().rev()
+        .zip(new_chars[prefix..].iter().rev())
+        .take_while(|((_, a), b)| a == *b)
+        .count();
+
+    let removed = &current_text[prefix..current_text.len() - suffix];
+    if !removed.is_empty() {
+        let mut ranges: Vec<(CharId, u32)> = Vec::new();
+        for (id, _) in removed {
+            match ranges.last_mut() {
+                Some((start, len)) if start.op == id.op && start.offset + *len == id.offset => *len += 1,
+                _ => ranges.push((id.clone(), 1)),
+            }
+        }
+        push(clock, BlockOpKind::DeleteText { ranges });
+    }
+
+    let inserted: String = new_chars[prefix..new_chars.len() - suffix].iter().collect();
+    if !inserted.is_empty() {
+        let after = prefix.checked_sub(1).map(|i| current_text[i].0.clone());
+        push(clock, BlockOpKind::InsertText { after, text: inserted });
+    }
+
+    let fields = [
+        (BlockField::Properties, current.and_then(|c| c.properties.clone()), target.properties.clone()),
+        (
+            BlockField::Refs,
+            current.map(|c| c.refs.clone()).filter(|r| !r.is_empty()),
+            Some(target.refs.clone()).filter(|r| !r.is_empty()),
+        ),
+        (
+            BlockField::Collapsed,
+            current.map(|c| c.collapsed.to_string()).filter(|c| c == "true"),
+            Some(target.collapsed.to_string()).filter(|c| c == "true"),
+        ),
+    ];
+    for (field, old, new) in fields {
+        if old != new {
+            push(clock, BlockOpKind::SetField { field, value: new.or_else(|| (field == BlockField::Collapsed).then(|| "false".to_string())) });
+        }
+    }
+
+    ops
+}
+
+/// 删除块的操作
+pub fn delete_block_op(clock: &mut HybridClock, block: &BlockSnapshot) -> BlockOp {
+    BlockOp {
+        id: clock.now(),
+        block_id: block.id.clone(),
+        page_id: block.page_id.clone(),
+        kind: BlockOpKind::Delete,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(id: &str, content: &str) -> BlockTarget {
+        BlockTarget {
+            id: id.to_string(),
+            page_id: "page".to_string(),
+            graph_id: "graph".to_string(),
+            parent_id: None,
+            after: None,
+            content: content.to_string(),
+            properties: None,
+            refs: String::new(),
+            collapsed: false,
+        }
+    }
+
+    /// 模拟一个设备：持有时钟和已知的全部操作
+    struct Replica {
+        clock: HybridClock,
+        ops: Vec<BlockOp>,
+    }
+
+    impl Replica {
+        fn new(node: &str) -> Self {
+            Self { clock: HybridClock::new(node, None), ops: Vec::new() }
+        }
+
+        fn snapshot(&self, id: &str) -> Option<BlockSnapshot> {
+            materialize(&self.ops).into_iter().find(|b| b.id == id)
+        }
+
+        fn edit(&mut self, target: BlockTarget) {
+            let current = self.snapshot(&target.id);
+            let text_ops: Vec<&BlockOp> = self.ops.iter().filter(|op| op.block_id == target.id).collect();
+            let text = visible_text(&text_ops);
+            let ops = diff_block(&mut self.clock, current.as_ref(), &text, &target);
+            self.ops.extend(ops);
+        }
+
+        fn merge(&mut self, other: &Replica) {
+            for op in &other.ops {
+                if !self.ops.iter().any(|o| o.id == op.id) {
+                    self.clock.observe(&op.id);
+                    self.ops.push(op.clone());
+                }
+            }
+        }
+
+        fn visible(&self) -> Vec<(String, Option<String>, String)> {
+            let mut blocks: Vec<_> = materialize(&self.ops).into_iter().filter(|b| !b.deleted).collect();
+            blocks.sort_by(|a, b| (&a.parent_id, a.order).cmp(&(&b.parent_id, b.order)));
+            blocks.into_iter().map(|b| (b.id, b.parent_id, b.content)).collect()
+        }
+    }
+
+    #[test]
+    fn test_hlc_ordering_and_round_trip() {
+        let mut clock = HybridClock::new("a", None);
+        let first = clock.tick(1000);
+        let second = clock.tick(1000);
+        let third = clock.tick(999);
+        assert!(first < second && second < third);
+
+        clock.observe(&Hlc { wall: 5000, counter: 7, node: "b".to_string() });
+        let after_remote = clock.tick(1000);
+        assert_eq!((after_remote.wall, after_remote.counter), (5000, 8));
+
+        let parsed: Hlc = after_remote.to_string().parse().unwrap();
+        assert_eq!(parsed, after_remote);
+        assert!(Hlc { wall: 2, counter: 0, node: "a".into() }.to_string()
+            < Hlc { wall: 10, counter: 0, node: "a".into() }.to_string());
+    }
+
+    #[test]
+    fn test_concurrent_text_edits_merge() {
+        let mut a = Replica::new("a");
+        a.edit(target("b1", "hello world"));
+        let mut b = Replica::new("b");
+        b.merge(&a);
+
+        a.edit(target("b1", "hello brave world"));
+        b.edit(target("b1", "hello world!"));
+
+        a.merge(&b);
+        b.merge(&a);
+        assert_eq!(a.snapshot("b1").unwrap().content, "hello brave world!");
+        assert_eq!(a.visible(), b.visible());
+    }
+
+    #[test]
+    fn test_concurrent_inserts_at_same_position_converge() {
+        let mut a = Replica::new("a");
+        a.edit(target("b1", "ac"));
+        let mut b = Replica::new("b");
+        b.merge(&a);
+
+        a.edit(target("b1", "aXc"));
+        b.edit(target("b1", "aYc"));
+        a.merge(&b);
+        b.merge(&a);
+
+        let content = a.snapshot("b1").unwrap().content;
+        assert!(content == "aXYc" || content == "aYXc");
+        assert_eq!(content, b.snapshot("b1").unwrap().content);
+    }
+
+    #[test]
+    fn test_sibling_order_and_concurrent_moves() {
+        let mut a = Replica::new("a");
+        a.edit(target("x", "x"));
+        a.edit(BlockTarget { after: Some("x".into()), ..target("y", "y") });
+        a.edit(BlockTarget { after: Some("y".into()), ..target("z", "z") });
+        let ids: Vec<String> = a.visible().into_iter().map(|(id, _, _)| id).collect();
+        assert_eq!(ids, vec!["x", "y", "z"]);
+
+        let mut b = Replica::new("b");
+        b.merge(&a);
+
+        // 并发地互相嵌套会形成环，后发生的移动被跳过
+        a.edit(BlockTarget { parent_id: Some("y".into()), ..target("x", "x") });
+        b.edit(BlockTarget { parent_id: Some("x".into()), ..target("y", "y") });
+        a.merge(&b);
+        b.merge(&a);
+
+        assert_eq!(a.visible(), b.visible());
+        let x = a.snapshot("x").unwrap();
+        let y = a.snapshot("y").unwrap();
+        assert!(x.parent_id.is_none() || y.parent_id.is_none());
+    }
+
+    #[test]
+    fn test_delete_hides_descendants() {
+        let mut a = Replica::new("a");
+        a.edit(target("parent", "p"));
+        a.edit(BlockTarget { parent_id: Some("parent".into()), ..target("child", "c") });
+        let parent = a.snapshot("parent").unwrap();
+        let op = delete_block_op(&mut a.clock, &parent);
+        a.ops.push(op);
+
+        assert!(a.snapshot("child").unwrap().deleted);
+        assert!(a.visible().is_empty());
+    }
+
+    #[test]
+    fn test_field_last_writer_wins() {
+        let mut a = Replica::new("a");
+        a.edit(target("b1", "text"));
+        let mut b = Replica::new("b");
+        b.merge(&a);
+
+        a.edit(BlockTarget { properties: Some("{\"k\":\"a\"}".into()), ..target("b1", "text") });
+        b.clock.tick(chrono::Utc::now().timestamp_millis() + 60_000);
+        b.edit(BlockTarget { properties: Some("{\"k\":\"b\"}".into()), collapsed: true, ..target("b1", "text") });
+        a.merge(&b);
+        b.merge(&a);
+
+        let merged = a.snapshot("b1").unwrap();
+        assert_eq!(merged.properties.as_deref(), Some("{\"k\":\"b\"}"));
+        assert!(merged.collapsed);
+        assert_eq!(Some(merged), b.snapshot("b1"));
+    }
+
+    #[test]
+    fn test_op_batch_serialization_is_compact() {
+        let mut a = Replica::new("a");
+        a.edit(target("b1", "a long line of text typed in one go"));
+        let json = serde_json::to_string(&OpBatch { device_id: "a".into(), pages: Vec::new(), ops: a.ops.clone() }).unwrap();
+        let batch: OpBatch = serde_json::from_str(&json).unwrap();
+        assert_eq!(batch.ops, a.ops);
+        // 一次输入的文本只产生一个插入操作
+        assert_eq!(a.ops.iter().filter(|op| matches!(op.kind, BlockOpKind::InsertText { .. })).count(), 1);
+    }
+}
@@ -0,0 +1,437 @@
+use super::backend::{content_hash, RemoteEntry, SyncBackend, WriteCondition};
+use super::{ConflictResolution, ConflictType, FileSyncInfo, SyncConflict, SyncDirection, SyncResult, SyncStatus};
+use crate::error::{AppError, Result};
+use chrono::Utc;
+use std::collections::{BTreeSet, HashMap};
+
+/// 同步根目录下的内部数据目录
+pub const INTERNAL_DIR: &str = ".minglog/";
+
+/// 单个文件的同步动作
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncAction {
+    Upload(String),
+    Download(String),
+    DeleteRemote(String),
+    DeleteLocal(String),
+    /// 两端都有变化，需要比较内容
+    Reconcile(String),
+    /// 一端删除、另一端修改
+    DeleteConflict(String),
+    /// 两端都已删除，清理同步记录
+    Forget(String),
+}
+
+/// 根据两端文件列表和上次同步记录计算同步动作（三方比较）
+pub fn plan(
+    local: &[RemoteEntry],
+    remote: &[RemoteEntry],
+    state: &HashMap<String, FileSyncInfo>,
+    direction: &SyncDirection,
+) -> Vec<SyncAction> {
+    let local: HashMap<&str, &RemoteEntry> = local.iter().map(|e| (e.path.as_str(), e)).collect();
+    let remote: HashMap<&str, &RemoteEntry> = remote.iter().map(|e| (e.path.as_str(), e)).collect();
+    let paths: BTreeSet<&str> = local.keys()
+        .chain(remote.keys())
+        .copied()
+        .chain(state.keys().map(String::as_str))
+        .collect();
+
+    let mut actions = Vec::new();
+    for path in paths {
+        let base = state.get(path);
+        let local_changed = |entry: &RemoteEntry| base.map_or(true, |b| b.local_hash != entry.etag);
+        let remote_changed = |entry: &RemoteEntry| base.map_or(true, |b| b.remote_hash.as_deref() != Some(entry.etag.as_str()));
+        let path = path.to_string();
+
+        let action = match (local.get(path.as_str()), remote.get(path.as_str()), base) {
+            (Some(_), Some(_), None) => Some(SyncAction::Reconcile(path)),
+            (Some(l), Some(r), Some(_)) => match (local_changed(l), remote_changed(r)) {
+                (false, false) => None,
+                (true, false) => Some(SyncAction::Upload(path)),
+                (false, true) => Some(SyncAction::Download(path)),
+                (true, true) => Some(SyncAction::Reconcile(path)),
+            },
+            (Some(_), None, None) => Some(SyncAction::Upload(path)),
+            (Some(l), None, Some(_)) if local_changed(l) => Some(SyncAction::DeleteConflict(path)),
+            (Some(_), None, Some(_)) => Some(SyncAction::DeleteLocal(path)),
+            (None, Some(_), None) => Some(SyncAction::Download(path)),
+            (None, Some(r), Some(_)) if remote_changed(r) => Some(SyncAction::DeleteConflict(path)),
+            (None, Some(_), Some(_)) => Some(SyncAction::DeleteRemote(path)),
+            (None, None, Some(_)) => Some(SyncAction::Forget(path)),
+            (None, None, None) => None,
+        };
+
+        // 单向同步只修改目标一端
+        let blocked = matches!(
+            (&action, direction),
+            (Some(SyncAction::Download(_) | SyncAction::DeleteLocal(_)), SyncDirection::Upload)
+                | (Some(SyncAction::Upload(_) | SyncAction::DeleteRemote(_)), SyncDirection::Download)
+        );
+        if let (Some(action), false) = (action, blocked) {
+            actions.push(action);
+        }
+    }
+    actions
+}
+
+/// 在本地存储和远端后端之间执行一次同步
+pub async fn sync(
+    local: &dyn SyncBackend,
+    remote: &dyn SyncBackend,
+    direction: &SyncDirection,
+    state: &mut HashMap<String, FileSyncInfo>,
+    result: &mut SyncResult,
+) -> Result<()> {
+    // 操作批次等内部数据由各自的流程处理，不作为普通文件同步
+    let is_internal = |entry: &RemoteEntry| entry.path.starts_with(INTERNAL_DIR);
+    let mut local_entries = local.list().await?;
+    local_entries.retain(|entry| !is_internal(entry));
+    let mut remote_entries = remote.list().await?;
+    remote_entries.retain(|entry| !is_internal(entry));
+    let actions = plan(&local_entries, &remote_entries, state, direction);
+    log::info!("Sync via {} planned {} actions", remote.name(), actions.len());
+
+    let local_index: HashMap<&str, &RemoteEntry> = local_entries.iter().map(|e| (e.path.as_str(), e)).collect();
+    let remote_index: HashMap<&str, &RemoteEntry> = remote_entries.iter().map(|e| (e.path.as_str(), e)).collect();
+
+    for action in actions {
+        let outcome = apply(local, remote, &action, &local_index, &remote_index, state, result).await;
+        if let Err(e) = outcome {
+            let error_msg = format!("{:?} failed: {}", action, e);
+            log::error!("{}", error_msg);
+            result.errors.push(error_msg);
+        }
+    }
+
+    Ok(())
+}
+
+async fn apply(
+    local: &dyn SyncBackend,
+    remote: &dyn SyncBackend,
+    action: &SyncAction,
+    local_index: &HashMap<&str, &RemoteEntry>,
+    remote_index: &HashMap<&str, &RemoteEntry>,
+    state: &mut HashMap<String, FileSyncInfo>,
+    result: &mut SyncResult,
+) -> Result<()> {
+    match action {
+        SyncAction::Upload(path) => {
+            let data = local.get(path).await?;
+            // 远端存在时要求其仍是上次同步的版本，不存在时只创建
+            let uploaded = remote.put(path, &data, expected_version(remote_index, path)).await?;
+            record(state, path, content_hash(&data), &uploaded);
+            result.files_uploaded += 1;
+        }
+        SyncAction::Download(path) => {
+            let data = remote.get(path).await?;
+            let written = local.put(path, &data, expected_version(local_index, path)).await?;
+            let remote_entry = remote_index.get(path.as_str())
+                .ok_or_else(|| AppError::Sync(format!("Remote entry missing: {}", path)))?;
+            record(state, path, written.etag, remote_entry);
+            result.files_downloaded += 1;
+        }
+        SyncAction::DeleteRemote(path) => {
+            remote.delete(path).await?;
+            state.remove(path);
+            result.files_deleted += 1;
+        }
+        SyncAction::DeleteLocal(path) => {
+            local.delete(path).await?;
+            state.remove(path);
+            result.files_deleted += 1;
+        }
+        SyncAction::Forget(path) => {
+            state.remove(path);
+        }
+        SyncAction::Reconcile(path) => {
+            let local_data = local.get(path).await?;
+            let remote_data = remote.get(path).await?;
+            let local_entry = local_index[path.as_str()];
+            let remote_entry = remote_index[path.as_str()];
+
+            if content_hash(&local_data) == content_hash(&remote_data) {
+                record(state, path, local_entry.etag.clone(), remote_entry);
+            } else {
+                let now = Utc::now();
+                result.conflicts.push(SyncConflict {
+                    file_path: path.clone(),
+                    local_content: String::from_utf8_lossy(&local_data).to_string(),
+                    remote_content: String::from_utf8_lossy(&remote_data).to_string(),
+                    local_modified: local_entry.modified.unwrap_or(now),
+                    remote_modified: remote_entry.modified.unwrap_or(now),
+                    conflict_type: ConflictType::ContentConflict,
+                });
+                mark_conflict(state, path, local_entry);
+            }
+        }
+        SyncAction::DeleteConflict(path) => {
+            let now = Utc::now();
+            let (local_content, local_modified) = match local_index.get(path.as_str()) {
+                Some(entry) => (String::from_utf8_lossy(&local.get(path).await?).to_string(), entry.modified.unwrap_or(now)),
+                None => (String::new(), now),
+            };
+            let (remote_content, remote_modified) = match remote_index.get(path.as_str()) {
+                Some(entry) => (String::from_utf8_lossy(&remote.get(path).await?).to_string(), entry.modified.unwrap_or(now)),
+                None => (String::new(), now),
+            };
+            result.conflicts.push(SyncConflict {
+                file_path: path.clone(),
+                local_content,
+                remote_content,
+                local_modified,
+                remote_modified,
+                conflict_type: ConflictType::DeleteConflict,
+            });
+            if let Some(info) = state.get_mut(path) {
+                info.sync_status = SyncStatus::Conflict;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 按用户选择解决冲突
+pub async fn resolve(
+    local: &dyn SyncBackend,
+    remote: &dyn SyncBackend,
+    state: &mut HashMap<String, FileSyncInfo>,
+    path: &str,
+    resolution: &ConflictResolution,
+) -> Result<()> {
+    match resolution {
+        ConflictResolution::UseLocal => copy_over(local, remote, state, path, true).await,
+        ConflictResolution::UseRemote => copy_over(remote, local, state, path, false).await,
+        ConflictResolution::CreateCopy => {
+            // 远端版本另存为本地副本，保留本地版本
+            if let Ok(remote_data) = remote.get(path).await {
+                local.put(&conflict_copy_path(path), &remote_data, WriteCondition::Overwrite).await?;
+            }
+            copy_over(local, remote, state, path, true).await
+        }
+        ConflictResolution::Merge => Err(AppError::Sync(
+            "Merge requires saving the merged content locally and resolving with UseLocal".to_string(),
+        )),
+    }
+}
+
+/// 用 `source` 的版本覆盖 `target`，source 中不存在则删除 target
+async fn copy_over(
+    source: &dyn SyncBackend,
+    target: &dyn SyncBackend,
+    state: &mut HashMap<String, FileSyncInfo>,
+    path: &str,
+    source_is_local: bool,
+) -> Result<()> {
+    if source.stat(path).await?.is_none() {
+        target.delete(path).await?;
+        state.remove(path);
+        return Ok(());
+    }
+
+    let data = source.get(path).await?;
+    let written = target.put(path, &data, WriteCondition::Overwrite).await?;
+    let source_etag = source.stat(path).await?
+        .map(|entry| entry.etag)
+        .unwrap_or_else(|| content_hash(&data));
+
+    if source_is_local {
+        record(state, path, source_etag, &written);
+    } else {
+        let remote_entry = RemoteEntry { etag: source_etag, ..written.clone() };
+        record(state, path, written.etag, &remote_entry);
+    }
+    Ok(())
+}
+
+/// 目标端已有该文件时要求其仍是列出时的版本，没有时只允许创建
+fn expected_version<'a>(index: &HashMap<&str, &'a RemoteEntry>, path: &str) -> WriteCondition<'a> {
+    match index.get(path) {
+        Some(entry) => WriteCondition::IfMatch(&entry.etag),
+        None => WriteCondition::Create,
+    }
+}
+
+fn conflict_copy_path(path: &str) -> String {
+    let suffix = format!(" (conflict {})", Utc::now().format("%Y%m%d-%H%M%S"));
+    let (dir, file) = match path.rfind('/') {
+        Some(pos) => (&path[..=pos], &path[pos + 1..]),
+        None => ("", path),
+    };
+    match file.rfind('.') {
+        Some(pos) if pos > 0 => format!("{}{}{}{}", dir, &file[..pos], suffix, &file[pos..]),
+        _ => format!("{}{}{}", dir, file, suffix),
+    }
+}
+
+fn record(state: &mut HashMap<String, FileSyncInfo>, path: &str, local_hash: String, remote: &RemoteEntry) {
+    let now = Utc::now();
+    state.insert(path.to_string(), FileSyncInfo {
+        file_path: path.to_string(),
+        local_hash,
+        remote_hash: Some(remote.etag.clone()),
+        local_modified: now,
+        remote_modified: remote.modified,
+        sync_status: SyncStatus::Success,
+        last_sync: Some(now),
+    });
+}
+
+fn mark_conflict(state: &mut HashMap<String, FileSyncInfo>, path: &str, local: &RemoteEntry) {
+    state.entry(path.to_string())
+        .or_insert_with(|| FileSyncInfo {
+            file_path: path.to_string(),
+            local_hash: String::new(),
+            remote_hash: None,
+            local_modified: local.modified.unwrap_or_else(Utc::now),
+            remote_modified: None,
+            sync_status: SyncStatus::Conflict,
+            last_sync: None,
+        })
+        .sync_status = SyncStatus::Conflict;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::local::LocalFolderBackend;
+    use tempfile::tempdir;
+
+    fn empty_result() -> SyncResult {
+        SyncResult {
+            status: SyncStatus::Syncing,
+            files_uploaded: 0,
+            files_downloaded: 0,
+            files_deleted: 0,
+            conflicts: Vec::new(),
+            errors: Vec::new(),
+            start_time: Utc::now(),
+            end_time: None,
+        }
+    }
+
+    async fn run(
+        local: &LocalFolderBackend,
+        remote: &LocalFolderBackend,
+        state: &mut HashMap<String, FileSyncInfo>,
+    ) -> SyncResult {
+        let mut result = empty_result();
+        sync(local, remote, &SyncDirection::Bidirectional, state, &mut result).await.unwrap();
+        assert!(result.errors.is_empty(), "unexpected errors: {:?}", result.errors);
+        result
+    }
+
+    #[tokio::test]
+    async fn test_bidirectional_sync_propagates_changes() {
+        let (local_dir, remote_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let local = LocalFolderBackend::new(local_dir.path());
+        let remote = LocalFolderBackend::new(remote_dir.path());
+        let mut state = HashMap::new();
+
+        local.put("pages/a.md", b"local a", WriteCondition::Overwrite).await.unwrap();
+        remote.put("pages/b.md", b"remote b", WriteCondition::Overwrite).await.unwrap();
+
+        let result = run(&local, &remote, &mut state).await;
+        assert_eq!(result.files_uploaded, 1);
+        assert_eq!(result.files_downloaded, 1);
+        assert_eq!(remote.get("pages/a.md").await.unwrap(), b"local a");
+        assert_eq!(local.get("pages/b.md").await.unwrap(), b"remote b");
+
+        // 第二次同步没有任何变化
+        let result = run(&local, &remote, &mut state).await;
+        assert_eq!(result.files_uploaded + result.files_downloaded + result.files_deleted, 0);
+
+        // 本地修改与远端删除分别传播
+        local.put("pages/a.md", b"local a v2", WriteCondition::Overwrite).await.unwrap();
+        remote.delete("pages/b.md").await.unwrap();
+        let result = run(&local, &remote, &mut state).await;
+        assert_eq!(result.files_uploaded, 1);
+        assert_eq!(result.files_deleted, 1);
+        assert_eq!(remote.get("pages/a.md").await.unwrap(), b"local a v2");
+        assert!(local.stat("pages/b.md").await.unwrap().is_none());
+        assert!(!state.contains_key("pages/b.md"));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_edits_produce_conflict() {
+        let (local_dir, remote_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let local = LocalFolderBackend::new(local_dir.path());
+        let remote = LocalFolderBackend::new(remote_dir.path());
+        let mut state = HashMap::new();
+
+        local.put("a.md", b"base", WriteCondition::Overwrite).await.unwrap();
+        run(&local, &remote, &mut state).await;
+
+        local.put("a.md", b"local edit", WriteCondition::Overwrite).await.unwrap();
+        remote.put("a.md", b"remote edit", WriteCondition::Overwrite).await.unwrap();
+        let result = run(&local, &remote, &mut state).await;
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].local_content, "local edit");
+        assert_eq!(result.conflicts[0].remote_content, "remote edit");
+        assert_eq!(state["a.md"].sync_status, SyncStatus::Conflict);
+
+        resolve(&local, &remote, &mut state, "a.md", &ConflictResolution::UseRemote).await.unwrap();
+        assert_eq!(local.get("a.md").await.unwrap(), b"remote edit");
+        let result = run(&local, &remote, &mut state).await;
+        assert!(result.conflicts.is_empty());
+        assert_eq!(result.files_uploaded + result.files_downloaded, 0);
+    }
+
+    #[tokio::test]
+    async fn test_identical_new_files_are_not_conflicts() {
+        let (local_dir, remote_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let local = LocalFolderBackend::new(local_dir.path());
+        let remote = LocalFolderBackend::new(remote_dir.path());
+        let mut state = HashMap::new();
+
+        local.put("same.md", b"same", WriteCondition::Overwrite).await.unwrap();
+        remote.put("same.md", b"same", WriteCondition::Overwrite).await.unwrap();
+        let result = run(&local, &remote, &mut state).await;
+        assert!(result.conflicts.is_empty());
+        assert_eq!(state["same.md"].sync_status, SyncStatus::Success);
+    }
+
+    #[tokio::test]
+    async fn test_create_copy_keeps_both_versions() {
+        let (local_dir, remote_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let local = LocalFolderBackend::new(local_dir.path());
+        let remote = LocalFolderBackend::new(remote_dir.path());
+        let mut state = HashMap::new();
+
+        local.put("notes/a.md", b"mine", WriteCondition::Overwrite).await.unwrap();
+        remote.put("notes/a.md", b"theirs", WriteCondition::Overwrite).await.unwrap();
+        run(&local, &remote, &mut state).await;
+
+        resolve(&local, &remote, &mut state, "notes/a.md", &ConflictResolution::CreateCopy).await.unwrap();
+        assert_eq!(remote.get("notes/a.md").await.unwrap(), b"mine");
+        let copies: Vec<String> = local.list().await.unwrap().into_iter()
+            .map(|e| e.path)
+            .filter(|p| p.starts_with("notes/a (conflict "))
+            .collect();
+        assert_eq!(copies.len(), 1);
+        assert!(copies[0].ends_with(".md"));
+    }
+
+    #[test]
+    fn test_plan_respects_direction() {
+        let entry = |path: &str| RemoteEntry {
+            path: path.to_string(),
+            etag: "etag".to_string(),
+            size: 0,
+            modified: None,
+        };
+        let local = vec![entry("local.md")];
+        let remote = vec![entry("remote.md")];
+        let state = HashMap::new();
+
+        assert_eq!(
+            plan(&local, &remote, &state, &SyncDirection::Upload),
+            vec![SyncAction::Upload("local.md".to_string())]
+        );
+        assert_eq!(
+            plan(&local, &remote, &state, &SyncDirection::Download),
+            vec![SyncAction::Download("remote.md".to_string())]
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use super::backend::{content_hash, normalize_path, precondition_failed, RemoteEntry, SyncBackend, WriteCondition};
+use crate::error::{AppError, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 写入过程中使用的临时文件后缀，列出文件时忽略
+const TEMP_SUFFIX: &str = ".minglog-tmp";
+
+/// 本地文件夹同步配置（Syncthing 目录、NAS 共享等）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderSyncConfig {
+    pub folder_path: String,
+    pub enabled: bool,
+    pub auto_sync_interval: Option<u64>, // 自动同步间隔（秒）
+}
+
+/// 以本地（或挂载的）文件夹作为同步目标的后端
+#[derive(Debug, Clone)]
+pub struct LocalFolderBackend {
+    root: PathBuf,
+}
+
+impl LocalFolderBackend {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    #[allow(dead_code)]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn resolve(&self, path: &str) -> Result<PathBuf> {
+        let normalized = normalize_path(path)?;
+        Ok(normalized.split('/').fold(self.root.clone(), |acc, segment| acc.join(segment)))
+    }
+
+    fn entry_for(path: String, file_path: &Path) -> Result<RemoteEntry> {
+        let data = fs::read(file_path)?;
+        let modified = fs::metadata(file_path)?
+            .modified()
+            .ok()
+            .map(DateTime::<Utc>::from);
+        Ok(RemoteEntry {
+            path,
+            etag: content_hash(&data),
+            size: data.len() as u64,
+            modified,
+        })
+    }
+
+    fn walk(root: &Path, dir: &Path, entries: &mut Vec<RemoteEntry>) -> Result<()> {
+        for item in fs::read_dir(dir)? {
+            let item = item?;
+            let file_path = item.path();
+            let file_type = item.file_type()?;
+            if file_type.is_dir() {
+                Self::walk(root, &file_path, entries)?;
+            } else if file_type.is_file() {
+                let name = item.file_name().to_string_lossy().to_string();
+                if name.ends_with(TEMP_SUFFIX) {
+                    continue;
+                }
+                let relative = file_path
+                    .strip_prefix(root)
+                    .map_err(|e| AppError::Internal(e.to_string()))?
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().to_string())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                entries.push(Self::entry_for(relative, &file_path)?);
+            }
+        }
+        Ok(())
+    }
+
+    fn stat_blocking(&self, path: &str) -> Result<Option<RemoteEntry>> {
+        let file_path = self.resolve(path)?;
+        if !file_path.is_file() {
+            return Ok(None);
+        }
+        Self::entry_for(normalize_path(path)?, &file_path).map(Some)
+    }
+}
+
+async fn blocking<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| AppError::Internal(format!("Blocking task failed: {}", e)))?
+}
+
+#[async_trait]
+impl SyncBackend for LocalFolderBackend {
+    fn name(&self) -> &str {
+        "folder"
+    }
+
+    fn location(&self) -> String {
+        format!("folder:{}", self.root.display())
+    }
+
+    async fn list(&self) -> Result<Vec<RemoteEntry>> {
+        let root = self.root.clone();
+        blocking(move || {
+            let mut entries = Vec::new();
+            if root.is_dir() {
+                Self::walk(&root, &root, &mut entries)?;
+            }
+            entries.sort_by(|a, b| a.path.cmp(&b.path));
+            Ok(entries)
+        })
+        .await
+    }
+
+    async fn get(&self, path: &str) -> Result<Vec<u8>> {
+        let file_path = self.resolve(path)?;
+        let display = path.to_string();
+        blocking(move || {
+            fs::read(&file_path).map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => AppError::NotFound(format!("Sync file not found: {}", display)),
+                _ => e.into(),
+            })
+        })
+        .await
+    }
+
+    async fn put(&self, path: &str, data: &[u8], condition: WriteCondition<'_>) -> Result<RemoteEntry> {
+        let backend = self.clone();
+        let path = normalize_path(path)?;
+        let data = data.to_vec();
+        let expected = match condition {
+            WriteCondition::Overwrite => None,
+            WriteCondition::Create => Some(None),
+            WriteCondition::IfMatch(etag) => Some(Some(etag.to_string())),
+        };
+        blocking(move || {
+            if let Some(expected) = expected {
+                let current = backend.stat_blocking(&path)?;
+                if current.map(|entry| entry.etag) != expected {
+                    return Err(precondition_failed(&path));
+                }
+            }
+
+            let file_path = backend.resolve(&path)?;
+            if let Some(parent) = file_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            // 先写临时文件再重命名，避免其他同步工具读到半个文件
+            let mut temp_name = file_path.file_name().unwrap_or_default().to_os_string();
+            temp_name.push(TEMP_SUFFIX);
+            let temp_path = file_path.with_file_name(temp_name);
+            fs::write(&temp_path, &data)?;
+            fs::rename(&temp_path, &file_path)?;
+
+            Self::entry_for(path, &file_path)
+        })
+        .await
+    }
+
+    async fn delete(&self, path: &str) -> Result<()> {
+        let file_path = self.resolve(path)?;
+        blocking(move || match fs::remove_file(&file_path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        })
+        .await
+    }
+
+    async fn stat(&self, path: &str) -> Result<Option<RemoteEntry>> {
+        let backend = self.clone();
+        let path = path.to_string();
+        blocking(move || backend.stat_blocking(&path)).await
+    }
+
+    async fn test_connection(&self) -> Result<bool> {
+        Ok(self.root.is_dir())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_put_get_list_delete() {
+        let dir = tempdir().unwrap();
+        let backend = LocalFolderBackend::new(dir.path());
+
+        let entry = backend.put("pages/a.md", b"hello", WriteCondition::Overwrite).await.unwrap();
+        assert_eq!(entry.path, "pages/a.md");
+        assert_eq!(entry.etag, content_hash(b"hello"));
+        assert_eq!(backend.get("pages/a.md").await.unwrap(), b"hello");
+
+        backend.put("b.md", b"world", WriteCondition::Overwrite).await.unwrap();
+        let paths: Vec<String> = backend.list().await.unwrap().into_iter().map(|e| e.path).collect();
+        assert_eq!(paths, vec!["b.md".to_string(), "pages/a.md".to_string()]);
+
+        backend.delete("pages/a.md").await.unwrap();
+        assert!(backend.stat("pages/a.md").await.unwrap().is_none());
+        assert!(backend.delete("pages/a.md").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_conditional_put() {
+        let dir = tempdir().unwrap();
+        let backend = LocalFolderBackend::new(dir.path());
+
+        let first = backend.put("a.md", b"v1", WriteCondition::Create).await.unwrap();
+        assert!(backend.put("a.md", b"v2", WriteCondition::IfMatch("stale")).await.is_err());
+        assert!(backend.put("a.md", b"v2", WriteCondition::Create).await.is_err());
+        assert!(backend.put("a.md", b"v2", WriteCondition::IfMatch(&first.etag)).await.is_ok());
+        assert_eq!(backend.get("a.md").await.unwrap(), b"v2");
+    }
+
+    #[tokio::test]
+    async fn test_rejects_escaping_paths() {
+        let dir = tempdir().unwrap();
+        let backend = LocalFolderBackend::new(dir.path());
+        assert!(backend.put("../outside.md", b"x", WriteCondition::Overwrite).await.is_err());
+        assert!(backend.get("../outside.md").await.is_err());
+    }
+}
@@ -0,0 +1,332 @@
+use super::backend::{RemoteEntry, SyncBackend, WriteCondition};
+use super::crdt::{Hlc, OpBatch};
+use crate::database::Database;
+use crate::error::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tokio::sync::Mutex;
+
+/// 远端存放操作批次的目录：`.minglog/ops/<设备ID>/<最后一个操作的时间戳>.json`
+pub const OPS_DIR: &str = ".minglog/ops";
+
+/// 已上传到远端的本设备最大操作时间戳
+pub(super) const UPLOADED_UNTIL_KEY: &str = "block_ops_uploaded_until";
+
+/// 远端保留的本设备批次超过这个数量时合并成一个
+const MAX_UPLOADED_BATCHES: usize = 16;
+
+/// 一次操作交换的结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OpExchangeResult {
+    pub ops_sent: usize,
+    pub ops_received: usize,
+}
+
+/// 通过同步后端与其他设备交换块操作批次
+///
+/// 只在读写本地操作时短暂锁住数据库，网络请求期间其他命令不受影响
+pub async fn exchange_block_ops(database: &Mutex<Database>, backend: &dyn SyncBackend) -> Result<OpExchangeResult> {
+    let mut result = OpExchangeResult::default();
+
+    // 上传本设备自上次上传以来的操作
+    let (device_id, batch) = {
+        let db = database.lock().await;
+        let device_id = db.get_device_id().await?;
+        let uploaded_until = db.get_setting(UPLOADED_UNTIL_KEY).await?
+            .map(|hlc| hlc.parse::<Hlc>())
+            .transpose()?;
+        let own_ops = db.get_device_block_ops(&device_id, uploaded_until.as_ref()).await?;
+        let batch = db.block_op_batch(&device_id, own_ops).await?;
+        (device_id, batch)
+    };
+    if let Some(last) = batch.ops.last().map(|op| op.id.clone()) {
+        let path = format!("{}/{}/{}.json", OPS_DIR, device_id, last);
+        result.ops_sent = batch.ops.len();
+        // 批次路径带设备ID，只有本设备写入；上次上传成功但没记下进度时重试会写入同样的内容
+        backend.put(&path, &serde_json::to_vec(&batch)?, WriteCondition::Overwrite).await?;
+        database.lock().await.set_setting(UPLOADED_UNTIL_KEY, &last.to_string()).await?;
+    }
+
+    let entries = backend.list().await?;
+    compact_uploaded_batches(database, backend, &device_id, &entries).await?;
+
+    // 按时间顺序下载其他设备的新批次
+    let version = database.lock().await.get_block_version_vector().await?;
+    let mut pending: Vec<(Hlc, String, String)> = entries
+        .into_iter()
+        .filter_map(|entry| {
+            let (device, file) = entry.path.strip_prefix(OPS_DIR)?.trim_start_matches('/').split_once('/')?;
+            let last: Hlc = file.strip_suffix(".json")?.parse().ok()?;
+            let known = version.get(device).map_or(false, |known| *known >= last);
+            (device != device_id && !known).then(|| (last, device.to_string(), entry.path.clone()))
+        })
+        .collect();
+    pending.sort();
+
+    // 列出之后被合并掉的批次：该设备剩下的批次留到下次，届时从合并后的批次读取
+    let mut merged_devices = HashSet::new();
+    for (_, device, path) in pending {
+        if merged_devices.contains(&device) {
+            continue;
+        }
+        let data = match backend.get(&path).await {
+            Ok(data) => data,
+            Err(AppError::NotFound(_)) => {
+                merged_devices.insert(device);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let batch: OpBatch = serde_json::from_slice(&data)?;
+        result.ops_received += database.lock().await.apply_block_ops(&batch).await?;
+    }
+
+    if result.ops_sent + result.ops_received > 0 {
+        log::info!(
+            "Exchanged block ops via {}: {} sent, {} received",
+            backend.name(), result.ops_sent, result.ops_received
+        );
+    }
+    Ok(result)
+}
+
+/// 把本设备在远端的批次合并成一个，只保留本地压缩后仍然有效的操作
+///
+/// 合并结果写在最新批次的路径上：读过最新批次的设备也读过之前的所有批次，
+/// 其他设备下次会读到完整的合并批次。
+async fn compact_uploaded_batches(
+    database: &Mutex<Database>,
+    backend: &dyn SyncBackend,
+    device_id: &str,
+    entries: &[RemoteEntry],
+) -> Result<()> {
+    let prefix = format!("{}/{}/", OPS_DIR, device_id);
+    let mut batches: Vec<(Hlc, &str)> = entries.iter()
+        .filter_map(|entry| {
+            let last = entry.path.strip_prefix(&prefix)?.strip_suffix(".json")?.parse().ok()?;
+            Some((last, entry.path.as_str()))
+        })
+        .collect();
+    if batches.len() <= MAX_UPLOADED_BATCHES {
+        return Ok(());
+    }
+    batches.sort();
+    let (latest, latest_path) = batches.pop().unwrap();
+
+    let batch = {
+        let db = database.lock().await;
+        let mut ops = db.get_device_block_ops(device_id, None).await?;
+        ops.retain(|op| op.id <= latest);
+        db.block_op_batch(device_id, ops).await?
+    };
+    backend.put(latest_path, &serde_json::to_vec(&batch)?, WriteCondition::Overwrite).await?;
+    for (_, path) in &batches {
+        backend.delete(path).await?;
+    }
+
+    log::info!("Merged {} block op batches on {}", batches.len() + 1, backend.name());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreateBlockRequest, CreatePageRequest, UpdateBlockRequest, UpdatePageRequest};
+    use crate::sync::LocalFolderBackend;
+    use tempfile::tempdir;
+
+    async fn device(dir: &std::path::Path, name: &str) -> Mutex<Database> {
+        Mutex::new(Database::new_with_path(dir.join(format!("{}.db", name)).to_str().unwrap()).await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_two_devices_merge_concurrent_block_edits() {
+        let dir = tempdir().unwrap();
+        let remote = LocalFolderBackend::new(dir.path().join("remote"));
+        let laptop = device(dir.path(), "laptop").await;
+        let desktop = device(dir.path(), "desktop").await;
+
+        // 页面只在笔记本上创建，随操作批次到达台式机
+        let page = laptop.lock().await.create_page(CreatePageRequest {
+            name: "Shared".to_string(),
+            title: None,
+            properties: None,
+            tags: None,
+            is_journal: None,
+            journal_date: None,
+            graph_id: "default".to_string(),
+        }).await.unwrap();
+
+        let block = laptop.lock().await.create_block(CreateBlockRequest {
+            content: "meeting notes".to_string(),
+            parent_id: None,
+            properties: None,
+            refs: None,
+            order: Some(0),
+            page_id: page.id.clone(),
+            graph_id: "default".to_string(),
+        }).await.unwrap();
+        laptop.lock().await.record_block_change(&block.id, &page.id).await.unwrap();
+
+        let sent = exchange_block_ops(&laptop, &remote).await.unwrap();
+        assert!(sent.ops_sent > 0);
+        let received = exchange_block_ops(&desktop, &remote).await.unwrap();
+        assert_eq!(received.ops_received, sent.ops_sent);
+        assert_eq!(desktop.lock().await.get_block(&block.id).await.unwrap().content, "meeting notes");
+        assert_eq!(desktop.lock().await.get_page(&page.id).await.unwrap().name, "Shared");
+
+        // 离线期间两端修改同一个块
+        let update = |content: &str| UpdateBlockRequest {
+            id: block.id.clone(),
+            content: Some(content.to_string()),
+            parent_id: None,
+            properties: None,
+            refs: None,
+            order: None,
+            collapsed: None,
+        };
+        laptop.lock().await.update_block(update("weekly meeting notes")).await.unwrap();
+        laptop.lock().await.record_block_change(&block.id, &page.id).await.unwrap();
+        desktop.lock().await.update_block(update("meeting notes and actions")).await.unwrap();
+        desktop.lock().await.record_block_change(&block.id, &page.id).await.unwrap();
+
+        exchange_block_ops(&laptop, &remote).await.unwrap();
+        exchange_block_ops(&desktop, &remote).await.unwrap();
+        exchange_block_ops(&laptop, &remote).await.unwrap();
+
+        let merged = "weekly meeting notes and actions";
+        assert_eq!(laptop.lock().await.get_block(&block.id).await.unwrap().content, merged);
+        assert_eq!(desktop.lock().await.get_block(&block.id).await.unwrap().content, merged);
+
+        // 改名随下一次块修改同步过去
+        laptop.lock().await.update_page(UpdatePageRequest {
+            id: page.id.clone(),
+            name: Some("Weekly".to_string()),
+            title: None,
+            properties: None,
+            tags: None,
+            is_journal: None,
+            journal_date: None,
+        }).await.unwrap();
+        laptop.lock().await.update_block(update("weekly meeting notes and actions!")).await.unwrap();
+        laptop.lock().await.record_block_change(&block.id, &page.id).await.unwrap();
+        exchange_block_ops(&laptop, &remote).await.unwrap();
+        exchange_block_ops(&desktop, &remote).await.unwrap();
+        assert_eq!(desktop.lock().await.get_page(&page.id).await.unwrap().name, "Weekly");
+
+        // 再次交换没有新内容
+        let idle = exchange_block_ops(&desktop, &remote).await.unwrap();
+        assert_eq!(idle.ops_sent + idle.ops_received, 0);
+    }
+
+    #[tokio::test]
+    async fn test_blocks_written_outside_commands_are_synced() {
+        let dir = tempdir().unwrap();
+        let remote = LocalFolderBackend::new(dir.path().join("remote"));
+        let laptop = device(dir.path(), "laptop").await;
+        let desktop = device(dir.path(), "desktop").await;
+
+        let page = laptop.lock().await.create_page(CreatePageRequest {
+            name: "Imported".to_string(),
+            title: None,
+            properties: None,
+            tags: None,
+            is_journal: None,
+            journal_date: None,
+            graph_id: "default".to_string(),
+        }).await.unwrap();
+
+        // 像导入那样直接写块，不调用 record_block_change
+        for (order, content) in ["first", "second"].iter().enumerate() {
+            laptop.lock().await.create_block(CreateBlockRequest {
+                content: content.to_string(),
+                parent_id: None,
+                properties: None,
+                refs: None,
+                order: Some(order as i32),
+                page_id: page.id.clone(),
+                graph_id: "default".to_string(),
+            }).await.unwrap();
+        }
+
+        exchange_block_ops(&laptop, &remote).await.unwrap();
+        exchange_block_ops(&desktop, &remote).await.unwrap();
+        let contents: Vec<String> = desktop.lock().await.get_blocks_by_page(&page.id).await.unwrap()
+            .into_iter()
+            .map(|block| block.content)
+            .collect();
+        assert_eq!(contents, ["first", "second"]);
+
+        // 删除页面会连带删除它的块，也要同步出去
+        laptop.lock().await.delete_page(&page.id).await.unwrap();
+        exchange_block_ops(&laptop, &remote).await.unwrap();
+        exchange_block_ops(&desktop, &remote).await.unwrap();
+        assert!(desktop.lock().await.get_blocks_by_page(&page.id).await.unwrap().is_empty());
+
+        let idle = exchange_block_ops(&desktop, &remote).await.unwrap();
+        assert_eq!(idle.ops_sent + idle.ops_received, 0);
+    }
+
+    #[tokio::test]
+    async fn test_uploaded_batches_are_merged() {
+        let dir = tempdir().unwrap();
+        let remote = LocalFolderBackend::new(dir.path().join("remote"));
+        let laptop = device(dir.path(), "laptop").await;
+
+        let page = laptop.lock().await.create_page(CreatePageRequest {
+            name: "Journal".to_string(),
+            title: None,
+            properties: None,
+            tags: None,
+            is_journal: None,
+            journal_date: None,
+            graph_id: "default".to_string(),
+        }).await.unwrap();
+        let block = laptop.lock().await.create_block(CreateBlockRequest {
+            content: "draft".to_string(),
+            parent_id: None,
+            properties: None,
+            refs: None,
+            order: Some(0),
+            page_id: page.id.clone(),
+            graph_id: "default".to_string(),
+        }).await.unwrap();
+
+        // 每次修改都单独上传一个批次
+        for revision in 0..MAX_UPLOADED_BATCHES + 4 {
+            laptop.lock().await.update_block(UpdateBlockRequest {
+                id: block.id.clone(),
+                content: None,
+                parent_id: None,
+                properties: Some(format!("{{\"revision\":{}}}", revision)),
+                refs: None,
+                order: None,
+                collapsed: None,
+            }).await.unwrap();
+            exchange_block_ops(&laptop, &remote).await.unwrap();
+        }
+
+        let batches = remote.list().await.unwrap().into_iter()
+            .filter(|entry| entry.path.starts_with(OPS_DIR))
+            .count();
+        assert!(batches <= MAX_UPLOADED_BATCHES, "{} batches left", batches);
+
+        // 被后来的值覆盖的字段操作已从本地操作日志中删除
+        let field_ops: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM block_ops WHERE block_id = ? AND json_extract(op, '$.type') = 'set_field'"
+        )
+        .bind(&block.id)
+        .fetch_one(laptop.lock().await.get_pool())
+        .await
+        .unwrap();
+        assert_eq!(field_ops, 1);
+
+        // 新设备从合并后的批次得到完整的块
+        let desktop = device(dir.path(), "desktop").await;
+        exchange_block_ops(&desktop, &remote).await.unwrap();
+        let synced = desktop.lock().await.get_block(&block.id).await.unwrap();
+        assert_eq!(synced.content, "draft");
+        let expected = format!("{{\"revision\":{}}}", MAX_UPLOADED_BATCHES + 3);
+        assert_eq!(synced.properties.as_deref(), Some(expected.as_str()));
+    }
+}
@@ -0,0 +1,770 @@
+use super::crdt::{OpBatch, VersionVector};
+use super::op_exchange::OpExchangeResult;
+use super::s3::hex;
+use crate::database::Database;
+use crate::error::{AppError, Result};
+use chrono::{DateTime, Duration, Utc};
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use snow::{HandshakeState, TransportState};
+use spake2::{Ed25519Group, Identity, Password, Spake2};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+/// 局域网同步默认端口，TCP 用于交换操作，UDP 用于设备发现
+pub const DEFAULT_P2P_PORT: u16 = 47321;
+
+/// 已配对设备列表在 settings 表中的键
+pub const PEERS_KEY: &str = "p2p_peers";
+const DISCOVERY_PROBE: &[u8] = b"MINGLOG_DISCOVER";
+const PAIRING_CODE_TTL_SECS: i64 = 300;
+const IO_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+const MAX_MESSAGE_BYTES: u64 = 64 * 1024 * 1024;
+const PROTOCOL: &str = "minglog-p2p-v2";
+
+/// 双方都没有长期公钥，靠预共享密钥互相认证：配对时是 SPAKE2 协商出的密钥，之后是配对时保存的密钥
+const NOISE_PARAMS: &str = "Noise_NNpsk0_25519_ChaChaPoly_SHA256";
+/// 单条 Noise 消息的长度上限和其中认证标签的长度
+const NOISE_MAX_MESSAGE: usize = 65535;
+const NOISE_TAG_LEN: usize = 16;
+
+/// 已配对的设备
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairedPeer {
+    pub device_id: String,
+    pub address: Option<String>,     // 最近一次主动连接时使用的地址
+    pub paired_at: DateTime<Utc>,
+    pub last_sync: Option<DateTime<Utc>>,
+}
+
+/// 通过局域网广播发现的设备
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiscoveredPeer {
+    pub device_id: String,
+    pub address: String,
+}
+
+/// 本地存储的配对记录，`key` 是配对握手派生出的 32 字节共享密钥
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PeerRecord {
+    #[serde(flatten)]
+    peer: PairedPeer,
+    key: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Beacon {
+    device_id: String,
+    port: u16,
+}
+
+/// 连接上传输的 JSON 消息。Noise 握手之前的消息按行明文传输，之后都加密
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message {
+    Hello { device_id: String, pairing: bool },
+    Challenge { device_id: String, spake: Option<String> },
+    Spake { message: String },
+    Pull { version: VersionVector },
+    Ops { batch: OpBatch, version: VersionVector },
+    Push { batch: OpBatch },
+    Done { applied: usize },
+    Error { message: String },
+}
+
+/// 发起连接的一方是客户端，在 SPAKE2 中是 A，在 Noise 中是发起方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Client,
+    Server,
+}
+
+/// 一次连接的公开参数，作为 Noise 的 prologue，双方不一致时握手失败
+struct Handshake {
+    client_id: String,
+    server_id: String,
+    pairing: bool,
+}
+
+impl Handshake {
+    fn prologue(&self) -> Vec<u8> {
+        let mut prologue = Vec::new();
+        for field in [
+            PROTOCOL.as_bytes(),
+            if self.pairing { b"pair".as_slice() } else { b"sync".as_slice() },
+            self.client_id.as_bytes(),
+            self.server_id.as_bytes(),
+        ] {
+            prologue.extend_from_slice(&(field.len() as u64).to_be_bytes());
+            prologue.extend_from_slice(field);
+        }
+        prologue
+    }
+
+    /// 用配对码开始 SPAKE2，返回状态和要发给对方的消息。
+    /// 监听者拿不到可以离线验证配对码的数据，主动攻击者每个配对码只能猜一次
+    fn start_pairing(&self, role: Role, code: &str) -> (Spake2<Ed25519Group>, String) {
+        let password = Password::new(code.trim().as_bytes());
+        let client = Identity::new(self.client_id.as_bytes());
+        let server = Identity::new(self.server_id.as_bytes());
+        let (spake, message) = match role {
+            Role::Client => Spake2::<Ed25519Group>::start_a(&password, &client, &server),
+            Role::Server => Spake2::<Ed25519Group>::start_b(&password, &client, &server),
+        };
+        (spake, hex(&message))
+    }
+
+    /// 准备 Noise 握手，`psk` 必须是 32 字节
+    fn noise(&self, role: Role, psk: &[u8]) -> Result<HandshakeState> {
+        let prologue = self.prologue();
+        let builder = snow::Builder::new(NOISE_PARAMS.parse().map_err(noise_error)?)
+            .psk(0, psk)
+            .prologue(&prologue);
+        match role {
+            Role::Client => builder.build_initiator(),
+            Role::Server => builder.build_responder(),
+        }
+        .map_err(noise_error)
+    }
+}
+
+/// 由对方的 SPAKE2 消息算出共享密钥，配对码不同时双方得到不同的密钥，在随后的 Noise 握手中失败
+fn finish_pairing(spake: Spake2<Ed25519Group>, peer_message: &str) -> Result<Vec<u8>> {
+    let invalid = || AppError::Sync("Invalid key exchange message from peer".to_string());
+    let message = decode_hex(peer_message).ok_or_else(invalid)?;
+    spake.finish(&message).map_err(|_| invalid())
+}
+
+/// 配对时保存的长期密钥，由握手哈希派生，双方相同且每次配对都不同
+fn peer_key(noise: &HandshakeState) -> String {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(None, noise.get_handshake_hash())
+        .expand(b"minglog peer key", &mut key)
+        .expect("32 bytes is a valid HKDF output length");
+    hex(&key)
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if value.len() % 2 != 0 || !value.is_ascii() {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&value[i..i + 2], 16).ok())
+        .collect()
+}
+
+fn noise_error(err: snow::Error) -> AppError {
+    AppError::Sync(format!("Secure channel error: {}", err))
+}
+
+struct Connection {
+    stream: BufReader<TcpStream>,
+    transport: Option<TransportState>,
+    /// Noise 握手进行中，这时发出的明文会被对方当成握手消息
+    handshaking: bool,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Self {
+        Self { stream: BufReader::new(stream), transport: None, handshaking: false }
+    }
+
+    /// 完成 Noise 握手，之后的消息都加密传输。预共享密钥不一致时握手失败。
+    /// 返回由本次握手派生的长期密钥
+    async fn secure(&mut self, mut noise: HandshakeState) -> Result<String> {
+        self.handshaking = true;
+        let mut buf = vec![0u8; NOISE_MAX_MESSAGE];
+        while !noise.is_handshake_finished() {
+            if noise.is_my_turn() {
+                let len = noise.write_message(&[], &mut buf).map_err(noise_error)?;
+                self.send_frame(&buf[..len]).await?;
+            } else {
+                let frame = self.recv_frame().await?;
+                noise
+                    .read_message(&frame, &mut buf)
+                    .map_err(|_| AppError::PermissionDenied("Peer failed authentication".to_string()))?;
+            }
+        }
+        let key = peer_key(&noise);
+        self.transport = Some(noise.into_transport_mode().map_err(noise_error)?);
+        self.handshaking = false;
+        Ok(key)
+    }
+
+    /// 能否向对方发送错误消息
+    fn can_report(&self) -> bool {
+        !self.handshaking
+    }
+
+    async fn send(&mut self, message: &Message) -> Result<()> {
+        let data = serde_json::to_vec(message)?;
+        if self.transport.is_none() {
+            let mut line = data;
+            line.push(b'\n');
+            return self.write(&line).await;
+        }
+
+        // 先发送消息长度，再按 Noise 消息的上限分块发送
+        self.send_encrypted(&(data.len() as u32).to_be_bytes()).await?;
+        for chunk in data.chunks(NOISE_MAX_MESSAGE - NOISE_TAG_LEN) {
+            self.send_encrypted(chunk).await?;
+        }
+        Ok(())
+    }
+
+    async fn recv(&mut self) -> Result<Message> {
+        let data = match self.transport.is_some() {
+            true => self.recv_encrypted_message().await?,
+            false => self.recv_line().await?,
+        };
+        match serde_json::from_slice(&data)? {
+            Message::Error { message } => Err(AppError::Sync(message)),
+            message => Ok(message),
+        }
+    }
+
+    async fn write(&mut self, data: &[u8]) -> Result<()> {
+        let stream = self.stream.get_mut();
+        tokio::time::timeout(IO_TIMEOUT, stream.write_all(data))
+            .await
+            .map_err(|_| AppError::Sync("Timed out sending to peer".to_string()))??;
+        Ok(())
+    }
+
+    async fn recv_line(&mut self) -> Result<Vec<u8>> {
+        let mut line = String::new();
+        let mut limited = (&mut self.stream).take(MAX_MESSAGE_BYTES);
+        let len = tokio::time::timeout(IO_TIMEOUT, limited.read_line(&mut line))
+            .await
+            .map_err(|_| AppError::Sync("Timed out waiting for peer".to_string()))??;
+        if len == 0 || !line.ends_with('\n') {
+            return Err(AppError::Sync("Peer closed the connection".to_string()));
+        }
+        Ok(line.into_bytes())
+    }
+
+    /// Noise 消息带两字节长度前缀传输
+    async fn send_frame(&mut self, data: &[u8]) -> Result<()> {
+        let mut frame = (data.len() as u16).to_be_bytes().to_vec();
+        frame.extend_from_slice(data);
+        self.write(&frame).await
+    }
+
+    async fn recv_frame(&mut self) -> Result<Vec<u8>> {
+        let closed = |_| AppError::Sync("Peer closed the connection".to_string());
+        let mut len = [0u8; 2];
+        tokio::time::timeout(IO_TIMEOUT, self.stream.read_exact(&mut len))
+            .await
+            .map_err(|_| AppError::Sync("Timed out waiting for peer".to_string()))?
+            .map_err(closed)?;
+        let mut data = vec![0u8; u16::from_be_bytes(len) as usize];
+        tokio::time::timeout(IO_TIMEOUT, self.stream.read_exact(&mut data))
+            .await
+            .map_err(|_| AppError::Sync("Timed out waiting for peer".to_string()))?
+            .map_err(closed)?;
+        Ok(data)
+    }
+
+    async fn send_encrypted(&mut self, plain: &[u8]) -> Result<()> {
+        let transport = self.transport.as_mut().expect("only called once the channel is secured");
+        let mut frame = vec![0u8; plain.len() + NOISE_TAG_LEN];
+        let len = transport.write_message(plain, &mut frame).map_err(noise_error)?;
+        frame.truncate(len);
+        self.send_frame(&frame).await
+    }
+
+    async fn recv_encrypted(&mut self) -> Result<Vec<u8>> {
+        let frame = self.recv_frame().await?;
+        let transport = self.transport.as_mut().expect("only called once the channel is secured");
+        let mut plain = vec![0u8; frame.len()];
+        let len = transport
+            .read_message(&frame, &mut plain)
+            .map_err(|_| AppError::PermissionDenied("Message from peer failed authentication".to_string()))?;
+        plain.truncate(len);
+        Ok(plain)
+    }
+
+    async fn recv_encrypted_message(&mut self) -> Result<Vec<u8>> {
+        let invalid = || AppError::Sync("Invalid message from peer".to_string());
+        let header: [u8; 4] = self.recv_encrypted().await?.try_into().map_err(|_| invalid())?;
+        let len = u32::from_be_bytes(header) as usize;
+        if len as u64 > MAX_MESSAGE_BYTES {
+            return Err(AppError::Sync("Message from peer is too large".to_string()));
+        }
+        let mut data = Vec::with_capacity(len);
+        while data.len() < len {
+            let chunk = self.recv_encrypted().await?;
+            if chunk.is_empty() {
+                return Err(invalid());
+            }
+            data.extend_from_slice(&chunk);
+        }
+        if data.len() != len {
+            return Err(invalid());
+        }
+        Ok(data)
+    }
+}
+
+#[derive(Debug)]
+struct PairingCode {
+    code: String,
+    expires: DateTime<Utc>,
+}
+
+/// 局域网同步服务端：接受其他设备的连接并响应发现广播
+#[derive(Debug)]
+pub struct P2PServer {
+    device_id: String,
+    port: u16,
+    pairing: Arc<StdMutex<Option<PairingCode>>>,
+    shutdown: CancellationToken,
+}
+
+impl P2PServer {
+    /// 在 `addr` 上监听，UDP 发现使用与 TCP 相同的端口
+    pub async fn start(db: Arc<Mutex<Database>>, addr: SocketAddr) -> Result<Self> {
+        let device_id = db.lock().await.get_device_id().await?;
+        let listener = TcpListener::bind(addr).await?;
+        let port = listener.local_addr()?.port();
+        let discovery = UdpSocket::bind(SocketAddr::new(addr.ip(), port)).await?;
+
+        let server = Self {
+            device_id: device_id.clone(),
+            port,
+            pairing: Arc::new(StdMutex::new(None)),
+            shutdown: CancellationToken::new(),
+        };
+        let pairing = server.pairing.clone();
+        let shutdown = server.shutdown.clone();
+        let beacon = serde_json::to_vec(&Beacon { device_id: device_id.clone(), port })?;
+
+        tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            loop {
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, remote)) => {
+                            let db = db.clone();
+                            let pairing = pairing.clone();
+                            let device_id = device_id.clone();
+                            tokio::spawn(async move {
+                                let mut conn = Connection::new(stream);
+                                if let Err(err) = serve(&mut conn, &db, &pairing, &device_id).await {
+                                    log::warn!("P2P sync with {} failed: {}", remote, err);
+                                    if conn.can_report() {
+                                        let _ = conn.send(&Message::Error { message: err.to_string() }).await;
+                                    }
+                                }
+                            });
+                        }
+                        Err(err) => log::warn!("Failed to accept P2P connection: {}", err),
+                    },
+                    received = discovery.recv_from(&mut buf) => {
+                        if let Ok((len, from)) = received {
+                            if &buf[..len] == DISCOVERY_PROBE {
+                                let _ = discovery.send_to(&beacon, from).await;
+                            }
+                        }
+                    }
+                }
+            }
+        });
+
+        log::info!("P2P sync listening on port {}", port);
+        Ok(server)
+    }
+
+    pub fn device_id(&self) -> &str {
+        &self.device_id
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// 生成 6 位配对码，5 分钟内有效，且无论成功与否只能使用一次
+    pub fn begin_pairing(&self) -> String {
+        let bytes = uuid::Uuid::new_v4().into_bytes();
+        let code = format!("{:06}", u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) % 1_000_000);
+        *self.pairing.lock().unwrap() = Some(PairingCode {
+            code: code.clone(),
+            expires: Utc::now() + Duration::seconds(PAIRING_CODE_TTL_SECS),
+        });
+        code
+    }
+
+    pub fn stop(&self) {
+        self.shutdown.cancel();
+    }
+}
+
+impl Drop for P2PServer {
+    fn drop(&mut self) {
+        self.shutdown.cancel();
+    }
+}
+
+/// 服务端处理一次连接：认证、下发对方缺少的操作、接收对方的操作
+async fn serve(
+    conn: &mut Connection,
+    db: &Mutex<Database>,
+    pairing: &StdMutex<Option<PairingCode>>,
+    device_id: &str,
+) -> Result<()> {
+    let (client_id, is_pairing) = match conn.recv().await? {
+        Message::Hello { device_id, pairing } => (device_id, pairing),
+        _ => return Err(unexpected_message()),
+    };
+
+    let handshake = Handshake { client_id, server_id: device_id.to_string(), pairing: is_pairing };
+    let psk = if is_pairing {
+        // 配对码在收到配对请求时即被消耗，避免被反复猜测
+        let code = pairing.lock().unwrap().take()
+            .filter(|code| code.expires > Utc::now())
+            .map(|code| code.code)
+            .ok_or_else(|| AppError::PermissionDenied("No active pairing code".to_string()))?;
+        let (spake, message) = handshake.start_pairing(Role::Server, &code);
+        conn.send(&Message::Challenge { device_id: device_id.to_string(), spake: Some(message) }).await?;
+        let client_message = match conn.recv().await? {
+            Message::Spake { message } => message,
+            _ => return Err(unexpected_message()),
+        };
+        finish_pairing(spake, &client_message)?
+    } else {
+        let key = load_peers(&*db.lock().await).await?
+            .remove(&handshake.client_id)
+            .and_then(|record| decode_hex(&record.key))
+            .ok_or_else(|| AppError::PermissionDenied(format!("Device {} is not paired", handshake.client_id)))?;
+        conn.send(&Message::Challenge { device_id: device_id.to_string(), spake: None }).await?;
+        key
+    };
+
+    let peer_key = conn.secure(handshake.noise(Role::Server, &psk)?).await?;
+    if is_pairing {
+        let db = db.lock().await;
+        remember_peer(&db, &handshake.client_id, Some(peer_key), None, false).await?;
+    }
+
+    // 同步令牌即对方的版本向量，只下发对方尚未见过的操作
+    let version = match conn.recv().await? {
+        Message::Pull { version } => version,
+        _ => return Err(unexpected_message()),
+    };
+    let (batch, own_version) = {
+        let db = db.lock().await;
+        let batch = db.block_op_batch(device_id, db.get_block_ops_since(&version).await?).await?;
+        (batch, db.get_block_version_vector().await?)
+    };
+    conn.send(&Message::Ops { batch, version: own_version }).await?;
+
+    let batch = match conn.recv().await? {
+        Message::Push { batch } => batch,
+        _ => return Err(unexpected_message()),
+    };
+    let applied = {
+        let db = db.lock().await;
+        let applied = db.apply_block_ops(&batch).await?;
+        remember_peer(&db, &handshake.client_id, None, None, true).await?;
+        applied
+    };
+    conn.send(&Message::Done { applied }).await?;
+
+    log::info!("P2P sync with {}: {} ops received", handshake.client_id, applied);
+    Ok(())
+}
+
+/// 与局域网内的另一台设备同步，首次连接需要提供对方显示的配对码
+///
+/// 只在读写本地数据时短暂锁住数据库，等待对方期间本机的服务端和其他命令照常使用数据库
+pub async fn sync_with_peer(db: &Mutex<Database>, address: &str, pairing_code: Option<&str>) -> Result<OpExchangeResult> {
+    let device_id = db.lock().await.get_device_id().await?;
+    let stream = tokio::time::timeout(IO_TIMEOUT, TcpStream::connect(address))
+        .await
+        .map_err(|_| AppError::Sync(format!("Timed out connecting to {}", address)))??;
+    let mut conn = Connection::new(stream);
+
+    conn.send(&Message::Hello { device_id: device_id.clone(), pairing: pairing_code.is_some() }).await?;
+
+    let (server_id, server_message) = match conn.recv().await? {
+        Message::Challenge { device_id, spake } => (device_id, spake),
+        _ => return Err(unexpected_message()),
+    };
+    let handshake = Handshake { client_id: device_id.clone(), server_id, pairing: pairing_code.is_some() };
+    let psk = match (pairing_code, server_message) {
+        (Some(code), Some(server_message)) => {
+            let (spake, message) = handshake.start_pairing(Role::Client, code);
+            conn.send(&Message::Spake { message }).await?;
+            finish_pairing(spake, &server_message)?
+        }
+        (None, None) => load_peers(&*db.lock().await).await?
+            .remove(&handshake.server_id)
+            .and_then(|record| decode_hex(&record.key))
+            .ok_or_else(|| AppError::PermissionDenied(format!("Device {} is not paired", handshake.server_id)))?,
+        _ => return Err(unexpected_message()),
+    };
+
+    let peer_key = conn.secure(handshake.noise(Role::Client, &psk)?).await?;
+    let peer_key = pairing_code.map(|_| peer_key);
+    remember_peer(&*db.lock().await, &handshake.server_id, peer_key, Some(address), false).await?;
+
+    let version = db.lock().await.get_block_version_vector().await?;
+    conn.send(&Message::Pull { version }).await?;
+    let (batch, server_version) = match conn.recv().await? {
+        Message::Ops { batch, version } => (batch, version),
+        _ => return Err(unexpected_message()),
+    };
+
+    let (ops_received, batch) = {
+        let db = db.lock().await;
+        let ops_received = db.apply_block_ops(&batch).await?;
+        (ops_received, db.block_op_batch(&device_id, db.get_block_ops_since(&server_version).await?).await?)
+    };
+    let ops_sent = batch.ops.len();
+    conn.send(&Message::Push { batch }).await?;
+    match conn.recv().await? {
+        Message::Done { .. } => {}
+        _ => return Err(unexpected_message()),
+    }
+    remember_peer(&*db.lock().await, &handshake.server_id, None, Some(address), true).await?;
+
+    Ok(OpExchangeResult { ops_sent, ops_received })
+}
+
+/// 在局域网内广播查找正在监听的设备
+pub async fn discover_peers(port: u16, wait: std::time::Duration) -> Result<Vec<DiscoveredPeer>> {
+    probe(SocketAddr::from(([255, 255, 255, 255], port)), wait).await
+}
+
+async fn probe(target: SocketAddr, wait: std::time::Duration) -> Result<Vec<DiscoveredPeer>> {
+    let socket = UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], 0))).await?;
+    socket.set_broadcast(true)?;
+    socket.send_to(DISCOVERY_PROBE, target).await?;
+
+    let deadline = tokio::time::Instant::now() + wait;
+    let mut peers = Vec::new();
+    let mut buf = [0u8; 1024];
+    while let Ok(Ok((len, from))) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await {
+        if let Ok(beacon) = serde_json::from_slice::<Beacon>(&buf[..len]) {
+            let peer = DiscoveredPeer {
+                device_id: beacon.device_id,
+                address: SocketAddr::new(from.ip(), beacon.port).to_string(),
+            };
+            if !peers.contains(&peer) {
+                peers.push(peer);
+            }
+        }
+    }
+    Ok(peers)
+}
+
+/// 获取已配对的设备
+pub async fn get_paired_peers(db: &Database) -> Result<Vec<PairedPeer>> {
+    let mut peers: Vec<PairedPeer> = load_peers(db).await?
+        .into_values()
+        .map(|record| record.peer)
+        .collect();
+    peers.sort_by_key(|peer| peer.paired_at);
+    Ok(peers)
+}
+
+/// 取消与设备的配对
+pub async fn remove_paired_peer(db: &Database, device_id: &str) -> Result<bool> {
+    let mut peers = load_peers(db).await?;
+    let removed = peers.remove(device_id).is_some();
+    if removed {
+        save_peers(db, &peers).await?;
+    }
+    Ok(removed)
+}
+
+async fn load_peers(db: &Database) -> Result<HashMap<String, PeerRecord>> {
+    match db.get_setting(PEERS_KEY).await? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(HashMap::new()),
+    }
+}
+
+async fn save_peers(db: &Database, peers: &HashMap<String, PeerRecord>) -> Result<()> {
+    db.set_setting(PEERS_KEY, &serde_json::to_string(peers)?).await
+}
+
+/// 新建或更新配对记录，`key` 为 None 时保留已有密钥
+async fn remember_peer(
+    db: &Database,
+    device_id: &str,
+    key: Option<String>,
+    address: Option<&str>,
+    synced: bool,
+) -> Result<()> {
+    let mut peers = load_peers(db).await?;
+    let mut record = match (peers.remove(device_id), key) {
+        (Some(mut record), key) => {
+            if let Some(key) = key {
+                record.key = key;
+                record.peer.paired_at = Utc::now();
+            }
+            record
+        }
+        (None, Some(key)) => PeerRecord {
+            peer: PairedPeer {
+                device_id: device_id.to_string(),
+                address: None,
+                paired_at: Utc::now(),
+                last_sync: None,
+            },
+            key,
+        },
+        (None, None) => return Err(AppError::PermissionDenied(format!("Device {} is not paired", device_id))),
+    };
+    if let Some(address) = address {
+        record.peer.address = Some(address.to_string());
+    }
+    if synced {
+        record.peer.last_sync = Some(Utc::now());
+    }
+    peers.insert(device_id.to_string(), record);
+    save_peers(db, &peers).await
+}
+
+fn unexpected_message() -> AppError {
+    AppError::Sync("Unexpected message from peer".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreateBlockRequest, CreatePageRequest, UpdateBlockRequest};
+    use tempfile::tempdir;
+
+    async fn device(dir: &std::path::Path, name: &str) -> Database {
+        Database::new_with_path(dir.join(format!("{}.db", name)).to_str().unwrap()).await.unwrap()
+    }
+
+    /// 在内存中走完配对握手，返回双方保存的密钥
+    fn pair(client_code: &str, server_code: &str) -> Result<(String, String)> {
+        let handshake = Handshake { client_id: "laptop".to_string(), server_id: "desktop".to_string(), pairing: true };
+        let (client_spake, client_message) = handshake.start_pairing(Role::Client, client_code);
+        let (server_spake, server_message) = handshake.start_pairing(Role::Server, server_code);
+        let mut client = handshake.noise(Role::Client, &finish_pairing(client_spake, &server_message)?)?;
+        let mut server = handshake.noise(Role::Server, &finish_pairing(server_spake, &client_message)?)?;
+
+        let mut message = vec![0u8; NOISE_MAX_MESSAGE];
+        let mut payload = vec![0u8; NOISE_MAX_MESSAGE];
+        let len = client.write_message(&[], &mut message).map_err(noise_error)?;
+        server.read_message(&message[..len], &mut payload).map_err(noise_error)?;
+        let len = server.write_message(&[], &mut message).map_err(noise_error)?;
+        client.read_message(&message[..len], &mut payload).map_err(noise_error)?;
+        Ok((peer_key(&client), peer_key(&server)))
+    }
+
+    #[test]
+    fn test_pairing_agrees_only_on_the_same_code() {
+        let (client, server) = pair("123456", "123456").unwrap();
+        assert_eq!(client, server);
+        assert_eq!(decode_hex(&client).map(|key| key.len()), Some(32));
+        // 每次配对的密钥都不同
+        assert_ne!(client, pair("123456", "123456").unwrap().0);
+
+        assert!(pair("123456", "654321").is_err());
+        assert!(decode_hex("abc").is_none());
+        assert!(decode_hex("zz").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_pair_and_sync_two_devices_on_localhost() {
+        let dir = tempdir().unwrap();
+        let laptop = Mutex::new(device(dir.path(), "laptop").await);
+        let desktop = Arc::new(Mutex::new(device(dir.path(), "desktop").await));
+
+        let page = laptop.lock().await.create_page(CreatePageRequest {
+            name: "Shared".to_string(),
+            title: None,
+            properties: None,
+            tags: None,
+            is_journal: None,
+            journal_date: None,
+            graph_id: "default".to_string(),
+        }).await.unwrap();
+        let block = laptop.lock().await.create_block(CreateBlockRequest {
+            content: "agenda".to_string(),
+            parent_id: None,
+            properties: None,
+            refs: None,
+            order: Some(0),
+            page_id: page.id.clone(),
+            graph_id: "default".to_string(),
+        }).await.unwrap();
+        laptop.lock().await.record_block_change(&block.id, &page.id).await.unwrap();
+        // 超过单条 Noise 消息上限的内容分块传输
+        let long = "minutes ".repeat(20_000);
+        let long_block = laptop.lock().await.create_block(CreateBlockRequest {
+            content: long.clone(),
+            parent_id: None,
+            properties: None,
+            refs: None,
+            order: Some(1),
+            page_id: page.id.clone(),
+            graph_id: "default".to_string(),
+        }).await.unwrap();
+        laptop.lock().await.record_block_change(&long_block.id, &page.id).await.unwrap();
+
+        let server = P2PServer::start(desktop.clone(), "127.0.0.1:0".parse().unwrap()).await.unwrap();
+        let address = format!("127.0.0.1:{}", server.port());
+
+        let found = probe(address.parse().unwrap(), std::time::Duration::from_millis(500)).await.unwrap();
+        assert_eq!(found, vec![DiscoveredPeer { device_id: server.device_id().to_string(), address: address.clone() }]);
+
+        // 未配对或配对码错误都会被拒绝，且配对码只能尝试一次
+        assert!(sync_with_peer(&laptop, &address, None).await.is_err());
+        let code = server.begin_pairing();
+        let wrong = if code == "000000" { "111111" } else { "000000" };
+        assert!(sync_with_peer(&laptop, &address, Some(wrong)).await.is_err());
+        assert!(sync_with_peer(&laptop, &address, Some(&code)).await.is_err());
+
+        let code = server.begin_pairing();
+        let paired = sync_with_peer(&laptop, &address, Some(&code)).await.unwrap();
+        assert!(paired.ops_sent > 0);
+        assert_eq!(paired.ops_received, 0);
+        assert_eq!(desktop.lock().await.get_block(&block.id).await.unwrap().content, "agenda");
+        assert_eq!(desktop.lock().await.get_page(&page.id).await.unwrap().name, "Shared");
+        assert_eq!(desktop.lock().await.get_block(&long_block.id).await.unwrap().content, long);
+
+        // 之后使用保存的密钥同步，无需再次输入配对码
+        {
+            let desktop = desktop.lock().await;
+            desktop.update_block(UpdateBlockRequest {
+                id: block.id.clone(),
+                content: Some("agenda and minutes".to_string()),
+                parent_id: None,
+                properties: None,
+                refs: None,
+                order: None,
+                collapsed: None,
+            }).await.unwrap();
+            desktop.record_block_change(&block.id, &page.id).await.unwrap();
+        }
+        let synced = sync_with_peer(&laptop, &address, None).await.unwrap();
+        assert_eq!(synced.ops_sent, 0);
+        assert!(synced.ops_received > 0);
+        assert_eq!(laptop.lock().await.get_block(&block.id).await.unwrap().content, "agenda and minutes");
+
+        let peers = get_paired_peers(&*laptop.lock().await).await.unwrap();
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].address.as_deref(), Some(address.as_str()));
+        assert!(peers[0].last_sync.is_some());
+        assert_eq!(get_paired_peers(&*desktop.lock().await).await.unwrap().len(), 1);
+
+        assert!(remove_paired_peer(&*laptop.lock().await, server.device_id()).await.unwrap());
+        assert!(sync_with_peer(&laptop, &address, None).await.is_err());
+        server.stop();
+    }
+}
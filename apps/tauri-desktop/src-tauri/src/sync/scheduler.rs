@@ -0,0 +1,399 @@
+use super::{exchange_block_ops, SyncDirection, SyncEvent, SyncEventListener, SyncResult, WebDAVSyncManager};
+use crate::database::Database;
+use crate::error::{AppError, Result};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::sync::{Mutex, Notify};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// 前端监听的同步事件名
+pub const SYNC_EVENT: &str = "sync-event";
+
+/// 未启用自动同步时重新检查配置的间隔
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 本地编辑后等待的静默时间
+const DEFAULT_DEBOUNCE: Duration = Duration::from_secs(5);
+
+/// 失败重试的最大退避时间
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// 后台自动同步调度器
+///
+/// 按当前同步配置的 `auto_sync_interval` 周期性执行双向同步，失败时指数退避，
+/// 本地编辑后防抖触发同步，并把 `SyncEvent` 转发给注册的监听器。
+pub struct SyncScheduler {
+    sync_manager: Arc<Mutex<WebDAVSyncManager>>,
+    database: Option<Arc<Mutex<Database>>>,
+    listener: StdMutex<Option<Arc<dyn SyncEventListener>>>,
+    in_flight: StdMutex<Option<CancellationToken>>,
+    local_changes: Notify,
+    reschedule: Notify,
+    shutdown: CancellationToken,
+    debounce: Duration,
+}
+
+impl std::fmt::Debug for SyncScheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SyncScheduler")
+            .field("debounce", &self.debounce)
+            .field("shutdown", &self.shutdown.is_cancelled())
+            .finish_non_exhaustive()
+    }
+}
+
+impl SyncScheduler {
+    /// 创建调度器，调用 `start` 之前不会自动同步
+    pub fn new(sync_manager: Arc<Mutex<WebDAVSyncManager>>) -> Self {
+        Self {
+            sync_manager,
+            database: None,
+            listener: StdMutex::new(None),
+            in_flight: StdMutex::new(None),
+            local_changes: Notify::new(),
+            reschedule: Notify::new(),
+            shutdown: CancellationToken::new(),
+            debounce: DEFAULT_DEBOUNCE,
+        }
+    }
+
+    /// 设置本地编辑后的防抖时间
+    #[allow(dead_code)]
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// 文件同步后同时交换块操作批次
+    pub fn with_database(mut self, database: Arc<Mutex<Database>>) -> Self {
+        self.database = Some(database);
+        self
+    }
+
+    /// 注册同步事件监听器
+    pub fn set_listener(&self, listener: Arc<dyn SyncEventListener>) {
+        *self.listener.lock().unwrap() = Some(listener);
+    }
+
+    /// 在后台启动调度循环
+    pub fn start(self: &Arc<Self>) -> tauri::async_runtime::JoinHandle<()> {
+        let scheduler = Arc::clone(self);
+        tauri::async_runtime::spawn(async move {
+            log::info!("Auto-sync scheduler started");
+            scheduler.run().await;
+            log::info!("Auto-sync scheduler stopped");
+        })
+    }
+
+    /// 停止调度循环并取消正在进行的同步
+    #[allow(dead_code)]
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// 记录一次本地编辑，防抖后触发同步
+    pub fn notify_local_change(&self) {
+        self.local_changes.notify_one();
+    }
+
+    /// 配置变更后重新计算下一次同步时间
+    pub fn notify_config_changed(&self) {
+        self.reschedule.notify_one();
+    }
+
+    /// 立即执行一次同步，可通过 `stop_sync` 取消
+    ///
+    /// 同步管理器和数据库只在开始、结束和应用远端操作时短暂加锁，网络请求期间不阻塞其他命令
+    pub async fn sync_now(&self, direction: SyncDirection) -> Result<SyncResult> {
+        let cancel = self.shutdown.child_token();
+        *self.in_flight.lock().unwrap() = Some(cancel.clone());
+        self.emit(SyncEvent::SyncStarted { direction: direction.clone() });
+
+        let job = self.sync_manager.lock().await.begin_sync(&direction);
+        let outcome = match job {
+            Ok(mut job) => {
+                let loaded = match &self.database {
+                    Some(database) => job.load_file_sync_info(&*database.lock().await).await,
+                    None => Ok(()),
+                };
+                let synced = match loaded {
+                    Ok(()) => {
+                        let synced = job.run(direction, &cancel).await;
+                        // 失败或取消时已经同步的文件也要记下
+                        match &self.database {
+                            Some(database) => synced.and(job.save_file_sync_info(&*database.lock().await).await),
+                            None => synced,
+                        }
+                    }
+                    Err(e) => Err(e),
+                };
+                let backend = job.backend();
+                let mut outcome = self.sync_manager.lock().await.finish_sync(job, synced, &cancel);
+                if let (Ok(result), Some(database)) = (&mut outcome, &self.database) {
+                    tokio::select! {
+                        exchanged = exchange_block_ops(database, backend.as_ref()) => {
+                            if let Err(e) = exchanged {
+                                result.errors.push(format!("Block op exchange failed: {}", e));
+                            }
+                        }
+                        _ = cancel.cancelled() => {}
+                    }
+                }
+                outcome
+            }
+            Err(e) => Err(e),
+        };
+        self.in_flight.lock().unwrap().take();
+
+        match &outcome {
+            Ok(result) => {
+                for conflict in &result.conflicts {
+                    self.emit(SyncEvent::ConflictDetected { conflict: conflict.clone() });
+                }
+                self.emit(SyncEvent::SyncCompleted { result: result.clone() });
+            }
+            Err(e) => self.emit(SyncEvent::SyncFailed { error: e.to_string() }),
+        }
+
+        outcome
+    }
+
+    /// 取消正在进行的同步请求
+    pub fn stop_sync(&self) -> Result<()> {
+        match self.in_flight.lock().unwrap().take() {
+            Some(cancel) => {
+                cancel.cancel();
+                log::info!("Sync stopped by user");
+                Ok(())
+            }
+            None => Err(AppError::Sync("No sync in progress".to_string())),
+        }
+    }
+
+    async fn run(&self) {
+        let mut failures: u32 = 0;
+
+        loop {
+            let interval = self.auto_sync_interval().await;
+            let delay = match interval {
+                Some(base) => backoff_delay(base, failures),
+                None => IDLE_POLL_INTERVAL,
+            };
+            let deadline = Instant::now() + delay;
+
+            // 等待下一次同步时机：到期、本地编辑或配置变更
+            let local_change = loop {
+                tokio::select! {
+                    _ = self.shutdown.cancelled() => return,
+                    _ = tokio::time::sleep_until(deadline) => break false,
+                    _ = self.reschedule.notified() => break false,
+                    _ = self.local_changes.notified() => {
+                        // 退避期间不因本地编辑提前重试
+                        if interval.is_some() && failures == 0 {
+                            break true;
+                        }
+                    }
+                }
+            };
+
+            if Instant::now() < deadline && !local_change {
+                // 配置变更，重新读取间隔
+                continue;
+            }
+            if interval.is_none() {
+                continue;
+            }
+            if local_change && !self.wait_for_quiet().await {
+                return;
+            }
+
+            match self.sync_now(SyncDirection::Bidirectional).await {
+                Ok(_) => failures = 0,
+                Err(e) => {
+                    failures = failures.saturating_add(1);
+                    log::warn!("Auto-sync failed ({} consecutive): {}", failures, e);
+                }
+            }
+        }
+    }
+
+    /// 等待本地编辑静默 `debounce` 时长，调度器关闭时返回 false
+    async fn wait_for_quiet(&self) -> bool {
+        loop {
+            tokio::select! {
+                _ = self.shutdown.cancelled() => return false,
+                _ = tokio::time::sleep(self.debounce) => return true,
+                _ = self.local_changes.notified() => {}
+            }
+        }
+    }
+
+    async fn auto_sync_interval(&self) -> Option<Duration> {
+        let manager = self.sync_manager.lock().await;
+        manager.auto_sync_interval().map(Duration::from_secs)
+    }
+
+    fn emit(&self, event: SyncEvent) {
+        let listener = self.listener.lock().unwrap().clone();
+        if let Some(listener) = listener {
+            listener.on_sync_event(event);
+        }
+    }
+}
+
+/// 计算第 `failures` 次连续失败后的等待时间
+pub(crate) fn backoff_delay(base: Duration, failures: u32) -> Duration {
+    let factor = 1u32.checked_shl(failures).unwrap_or(u32::MAX);
+    base.checked_mul(factor)
+        .unwrap_or(MAX_BACKOFF)
+        .min(MAX_BACKOFF.max(base))
+}
+
+/// 通过 Tauri 事件把同步事件推送给前端
+pub struct TauriSyncEventListener {
+    app_handle: AppHandle,
+}
+
+impl TauriSyncEventListener {
+    pub fn new(app_handle: AppHandle) -> Self {
+        Self { app_handle }
+    }
+}
+
+impl SyncEventListener for TauriSyncEventListener {
+    fn on_sync_event(&self, event: SyncEvent) {
+        if let Err(e) = self.app_handle.emit_all(SYNC_EVENT, event) {
+            log::warn!("Failed to emit sync event: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::WebDAVConfig;
+
+    #[derive(Default)]
+    struct RecordingListener {
+        events: StdMutex<Vec<SyncEvent>>,
+    }
+
+    impl SyncEventListener for RecordingListener {
+        fn on_sync_event(&self, event: SyncEvent) {
+            self.events.lock().unwrap().push(event);
+        }
+    }
+
+    fn scheduler() -> SyncScheduler {
+        SyncScheduler::new(Arc::new(Mutex::new(WebDAVSyncManager::new())))
+    }
+
+    #[test]
+    fn test_backoff_delay_doubles_and_caps() {
+        let base = Duration::from_secs(60);
+        assert_eq!(backoff_delay(base, 0), base);
+        assert_eq!(backoff_delay(base, 1), Duration::from_secs(120));
+        assert_eq!(backoff_delay(base, 3), Duration::from_secs(480));
+        assert_eq!(backoff_delay(base, 20), MAX_BACKOFF);
+        assert_eq!(backoff_delay(base, 64), MAX_BACKOFF);
+
+        let long = Duration::from_secs(2 * 60 * 60);
+        assert_eq!(backoff_delay(long, 2), long);
+    }
+
+    #[test]
+    fn test_stop_without_sync_in_progress() {
+        assert!(scheduler().stop_sync().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sync_without_config_emits_failure() {
+        let scheduler = scheduler();
+        let listener = Arc::new(RecordingListener::default());
+        scheduler.set_listener(listener.clone());
+
+        assert!(scheduler.sync_now(SyncDirection::Bidirectional).await.is_err());
+
+        let events = listener.events.lock().unwrap();
+        assert!(matches!(events.first(), Some(SyncEvent::SyncStarted { .. })));
+        assert!(matches!(events.last(), Some(SyncEvent::SyncFailed { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_stop_cancels_in_flight_request() {
+        // 一个接受连接但从不响应的服务器
+        let server = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut connections = Vec::new();
+            while let Ok((socket, _)) = server.accept().await {
+                connections.push(socket);
+            }
+        });
+
+        let mut manager = WebDAVSyncManager::new();
+        manager.set_config(WebDAVConfig {
+            server_url: format!("http://{}", addr),
+            username: "user".to_string(),
+            password: "password".to_string(),
+            remote_path: "/minglog".to_string(),
+            enabled: true,
+            auto_sync_interval: None,
+        }).unwrap();
+        let manager = Arc::new(Mutex::new(manager));
+        let scheduler = Arc::new(SyncScheduler::new(manager.clone()));
+
+        let running = Arc::clone(&scheduler);
+        let handle = tokio::spawn(async move { running.sync_now(SyncDirection::Upload).await });
+
+        // 等待请求发出
+        while scheduler.in_flight.lock().unwrap().is_none() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        // 请求进行中时同步管理器没有被锁住
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(manager.lock().await.get_sync_status(), crate::sync::SyncStatus::Syncing);
+        scheduler.stop_sync().unwrap();
+
+        let outcome = tokio::time::timeout(Duration::from_secs(5), handle).await
+            .expect("cancelled sync should return promptly")
+            .unwrap();
+        assert!(outcome.is_err());
+        assert_eq!(manager.lock().await.get_sync_status(), crate::sync::SyncStatus::Idle);
+    }
+
+    #[tokio::test]
+    async fn test_remote_deletion_is_kept_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let local_dir = dir.path().join("local");
+        let folder = dir.path().join("remote");
+        std::fs::create_dir_all(&local_dir).unwrap();
+        let database = Arc::new(Mutex::new(
+            Database::new_with_path(dir.path().join("sync.db").to_str().unwrap()).await.unwrap()
+        ));
+
+        // 每次创建新的同步管理器，相当于重启应用
+        let restarted = || {
+            let mut manager = WebDAVSyncManager::new();
+            manager.set_local_dir(&local_dir);
+            manager.set_folder_config(crate::sync::FolderSyncConfig {
+                folder_path: folder.to_string_lossy().to_string(),
+                enabled: true,
+                auto_sync_interval: None,
+            }).unwrap();
+            SyncScheduler::new(Arc::new(Mutex::new(manager))).with_database(database.clone())
+        };
+
+        std::fs::write(local_dir.join("page.md"), "content").unwrap();
+        let result = restarted().sync_now(SyncDirection::Bidirectional).await.unwrap();
+        assert_eq!(result.files_uploaded, 1);
+
+        std::fs::remove_file(folder.join("page.md")).unwrap();
+        let result = restarted().sync_now(SyncDirection::Bidirectional).await.unwrap();
+        assert_eq!(result.files_uploaded, 0);
+        assert!(!local_dir.join("page.md").exists());
+        assert!(!folder.join("page.md").exists());
+    }
+}
@@ -0,0 +1,387 @@
+use super::backend::{normalize_path, precondition_failed, RemoteEntry, SyncBackend, WriteCondition};
+use super::xml::{has_xml_element, xml_elements};
+use crate::encoding::{percent_decode, unescape_xml};
+use super::WebDAVConfig;
+use crate::error::{AppError, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::{Client, Method, RequestBuilder, StatusCode, Url};
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<D:propfind xmlns:D="DAV:">
+    <D:prop>
+        <D:resourcetype/>
+        <D:getetag/>
+        <D:getcontentlength/>
+        <D:getlastmodified/>
+    </D:prop>
+</D:propfind>"#;
+
+/// WebDAV 同步后端
+#[derive(Debug, Clone)]
+pub struct WebDAVBackend {
+    config: WebDAVConfig,
+    http_client: Client,
+}
+
+/// PROPFIND 响应中的单个资源
+#[derive(Debug, Clone, PartialEq)]
+struct DavResource {
+    href: String,
+    is_collection: bool,
+    etag: Option<String>,
+    size: u64,
+    modified: Option<DateTime<Utc>>,
+}
+
+impl WebDAVBackend {
+    pub fn new(config: WebDAVConfig, http_client: Client) -> Self {
+        Self { config, http_client }
+    }
+
+    /// 同步根目录的URL，以 `/` 结尾
+    fn root_url(&self) -> Result<Url> {
+        let remote_path = self.config.remote_path.trim_matches('/');
+        let base = if remote_path.is_empty() {
+            format!("{}/", self.config.server_url.trim_end_matches('/'))
+        } else {
+            format!("{}/{}/", self.config.server_url.trim_end_matches('/'), remote_path)
+        };
+        Url::parse(&base).map_err(|e| AppError::Sync(format!("Invalid server URL: {}", e)))
+    }
+
+    fn file_url(&self, path: &str) -> Result<Url> {
+        let normalized = normalize_path(path)?;
+        let mut url = self.root_url()?;
+        url.path_segments_mut()
+            .map_err(|_| AppError::Sync("Server URL cannot be a base".to_string()))?
+            .pop_if_empty()
+            .extend(normalized.split('/'));
+        Ok(url)
+    }
+
+    fn request(&self, method: Method, url: Url) -> RequestBuilder {
+        self.http_client
+            .request(method, url)
+            .basic_auth(&self.config.username, Some(&self.config.password))
+    }
+
+    async fn propfind(&self, url: Url, depth: &str) -> Result<Option<Vec<DavResource>>> {
+        let response = self
+            .request(Method::from_bytes(b"PROPFIND").unwrap(), url)
+            .header("Depth", depth)
+            .header("Content-Type", "application/xml")
+            .body(PROPFIND_BODY)
+            .send()
+            .await
+            .map_err(|e| AppError::Sync(format!("PROPFIND failed: {}", e)))?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => {
+                let body = response.text().await
+                    .map_err(|e| AppError::Sync(format!("Failed to read response: {}", e)))?;
+                Ok(Some(parse_multistatus(&body)))
+            }
+            status => Err(AppError::Sync(format!("PROPFIND failed with status: {}", status))),
+        }
+    }
+
+    /// 从根目录逐层创建父集合
+    async fn create_parent_collections(&self, path: &str) -> Result<()> {
+        let normalized = normalize_path(path)?;
+        let segments: Vec<&str> = normalized.split('/').collect();
+        let mut url = self.root_url()?;
+        for segment in &segments[..segments.len() - 1] {
+            url.path_segments_mut()
+                .map_err(|_| AppError::Sync("Server URL cannot be a base".to_string()))?
+                .pop_if_empty()
+                .push(segment)
+                .push("");
+            let response = self
+                .request(Method::from_bytes(b"MKCOL").unwrap(), url.clone())
+                .send()
+                .await
+                .map_err(|e| AppError::Sync(format!("MKCOL failed: {}", e)))?;
+            // 405 表示集合已存在
+            if !response.status().is_success() && response.status() != StatusCode::METHOD_NOT_ALLOWED {
+                return Err(AppError::Sync(format!("MKCOL failed with status: {}", response.status())));
+            }
+        }
+        Ok(())
+    }
+
+    fn relative_path(&self, root: &Url, href: &str) -> Option<String> {
+        let href_url = root.join(href).ok()?;
+        let root_path = percent_decode(root.path());
+        let href_path = percent_decode(href_url.path());
+        let relative = href_path.strip_prefix(&root_path)?.trim_matches('/');
+        if relative.is_empty() {
+            None
+        } else {
+            Some(relative.to_string())
+        }
+    }
+
+    fn entry_from(&self, root: &Url, resource: DavResource) -> Option<RemoteEntry> {
+        let path = self.relative_path(root, &resource.href)?;
+        let etag = resource.etag.unwrap_or_else(|| {
+            // 没有 ETag 时退化为长度+修改时间
+            format!("{}-{}", resource.size, resource.modified.map(|m| m.timestamp()).unwrap_or(0))
+        });
+        Some(RemoteEntry {
+            path,
+            etag,
+            size: resource.size,
+            modified: resource.modified,
+        })
+    }
+}
+
+#[async_trait]
+impl SyncBackend for WebDAVBackend {
+    fn name(&self) -> &str {
+        "webdav"
+    }
+
+    fn location(&self) -> String {
+        format!(
+            "webdav:{}@{}/{}",
+            self.config.username,
+            self.config.server_url.trim_end_matches('/'),
+            self.config.remote_path.trim_matches('/')
+        )
+    }
+
+    async fn list(&self) -> Result<Vec<RemoteEntry>> {
+        let root = self.root_url()?;
+        let root_path = percent_decode(root.path());
+        let mut entries = Vec::new();
+        let mut pending = vec![root.clone()];
+
+        while let Some(collection) = pending.pop() {
+            let collection_path = percent_decode(collection.path());
+            let resources = match self.propfind(collection.clone(), "1").await? {
+                Some(resources) => resources,
+                None if collection_path == root_path => return Ok(Vec::new()),
+                None => continue,
+            };
+
+            for resource in resources {
+                let Ok(url) = collection.join(&resource.href) else { continue };
+                if percent_decode(url.path()).trim_end_matches('/') == collection_path.trim_end_matches('/') {
+                    continue;
+                }
+                if resource.is_collection {
+                    pending.push(url);
+                } else if let Some(entry) = self.entry_from(&root, resource) {
+                    entries.push(entry);
+                }
+            }
+        }
+
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(entries)
+    }
+
+    async fn get(&self, path: &str) -> Result<Vec<u8>> {
+        let response = self
+            .request(Method::GET, self.file_url(path)?)
+            .send()
+            .await
+            .map_err(|e| AppError::Sync(format!("Download failed: {}", e)))?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => Err(AppError::NotFound(format!("Remote file not found: {}", path))),
+            status if status.is_success() => {
+                let bytes = response.bytes().await
+                    .map_err(|e| AppError::Sync(format!("Failed to read response: {}", e)))?;
+                Ok(bytes.to_vec())
+            }
+            status => Err(AppError::Sync(format!("Download failed with status: {}", status))),
+        }
+    }
+
+    async fn put(&self, path: &str, data: &[u8], condition: WriteCondition<'_>) -> Result<RemoteEntry> {
+        let url = self.file_url(path)?;
+        let mut created_parents = false;
+
+        loop {
+            let mut request = self
+                .request(Method::PUT, url.clone())
+                .header("Content-Type", "application/octet-stream")
+                .body(data.to_vec());
+            match condition {
+                WriteCondition::Overwrite => {}
+                WriteCondition::Create => request = request.header("If-None-Match", "*"),
+                WriteCondition::IfMatch(etag) => request = request.header("If-Match", format!("\"{}\"", etag)),
+            }
+
+            let response = request
+                .send()
+                .await
+                .map_err(|e| AppError::Sync(format!("Upload failed: {}", e)))?;
+
+            match response.status() {
+                StatusCode::PRECONDITION_FAILED => return Err(precondition_failed(path)),
+                // 409 表示父集合不存在
+                StatusCode::CONFLICT if !created_parents => {
+                    self.create_parent_collections(path).await?;
+                    created_parents = true;
+                }
+                status if status.is_success() => break,
+                status => return Err(AppError::Sync(format!("Upload failed with status: {}", status))),
+            }
+        }
+
+        self.stat(path).await?
+            .ok_or_else(|| AppError::Sync(format!("Uploaded file missing on server: {}", path)))
+    }
+
+    async fn delete(&self, path: &str) -> Result<()> {
+        let response = self
+            .request(Method::DELETE, self.file_url(path)?)
+            .send()
+            .await
+            .map_err(|e| AppError::Sync(format!("Delete failed: {}", e)))?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(()),
+            status if status.is_success() => Ok(()),
+            status => Err(AppError::Sync(format!("Delete failed with status: {}", status))),
+        }
+    }
+
+    async fn stat(&self, path: &str) -> Result<Option<RemoteEntry>> {
+        let root = self.root_url()?;
+        let resources = self.propfind(self.file_url(path)?, "0").await?;
+        Ok(resources
+            .unwrap_or_default()
+            .into_iter()
+            .find(|resource| !resource.is_collection)
+            .and_then(|resource| self.entry_from(&root, resource)))
+    }
+
+    async fn test_connection(&self) -> Result<bool> {
+        log::info!("Testing WebDAV connection to: {}", self.config.server_url);
+
+        let response = self
+            .request(Method::from_bytes(b"PROPFIND").unwrap(), self.root_url()?)
+            .header("Depth", "0")
+            .header("Content-Type", "application/xml")
+            .body(PROPFIND_BODY)
+            .send()
+            .await
+            .map_err(|e| AppError::Sync(format!("Connection failed: {}", e)))?;
+
+        let is_success = response.status().is_success(); // 包括 207 Multi-Status
+
+        if is_success {
+            log::info!("WebDAV connection test successful");
+        } else {
+            log::warn!("WebDAV connection test failed with status: {}", response.status());
+        }
+
+        Ok(is_success)
+    }
+}
+
+/// 解析 PROPFIND 的 multistatus 响应，忽略命名空间前缀
+fn parse_multistatus(xml: &str) -> Vec<DavResource> {
+    xml_elements(xml, "response")
+        .into_iter()
+        .filter_map(|response| {
+            let href = xml_elements(response, "href").into_iter().next()?.trim().to_string();
+            let is_collection = xml_elements(response, "resourcetype")
+                .into_iter()
+                .any(|resource_type| has_xml_element(resource_type, "collection"));
+            let etag = xml_elements(response, "getetag")
+                .into_iter()
+                .next()
+                .map(|etag| unescape_xml(etag.trim()).trim_start_matches("W/").trim_matches('"').to_string())
+                .filter(|etag| !etag.is_empty());
+            let size = xml_elements(response, "getcontentlength")
+                .into_iter()
+                .next()
+                .and_then(|size| size.trim().parse().ok())
+                .unwrap_or(0);
+            let modified = xml_elements(response, "getlastmodified")
+                .into_iter()
+                .next()
+                .and_then(|modified| DateTime::parse_from_rfc2822(modified.trim()).ok())
+                .map(|modified| modified.with_timezone(&Utc));
+            Some(DavResource {
+                href: unescape_xml(&href),
+                is_collection,
+                etag,
+                size,
+                modified,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MULTISTATUS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:multistatus xmlns:d="DAV:">
+  <d:response>
+    <d:href>/dav/minglog/</d:href>
+    <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/dav/minglog/pages/My%20Page.md</d:href>
+    <d:propstat>
+      <d:prop>
+        <d:resourcetype/>
+        <d:getetag>&quot;abc123&quot;</d:getetag>
+        <d:getcontentlength>42</d:getcontentlength>
+        <d:getlastmodified>Mon, 01 Jan 2024 00:00:00 GMT</d:getlastmodified>
+      </d:prop>
+    </d:propstat>
+  </d:response>
+</d:multistatus>"#;
+
+    fn backend() -> WebDAVBackend {
+        WebDAVBackend::new(
+            WebDAVConfig {
+                server_url: "https://example.com/dav/".to_string(),
+                username: "user".to_string(),
+                password: "password".to_string(),
+                remote_path: "/minglog/".to_string(),
+                enabled: true,
+                auto_sync_interval: None,
+            },
+            Client::new(),
+        )
+    }
+
+    #[test]
+    fn test_parse_multistatus() {
+        let resources = parse_multistatus(MULTISTATUS);
+        assert_eq!(resources.len(), 2);
+        assert!(resources[0].is_collection);
+        assert_eq!(resources[1].href, "/dav/minglog/pages/My%20Page.md");
+        assert_eq!(resources[1].etag.as_deref(), Some("abc123"));
+        assert_eq!(resources[1].size, 42);
+        assert!(resources[1].modified.is_some());
+    }
+
+    #[test]
+    fn test_relative_paths() {
+        let backend = backend();
+        let root = backend.root_url().unwrap();
+        assert_eq!(root.as_str(), "https://example.com/dav/minglog/");
+        assert_eq!(
+            backend.relative_path(&root, "/dav/minglog/pages/My%20Page.md").as_deref(),
+            Some("pages/My Page.md")
+        );
+        assert_eq!(backend.relative_path(&root, "/dav/minglog/"), None);
+        assert_eq!(
+            backend.file_url("pages/My Page.md").unwrap().as_str(),
+            "https://example.com/dav/minglog/pages/My%20Page.md"
+        );
+    }
+}
@@ -0,0 +1,41 @@
+/// 返回本地名为 `local_name` 的所有元素的内部文本
+pub fn xml_elements<'a>(xml: &'a str, local_name: &str) -> Vec<&'a str> {
+    let mut elements = Vec::new();
+    let mut rest = xml;
+    let mut offset = 0;
+
+    while let Some(start) = rest.find('<') {
+        let tag_start = offset + start;
+        let Some(tag_len) = xml[tag_start..].find('>') else { break };
+        let tag = &xml[tag_start + 1..tag_start + tag_len];
+        let after_tag = tag_start + tag_len + 1;
+
+        let name = tag.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or("");
+        let is_match = !tag.starts_with('/')
+            && !tag.ends_with('/')
+            && name.rsplit(':').next() == Some(local_name);
+
+        if is_match {
+            let closing = format!("</{}>", name);
+            if let Some(end) = xml[after_tag..].find(&closing) {
+                elements.push(&xml[after_tag..after_tag + end]);
+                offset = after_tag + end + closing.len();
+                rest = &xml[offset..];
+                continue;
+            }
+        }
+
+        offset = after_tag;
+        rest = &xml[offset..];
+    }
+
+    elements
+}
+
+/// 是否包含本地名为 `local_name` 的元素
+pub fn has_xml_element(xml: &str, local_name: &str) -> bool {
+    xml.split('<').skip(1).any(|tag| {
+        let name = tag.split(|c: char| c.is_whitespace() || c == '/' || c == '>').next().unwrap_or("");
+        !tag.starts_with('/') && name.rsplit(':').next() == Some(local_name)
+    })
+}
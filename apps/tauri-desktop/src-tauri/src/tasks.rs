@@ -0,0 +1,9 @@
+//! Task management beyond plain CRUD, which lives in `Database`.
+
+pub mod block_task;
+pub mod dependencies;
+pub mod gtd;
+pub mod recurrence;
+pub mod reminders;
+pub mod rollup;
+pub mod time_report;
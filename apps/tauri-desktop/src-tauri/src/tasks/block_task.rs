@@ -0,0 +1,338 @@
+//! Tasks written inline in blocks, e.g. `TODO [#A] Buy milk`.
+//!
+//! The first line of a block may start with a Logseq-style marker, optionally
+//! followed by a priority. `SCHEDULED: <2026-10-20 Tue>` and
+//! `DEADLINE: <2026-10-21 Wed 18:00>` lines set the task's scheduled and due
+//! dates; they carry no timezone and are read and written in the local zone,
+//! like floating times in ICS files. Any other lines become the task
+//! description.
+
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, Utc};
+
+use super::recurrence;
+use crate::models::{Task, TaskPriority, TaskStatus};
+
+const MARKERS: [(&str, TaskStatus); 8] = [
+    ("TODO", TaskStatus::Todo),
+    ("LATER", TaskStatus::Todo),
+    ("DOING", TaskStatus::InProgress),
+    ("NOW", TaskStatus::InProgress),
+    ("WAITING", TaskStatus::Waiting),
+    ("DONE", TaskStatus::Done),
+    ("CANCELED", TaskStatus::Cancelled),
+    ("CANCELLED", TaskStatus::Cancelled),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockTask {
+    pub status: TaskStatus,
+    pub priority: Option<TaskPriority>,
+    pub title: String,
+    pub description: Option<String>,
+    pub scheduled: Option<DateTime<Utc>>,
+    pub deadline: Option<DateTime<Utc>>,
+}
+
+/// Parse the task written in a block, `None` when the block isn't a task
+pub fn parse(content: &str) -> Option<BlockTask> {
+    let mut lines = content.lines();
+    let (marker, rest) = split_marker(lines.next()?)?;
+    let (priority, title) = split_priority(rest);
+    let title = title.trim();
+    if title.is_empty() {
+        return None;
+    }
+
+    let mut task = BlockTask {
+        status: marker_status(marker),
+        priority: priority.map(letter_priority),
+        title: title.to_string(),
+        description: None,
+        scheduled: None,
+        deadline: None,
+    };
+    let mut body = Vec::new();
+    for line in lines {
+        let trimmed = line.trim();
+        if let Some(value) = trimmed.strip_prefix("SCHEDULED:") {
+            task.scheduled = parse_timestamp(value);
+        } else if let Some(value) = trimmed.strip_prefix("DEADLINE:") {
+            task.deadline = parse_timestamp(value);
+        } else {
+            body.push(line);
+        }
+    }
+    let description = body.join("\n");
+    task.description = Some(description.trim().to_string()).filter(|description| !description.is_empty());
+
+    Some(task)
+}
+
+/// Rewrite a task block to match the task: the first line, the `SCHEDULED:`
+/// and `DEADLINE:` lines and the description. Parts that still agree keep
+/// the block's own spelling.
+pub fn rewrite(content: &str, task: &Task) -> Option<String> {
+    let parsed = parse(content);
+    let (first, rest) = match content.split_once('\n') {
+        Some((first, rest)) => (first, Some(rest)),
+        None => (content, None),
+    };
+    let (marker, after_marker) = split_marker(first)?;
+    let (letter, _) = split_priority(after_marker);
+
+    let marker = if same_status(marker_status(marker), task.status) {
+        marker
+    } else {
+        match task.status {
+            TaskStatus::Inbox | TaskStatus::Todo => "TODO",
+            TaskStatus::Someday => "LATER",
+            TaskStatus::InProgress => "DOING",
+            TaskStatus::Waiting => "WAITING",
+            TaskStatus::Done => "DONE",
+            TaskStatus::Cancelled => "CANCELED",
+        }
+    };
+    let letter = match letter {
+        Some(letter) if same_priority(letter_priority(letter), task.priority) => Some(letter),
+        // Medium is the default, so blocks without a priority stay that way
+        None if task.priority == TaskPriority::Medium => None,
+        _ => Some(match task.priority {
+            TaskPriority::Urgent | TaskPriority::High => 'A',
+            TaskPriority::Medium => 'B',
+            TaskPriority::Low => 'C',
+        }),
+    };
+
+    let mut line = marker.to_string();
+    if let Some(letter) = letter {
+        line.push_str(&format!(" [#{}]", letter));
+    }
+    line.push(' ');
+    line.push_str(task.title.trim());
+
+    let agrees = parsed.map_or(false, |parsed| {
+        parsed.description == task.description
+            && parsed.scheduled == task.scheduled_date
+            && parsed.deadline == task.due_date
+    });
+    let rest = if agrees { rest.map(str::to_string) } else { rewrite_body(rest.unwrap_or(""), task) };
+    if let Some(rest) = rest {
+        line.push('\n');
+        line.push_str(&rest);
+    }
+    Some(line)
+}
+
+// The lines after the first, with timestamps and description taken from the task
+fn rewrite_body(rest: &str, task: &Task) -> Option<String> {
+    let find = |prefix: &str| rest.lines().find(|line| line.trim().starts_with(prefix));
+    let mut lines: Vec<String> = Vec::new();
+    for (prefix, date) in [("SCHEDULED:", task.scheduled_date), ("DEADLINE:", task.due_date)] {
+        let Some(date) = date else { continue };
+        match find(prefix) {
+            Some(line) if line.trim().strip_prefix(prefix).and_then(parse_timestamp) == Some(date) => {
+                lines.push(line.to_string())
+            }
+            _ => lines.push(format!("{} {}", prefix, timestamp(date))),
+        }
+    }
+
+    let body: Vec<&str> = rest
+        .lines()
+        .filter(|line| !line.trim().starts_with("SCHEDULED:") && !line.trim().starts_with("DEADLINE:"))
+        .collect();
+    let description = Some(body.join("\n").trim().to_string()).filter(|description| !description.is_empty());
+    if description == task.description {
+        lines.extend(body.into_iter().map(str::to_string));
+    } else if let Some(description) = &task.description {
+        lines.push(description.clone());
+    }
+
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+// A block timestamp for a stored date in the local zone, without a time at midnight
+fn timestamp(date: DateTime<Utc>) -> String {
+    let local = date.with_timezone(&Local).naive_local();
+    let time = local.time();
+    format_timestamp(local.date(), (time != NaiveTime::MIN).then(|| time))
+}
+
+/// Whether a marker's status agrees with the task's. `TODO` and `LATER` cover
+/// the GTD states that have no marker of their own.
+pub fn same_status(marker: TaskStatus, task: TaskStatus) -> bool {
+    marker == task || (marker == TaskStatus::Todo && matches!(task, TaskStatus::Inbox | TaskStatus::Someday))
+}
+
+/// Whether a block priority agrees with the task's; `[#A]` also covers urgent
+pub fn same_priority(block: TaskPriority, task: TaskPriority) -> bool {
+    block == task || (block == TaskPriority::High && task == TaskPriority::Urgent)
+}
+
+fn split_marker(line: &str) -> Option<(&'static str, &str)> {
+    let line = line.trim_start();
+    MARKERS.iter().find_map(|(marker, _)| {
+        let rest = line.strip_prefix(marker)?;
+        (rest.is_empty() || rest.starts_with(char::is_whitespace)).then(|| (*marker, rest))
+    })
+}
+
+fn split_priority(rest: &str) -> (Option<char>, &str) {
+    let trimmed = rest.trim_start();
+    let mut chars = trimmed.chars();
+    match (chars.next(), chars.next(), chars.next(), chars.next()) {
+        (Some('['), Some('#'), Some(letter @ ('A' | 'B' | 'C')), Some(']')) => (Some(letter), &trimmed[4..]),
+        _ => (None, rest),
+    }
+}
+
+fn marker_status(marker: &str) -> TaskStatus {
+    MARKERS
+        .iter()
+        .find(|(candidate, _)| *candidate == marker)
+        .map_or(TaskStatus::Todo, |(_, status)| *status)
+}
+
+fn letter_priority(letter: char) -> TaskPriority {
+    match letter {
+        'A' => TaskPriority::High,
+        'C' => TaskPriority::Low,
+        _ => TaskPriority::Medium,
+    }
+}
+
+/// Date and optional time of a block's `SCHEDULED:` line, task or not
+pub fn scheduled(content: &str) -> Option<(NaiveDate, Option<NaiveTime>)> {
+    content
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("SCHEDULED:"))
+        .and_then(timestamp_parts)
+}
+
+/// `<2026-10-20 Tue>`, or `<2026-10-20 Tue 10:00>` with a time
+pub fn format_timestamp(date: NaiveDate, time: Option<NaiveTime>) -> String {
+    match time {
+        Some(time) => format!("<{} {}>", date.format("%Y-%m-%d %a"), time.format("%H:%M")),
+        None => format!("<{}>", date.format("%Y-%m-%d %a")),
+    }
+}
+
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    let (date, time) = timestamp_parts(value)?;
+    recurrence::from_local(&Local, date.and_time(time.unwrap_or(NaiveTime::MIN)))
+}
+
+/// `<2026-10-20 Tue>` or `<2026-10-20 Tue 10:00 .+1w>`; repeaters are ignored
+fn timestamp_parts(value: &str) -> Option<(NaiveDate, Option<NaiveTime>)> {
+    let value = value.trim().strip_prefix('<')?;
+    let value = &value[..value.find('>')?];
+    let mut parts = value.split_whitespace();
+    let date = NaiveDate::parse_from_str(parts.next()?, "%Y-%m-%d").ok()?;
+    let time = parts.find_map(|part| NaiveTime::parse_from_str(part, "%H:%M").ok());
+    Some((date, time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let task = parse("DOING [#A] Write report\nSCHEDULED: <2026-10-20 Tue>\nDEADLINE: <2026-10-21 Wed 18:00 .+1w>\nsee [[Q4]]").unwrap();
+        assert_eq!(task.status, TaskStatus::InProgress);
+        assert_eq!(task.priority, Some(TaskPriority::High));
+        assert_eq!(task.title, "Write report");
+        assert_eq!(task.description.as_deref(), Some("see [[Q4]]"));
+        assert_eq!(task.scheduled, Some(local("2026-10-20 00:00")));
+        assert_eq!(task.deadline, Some(local("2026-10-21 18:00")));
+
+        let task = parse("LATER buy milk").unwrap();
+        assert_eq!((task.status, task.priority, task.description), (TaskStatus::Todo, None, None));
+
+        assert!(parse("TODOS are fun").is_none());
+        assert!(parse("TODO").is_none());
+        assert!(parse("Just a note with TODO inside").is_none());
+    }
+
+    fn local(value: &str) -> DateTime<Utc> {
+        let value = chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap();
+        recurrence::from_local(&Local, value).unwrap()
+    }
+
+    // The task a block was parsed into, then changed to the given fields
+    fn task(content: &str, status: TaskStatus, priority: TaskPriority, title: &str) -> Task {
+        let parsed = parse(content);
+        let now = Utc::now();
+        Task {
+            id: "task".to_string(),
+            title: title.to_string(),
+            description: parsed.as_ref().and_then(|parsed| parsed.description.clone()),
+            status,
+            priority,
+            due_date: parsed.as_ref().and_then(|parsed| parsed.deadline),
+            scheduled_date: parsed.as_ref().and_then(|parsed| parsed.scheduled),
+            completed_at: None,
+            estimated_time: None,
+            actual_time: None,
+            project_id: None,
+            parent_task_id: None,
+            linked_notes: "[]".to_string(),
+            linked_files: "[]".to_string(),
+            tags: "[]".to_string(),
+            contexts: "[]".to_string(),
+            recurrence: None,
+            block_id: Some("block".to_string()),
+            created_at: now,
+            updated_at: now,
+            created_by: None,
+        }
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let content = "TODO [#B] Write report\nDEADLINE: <2026-10-21 Wed>";
+        assert_eq!(
+            rewrite(content, &task(content, TaskStatus::Done, TaskPriority::Medium, "Write report")).unwrap(),
+            "DONE [#B] Write report\nDEADLINE: <2026-10-21 Wed>"
+        );
+        assert_eq!(
+            rewrite("LATER buy milk", &task("LATER buy milk", TaskStatus::Someday, TaskPriority::Medium, "buy milk"))
+                .unwrap(),
+            "LATER buy milk"
+        );
+        assert_eq!(
+            rewrite("NOW [#A] ship", &task("NOW [#A] ship", TaskStatus::InProgress, TaskPriority::Urgent, "ship it"))
+                .unwrap(),
+            "NOW [#A] ship it"
+        );
+        assert_eq!(
+            rewrite("TODO call", &task("TODO call", TaskStatus::Waiting, TaskPriority::Low, "call")).unwrap(),
+            "WAITING [#C] call"
+        );
+        assert!(rewrite("not a task", &task("not a task", TaskStatus::Done, TaskPriority::Medium, "x")).is_none());
+    }
+
+    #[test]
+    fn test_rewrite_dates_and_description() {
+        let content = "TODO Write report\nSCHEDULED: <2026-10-20 Tue .+1w>\nsee [[Q4]]";
+        let unchanged = task(content, TaskStatus::Todo, TaskPriority::Medium, "Write report");
+        assert_eq!(rewrite(content, &unchanged).unwrap(), content);
+
+        let deadline = local("2026-10-21 18:00");
+        let due = Task { due_date: Some(deadline), ..unchanged.clone() };
+        assert_eq!(
+            rewrite(content, &due).unwrap(),
+            "TODO Write report\nSCHEDULED: <2026-10-20 Tue .+1w>\nDEADLINE: <2026-10-21 Wed 18:00>\nsee [[Q4]]"
+        );
+        assert_eq!(parse(&rewrite(content, &due).unwrap()).unwrap().deadline, Some(deadline));
+
+        let described = Task { description: Some("agenda".to_string()), scheduled_date: None, ..unchanged };
+        assert_eq!(rewrite(content, &described).unwrap(), "TODO Write report\nagenda");
+        let cleared = Task { description: None, ..described };
+        assert_eq!(rewrite(content, &cleared).unwrap(), "TODO Write report");
+    }
+}
@@ -0,0 +1,239 @@
+//! "Blocked by" relations between tasks and critical-path scheduling.
+//!
+//! A dependency `(task, blocker)` means `task` can't start before `blocker` is
+//! finished. Schedules are computed in minutes from a start instant: each
+//! task takes its `estimated_time` (nothing once it's done), starts when its
+//! last blocker finishes, and must finish by the earliest of its due date and
+//! the latest start of the tasks it blocks. Tasks without slack make up the
+//! critical path; negative slack means a due date can't be met.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskDependency {
+    pub task_id: String,
+    pub blocker_id: String,
+}
+
+/// A task as far as scheduling is concerned
+#[derive(Debug, Clone)]
+pub struct ScheduleInput {
+    pub task_id: String,
+    pub title: String,
+    pub duration_minutes: i64,
+    pub due_date: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledTask {
+    pub task_id: String,
+    pub title: String,
+    pub duration_minutes: i64,
+    pub earliest_start: DateTime<Utc>,
+    pub earliest_finish: DateTime<Utc>,
+    pub latest_start: DateTime<Utc>,
+    pub latest_finish: DateTime<Utc>,
+    pub slack_minutes: i64,
+    pub critical: bool,
+    /// Finishes after its due date even if started as early as possible
+    pub late: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectSchedule {
+    pub project_id: String,
+    pub start: DateTime<Utc>,
+    pub finish: DateTime<Utc>,
+    /// In dependency order
+    pub tasks: Vec<ScheduledTask>,
+    /// Ids of the critical tasks, in dependency order
+    pub critical_path: Vec<String>,
+}
+
+/// Whether making `task_id` wait for `blocker_id` would close a loop, i.e.
+/// the blocker already waits for the task, directly or not
+pub fn creates_cycle(dependencies: &[TaskDependency], task_id: &str, blocker_id: &str) -> bool {
+    if task_id == blocker_id {
+        return true;
+    }
+
+    let mut blockers: HashMap<&str, Vec<&str>> = HashMap::new();
+    for dependency in dependencies {
+        blockers.entry(dependency.task_id.as_str()).or_default().push(dependency.blocker_id.as_str());
+    }
+
+    let mut seen = HashSet::new();
+    let mut stack = vec![blocker_id];
+    while let Some(current) = stack.pop() {
+        if current == task_id {
+            return true;
+        }
+        if seen.insert(current) {
+            stack.extend(blockers.get(current).into_iter().flatten());
+        }
+    }
+    false
+}
+
+/// Schedule tasks from `start`. Dependencies on tasks outside `inputs` are
+/// ignored.
+pub fn schedule(
+    project_id: &str,
+    inputs: &[ScheduleInput],
+    dependencies: &[TaskDependency],
+    start: DateTime<Utc>,
+) -> Result<ProjectSchedule> {
+    let index: HashMap<&str, usize> = inputs
+        .iter()
+        .enumerate()
+        .map(|(i, input)| (input.task_id.as_str(), i))
+        .collect();
+    let mut blockers = vec![Vec::new(); inputs.len()];
+    let mut dependents = vec![Vec::new(); inputs.len()];
+    for dependency in dependencies {
+        if let (Some(&task), Some(&blocker)) = (
+            index.get(dependency.task_id.as_str()),
+            index.get(dependency.blocker_id.as_str()),
+        ) {
+            blockers[task].push(blocker);
+            dependents[blocker].push(task);
+        }
+    }
+
+    // Topological order: tasks come after everything they wait for
+    let mut waiting: Vec<usize> = blockers.iter().map(Vec::len).collect();
+    let mut ready: VecDeque<usize> = (0..inputs.len()).filter(|&i| waiting[i] == 0).collect();
+    let mut order = Vec::with_capacity(inputs.len());
+    while let Some(i) = ready.pop_front() {
+        order.push(i);
+        for &dependent in &dependents[i] {
+            waiting[dependent] -= 1;
+            if waiting[dependent] == 0 {
+                ready.push_back(dependent);
+            }
+        }
+    }
+    if order.len() != inputs.len() {
+        return Err(AppError::InvalidInput(format!("Tasks in project '{}' depend on each other in a cycle", project_id)));
+    }
+
+    let duration = |i: usize| inputs[i].duration_minutes.max(0);
+    let mut earliest_start = vec![0i64; inputs.len()];
+    for &i in &order {
+        earliest_start[i] = blockers[i]
+            .iter()
+            .map(|&blocker| earliest_start[blocker] + duration(blocker))
+            .max()
+            .unwrap_or(0);
+    }
+    let finish = (0..inputs.len()).map(|i| earliest_start[i] + duration(i)).max().unwrap_or(0);
+
+    let mut latest_finish = vec![finish; inputs.len()];
+    for &i in order.iter().rev() {
+        let mut latest = dependents[i]
+            .iter()
+            .map(|&dependent| latest_finish[dependent] - duration(dependent))
+            .min()
+            .unwrap_or(finish);
+        if let Some(due_date) = inputs[i].due_date {
+            latest = latest.min((due_date - start).num_minutes());
+        }
+        latest_finish[i] = latest;
+    }
+
+    let at = |minutes: i64| start + Duration::minutes(minutes);
+    let tasks: Vec<ScheduledTask> = order
+        .iter()
+        .map(|&i| {
+            let input = &inputs[i];
+            let earliest_finish = earliest_start[i] + duration(i);
+            let slack = latest_finish[i] - earliest_finish;
+            ScheduledTask {
+                task_id: input.task_id.clone(),
+                title: input.title.clone(),
+                duration_minutes: duration(i),
+                earliest_start: at(earliest_start[i]),
+                earliest_finish: at(earliest_finish),
+                latest_start: at(latest_finish[i] - duration(i)),
+                latest_finish: at(latest_finish[i]),
+                slack_minutes: slack,
+                critical: slack <= 0,
+                late: input.due_date.map_or(false, |due_date| at(earliest_finish) > due_date),
+            }
+        })
+        .collect();
+    let critical_path = tasks.iter().filter(|task| task.critical).map(|task| task.task_id.clone()).collect();
+
+    Ok(ProjectSchedule {
+        project_id: project_id.to_string(),
+        start,
+        finish: at(finish),
+        tasks,
+        critical_path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dependency(task_id: &str, blocker_id: &str) -> TaskDependency {
+        TaskDependency {
+            task_id: task_id.to_string(),
+            blocker_id: blocker_id.to_string(),
+        }
+    }
+
+    fn input(task_id: &str, hours: i64, due_date: Option<DateTime<Utc>>) -> ScheduleInput {
+        ScheduleInput {
+            task_id: task_id.to_string(),
+            title: task_id.to_uppercase(),
+            duration_minutes: hours * 60,
+            due_date,
+        }
+    }
+
+    #[test]
+    fn test_creates_cycle() {
+        let dependencies = vec![dependency("b", "a"), dependency("c", "b")];
+        assert!(creates_cycle(&dependencies, "a", "c"));
+        assert!(creates_cycle(&dependencies, "a", "a"));
+        assert!(!creates_cycle(&dependencies, "c", "a"));
+        assert!(!creates_cycle(&dependencies, "d", "c"));
+    }
+
+    #[test]
+    fn test_schedule() {
+        let start = DateTime::parse_from_rfc3339("2026-10-19T09:00:00Z").unwrap().with_timezone(&Utc);
+        // a -> b -> d and a -> c -> d, where b is the longer branch
+        let inputs = vec![
+            input("d", 1, None),
+            input("c", 1, None),
+            input("b", 3, None),
+            input("a", 2, None),
+        ];
+        let dependencies = vec![dependency("b", "a"), dependency("c", "a"), dependency("d", "b"), dependency("d", "c")];
+        let plan = schedule("p", &inputs, &dependencies, start).unwrap();
+        let order: Vec<&str> = plan.tasks.iter().map(|task| task.task_id.as_str()).collect();
+        assert_eq!(order, ["a", "b", "c", "d"]);
+        assert_eq!(plan.critical_path, ["a", "b", "d"]);
+        assert_eq!(plan.finish, start + Duration::hours(6));
+        let c = &plan.tasks[2];
+        assert_eq!((c.slack_minutes, c.earliest_start, c.latest_start), (120, start + Duration::hours(2), start + Duration::hours(4)));
+
+        // A due date earlier than the chain allows makes it late
+        let mut inputs = inputs;
+        inputs[0].due_date = Some(start + Duration::hours(5));
+        let plan = schedule("p", &inputs, &dependencies, start).unwrap();
+        let d = plan.tasks.last().unwrap();
+        assert_eq!((d.slack_minutes, d.late), (-60, true));
+        assert_eq!(plan.tasks[0].slack_minutes, -60);
+
+        assert!(schedule("p", &inputs, &[dependency("a", "b"), dependency("b", "a")], start).is_err());
+    }
+}
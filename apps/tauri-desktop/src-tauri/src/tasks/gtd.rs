@@ -0,0 +1,179 @@
+//! GTD views over tasks: inbox, next actions by context, waiting-for,
+//! overdue, due this week, someday/maybe and the weekly review.
+//!
+//! Next actions are open `todo` and `in-progress` tasks that aren't scheduled
+//! for later or waiting for another open task. Weeks start on Monday in the
+//! user's timezone, given as an offset from UTC like time reports.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, Duration, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, Result};
+use crate::models::Task;
+
+/// Next actions sharing a context; `context` is empty for tasks without one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextGroup {
+    pub context: String,
+    pub tasks: Vec<Task>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WaitingTask {
+    #[serde(flatten)]
+    pub task: Task,
+    pub waiting_since: DateTime<Utc>,
+    pub waiting_days: i64,
+}
+
+/// What happened in the last seven days, and what is left to look at
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeeklyReview {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub completed: i64,
+    pub created: i64,
+    pub inbox: i64,
+    pub next_actions: i64,
+    pub waiting: i64,
+    pub overdue: i64,
+    pub due_this_week: i64,
+    pub someday: i64,
+}
+
+pub fn validate_offset(utc_offset_minutes: i32) -> Result<()> {
+    if utc_offset_minutes.abs() > 14 * 60 {
+        return Err(AppError::InvalidInput(format!("Invalid UTC offset {}", utc_offset_minutes)));
+    }
+    Ok(())
+}
+
+/// Start and end of the week containing `now`, as UTC instants
+pub fn week_bounds(now: DateTime<Utc>, utc_offset_minutes: i32) -> (DateTime<Utc>, DateTime<Utc>) {
+    let offset = Duration::minutes(utc_offset_minutes as i64);
+    let local = (now + offset).date_naive();
+    let monday = local - Duration::days(local.weekday().num_days_from_monday() as i64);
+    let start = Utc.from_utc_datetime(&monday.and_time(NaiveTime::MIN)) - offset;
+    (start, start + Duration::weeks(1))
+}
+
+/// Group tasks by context. A task with several contexts is listed under each;
+/// tasks without one come last. Within a group, higher priority and earlier
+/// due dates come first.
+pub fn group_by_context(tasks: Vec<Task>) -> Vec<ContextGroup> {
+    let mut groups: BTreeMap<String, Vec<Task>> = BTreeMap::new();
+    let mut without_context = Vec::new();
+    for task in tasks {
+        let contexts: Vec<String> = serde_json::from_str(&task.contexts).unwrap_or_default();
+        let mut contexts: Vec<String> = contexts
+            .into_iter()
+            .map(|context| context.trim().to_string())
+            .filter(|context| !context.is_empty())
+            .collect();
+        contexts.sort();
+        contexts.dedup();
+        if contexts.is_empty() {
+            without_context.push(task);
+            continue;
+        }
+        for context in contexts {
+            groups.entry(context).or_default().push(task.clone());
+        }
+    }
+
+    let mut groups: Vec<ContextGroup> = groups
+        .into_iter()
+        .map(|(context, tasks)| ContextGroup { context, tasks })
+        .collect();
+    if !without_context.is_empty() {
+        groups.push(ContextGroup { context: String::new(), tasks: without_context });
+    }
+    for group in &mut groups {
+        group.tasks.sort_by(|a, b| {
+            b.priority
+                .cmp(&a.priority)
+                .then_with(|| match (a.due_date, b.due_date) {
+                    (Some(a), Some(b)) => a.cmp(&b),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                })
+                .then_with(|| a.created_at.cmp(&b.created_at))
+        });
+    }
+    groups
+}
+
+pub fn waiting_days(since: DateTime<Utc>, now: DateTime<Utc>) -> i64 {
+    (now - since).num_days().max(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{TaskPriority, TaskStatus};
+
+    fn at(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    fn task(title: &str, priority: TaskPriority, due: Option<&str>, contexts: &[&str]) -> Task {
+        Task {
+            id: title.to_string(),
+            title: title.to_string(),
+            description: None,
+            status: TaskStatus::Todo,
+            priority,
+            due_date: due.map(at),
+            scheduled_date: None,
+            completed_at: None,
+            estimated_time: None,
+            actual_time: None,
+            project_id: None,
+            parent_task_id: None,
+            linked_notes: "[]".to_string(),
+            linked_files: "[]".to_string(),
+            tags: "[]".to_string(),
+            contexts: serde_json::to_string(contexts).unwrap(),
+            recurrence: None,
+            block_id: None,
+            created_at: at("2026-10-01T00:00:00Z"),
+            updated_at: at("2026-10-01T00:00:00Z"),
+            created_by: None,
+        }
+    }
+
+    #[test]
+    fn test_group_by_context() {
+        let groups = group_by_context(vec![
+            task("call", TaskPriority::Medium, None, &["@phone"]),
+            task("buy", TaskPriority::Low, None, &["@errands", "@phone"]),
+            task("email", TaskPriority::Medium, Some("2026-10-20T00:00:00Z"), &["@phone"]),
+            task("think", TaskPriority::High, None, &[]),
+        ]);
+        let titles: Vec<(&str, Vec<&str>)> = groups
+            .iter()
+            .map(|group| (group.context.as_str(), group.tasks.iter().map(|task| task.title.as_str()).collect()))
+            .collect();
+        assert_eq!(
+            titles,
+            [
+                ("@errands", vec!["buy"]),
+                ("@phone", vec!["email", "call", "buy"]),
+                ("", vec!["think"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_week_bounds() {
+        // Sunday 20:00 UTC is already Monday at UTC+8
+        let now = at("2026-10-18T20:00:00Z");
+        assert_eq!(week_bounds(now, 0), (at("2026-10-12T00:00:00Z"), at("2026-10-19T00:00:00Z")));
+        assert_eq!(week_bounds(now, 8 * 60), (at("2026-10-18T16:00:00Z"), at("2026-10-25T16:00:00Z")));
+        assert!(validate_offset(15 * 60).is_err());
+    }
+}
@@ -0,0 +1,460 @@
+//! Recurrence rules for repeating tasks.
+//!
+//! A rule is stored as JSON in `tasks.recurrence` and covers the part of the
+//! RFC 5545 RRULE grammar a task manager needs: `FREQ`, `INTERVAL`, `BYDAY`,
+//! `UNTIL` and `COUNT`. The task's due date plays the role of `DTSTART`.
+//!
+//! `count` is the number of occurrences left in the series including the
+//! current task, so each spawned occurrence carries `count - 1`.
+//!
+//! Rules are expanded in the user's time zone: a task due every Monday at
+//! 09:00 stays on Monday 09:00 local time whatever that is in UTC.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Give up after this many consecutive periods without an occurrence
+const MAX_EMPTY_PERIODS: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Frequency {
+    fn as_rrule(&self) -> &'static str {
+        match self {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        }
+    }
+}
+
+/// A `BYDAY` entry such as `MO`, `2TU` (second Tuesday) or `-1FR` (last Friday)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByDay {
+    pub ordinal: Option<i8>,
+    pub weekday: Weekday,
+}
+
+impl fmt::Display for ByDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let weekday = match self.weekday {
+            Weekday::Mon => "MO",
+            Weekday::Tue => "TU",
+            Weekday::Wed => "WE",
+            Weekday::Thu => "TH",
+            Weekday::Fri => "FR",
+            Weekday::Sat => "SA",
+            Weekday::Sun => "SU",
+        };
+        match self.ordinal {
+            Some(ordinal) => write!(f, "{}{}", ordinal, weekday),
+            None => f.write_str(weekday),
+        }
+    }
+}
+
+impl FromStr for ByDay {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim().to_ascii_uppercase();
+        if value.len() < 2 || !value.is_char_boundary(value.len() - 2) {
+            return Err(format!("Invalid BYDAY value '{}'", value));
+        }
+        let (ordinal, weekday) = value.split_at(value.len() - 2);
+        let weekday = match weekday {
+            "MO" => Weekday::Mon,
+            "TU" => Weekday::Tue,
+            "WE" => Weekday::Wed,
+            "TH" => Weekday::Thu,
+            "FR" => Weekday::Fri,
+            "SA" => Weekday::Sat,
+            "SU" => Weekday::Sun,
+            _ => return Err(format!("Invalid BYDAY value '{}'", value)),
+        };
+        let ordinal = match ordinal.trim_start_matches('+') {
+            "" => None,
+            ordinal => Some(ordinal.parse::<i8>().map_err(|_| format!("Invalid BYDAY value '{}'", value))?),
+        };
+        Ok(Self { ordinal, weekday })
+    }
+}
+
+impl Serialize for ByDay {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ByDay {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+    }
+}
+
+fn default_interval() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recurrence {
+    pub freq: Frequency,
+    #[serde(default = "default_interval")]
+    pub interval: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub by_day: Vec<ByDay>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<u32>,
+}
+
+impl Recurrence {
+    /// Parse a rule stored as JSON or written as an RRULE (`FREQ=WEEKLY;BYDAY=MO`)
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        let rule = if value.starts_with('{') {
+            serde_json::from_str::<Self>(value).map_err(|e| format!("Invalid recurrence: {}", e))?
+        } else {
+            Self::from_rrule(value)?
+        };
+        rule.validate()?;
+        Ok(rule)
+    }
+
+    pub fn from_rrule(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        let value = value.strip_prefix("RRULE:").unwrap_or(value);
+
+        let mut freq = None;
+        let mut rule = Self {
+            freq: Frequency::Daily,
+            interval: 1,
+            by_day: Vec::new(),
+            until: None,
+            count: None,
+        };
+        for part in value.split(';').filter(|part| !part.is_empty()) {
+            let (name, part_value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid RRULE part '{}'", part))?;
+            match name.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    freq = Some(match part_value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(format!("Unsupported FREQ '{}'", part_value)),
+                    })
+                }
+                "INTERVAL" => {
+                    rule.interval = part_value.parse().map_err(|_| format!("Invalid INTERVAL '{}'", part_value))?
+                }
+                "COUNT" => {
+                    rule.count = Some(part_value.parse().map_err(|_| format!("Invalid COUNT '{}'", part_value))?)
+                }
+                "UNTIL" => rule.until = Some(parse_until(part_value)?),
+                "BYDAY" => {
+                    rule.by_day = part_value.split(',').map(str::parse).collect::<Result<_, _>>()?
+                }
+                // Weeks always start on Monday here, which is the RRULE default
+                "WKST" if part_value.eq_ignore_ascii_case("MO") => {}
+                _ => return Err(format!("Unsupported RRULE part '{}'", part)),
+            }
+        }
+        rule.freq = freq.ok_or_else(|| "RRULE is missing FREQ".to_string())?;
+        rule.validate()?;
+        Ok(rule)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval == 0 {
+            return Err("Recurrence interval must be at least 1".to_string());
+        }
+        if self.count == Some(0) {
+            return Err("Recurrence count must be at least 1".to_string());
+        }
+        if self.count.is_some() && self.until.is_some() {
+            return Err("Recurrence cannot have both count and until".to_string());
+        }
+        for by_day in &self.by_day {
+            match by_day.ordinal {
+                None => {}
+                Some(_) if matches!(self.freq, Frequency::Daily | Frequency::Weekly) => {
+                    return Err(format!("BYDAY '{}' needs a monthly or yearly rule", by_day));
+                }
+                Some(ordinal) if ordinal == 0 || !(-5..=5).contains(&ordinal) => {
+                    return Err(format!("Invalid BYDAY ordinal in '{}'", by_day));
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Occurrences starting at `start`, which is always the first one, with
+    /// weekdays and time of day taken in `zone`
+    pub fn occurrences<'a, Tz: TimeZone + 'a>(
+        &'a self,
+        start: DateTime<Utc>,
+        zone: Tz,
+    ) -> impl Iterator<Item = DateTime<Utc>> + 'a {
+        let local = start.with_timezone(&zone).naive_local();
+        let first = local.date();
+        let time = local.time();
+        let mut pending = VecDeque::from([start]);
+        let mut period = 0i64;
+        let mut empty_periods = 0;
+        let mut emitted = 0u32;
+
+        std::iter::from_fn(move || loop {
+            if self.count.map_or(false, |count| emitted >= count) {
+                return None;
+            }
+            if let Some(next) = pending.pop_front() {
+                if self.until.map_or(false, |until| next > until) {
+                    return None;
+                }
+                emitted += 1;
+                return Some(next);
+            }
+            if empty_periods >= MAX_EMPTY_PERIODS {
+                return None;
+            }
+
+            let before = pending.len();
+            pending.extend(
+                self.period_dates(first, period)
+                    .into_iter()
+                    .filter_map(|date| from_local(&zone, NaiveDateTime::new(date, time)))
+                    .filter(|occurrence| *occurrence > start),
+            );
+            period += 1;
+            empty_periods = if pending.len() == before { empty_periods + 1 } else { 0 };
+        })
+    }
+
+    /// The occurrence that follows `current` in a series starting at `current`
+    pub fn next_after<Tz: TimeZone>(&self, current: DateTime<Utc>, zone: Tz) -> Option<DateTime<Utc>> {
+        self.occurrences(current, zone).nth(1)
+    }
+
+    /// The rule carried by the occurrence after this one
+    pub fn advance(&self) -> Self {
+        Self {
+            count: self.count.map(|count| count.saturating_sub(1)),
+            ..self.clone()
+        }
+    }
+
+    /// Candidate dates in the `index`-th period, sorted
+    fn period_dates(&self, first: NaiveDate, index: i64) -> Vec<NaiveDate> {
+        let step = index * self.interval as i64;
+        let mut dates = match self.freq {
+            Frequency::Daily => {
+                let date = first + Duration::days(step);
+                if self.by_day.is_empty() || self.by_day.iter().any(|by_day| by_day.weekday == date.weekday()) {
+                    vec![date]
+                } else {
+                    Vec::new()
+                }
+            }
+            Frequency::Weekly => {
+                let monday = first - Duration::days(first.weekday().num_days_from_monday() as i64)
+                    + Duration::weeks(step);
+                if self.by_day.is_empty() {
+                    vec![monday + Duration::days(first.weekday().num_days_from_monday() as i64)]
+                } else {
+                    self.by_day
+                        .iter()
+                        .map(|by_day| monday + Duration::days(by_day.weekday.num_days_from_monday() as i64))
+                        .collect()
+                }
+            }
+            Frequency::Monthly => {
+                let months = first.year() as i64 * 12 + first.month0() as i64 + step;
+                self.month_dates(first, months.div_euclid(12) as i32, months.rem_euclid(12) as u32 + 1)
+            }
+            Frequency::Yearly => self.month_dates(first, first.year() + step as i32, first.month()),
+        };
+        dates.sort();
+        dates.dedup();
+        dates
+    }
+
+    fn month_dates(&self, first: NaiveDate, year: i32, month: u32) -> Vec<NaiveDate> {
+        if self.by_day.is_empty() {
+            // Months without this day are skipped, as RRULE does
+            return NaiveDate::from_ymd_opt(year, month, first.day()).into_iter().collect();
+        }
+
+        let weekdays_in_month = |weekday: Weekday| -> Vec<NaiveDate> {
+            (1..=5)
+                .filter_map(|n| NaiveDate::from_weekday_of_month_opt(year, month, weekday, n))
+                .collect()
+        };
+        self.by_day
+            .iter()
+            .flat_map(|by_day| {
+                let all = weekdays_in_month(by_day.weekday);
+                match by_day.ordinal {
+                    None => all,
+                    Some(ordinal) if ordinal > 0 => all.get(ordinal as usize - 1).copied().into_iter().collect(),
+                    Some(ordinal) => all
+                        .len()
+                        .checked_sub(ordinal.unsigned_abs() as usize)
+                        .and_then(|index| all.get(index).copied())
+                        .into_iter()
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Recurrence {
+    /// Formats the rule as an RRULE value
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FREQ={}", self.freq.as_rrule())?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.by_day.is_empty() {
+            let by_day: Vec<String> = self.by_day.iter().map(ToString::to_string).collect();
+            write!(f, ";BYDAY={}", by_day.join(","))?;
+        }
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", until.format("%Y%m%dT%H%M%SZ"))?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::parse(value)
+    }
+}
+
+/// A local time in `zone`; times skipped by a DST change move an hour later
+pub fn from_local<Tz: TimeZone>(zone: &Tz, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+    zone.from_local_datetime(&local)
+        .earliest()
+        .or_else(|| zone.from_local_datetime(&(local + Duration::hours(1))).earliest())
+        .map(|occurrence| occurrence.with_timezone(&Utc))
+}
+
+/// `UNTIL` is either a UTC date-time or a date, which includes the whole day
+fn parse_until(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(until) = NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), "%Y%m%dT%H%M%S") {
+        return Ok(Utc.from_utc_datetime(&until));
+    }
+    NaiveDate::parse_from_str(value, "%Y%m%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(23, 59, 59))
+        .map(|until| Utc.from_utc_datetime(&until))
+        .ok_or_else(|| format!("Invalid UNTIL '{}'", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    fn at(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    fn dates(rule: &str, start: &str, limit: usize) -> Vec<String> {
+        Recurrence::parse(rule)
+            .unwrap()
+            .occurrences(at(start), Utc)
+            .take(limit)
+            .map(|occurrence| occurrence.format("%Y-%m-%d").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_rules() {
+        assert_eq!(
+            dates("FREQ=DAILY;INTERVAL=3", "2026-10-18T09:00:00Z", 3),
+            ["2026-10-18", "2026-10-21", "2026-10-24"]
+        );
+        // Every other week on Monday and Wednesday, starting on a Wednesday
+        assert_eq!(
+            dates("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE", "2026-10-21T09:00:00Z", 4),
+            ["2026-10-21", "2026-11-02", "2026-11-04", "2026-11-16"]
+        );
+        // The 31st only exists in some months
+        assert_eq!(
+            dates("FREQ=MONTHLY", "2026-01-31T09:00:00Z", 3),
+            ["2026-01-31", "2026-03-31", "2026-05-31"]
+        );
+        assert_eq!(
+            dates("RRULE:FREQ=MONTHLY;BYDAY=-1FR", "2026-10-30T09:00:00Z", 3),
+            ["2026-10-30", "2026-11-27", "2026-12-25"]
+        );
+        assert_eq!(
+            dates(r#"{"freq":"yearly"}"#, "2028-02-29T09:00:00Z", 2),
+            ["2028-02-29", "2032-02-29"]
+        );
+        assert_eq!(dates("FREQ=DAILY;COUNT=2", "2026-10-18T09:00:00Z", 5).len(), 2);
+        assert_eq!(
+            dates("FREQ=WEEKLY;UNTIL=20261101", "2026-10-18T09:00:00Z", 5),
+            ["2026-10-18", "2026-10-25", "2026-11-01"]
+        );
+
+        let next = Recurrence::parse("FREQ=DAILY").unwrap().next_after(at("2026-10-18T09:00:00Z"), Utc);
+        assert_eq!(next, Some(at("2026-10-19T09:00:00Z")));
+        assert_eq!(Recurrence::parse("FREQ=DAILY;COUNT=1").unwrap().next_after(at("2026-10-18T09:00:00Z"), Utc), None);
+    }
+
+    #[test]
+    fn test_local_time() {
+        // Monday 01:00 at UTC+8 is still Sunday in UTC
+        let shanghai = FixedOffset::east_opt(8 * 3600).unwrap();
+        let rule = Recurrence::parse("FREQ=WEEKLY;BYDAY=MO,WE").unwrap();
+        let occurrences: Vec<DateTime<Utc>> = rule.occurrences(at("2026-10-18T17:00:00Z"), shanghai).take(3).collect();
+        assert_eq!(
+            occurrences,
+            [at("2026-10-18T17:00:00Z"), at("2026-10-20T17:00:00Z"), at("2026-10-25T17:00:00Z")]
+        );
+    }
+
+    #[test]
+    fn test_parse_and_format() {
+        let rule = Recurrence::parse("FREQ=MONTHLY;INTERVAL=2;BYDAY=2TU,-1FR;COUNT=4").unwrap();
+        assert_eq!(rule.to_string(), "FREQ=MONTHLY;INTERVAL=2;BYDAY=2TU,-1FR;COUNT=4");
+        assert_eq!(Recurrence::parse(&rule.to_json()).unwrap(), rule);
+        assert_eq!(rule.advance().count, Some(3));
+
+        assert!(Recurrence::parse("FREQ=HOURLY").is_err());
+        assert!(Recurrence::parse("FREQ=WEEKLY;BYDAY=2MO").is_err());
+        assert!(Recurrence::parse("FREQ=DAILY;INTERVAL=0").is_err());
+        assert!(Recurrence::parse("FREQ=DAILY;COUNT=2;UNTIL=20261231").is_err());
+        assert!(Recurrence::parse("FREQ=DAILY;BYMONTH=1").is_err());
+        assert!(Recurrence::parse(r#"{"freq":"weekly","by_day":["XX"]}"#).is_err());
+    }
+}
@@ -0,0 +1,271 @@
+//! Task reminders.
+//!
+//! A reminder fires `offset_minutes` before its task is due, or at the time it
+//! was snoozed to. Everything lives in the `reminders` table, so the scheduler
+//! only has to look at the database to pick up where it left off after a
+//! restart. It never sleeps longer than `MAX_SLEEP` and compares against the
+//! wall clock when it wakes, so reminders missed while the machine was asleep
+//! or the app was closed fire as soon as it gets the chance, marked as late.
+
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tokio::sync::{Mutex, Notify};
+use tokio_util::sync::CancellationToken;
+
+use crate::database::Database;
+use crate::error::Result;
+
+/// Event the frontend listens to for reminders
+pub const REMINDER_EVENT: &str = "task-reminder";
+
+/// Longest the scheduler sleeps between checks. Timers don't run while the
+/// machine is suspended, so this bounds how late a missed reminder fires.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+/// Reminders firing later than this are reported as late
+const LATE_AFTER: chrono::Duration = chrono::Duration::minutes(1);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReminderEvent {
+    pub reminder_id: String,
+    pub task_id: String,
+    pub title: String,
+    pub due_date: Option<DateTime<Utc>>,
+    pub remind_at: DateTime<Utc>,
+    /// Missed while the app was closed or the machine was asleep
+    pub late: bool,
+}
+
+pub trait ReminderListener: Send + Sync {
+    fn on_reminder(&self, event: ReminderEvent);
+}
+
+/// Fires task reminders in the background
+pub struct ReminderScheduler {
+    database: Arc<Mutex<Database>>,
+    listener: StdMutex<Option<Arc<dyn ReminderListener>>>,
+    reschedule: Notify,
+    shutdown: CancellationToken,
+}
+
+impl std::fmt::Debug for ReminderScheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReminderScheduler")
+            .field("shutdown", &self.shutdown.is_cancelled())
+            .finish_non_exhaustive()
+    }
+}
+
+impl ReminderScheduler {
+    pub fn new(database: Arc<Mutex<Database>>) -> Self {
+        Self {
+            database,
+            listener: StdMutex::new(None),
+            reschedule: Notify::new(),
+            shutdown: CancellationToken::new(),
+        }
+    }
+
+    pub fn set_listener(&self, listener: Arc<dyn ReminderListener>) {
+        *self.listener.lock().unwrap() = Some(listener);
+    }
+
+    /// Start the scheduling loop in the background
+    pub fn start(self: &Arc<Self>) -> tauri::async_runtime::JoinHandle<()> {
+        let scheduler = Arc::clone(self);
+        tauri::async_runtime::spawn(async move {
+            log::info!("Reminder scheduler started");
+            scheduler.run().await;
+            log::info!("Reminder scheduler stopped");
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// Reminders were added, removed, snoozed or moved
+    pub fn notify_changed(&self) {
+        self.reschedule.notify_one();
+    }
+
+    /// Fire every reminder due by `now` and mark it fired
+    pub async fn fire_due(&self, now: DateTime<Utc>) -> Result<Vec<ReminderEvent>> {
+        let db = self.database.lock().await;
+        let mut events = Vec::new();
+        for (reminder, task) in db.get_due_reminders(now).await? {
+            db.mark_reminder_fired(&reminder.id, now).await?;
+            events.push(ReminderEvent {
+                late: now - reminder.next_at() > LATE_AFTER,
+                reminder_id: reminder.id.clone(),
+                task_id: task.id,
+                title: task.title,
+                due_date: task.due_date,
+                remind_at: reminder.next_at(),
+            });
+        }
+        drop(db);
+
+        let listener = self.listener.lock().unwrap().clone();
+        if let Some(listener) = listener {
+            for event in &events {
+                listener.on_reminder(event.clone());
+            }
+        }
+        Ok(events)
+    }
+
+    async fn run(&self) {
+        loop {
+            // Catches up on anything missed before the app started, too
+            if let Err(e) = self.fire_due(Utc::now()).await {
+                log::warn!("Failed to fire reminders: {}", e);
+            }
+
+            let delay = match self.next_delay().await {
+                Ok(Some(delay)) => delay.min(MAX_SLEEP),
+                Ok(None) => MAX_SLEEP,
+                Err(e) => {
+                    log::warn!("Failed to read reminders: {}", e);
+                    MAX_SLEEP
+                }
+            };
+
+            tokio::select! {
+                _ = self.shutdown.cancelled() => return,
+                _ = self.reschedule.notified() => {}
+                _ = tokio::time::sleep(delay) => {}
+            }
+        }
+    }
+
+    /// Time until the next pending reminder, `None` when there is none
+    async fn next_delay(&self) -> Result<Option<Duration>> {
+        let next = self.database.lock().await.next_reminder_at().await?;
+        Ok(next.map(|next| (next - Utc::now()).to_std().unwrap_or(Duration::ZERO)))
+    }
+}
+
+/// Forwards reminders to the frontend and shows a system notification
+pub struct TauriReminderListener {
+    app_handle: AppHandle,
+}
+
+impl TauriReminderListener {
+    pub fn new(app_handle: AppHandle) -> Self {
+        Self { app_handle }
+    }
+}
+
+impl ReminderListener for TauriReminderListener {
+    fn on_reminder(&self, event: ReminderEvent) {
+        let body = match event.due_date {
+            Some(due_date) => format!("Due {}", due_date.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")),
+            None => "Reminder".to_string(),
+        };
+        let identifier = self.app_handle.config().tauri.bundle.identifier.clone();
+        if let Err(e) = tauri::api::notification::Notification::new(identifier)
+            .title(&event.title)
+            .body(body)
+            .show()
+        {
+            log::warn!("Failed to show reminder notification: {}", e);
+        }
+
+        if let Err(e) = self.app_handle.emit_all(REMINDER_EVENT, event) {
+            log::warn!("Failed to emit reminder event: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreateTaskRequest, TaskStatus, UpdateTaskRequest};
+
+    #[derive(Default)]
+    struct RecordingListener {
+        events: StdMutex<Vec<ReminderEvent>>,
+    }
+
+    impl ReminderListener for RecordingListener {
+        fn on_reminder(&self, event: ReminderEvent) {
+            self.events.lock().unwrap().push(event);
+        }
+    }
+
+    fn task(title: &str, due_date: DateTime<Utc>) -> CreateTaskRequest {
+        CreateTaskRequest {
+            title: title.to_string(),
+            description: None,
+            priority: None,
+            due_date: Some(due_date),
+            scheduled_date: None,
+            estimated_time: None,
+            project_id: None,
+            parent_task_id: None,
+            linked_notes: None,
+            linked_files: None,
+            tags: None,
+            contexts: None,
+            recurrence: None,
+        }
+    }
+
+    fn update(id: &str) -> UpdateTaskRequest {
+        serde_json::from_value(serde_json::json!({ "id": id })).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fire_due_catches_up_and_snoozes() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new_with_path(dir.path().join("reminders.db").to_str().unwrap()).await.unwrap();
+        let now = Utc::now();
+
+        // Missed an hour ago, e.g. while the app was closed
+        let missed = db.create_task(task("Missed", now)).await.unwrap();
+        let missed_reminder = db.create_reminder(&missed.id, 60).await.unwrap();
+        let upcoming = db.create_task(task("Upcoming", now + chrono::Duration::days(1))).await.unwrap();
+        let upcoming_reminder = db.create_reminder(&upcoming.id, 30).await.unwrap();
+        let done = db.create_task(task("Done", now)).await.unwrap();
+        db.create_reminder(&done.id, 10).await.unwrap();
+        db.update_task(UpdateTaskRequest { status: Some(TaskStatus::Done), ..update(&done.id) }, false)
+            .await
+            .unwrap();
+        let undated = db.create_task(CreateTaskRequest { due_date: None, ..task("Undated", now) }).await.unwrap();
+        assert!(db.create_reminder(&undated.id, 0).await.is_err());
+
+        let scheduler = ReminderScheduler::new(Arc::new(Mutex::new(db)));
+        let listener = Arc::new(RecordingListener::default());
+        scheduler.set_listener(listener.clone());
+
+        let events = scheduler.fire_due(now).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].reminder_id.as_str(), events[0].late), (missed_reminder.id.as_str(), true));
+        assert_eq!(*listener.events.lock().unwrap(), events);
+        // Fired reminders don't fire again
+        assert!(scheduler.fire_due(now).await.unwrap().is_empty());
+
+        let db = scheduler.database.lock().await;
+        let snoozed_until = now + chrono::Duration::minutes(10);
+        db.snooze_reminder(&missed_reminder.id, snoozed_until).await.unwrap();
+        assert_eq!(db.next_reminder_at().await.unwrap(), Some(snoozed_until));
+        drop(db);
+        let events = scheduler.fire_due(snoozed_until).await.unwrap();
+        assert_eq!((events.len(), events[0].late), (1, false));
+
+        // Moving the due date moves the reminder along
+        let db = scheduler.database.lock().await;
+        let due_date = now + chrono::Duration::days(2);
+        db.update_task(UpdateTaskRequest { due_date: Some(due_date), ..update(&upcoming.id) }, false)
+            .await
+            .unwrap();
+        let moved = db.get_reminder(&upcoming_reminder.id).await.unwrap();
+        assert_eq!(moved.remind_at, due_date - chrono::Duration::minutes(30));
+    }
+}
@@ -0,0 +1,67 @@
+//! Project progress rollups.
+//!
+//! `projects.total_tasks`, `completed_tasks` and `progress` are derived from the
+//! project's tasks; `Database` refreshes them whenever a task is created,
+//! updated or deleted. Cancelled tasks don't count towards either total.
+
+/// Setting that weights progress by each task's `estimated_time`
+pub const WEIGHTED_PROGRESS_KEY: &str = "project_progress_weighted";
+
+/// The part of a task that matters for rollups
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskWeight {
+    pub done: bool,
+    pub estimated_time: Option<i32>,
+}
+
+/// Progress as a percentage from 0 to 100.
+///
+/// When weighted, a task without an estimate counts as much as the average
+/// estimated task, and with no estimates at all every task counts the same.
+pub fn progress(tasks: &[TaskWeight], weighted: bool) -> i32 {
+    if tasks.is_empty() {
+        return 0;
+    }
+
+    let estimates: Vec<f64> = tasks
+        .iter()
+        .filter_map(|task| task.estimated_time.filter(|minutes| *minutes > 0))
+        .map(f64::from)
+        .collect();
+    let default_weight = if weighted && !estimates.is_empty() {
+        estimates.iter().sum::<f64>() / estimates.len() as f64
+    } else {
+        1.0
+    };
+    let weight = |task: &TaskWeight| match task.estimated_time {
+        Some(minutes) if weighted && minutes > 0 => f64::from(minutes),
+        _ => default_weight,
+    };
+
+    let total: f64 = tasks.iter().map(weight).sum();
+    let done: f64 = tasks.iter().filter(|task| task.done).map(weight).sum();
+    (done / total * 100.0).round() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(done: bool, estimated_time: Option<i32>) -> TaskWeight {
+        TaskWeight { done, estimated_time }
+    }
+
+    #[test]
+    fn test_progress() {
+        assert_eq!(progress(&[], true), 0);
+
+        let tasks = [task(true, Some(60)), task(false, Some(180)), task(false, None)];
+        assert_eq!(progress(&tasks, false), 33);
+        // The unestimated task weighs the average of 60 and 180 minutes
+        assert_eq!(progress(&tasks, true), 17);
+
+        let unestimated = [task(true, None), task(false, None), task(false, None), task(false, None)];
+        assert_eq!(progress(&unestimated, true), 25);
+        assert_eq!(progress(&[task(true, Some(30))], true), 100);
+    }
+}
@@ -0,0 +1,211 @@
+//! Time tracking reports.
+//!
+//! Stopped time entries are grouped by task, project, tag or day. An entry
+//! belongs to the range when it starts inside it; running timers are left out
+//! until they stop.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeReportGroup {
+    Task,
+    Project,
+    Tag,
+    Day,
+}
+
+impl TimeReportGroup {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimeReportGroup::Task => "task",
+            TimeReportGroup::Project => "project",
+            TimeReportGroup::Tag => "tag",
+            TimeReportGroup::Day => "day",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeReportRequest {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub group_by: TimeReportGroup,
+    /// Offset of the user's timezone, used to decide which day an entry falls on
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeReportRow {
+    /// Task or project id, tag, or `YYYY-MM-DD`; empty for untagged or unassigned time
+    pub key: String,
+    pub label: String,
+    pub seconds: i64,
+    pub entries: usize,
+}
+
+/// A stopped time entry with the task details reports group by
+#[derive(Debug, Clone)]
+pub struct TrackedEntry {
+    pub start_time: DateTime<Utc>,
+    pub duration: i64,
+    pub task_id: String,
+    pub task_title: String,
+    pub project_id: Option<String>,
+    pub project_name: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl TimeReportRequest {
+    pub fn validate(&self) -> Result<()> {
+        if self.to <= self.from {
+            return Err(AppError::InvalidInput("Report range must end after it starts".to_string()));
+        }
+        if self.utc_offset_minutes.abs() > 14 * 60 {
+            return Err(AppError::InvalidInput(format!("Invalid UTC offset {}", self.utc_offset_minutes)));
+        }
+        Ok(())
+    }
+}
+
+/// Group entries into report rows. Days are listed in order, everything else
+/// by time spent. An entry with several tags counts towards each of them.
+pub fn build_report(entries: &[TrackedEntry], request: &TimeReportRequest) -> Vec<TimeReportRow> {
+    let mut rows: HashMap<String, TimeReportRow> = HashMap::new();
+    let offset = Duration::minutes(request.utc_offset_minutes as i64);
+
+    for entry in entries.iter().filter(|entry| entry.start_time >= request.from && entry.start_time < request.to) {
+        let groups: Vec<(String, String)> = match request.group_by {
+            TimeReportGroup::Task => vec![(entry.task_id.clone(), entry.task_title.clone())],
+            TimeReportGroup::Project => vec![match &entry.project_id {
+                Some(project_id) => (
+                    project_id.clone(),
+                    entry.project_name.clone().unwrap_or_else(|| project_id.clone()),
+                ),
+                None => (String::new(), "No project".to_string()),
+            }],
+            TimeReportGroup::Tag if entry.tags.is_empty() => vec![(String::new(), "Untagged".to_string())],
+            TimeReportGroup::Tag => entry.tags.iter().map(|tag| (tag.clone(), tag.clone())).collect(),
+            TimeReportGroup::Day => {
+                let day = (entry.start_time + offset).format("%Y-%m-%d").to_string();
+                vec![(day.clone(), day)]
+            }
+        };
+
+        for (key, label) in groups {
+            let row = rows.entry(key.clone()).or_insert_with(|| TimeReportRow {
+                key,
+                label,
+                seconds: 0,
+                entries: 0,
+            });
+            row.seconds += entry.duration;
+            row.entries += 1;
+        }
+    }
+
+    let mut rows: Vec<TimeReportRow> = rows.into_values().collect();
+    match request.group_by {
+        TimeReportGroup::Day => rows.sort_by(|a, b| a.key.cmp(&b.key)),
+        _ => rows.sort_by(|a, b| b.seconds.cmp(&a.seconds).then_with(|| a.label.cmp(&b.label))),
+    }
+    rows
+}
+
+pub fn write_csv<W: Write>(rows: &[TimeReportRow], group_by: TimeReportGroup, writer: W) -> Result<()> {
+    let csv_error = |e: csv::Error| AppError::Io(format!("Failed to write CSV: {}", e));
+
+    let mut writer = csv::Writer::from_writer(writer);
+    writer
+        .write_record([group_by.as_str(), "label", "entries", "seconds", "hours"])
+        .map_err(csv_error)?;
+    for row in rows {
+        writer
+            .write_record([
+                row.key.clone(),
+                row.label.clone(),
+                row.entries.to_string(),
+                row.seconds.to_string(),
+                format!("{:.2}", row.seconds as f64 / 3600.0),
+            ])
+            .map_err(csv_error)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    fn entry(start: &str, minutes: i64, task: &str, project: Option<&str>, tags: &[&str]) -> TrackedEntry {
+        TrackedEntry {
+            start_time: at(start),
+            duration: minutes * 60,
+            task_id: task.to_string(),
+            task_title: format!("Task {}", task),
+            project_id: project.map(str::to_string),
+            project_name: project.map(|project| format!("Project {}", project)),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_build_report_and_csv() {
+        let entries = vec![
+            entry("2026-10-18T23:30:00Z", 60, "a", Some("p"), &["deep", "writing"]),
+            entry("2026-10-19T10:00:00Z", 30, "b", None, &[]),
+            entry("2026-10-19T12:00:00Z", 45, "a", Some("p"), &["deep"]),
+            entry("2026-10-25T12:00:00Z", 15, "b", None, &[]),
+        ];
+        let mut request = TimeReportRequest {
+            from: at("2026-10-18T00:00:00Z"),
+            to: at("2026-10-20T00:00:00Z"),
+            group_by: TimeReportGroup::Task,
+            utc_offset_minutes: 0,
+        };
+
+        let rows = build_report(&entries, &request);
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].key.as_str(), rows[0].seconds, rows[0].entries), ("a", 105 * 60, 2));
+        assert_eq!((rows[1].key.as_str(), rows[1].seconds), ("b", 30 * 60));
+
+        request.group_by = TimeReportGroup::Project;
+        let labels: Vec<String> = build_report(&entries, &request).into_iter().map(|row| row.label).collect();
+        assert_eq!(labels, ["Project p", "No project"]);
+
+        request.group_by = TimeReportGroup::Tag;
+        let tags: Vec<(String, i64)> = build_report(&entries, &request)
+            .into_iter()
+            .map(|row| (row.label, row.seconds / 60))
+            .collect();
+        assert_eq!(tags, [("deep".to_string(), 105), ("writing".to_string(), 60), ("Untagged".to_string(), 30)]);
+
+        // 23:30 UTC is already the next day at UTC+8
+        request.group_by = TimeReportGroup::Day;
+        request.utc_offset_minutes = 8 * 60;
+        let days: Vec<(String, i64)> = build_report(&entries, &request)
+            .into_iter()
+            .map(|row| (row.key, row.seconds / 60))
+            .collect();
+        assert_eq!(days, [("2026-10-19".to_string(), 135)]);
+
+        let mut csv = Vec::new();
+        write_csv(&build_report(&entries, &request), request.group_by, &mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "day,label,entries,seconds,hours\n2026-10-19,2026-10-19,3,8100,2.25\n");
+
+        request.to = request.from;
+        assert!(request.validate().is_err());
+    }
+}